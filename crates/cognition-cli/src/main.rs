@@ -1,11 +1,360 @@
+use async_trait::async_trait;
+use clap::{Parser, Subcommand, ValueEnum};
 use cognition::{
-    run_decision, tools, CognitionError, Decision, DecisionPromptTemplate, DecisionState,
+    apply_suggestions, evaluate, export, import, replay, run_decision, schema,
+    suggest_improvements, test_template, tools, tree_diff, validate_tree, CognitionError, Decision,
+    DecisionEvent, DecisionPromptTemplate, DecisionResult, DecisionState, EvalCase, EvalReport,
+    Funnel, Observer, SchemaKind, SessionAnalytics, SuggestionOptions, TemplateFixture,
+    ToolOutcome, Transcript, TreeStats, TurnRecord,
 };
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{IsTerminal, Read, Write};
+use voice::Voice;
+
+mod voice;
+
+#[derive(Parser)]
+#[command(about = "Cognitive decision-making system")]
+struct Args {
+    /// Record every decision result and user input to a transcript file
+    #[arg(long, value_name = "FILE")]
+    record: Option<String>,
+
+    /// Replay the user inputs of a transcript file, asserting the same node path
+    #[arg(long, value_name = "FILE", conflicts_with = "record")]
+    replay: Option<String>,
+
+    /// Print the model calls, tokens and cost of every turn, and the session total at exit
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Print every decision result as a JSON line instead of the conversation
+    #[arg(long)]
+    json: bool,
+
+    /// Listen for spoken answers and speak every turn, needs the `speech` feature
+    #[arg(long, conflicts_with_all = ["json", "replay"])]
+    voice: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Aggregate node visits, drop-offs, retries and paths from turn logs
+    Analyze {
+        /// Directory of `*.jsonl` turn logs written with `logging.turns`
+        logs_dir: String,
+
+        #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+        format: ReportFormat,
+
+        /// Config file with `analytics.funnels` to compute conversion for
+        #[arg(long, value_name = "FILE")]
+        config: Option<String>,
+    },
+
+    /// Propose tree edits from turn logs with the configured model, printed as a diff
+    Suggest {
+        /// Directory of `*.jsonl` turn logs written with `logging.turns`
+        logs_dir: String,
+
+        /// Config file of the model writing the suggestions
+        #[arg(long, value_name = "FILE")]
+        config: String,
+
+        #[arg(long, default_value = "decision_tree.yaml")]
+        tree: String,
+
+        #[arg(long, default_value = "decision_prompt_template.yaml")]
+        template: String,
+
+        /// Times an unmatched answer must be given to be reviewed
+        #[arg(long, default_value_t = 2)]
+        min_count: usize,
+
+        /// Split nodes with more choices than this
+        #[arg(long, default_value_t = 6)]
+        max_choices: usize,
+
+        /// Print the suggestions as JSON instead of a diff
+        #[arg(long)]
+        json: bool,
+
+        /// Write the tree with every suggestion applied to this file
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+
+    /// Prompt template tools
+    #[command(subcommand)]
+    Template(TemplateCommand),
+
+    /// Offline evaluation of choice routing
+    #[command(subcommand)]
+    Eval(EvalCommand),
+
+    /// Convert a story or dialogue file to a decision tree, printed as YAML
+    Import {
+        #[arg(value_enum)]
+        format: ImportFormat,
+
+        file: String,
+    },
+
+    /// Convert a decision tree to another format
+    Export {
+        #[arg(long, value_enum)]
+        format: ExportFormat,
+
+        #[arg(default_value = "decision_tree.yaml")]
+        file: String,
+    },
+
+    /// Print depths, reachability, lint errors and token estimates of a tree as JSON
+    Stats {
+        #[arg(default_value = "decision_tree.yaml")]
+        file: String,
+    },
+
+    /// Print the JSON Schema of the decision tree or config format
+    Schema {
+        #[arg(value_enum, default_value_t = SchemaFormat::Tree)]
+        format: SchemaFormat,
+    },
+}
+
+#[derive(Clone, ValueEnum)]
+enum ImportFormat {
+    /// Twee 3 source of a Twine story
+    Twine,
+    /// Ink script, knots, choices and diverts only
+    Ink,
+    /// Mermaid flowchart
+    Mermaid,
+}
+
+#[derive(Clone, ValueEnum)]
+enum ExportFormat {
+    /// Mermaid flowchart
+    Mermaid,
+    /// Dialogflow CX flow, pages and intents as JSON
+    Dialogflow,
+    /// Rasa responses, NLU data and rules as YAML
+    Rasa,
+}
+
+#[derive(Clone, ValueEnum)]
+enum SchemaFormat {
+    Tree,
+    Config,
+}
+
+#[derive(Subcommand)]
+enum TemplateCommand {
+    /// Render a template against fixture contexts and check the results
+    Test {
+        /// Prompt template, like `decision_prompt_template.yaml`
+        template: String,
+
+        /// YAML list of fixtures, see `template_fixtures.yaml`
+        fixtures: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum EvalCommand {
+    /// Route a labeled dataset with several backends or prompt templates and
+    /// compare accuracy, latency and cost with each other and with earlier runs
+    Compare {
+        /// YAML list of cases: `node` (start by default), `input` and `expected` node
+        dataset: String,
+
+        /// Variant to compare, as `NAME=CONFIG` or `NAME=CONFIG,TEMPLATE`
+        #[arg(
+            long = "variant",
+            value_name = "NAME=CONFIG[,TEMPLATE]",
+            required = true
+        )]
+        variants: Vec<String>,
+
+        #[arg(long, default_value = "decision_tree.yaml")]
+        tree: String,
+
+        /// JSONL file the reports are appended to and compared against
+        #[arg(long, value_name = "FILE", default_value = "eval_history.jsonl")]
+        history: String,
+
+        #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+        format: ReportFormat,
+    },
+}
+
+#[derive(Clone, ValueEnum)]
+enum ReportFormat {
+    Json,
+    Csv,
+}
+
+// Prints status messages of long-running tools
+struct ProgressPrinter;
+
+#[async_trait(?Send)]
+impl Observer for ProgressPrinter {
+    async fn notify(&self, event: &DecisionEvent) {
+        if let DecisionEvent::ToolProgress {
+            tool_id,
+            message,
+            percent,
+            ..
+        } = event
+        {
+            match percent {
+                Some(percent) => println!("[{}] {} {:.0}%", tool_id, message, percent),
+                None => println!("[{}] {}", tool_id, message),
+            }
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), CognitionError> {
+    let args = Args::parse();
+
+    if let Some(Command::Analyze {
+        logs_dir,
+        format,
+        config,
+    }) = &args.command
+    {
+        let funnels = match config {
+            Some(path) => Funnel::from_config(
+                &std::fs::read_to_string(path)
+                    .map_err(|err| CognitionError(format!("Failed to read config: {}", err)))?,
+            ),
+            None => vec![],
+        };
+        let analytics = SessionAnalytics::load_dir(logs_dir, &funnels)?;
+        match format {
+            ReportFormat::Json => println!("{}", analytics.to_json()?),
+            ReportFormat::Csv => print!("{}", analytics.to_csv()),
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Suggest {
+        logs_dir,
+        config,
+        tree,
+        template,
+        min_count,
+        max_choices,
+        json,
+        output,
+    }) = &args.command
+    {
+        let options = SuggestionOptions {
+            min_count: *min_count,
+            max_choices: *max_choices,
+            ..SuggestionOptions::default()
+        };
+        return suggest(logs_dir, config, tree, template, &options, *json, output).await;
+    }
+
+    if let Some(Command::Import { format, file }) = &args.command {
+        let source = std::fs::read_to_string(file)
+            .map_err(|err| CognitionError(format!("Failed to read {}: {}", file, err)))?;
+        let decision_nodes = match format {
+            ImportFormat::Twine => import::twine::from_twee(&source)?,
+            ImportFormat::Ink => import::ink::from_ink(&source)?,
+            ImportFormat::Mermaid => import::mermaid::from_mermaid(&source)?,
+        };
+        if let Err(errors) = validate_tree(&decision_nodes) {
+            for error in errors {
+                eprintln!("[!] {}", error);
+            }
+        }
+        let yaml = serde_yaml::to_string(&decision_nodes)
+            .map_err(|err| CognitionError(format!("Failed to serialize tree: {}", err)))?;
+        print!("{}", yaml);
+        return Ok(());
+    }
+
+    if let Some(Command::Export { format, file }) = &args.command {
+        let decision_nodes = std::fs::read_to_string(file)
+            .map_err(|err| CognitionError(format!("Failed to read {}: {}", file, err)))?;
+        let decision_nodes: Vec<Decision> = serde_yaml::from_str(&decision_nodes)
+            .map_err(|err| CognitionError(format!("Failed to parse {}: {}", file, err)))?;
+        match format {
+            ExportFormat::Mermaid => print!("{}", export::mermaid::to_mermaid(&decision_nodes)),
+            ExportFormat::Dialogflow => {
+                let flow = serde_json::to_string_pretty(&export::dialogflow::to_dialogflow(
+                    &decision_nodes,
+                ))
+                .map_err(|err| CognitionError(format!("Failed to serialize flow: {}", err)))?;
+                println!("{}", flow);
+            }
+            ExportFormat::Rasa => {
+                let data = serde_yaml::to_string(&export::rasa::to_rasa(&decision_nodes))
+                    .map_err(|err| CognitionError(format!("Failed to serialize data: {}", err)))?;
+                print!("{}", data);
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Stats { file }) = &args.command {
+        let decision_nodes = std::fs::read_to_string(file)
+            .map_err(|err| CognitionError(format!("Failed to read {}: {}", file, err)))?;
+        let decision_nodes: Vec<Decision> = serde_yaml::from_str(&decision_nodes)
+            .map_err(|err| CognitionError(format!("Failed to parse {}: {}", file, err)))?;
+        let stats = serde_json::to_string_pretty(&TreeStats::new(&decision_nodes))
+            .map_err(|err| CognitionError(format!("Failed to serialize stats: {}", err)))?;
+        println!("{}", stats);
+        return Ok(());
+    }
+
+    if let Some(Command::Schema { format }) = &args.command {
+        let kind = match format {
+            SchemaFormat::Tree => SchemaKind::Tree,
+            SchemaFormat::Config => SchemaKind::Config,
+        };
+        let schema = serde_json::to_string_pretty(&schema(kind))
+            .map_err(|err| CognitionError(format!("Failed to serialize schema: {}", err)))?;
+        println!("{}", schema);
+        return Ok(());
+    }
+
+    if let Some(Command::Template(TemplateCommand::Test { template, fixtures })) = &args.command {
+        let template = std::fs::read_to_string(template)
+            .map_err(|err| CognitionError(format!("Failed to read template: {}", err)))?;
+        let fixtures = TemplateFixture::load(fixtures)?;
+        let failures = test_template(&DecisionPromptTemplate::new(template), &fixtures);
+        for failure in &failures {
+            println!("FAIL {}", failure);
+        }
+        if !failures.is_empty() {
+            return Err(CognitionError(format!(
+                "{} template checks failed",
+                failures.len()
+            )));
+        }
+        println!("{} fixtures OK", fixtures.len());
+        return Ok(());
+    }
+
+    if let Some(Command::Eval(EvalCommand::Compare {
+        dataset,
+        variants,
+        tree,
+        history,
+        format,
+    })) = &args.command
+    {
+        return eval_compare(dataset, variants, tree, history, format).await;
+    }
+
     let decision_prompt_template = {
         let mut file = File::open("decision_prompt_template.yaml").unwrap();
         let mut decision_prompt_template = String::new();
@@ -20,6 +369,13 @@ async fn main() -> Result<(), CognitionError> {
         file.read_to_string(&mut decision_nodes).unwrap();
 
         let decision_nodes: Vec<Decision> = serde_yaml::from_str(&decision_nodes).unwrap();
+
+        // Refuse to run a broken tree
+        if let Err(errors) = validate_tree(&decision_nodes) {
+            let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+            return Err(CognitionError(errors.join("\n")));
+        }
+
         decision_nodes
     };
 
@@ -28,13 +384,20 @@ async fn main() -> Result<(), CognitionError> {
     models:
       davinci003:
         api_key: {}
+    pricing:
+      prompt_per_1k: 0.02
+      completion_per_1k: 0.02
     "#,
         std::env::var("OPENAI_API_KEY").unwrap(),
     );
 
-    let wolfram_alpha = Box::new(tools::WolframAlpha::new(
+    let mut wolfram_alpha = Box::new(tools::WolframAlpha::new(
         std::env::var("WOLFRAM_APP_ID").unwrap(),
     ));
+    // Ask Wolfram|Alpha the user's question rather than their whole answer
+    wolfram_alpha.args_prompt = Some(
+        "The factual or mathematical question the user wants answered, as {\"i\": \"...\"}".into(),
+    );
 
     let signal_book = Box::new(tools::Signal {
         id: "signal_book".into(),
@@ -43,59 +406,358 @@ async fn main() -> Result<(), CognitionError> {
         signal: "Beep!".into(),
     });
 
-    let mut state = DecisionState::new(&config, decision_prompt_template, decision_nodes);
+    let mut state = DecisionState::new(&config, decision_prompt_template, decision_nodes)?;
     state.add_tool(wolfram_alpha);
     state.add_tool(signal_book);
+    state.add_observer(Box::new(ProgressPrinter));
+
+    // Open model connections before the first turn
+    if let Err(err) = state.warmup(false).await {
+        eprintln!("[!] {}", err);
+    }
+
+    // Replay a recorded transcript instead of reading from stdin
+    if let Some(path) = args.replay {
+        let transcript = Transcript::load(&path)?;
+        let replayed = replay(&transcript, &mut state).await?;
+        println!(
+            "Replay OK: {} turns, path: {}",
+            replayed.results.len(),
+            replayed.path().join(" -> ")
+        );
+        return Ok(());
+    }
+
+    // Spoken answers and turns
+    let voice = if args.voice {
+        Some(Voice::from_env()?)
+    } else {
+        None
+    };
+
+    let mut transcript = Transcript::new();
+
+    // Pasted multi-line text arrives as one answer instead of one per line
+    let paste_mode = std::io::stdin().is_terminal();
+    if paste_mode {
+        print!("{}", PASTE_ON);
+    }
 
     let mut user_input = None;
     while let Some(result) = run_decision(user_input, &mut state).await? {
-        // Print decision prompt, if any
-        if let Some(decision_prompt) = result.decision_prompt {
-            println!("\n++++++ PROMPT ++++++");
-            println!("{}", decision_prompt);
-            println!("--------------------");
+        // Record the result, saving after every turn so an interrupted session is kept
+        if let Some(path) = &args.record {
+            transcript.record(&state.prompt_privacy.result(&result));
+            transcript.save(path)?;
         }
 
-        // Print choice if any
-        if let Some(choice) = result.choice {
-            println!("\nCHOICE: {}", choice);
+        if args.json {
+            // One result per line, including the turn's `usage`
+            let line = serde_json::to_string(&result)
+                .map_err(|err| CognitionError(format!("Failed to serialize result: {}", err)))?;
+            println!("{}", line);
+        } else {
+            print_result(&state, &result);
+            if args.verbose {
+                println!("\nUSAGE: {}", result.usage);
+            }
         }
-
-        // Print tool results, if any
-        if let Some(tool_response) = result.tool_response {
-            println!("\nTOOL: [{}] {}", tool_response.id, tool_response.response);
+        if let Some(voice) = &voice {
+            voice.speak(&result).await?;
         }
 
-        // Print predictions, if any
-        if !result.predictions.is_empty() {
-            println!("\nPREDICTIONS:");
-            for prediction in result.predictions {
-                println!("  [✓] {}: {}", prediction.id, prediction.choice);
+        if result.decision_node.choices().is_empty() {
+            if !args.json {
+                println!("\n[!] No choices available. Exiting.");
             }
+            break;
         }
 
-        // Display the current decision text and choices
-        println!(
-            "\nDECISION: {}: {}",
-            result.decision_node.id, result.decision_node.text
-        );
-        println!("\n{}: {}", state.agent, result.decision_node.text);
-        for choice in result.decision_node.choices() {
-            println!("- {}", choice.text);
+        // Get user input, ending the session on end of input
+        if !args.json {
+            let choices = result.decision_node.choices().len();
+            if state.number_input && result.confirm.is_none() && choices > 1 {
+                print!("{} (1-{}): ", state.user, choices);
+            } else {
+                print!("{}: ", state.user);
+            }
+            std::io::stdout().flush().unwrap();
         }
-
-        if result.decision_node.choices().len() == 0 {
-            println!("\n[!] No choices available. Exiting.");
+        let secret = result.confirm.is_none() && result.decision_node.expects_secret();
+        user_input = match &voice {
+            // Secret answers are typed even in voice mode
+            _ if secret => read_secret(),
+            Some(voice) => voice.listen().await?,
+            None => read_input(),
+        };
+        if user_input.is_none() {
             break;
         }
+    }
+
+    if paste_mode {
+        print!("{}", PASTE_OFF);
+    }
+
+    // Session total
+    if args.verbose {
+        if args.json {
+            println!("{}", serde_json::json!({ "session_usage": state.usage() }));
+        } else {
+            println!("\nSESSION USAGE: {}", state.usage());
+        }
+    }
+    Ok(())
+}
+
+// Suggested edits of the tree at `tree` from the turn logs in `logs_dir`
+async fn suggest(
+    logs_dir: &str,
+    config: &str,
+    tree: &str,
+    template: &str,
+    options: &SuggestionOptions,
+    json: bool,
+    output: &Option<String>,
+) -> Result<(), CognitionError> {
+    let read = |path: &str| {
+        std::fs::read_to_string(path)
+            .map_err(|err| CognitionError(format!("Failed to read {}: {}", path, err)))
+    };
+    let decision_nodes: Vec<Decision> = serde_yaml::from_str(&read(tree)?)
+        .map_err(|err| CognitionError(format!("Failed to parse {}: {}", tree, err)))?;
+    let state = DecisionState::with_registry(
+        &read(config)?,
+        DecisionPromptTemplate::new(read(template)?),
+        decision_nodes.clone(),
+        &Default::default(),
+    )?;
+    let turns = TurnRecord::load_dir(logs_dir)?;
+
+    let suggestions = suggest_improvements(&state, &decision_nodes, &turns, options).await?;
+    let (suggested, suggestions) = apply_suggestions(&decision_nodes, &suggestions);
+    if json {
+        let json = serde_json::to_string_pretty(&suggestions)
+            .map_err(|err| CognitionError(format!("Failed to serialize suggestions: {}", err)))?;
+        println!("{}", json);
+    } else if suggestions.is_empty() {
+        println!("No suggestions");
+    } else {
+        println!("--- {}\n+++ {} (suggested)", tree, tree);
+        print!("{}", tree_diff(&decision_nodes, &suggested)?);
+    }
+
+    if let Some(path) = output {
+        let yaml = serde_yaml::to_string(&suggested)
+            .map_err(|err| CognitionError(format!("Failed to serialize tree: {}", err)))?;
+        std::fs::write(path, yaml)
+            .map_err(|err| CognitionError(format!("Failed to write {}: {}", path, err)))?;
+    }
+    Ok(())
+}
+
+// Evaluate every variant on the dataset, append the reports to the history
+// and print them with the accuracy of each variant's previous run
+async fn eval_compare(
+    dataset: &str,
+    variants: &[String],
+    tree: &str,
+    history: &str,
+    format: &ReportFormat,
+) -> Result<(), CognitionError> {
+    let read = |path: &str| {
+        std::fs::read_to_string(path)
+            .map_err(|err| CognitionError(format!("Failed to read {}: {}", path, err)))
+    };
+    let cases = EvalCase::load(dataset)?;
+    let decision_nodes: Vec<Decision> = serde_yaml::from_str(&read(tree)?)
+        .map_err(|err| CognitionError(format!("Failed to parse {}: {}", tree, err)))?;
+    let earlier = EvalReport::load_history(history)?;
 
-        // Get user input
-        let mut input = String::new();
-        print!("{}: ", state.user);
-        std::io::stdout().flush().unwrap();
-        std::io::stdin().read_line(&mut input).unwrap();
-        user_input = Some(input.trim().to_string());
+    let mut rows = vec![];
+    for variant in variants {
+        let (name, files) = variant.split_once('=').ok_or_else(|| {
+            CognitionError(format!("Variant {} is not NAME=CONFIG[,TEMPLATE]", variant))
+        })?;
+        let (config, template) = files
+            .split_once(',')
+            .unwrap_or((files, "decision_prompt_template.yaml"));
+        let config = read(config)?;
+        let template = read(template)?;
+        let report = evaluate(name, &cases, || {
+            DecisionState::with_registry(
+                &config,
+                DecisionPromptTemplate::new(template.clone()),
+                decision_nodes.clone(),
+                &Default::default(),
+            )
+        })
+        .await?;
+        report.append(history)?;
+        let previous_accuracy = earlier
+            .iter()
+            .rev()
+            .find(|earlier| earlier.variant == name)
+            .map(|earlier| earlier.accuracy);
+        rows.push((report, previous_accuracy));
     }
 
+    match format {
+        ReportFormat::Json => {
+            let rows: Vec<_> = rows
+                .iter()
+                .map(|(report, previous_accuracy)| {
+                    serde_json::json!({ "report": report, "previous_accuracy": previous_accuracy })
+                })
+                .collect();
+            let json = serde_json::to_string_pretty(&rows)
+                .map_err(|err| CognitionError(format!("Failed to serialize reports: {}", err)))?;
+            println!("{}", json);
+        }
+        ReportFormat::Csv => {
+            println!("variant,cases,correct,accuracy,previous_accuracy,mean_latency_ms,max_latency_ms,tokens,cost");
+            for (report, previous_accuracy) in &rows {
+                println!(
+                    "{},{},{},{:.4},{},{:.1},{},{},{}",
+                    report.variant,
+                    report.cases,
+                    report.correct,
+                    report.accuracy,
+                    previous_accuracy
+                        .map(|accuracy| format!("{:.4}", accuracy))
+                        .unwrap_or_default(),
+                    report.mean_latency_ms,
+                    report.max_latency_ms,
+                    report.usage.total_tokens(),
+                    report
+                        .usage
+                        .cost
+                        .map(|cost| format!("{:.4}", cost))
+                        .unwrap_or_default(),
+                );
+            }
+        }
+    }
     Ok(())
 }
+
+// Print a turn: the prompt, choices and tool calls, then the agent's text
+fn print_result(state: &DecisionState, result: &DecisionResult) {
+    // Print decision prompt, if any
+    if let Some(decision_prompt) = &result.decision_prompt {
+        println!("\n++++++ PROMPT ++++++");
+        println!("{}", decision_prompt);
+        println!("--------------------");
+    }
+
+    // Print choice if any
+    if let Some(choice) = &result.choice {
+        println!("\nCHOICE: {}", choice);
+    }
+
+    // Print tool results, if any
+    for tool_response in &result.tool_responses {
+        println!("\nTOOL: [{}] {}", tool_response.id, tool_response.response);
+    }
+    for outcome in &result.tool_outcomes {
+        if let ToolOutcome::Error {
+            tool_id, message, ..
+        } = outcome
+        {
+            println!("\nTOOL ERROR: [{}] {}", tool_id, message);
+        }
+    }
+
+    // Print predictions, if any
+    if !result.predictions.is_empty() {
+        println!("\nPREDICTIONS:");
+        for prediction in &result.predictions {
+            println!("  [✓] {}: {}", prediction.id, prediction.choice);
+        }
+    }
+
+    // Display informational nodes passed on the way
+    for interstitial in &result.interstitials {
+        let agent = &state.speaker_persona(interstitial).name;
+        println!("\n{}: {}", agent, interstitial.text);
+    }
+
+    // Display the current decision text and choices
+    println!(
+        "\nDECISION: {}: {}",
+        result.decision_node.id, result.decision_node.text
+    );
+    let agent = &state.speaker_persona(&result.decision_node).name;
+    if let Some(refusal) = &result.refusal {
+        println!("\n{}: {}", agent, refusal);
+    }
+
+    if let Some(confirm) = &result.confirm {
+        // A pending confirmation is asked instead of the node's question
+        println!("\n{}: {}", agent, confirm);
+    } else {
+        println!("\n{}: {}", agent, result.decision_node.text);
+        // Answering with a choice number skips the model
+        for (index, choice) in result.decision_node.choices().iter().enumerate() {
+            println!("{}. {}", index + 1, choice.text);
+        }
+    }
+}
+
+// Terminals wrap pasted text in these markers once bracketed paste is on
+const PASTE_ON: &str = "\x1b[?2004h";
+const PASTE_OFF: &str = "\x1b[?2004l";
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+// Lines between two `"""` lines are one multi-line answer
+const HEREDOC: &str = "\"\"\"";
+
+// Read a secret answer from stdin without echoing it on a terminal
+fn read_secret() -> Option<String> {
+    let terminal = std::io::stdin().is_terminal();
+    let stty = |arg: &str| {
+        if terminal {
+            let _ = std::process::Command::new("stty")
+                .arg(arg)
+                .stdin(std::process::Stdio::inherit())
+                .status();
+        }
+    };
+    stty("-echo");
+    let input = std::io::stdin().lines().next().and_then(Result::ok);
+    stty("echo");
+    if terminal {
+        println!();
+    }
+    input.map(|input| input.trim().to_string())
+}
+
+// Read one answer from stdin, keeping the newlines of pasted text and of
+// `"""` blocks. None at end of input.
+fn read_input() -> Option<String> {
+    let mut lines = std::io::stdin().lines().map_while(Result::ok);
+    let first = lines.next()?;
+
+    let input = if first.trim() == HEREDOC {
+        lines
+            .take_while(|line| line.trim() != HEREDOC)
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else if first.contains(PASTE_START) {
+        let mut pasted = vec![first];
+        while !pasted.last().is_some_and(|line| line.contains(PASTE_END)) {
+            match lines.next() {
+                Some(line) => pasted.push(line),
+                None => break,
+            }
+        }
+        pasted
+            .join("\n")
+            .replace(PASTE_START, "")
+            .replace(PASTE_END, "")
+    } else {
+        first
+    };
+    Some(input.trim().to_string())
+}
@@ -0,0 +1,151 @@
+// `--voice` mode: every turn is spoken and answers are recorded and transcribed.
+// Backends come from the environment: whisper.cpp with `WHISPER_CPP_MODEL`,
+// else the Whisper API, and ElevenLabs with `ELEVENLABS_API_KEY` and
+// `ELEVENLABS_VOICE_ID`, else OpenAI speech. Audio is recorded and played with
+// sox unless `RECORD_COMMAND` and `PLAY_COMMAND` say otherwise, `{file}`
+// standing for the audio file.
+use cognition::{CognitionError, DecisionResult};
+
+#[cfg(feature = "speech")]
+use cognition::speech::{self, SpeechToText, TextToSpeech};
+
+// Record until 1.5 seconds of silence
+#[cfg(feature = "speech")]
+const RECORD_COMMAND: &str = "rec -q -c 1 -r 16000 {file} silence 1 0.1 1% 1 1.5 1%";
+#[cfg(feature = "speech")]
+const PLAY_COMMAND: &str = "play -q {file}";
+
+#[cfg(feature = "speech")]
+pub struct Voice {
+    stt: Box<dyn SpeechToText>,
+    tts: Box<dyn TextToSpeech>,
+    record_command: String,
+    play_command: String,
+}
+
+#[cfg(feature = "speech")]
+impl Voice {
+    pub fn from_env() -> Result<Self, CognitionError> {
+        let env = |name: &str| std::env::var(name).ok();
+        let openai_api_key = env("OPENAI_API_KEY");
+        let mut config = serde_json::json!({
+            "speech": {
+                "whisper_api": { "api_key": openai_api_key },
+                "openai": { "api_key": openai_api_key },
+            }
+        });
+        if let Some(model_path) = env("WHISPER_CPP_MODEL") {
+            config["speech"]["stt"] = "whisper_cpp".into();
+            config["speech"]["whisper_cpp"] = serde_json::json!({
+                "model_path": model_path,
+                "binary": env("WHISPER_CPP_BINARY").unwrap_or_else(|| "whisper-cli".to_string()),
+            });
+        }
+        if let (Some(api_key), Some(voice_id)) =
+            (env("ELEVENLABS_API_KEY"), env("ELEVENLABS_VOICE_ID"))
+        {
+            config["speech"]["tts"] = "elevenlabs".into();
+            config["speech"]["elevenlabs"] =
+                serde_json::json!({ "api_key": api_key, "voice_id": voice_id });
+        }
+        let config = config.to_string();
+
+        Ok(Self {
+            stt: speech::speech_to_text(&config)?,
+            tts: speech::text_to_speech(&config)?,
+            record_command: env("RECORD_COMMAND").unwrap_or_else(|| RECORD_COMMAND.to_string()),
+            play_command: env("PLAY_COMMAND").unwrap_or_else(|| PLAY_COMMAND.to_string()),
+        })
+    }
+
+    // Say a turn's informational nodes, its refusal, and its confirmation
+    // question or node text
+    pub async fn speak(&self, result: &DecisionResult) -> Result<(), CognitionError> {
+        let mut lines: Vec<&str> = result
+            .interstitials
+            .iter()
+            .map(|interstitial| interstitial.text.as_str())
+            .collect();
+        lines.extend(result.refusal.as_deref());
+        lines.push(
+            result
+                .confirm
+                .as_deref()
+                .unwrap_or(&result.decision_node.text),
+        );
+        let text = lines.join("\n");
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+
+        let audio = self.tts.synthesize(&text).await?;
+        let file = temp_file(self.tts.format());
+        std::fs::write(&file, audio)
+            .map_err(|err| CognitionError(format!("Failed to write audio: {}", err)))?;
+        let played = run(&self.play_command, &file);
+        let _ = std::fs::remove_file(&file);
+        played
+    }
+
+    // Record and transcribe one answer, printed once heard. Silence is
+    // listened to again.
+    pub async fn listen(&self) -> Result<Option<String>, CognitionError> {
+        loop {
+            let file = temp_file("wav");
+            let recorded = run(&self.record_command, &file).and_then(|_| {
+                std::fs::read(&file)
+                    .map_err(|err| CognitionError(format!("Failed to read recording: {}", err)))
+            });
+            let _ = std::fs::remove_file(&file);
+            let answer = self.stt.transcribe(&recorded?).await?;
+            if !answer.is_empty() {
+                println!("{}", answer);
+                return Ok(Some(answer));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "speech")]
+fn temp_file(extension: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "cognition-voice-{}.{}",
+        std::process::id(),
+        extension
+    ))
+}
+
+// Run a record or play command through the shell
+#[cfg(feature = "speech")]
+fn run(command: &str, file: &std::path::Path) -> Result<(), CognitionError> {
+    let command = command.replace("{file}", &file.display().to_string());
+    let status = std::process::Command::new("sh")
+        .args(["-c", &command])
+        .status()
+        .map_err(|err| CognitionError(format!("Failed to run {}: {}", command, err)))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CognitionError(format!("{} failed: {}", command, status)))
+    }
+}
+
+#[cfg(not(feature = "speech"))]
+pub struct Voice;
+
+#[cfg(not(feature = "speech"))]
+impl Voice {
+    pub fn from_env() -> Result<Self, CognitionError> {
+        Err(CognitionError(
+            "--voice needs the `speech` feature".to_string(),
+        ))
+    }
+
+    pub async fn speak(&self, _result: &DecisionResult) -> Result<(), CognitionError> {
+        Ok(())
+    }
+
+    pub async fn listen(&self) -> Result<Option<String>, CognitionError> {
+        Ok(None)
+    }
+}
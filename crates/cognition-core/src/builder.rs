@@ -0,0 +1,217 @@
+use crate::{
+    validate_tree, Choice, ChoicesMode, Decision, InputKind, InputLimit, InputLimitStrategy,
+    NodeKind, PipelineStep, PredictionPolicy, ToolIds, Transition, TreeError,
+};
+use alloc::{string::ToString, vec, vec::Vec};
+
+// Entry point of the fluent tree builder:
+//
+//     Tree::node("start")
+//         .text("Hello! How can I help you today?")
+//         .choice("Ask Wolfram|Alpha", "ask_wolfram_alpha")
+//         .node("ask_wolfram_alpha")
+//         .text("Asked Wolfram|Alpha...")
+//         .tool("wolfram_alpha")
+//         .choice("Done", "exit")
+//         .build()
+pub struct Tree;
+
+impl Tree {
+    pub fn node(id: &str) -> TreeBuilder {
+        TreeBuilder::new().node(id)
+    }
+}
+
+// Decision nodes being built, settings apply to the most recent `node`
+#[derive(Default)]
+pub struct TreeBuilder {
+    nodes: Vec<Decision>,
+}
+
+impl TreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Start a new node
+    pub fn node(mut self, id: &str) -> Self {
+        self.nodes.push(Decision {
+            id: id.to_string(),
+            ..Decision::default()
+        });
+        self
+    }
+
+    pub fn text(self, text: &str) -> Self {
+        self.with_current(|node| node.text = text.to_string())
+    }
+
+    pub fn predicted_text(self, text: &str) -> Self {
+        self.with_current(|node| node.predicted_text = Some(text.to_string()))
+    }
+
+    // Add a choice leading to `next_id`, e.g. a node ID, `exit` or `call:<id>`
+    pub fn choice(self, text: &str, next_id: &str) -> Self {
+        self.with_current(|node| {
+            node.choices
+                .get_or_insert_with(Vec::new)
+                .push(Choice::new(text, Transition::from(next_id.to_string())))
+        })
+    }
+
+    // Only offer the node's last choice while `condition` holds
+    pub fn choice_requires(self, condition: &str) -> Self {
+        self.with_current(|node| {
+            if let Some(choice) = node.choices.iter_mut().flatten().last() {
+                choice.requires = Some(condition.to_string());
+            }
+        })
+    }
+
+    // Add an A/B variant, making the node pick one of its choices by weight
+    pub fn variant(self, text: &str, next_id: &str, weight: f64) -> Self {
+        self.with_current(|node| {
+            let mut choice = Choice::new(text, Transition::from(next_id.to_string()));
+            choice.weight = Some(weight);
+            node.choices_mode = Some(ChoicesMode::WeightedRandom);
+            node.choices.get_or_insert_with(Vec::new).push(choice);
+        })
+    }
+
+    // Add a tool, several tools run concurrently
+    pub fn tool(self, id: &str) -> Self {
+        self.with_current(|node| {
+            node.tool = Some(match node.tool.take() {
+                None => ToolIds::One(id.to_string()),
+                Some(ToolIds::One(first)) => ToolIds::Many(vec![first, id.to_string()]),
+                Some(ToolIds::Many(mut ids)) => {
+                    ids.push(id.to_string());
+                    ToolIds::Many(ids)
+                }
+            })
+        })
+    }
+
+    // Add a pipeline step, getting the previous step's output as its input
+    pub fn pipeline_step(self, id: &str) -> Self {
+        self.with_current(|node| {
+            node.pipeline
+                .get_or_insert_with(Vec::new)
+                .push(PipelineStep::Tool(id.to_string()))
+        })
+    }
+
+    pub fn predict(self, policy: PredictionPolicy) -> Self {
+        self.with_current(|node| node.predict = Some(policy))
+    }
+
+    pub fn reset(self, reset: bool) -> Self {
+        self.with_current(|node| node.reset = Some(reset))
+    }
+
+    pub fn return_to_caller(self) -> Self {
+        self.with_current(|node| node.return_to_caller = Some(true))
+    }
+
+    pub fn script(self, script: &str) -> Self {
+        self.with_current(|node| node.script = Some(script.to_string()))
+    }
+
+    pub fn secret(self) -> Self {
+        self.with_current(|node| node.input = Some(InputKind::Secret))
+    }
+
+    // Answers longer than `max_chars` are rejected, truncated or summarized
+    pub fn max_input_chars(self, max_chars: usize, strategy: InputLimitStrategy) -> Self {
+        self.with_current(|node| {
+            node.input_limit = Some(InputLimit {
+                max_chars: Some(max_chars),
+                strategy,
+                ..InputLimit::default()
+            })
+        })
+    }
+
+    pub fn generate(self) -> Self {
+        self.with_current(|node| node.kind = Some(NodeKind::Generate))
+    }
+
+    pub fn next(self, next_id: &str) -> Self {
+        self.with_current(|node| node.next = Some(Transition::from(next_id.to_string())))
+    }
+
+    pub fn default_next(self, next_id: &str) -> Self {
+        self.with_current(|node| node.default_next_id = Some(Transition::from(next_id.to_string())))
+    }
+
+    pub fn on_tool_error(self, next_id: &str) -> Self {
+        self.with_current(|node| node.on_tool_error = Some(Transition::from(next_id.to_string())))
+    }
+
+    // Only enter the node while `condition` holds, see `Decision::requires`
+    pub fn requires(self, condition: &str) -> Self {
+        self.with_current(|node| node.requires = Some(condition.to_string()))
+    }
+
+    pub fn on_fail(self, next_id: &str) -> Self {
+        self.with_current(|node| node.on_fail = Some(Transition::from(next_id.to_string())))
+    }
+
+    pub fn max_latency_ms(self, max_latency_ms: u64) -> Self {
+        self.with_current(|node| node.max_latency_ms = Some(max_latency_ms))
+    }
+
+    pub fn speaker(self, agent: &str) -> Self {
+        self.with_current(|node| node.speaker = Some(agent.to_string()))
+    }
+
+    // The validated tree, see `validate_tree`
+    pub fn build(self) -> Result<Vec<Decision>, Vec<TreeError>> {
+        validate_tree(&self.nodes)?;
+        Ok(self.nodes)
+    }
+
+    fn with_current(mut self, update: impl FnOnce(&mut Decision)) -> Self {
+        match self.nodes.last_mut() {
+            Some(node) => update(node),
+            // Settings before the first node start an unnamed one, reported by `build`
+            None => {
+                let mut node = Decision::default();
+                update(&mut node);
+                self.nodes.push(node);
+            }
+        }
+        self
+    }
+}
+
+// Define a tree in Rust, checked for syntax at compile time and validated like
+// YAML trees at runtime. Node IDs and targets are identifiers:
+//
+//     let nodes = decision_tree! {
+//         start: "Hello! How can I help you today?" {
+//             "I want to buy something." => buy_something,
+//             "Nothing, thanks." => exit,
+//         }
+//         buy_something: "What would you like to buy?" {
+//             "Start over" => start,
+//         }
+//     }?;
+#[macro_export]
+macro_rules! decision_tree {
+    ($($id:ident : $text:literal { $($choice:literal => $next:ident),* $(,)? })*) => {
+        $crate::TreeBuilder::new()
+            $(
+                .node(stringify!($id))
+                .text($text)
+                $(.choice($choice, stringify!($next)))*
+            )*
+            .build()
+    };
+}
+
+// Nodes embedded by `include_tree!`, parsed and validated when compiled in
+#[doc(hidden)]
+pub fn embedded_tree(json: &str) -> Vec<Decision> {
+    serde_json::from_str(json).expect("Embedded tree was validated at compile time")
+}
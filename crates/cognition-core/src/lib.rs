@@ -0,0 +1,24 @@
+// Decision tree data structures, validation and template rendering, without
+// any async runtime or HTTP dependencies
+#![no_std]
+
+extern crate alloc;
+
+mod builder;
+mod prediction;
+mod templates;
+mod transition;
+mod tree;
+mod validate;
+
+pub use builder::{embedded_tree, Tree, TreeBuilder};
+pub use prediction::PredictionPolicy;
+pub use templates::decision::DecisionPromptTemplate;
+pub use templates::expression::{condition, ExpressionError};
+pub use templates::variables::render_variables;
+pub use transition::{Transition, EXIT_ID, START_ID};
+pub use tree::{
+    AnalyticsEvent, Choice, ChoicesMode, Compression, Decision, InputKind, InputLimit,
+    InputLimitStrategy, NodeKind, PipelineStep, StepFailure, ToolIds,
+};
+pub use validate::{validate_tree, TreeError};
@@ -0,0 +1,87 @@
+use alloc::string::{String, ToString};
+use core::fmt::{self, Display};
+use serde::{Deserialize, Serialize};
+
+// How far the engine follows up the user's choice with choices it predicts
+// from the same answer. In YAML a policy name; nodes also take `predict: false`
+// for `off` and `predict: true` for `aggressive`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(try_from = "PolicyValue", into = "String")]
+pub enum PredictionPolicy {
+    // Only the user's own choice is made
+    Off,
+    // Predicted choices are only taken with a reported confidence over the threshold
+    Conservative,
+    // Predicted choices are taken unless their reported confidence is under the threshold
+    #[default]
+    Aggressive,
+}
+
+impl PredictionPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "off" => Some(Self::Off),
+            "conservative" => Some(Self::Conservative),
+            "aggressive" => Some(Self::Aggressive),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PolicyValue {
+    Enabled(bool),
+    Name(String),
+}
+
+impl TryFrom<PolicyValue> for PredictionPolicy {
+    type Error = String;
+
+    fn try_from(value: PolicyValue) -> Result<Self, String> {
+        match value {
+            PolicyValue::Enabled(false) => Ok(Self::Off),
+            PolicyValue::Enabled(true) => Ok(Self::Aggressive),
+            PolicyValue::Name(name) => Self::parse(&name).ok_or_else(|| {
+                alloc::format!(
+                    "unknown prediction policy '{}', expected off, conservative or aggressive",
+                    name
+                )
+            }),
+        }
+    }
+}
+
+impl From<PredictionPolicy> for String {
+    fn from(policy: PredictionPolicy) -> Self {
+        policy.to_string()
+    }
+}
+
+impl Display for PredictionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Conservative => write!(f, "conservative"),
+            Self::Aggressive => write!(f, "aggressive"),
+        }
+    }
+}
+
+// A policy name or a boolean in the schema, like in YAML
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for PredictionPolicy {
+    fn schema_name() -> alloc::borrow::Cow<'static, str> {
+        "PredictionPolicy".into()
+    }
+
+    fn json_schema(_generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "Prediction policy: `off`, `conservative` or `aggressive`, `false` for off",
+            "anyOf": [
+                { "type": "string", "enum": ["off", "conservative", "aggressive"] },
+                { "type": "boolean" },
+            ],
+        })
+    }
+}
@@ -0,0 +1,36 @@
+use crate::render_variables;
+use alloc::{collections::BTreeMap, string::String, string::ToString};
+
+// YAML prompt_decision template object
+pub struct DecisionPromptTemplate(String);
+
+impl DecisionPromptTemplate {
+    pub fn new(content: String) -> Self {
+        Self(content)
+    }
+
+    pub fn content(&self) -> &str {
+        &self.0
+    }
+
+    // Format the decision prompt template with the given parameters
+    pub fn format(
+        &self,
+        history: &str,
+        decision_prompt: &str,
+        choices: &str,
+        user_input: &str,
+    ) -> String {
+        let mut variables = BTreeMap::new();
+        variables.insert("history".to_string(), history.to_string());
+        variables.insert("decision_prompt".to_string(), decision_prompt.to_string());
+        variables.insert("choices".to_string(), choices.to_string());
+        variables.insert("user_input".to_string(), user_input.to_string());
+        self.render(&variables)
+    }
+
+    // Render the template with any set of variables
+    pub fn render(&self, variables: &BTreeMap<String, String>) -> String {
+        render_variables(&self.0, variables)
+    }
+}
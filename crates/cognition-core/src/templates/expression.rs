@@ -0,0 +1,359 @@
+use alloc::{collections::BTreeMap, format, string::String, string::ToString, vec::Vec};
+use core::fmt::{self, Display};
+
+// Parentheses, `!`, `-` and function calls nested deeper fail to evaluate
+const MAX_DEPTH: usize = 64;
+
+// Simple expressions inside `{{ }}` placeholders, e.g. `{{ vars.count + 1 }}` or
+// `{{ upper(vars.name) }}`. Numbers support `+ - * / %`, `+` joins strings, and
+// a few functions transform values. Comparisons and `&& || !` give `true` or
+// `false`. Anything invalid or unknown fails, and the placeholder is then left
+// as it is.
+pub fn evaluate(
+    expression: &str,
+    variables: &BTreeMap<String, String>,
+) -> Result<String, ExpressionError> {
+    parse(expression, variables).map(|value| value.to_text())
+}
+
+// Whether an expression holds, e.g. `vars.authenticated && len(vars.cart) > 0`.
+// Zero, empty text and `false` do not.
+pub fn condition(
+    expression: &str,
+    variables: &BTreeMap<String, String>,
+) -> Result<bool, ExpressionError> {
+    parse(expression, variables).map(|value| value.truthy())
+}
+
+// Why an expression could not be evaluated
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExpressionError {
+    UnknownVariable(String),
+    // Malformed, or applying an operator or function to values it does not
+    // take, e.g. dividing by zero, failing at the given character
+    Invalid { position: usize },
+}
+
+impl Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionError::UnknownVariable(name) => write!(f, "Unknown variable '{}'", name),
+            ExpressionError::Invalid { position } => {
+                write!(f, "Invalid expression at character {}", position + 1)
+            }
+        }
+    }
+}
+
+fn parse(expression: &str, variables: &BTreeMap<String, String>) -> Result<Value, ExpressionError> {
+    let mut parser = Parser {
+        chars: expression.chars().collect(),
+        position: 0,
+        depth: 0,
+        variables,
+        unknown: None,
+    };
+    let value = parser.or();
+    parser.skip_whitespace();
+    match (value, parser.unknown) {
+        (Some(value), _) if parser.position == parser.chars.len() => Ok(value),
+        (_, Some(name)) => Err(ExpressionError::UnknownVariable(name)),
+        _ => Err(ExpressionError::Invalid {
+            position: parser.position,
+        }),
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl Value {
+    fn from_variable(value: &str) -> Self {
+        match value.trim() {
+            "true" => return Value::Bool(true),
+            "false" => return Value::Bool(false),
+            _ => {}
+        }
+        match value.trim().parse::<f64>() {
+            Ok(number) => Value::Number(number),
+            Err(_) => Value::Text(value.to_string()),
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            Value::Text(_) | Value::Bool(_) => None,
+        }
+    }
+
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Number(number) => *number != 0.0,
+            Value::Text(text) => !text.is_empty(),
+            Value::Bool(value) => *value,
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            Value::Bool(value) => value.to_string(),
+            Value::Number(number) if *number == (*number as i64) as f64 => {
+                format!("{}", *number as i64)
+            }
+            Value::Number(number) => format!("{}", number),
+            Value::Text(text) => text.clone(),
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    depth: usize,
+    variables: &'a BTreeMap<String, String>,
+    // Variable the expression failed on
+    unknown: Option<String>,
+}
+
+impl Parser<'_> {
+    // Parse one level deeper, failing past `MAX_DEPTH`
+    fn nested(&mut self, parse: fn(&mut Self) -> Option<Value>) -> Option<Value> {
+        if self.depth == MAX_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    // Next non-whitespace character, consumed if it is `expected`
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Next non-whitespace characters, consumed if they are `expected`
+    fn eat_str(&mut self, expected: &str) -> bool {
+        self.skip_whitespace();
+        let end = self.position + expected.chars().count();
+        let matches = self
+            .chars
+            .get(self.position..end)
+            .is_some_and(|chars| chars.iter().copied().eq(expected.chars()));
+        if matches {
+            self.position = end;
+        }
+        matches
+    }
+
+    // or := and ('||' and)*
+    fn or(&mut self) -> Option<Value> {
+        let mut value = self.and()?;
+        while self.eat_str("||") {
+            let right = self.and()?;
+            value = Value::Bool(value.truthy() || right.truthy());
+        }
+        Some(value)
+    }
+
+    // and := not ('&&' not)*
+    fn and(&mut self) -> Option<Value> {
+        let mut value = self.not()?;
+        while self.eat_str("&&") {
+            let right = self.not()?;
+            value = Value::Bool(value.truthy() && right.truthy());
+        }
+        Some(value)
+    }
+
+    // not := '!' not | comparison
+    fn not(&mut self) -> Option<Value> {
+        if self.eat('!') {
+            return Some(Value::Bool(!self.nested(Self::not)?.truthy()));
+        }
+        self.comparison()
+    }
+
+    // comparison := expression (('==' | '!=' | '<=' | '>=' | '<' | '>') expression)?
+    fn comparison(&mut self) -> Option<Value> {
+        let left = self.expression()?;
+        let Some(operator) = ["==", "!=", "<=", ">=", "<", ">"]
+            .into_iter()
+            .find(|operator| self.eat_str(operator))
+        else {
+            return Some(left);
+        };
+        let right = self.expression()?;
+        let holds = match (operator, left.number(), right.number()) {
+            ("==", Some(left), Some(right)) => left == right,
+            ("!=", Some(left), Some(right)) => left != right,
+            ("==", _, _) => left.to_text() == right.to_text(),
+            ("!=", _, _) => left.to_text() != right.to_text(),
+            ("<=", Some(left), Some(right)) => left <= right,
+            (">=", Some(left), Some(right)) => left >= right,
+            ("<", Some(left), Some(right)) => left < right,
+            (">", Some(left), Some(right)) => left > right,
+            _ => return None,
+        };
+        Some(Value::Bool(holds))
+    }
+
+    // expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Option<Value> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                let right = self.term()?;
+                value = match (value.number(), right.number()) {
+                    (Some(left), Some(right)) => Value::Number(left + right),
+                    _ => Value::Text(value.to_text() + &right.to_text()),
+                };
+            } else if self.eat('-') {
+                let right = self.term()?.number()?;
+                value = Value::Number(value.number()? - right);
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    // term := factor (('*' | '/' | '%') factor)*
+    fn term(&mut self) -> Option<Value> {
+        let mut value = self.factor()?;
+        loop {
+            let operator = if self.eat('*') {
+                '*'
+            } else if self.eat('/') {
+                '/'
+            } else if self.eat('%') {
+                '%'
+            } else {
+                return Some(value);
+            };
+            let left = value.number()?;
+            let right = self.factor()?.number()?;
+            value = Value::Number(match operator {
+                '*' => left * right,
+                _ if right == 0.0 => return None,
+                '/' => left / right,
+                _ => left % right,
+            });
+        }
+    }
+
+    // factor := number | string | `true` | `false` | name | name '(' arguments ')' | '(' or ')' | '-' factor
+    fn factor(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        let next = self.peek()?;
+
+        if self.eat('(') {
+            let value = self.nested(Self::or)?;
+            return self.eat(')').then_some(value);
+        }
+        if self.eat('-') {
+            return Some(Value::Number(-self.nested(Self::factor)?.number()?));
+        }
+        if next == '"' || next == '\'' {
+            return self.string(next);
+        }
+        if next.is_ascii_digit() {
+            let start = self.position;
+            while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                self.position += 1;
+            }
+            let number: String = self.chars[start..self.position].iter().collect();
+            return number.parse().ok().map(Value::Number);
+        }
+
+        let name = self.name()?;
+        if self.eat('(') {
+            let mut arguments = Vec::new();
+            if !self.eat(')') {
+                loop {
+                    arguments.push(self.nested(Self::expression)?);
+                    if self.eat(')') {
+                        break;
+                    }
+                    if !self.eat(',') {
+                        return None;
+                    }
+                }
+            }
+            return call(&name, &arguments);
+        }
+        match name.as_str() {
+            "true" => return Some(Value::Bool(true)),
+            "false" => return Some(Value::Bool(false)),
+            _ => {}
+        }
+        match self.variables.get(&name) {
+            Some(value) => Some(Value::from_variable(value)),
+            None => {
+                self.unknown = Some(name);
+                None
+            }
+        }
+    }
+
+    fn string(&mut self, quote: char) -> Option<Value> {
+        self.position += 1;
+        let start = self.position;
+        while self.peek()? != quote {
+            self.position += 1;
+        }
+        let text = self.chars[start..self.position].iter().collect();
+        self.position += 1;
+        Some(Value::Text(text))
+    }
+
+    // Variable or function name, e.g. `vars.count` or `upper`
+    fn name(&mut self) -> Option<String> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        {
+            self.position += 1;
+        }
+        (self.position > start).then(|| self.chars[start..self.position].iter().collect())
+    }
+}
+
+fn call(name: &str, arguments: &[Value]) -> Option<Value> {
+    let value = match (name, arguments) {
+        ("upper", [value]) => Value::Text(value.to_text().to_uppercase()),
+        ("lower", [value]) => Value::Text(value.to_text().to_lowercase()),
+        ("trim", [value]) => Value::Text(value.to_text().trim().to_string()),
+        ("len", [value]) => Value::Number(value.to_text().chars().count() as f64),
+        ("round", [value]) => {
+            let number = value.number()?;
+            let offset = if number < 0.0 { -0.5 } else { 0.5 };
+            Value::Number(((number + offset) as i64) as f64)
+        }
+        ("min", [left, right]) => Value::Number(left.number()?.min(right.number()?)),
+        ("max", [left, right]) => Value::Number(left.number()?.max(right.number()?)),
+        _ => return None,
+    };
+    Some(value)
+}
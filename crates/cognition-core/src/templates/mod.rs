@@ -0,0 +1,3 @@
+pub mod decision;
+pub mod expression;
+pub mod variables;
@@ -0,0 +1,36 @@
+use super::expression::evaluate;
+use alloc::{collections::BTreeMap, string::String};
+
+// Replace `{{name}}` placeholders with template variables in a single pass, so
+// values containing placeholders (like user input) are never expanded.
+// Placeholders can also hold expressions, see `expression::evaluate`.
+// Unknown placeholders are left as they are.
+pub fn render_variables(text: &str, variables: &BTreeMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let placeholder = &rest[start + 2..];
+        match placeholder.find("}}") {
+            Some(end) => {
+                let name = placeholder[..end].trim();
+                match variables.get(name) {
+                    Some(value) => rendered.push_str(value),
+                    None => match evaluate(name, variables) {
+                        Ok(value) => rendered.push_str(&value),
+                        Err(_) => rendered.push_str(&rest[start..start + end + 4]),
+                    },
+                }
+                rest = &placeholder[end + 2..];
+            }
+            None => {
+                rendered.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
@@ -0,0 +1,66 @@
+use alloc::string::{String, ToString};
+use core::fmt::{self, Display};
+use serde::{Deserialize, Serialize};
+
+// Reserved node IDs
+pub const START_ID: &str = "start";
+pub const EXIT_ID: &str = "exit";
+
+// Where a choice leads. In YAML this stays a plain `next_id` string:
+// `exit`, `start`, `back`, `call:<node_id>` or any other node ID.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(from = "String", into = "String")]
+pub enum Transition {
+    Node(String),
+    Exit,
+    Restart,
+    Back,
+    Call(String),
+}
+
+// A plain string in the schema, like in YAML
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Transition {
+    fn schema_name() -> alloc::borrow::Cow<'static, str> {
+        "Transition".into()
+    }
+
+    fn json_schema(_generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "description": "Next node: `exit`, `start`, `back`, `call:<node_id>` or a node ID",
+        })
+    }
+}
+
+impl From<String> for Transition {
+    fn from(next_id: String) -> Self {
+        if let Some(subtree_id) = next_id.strip_prefix("call:") {
+            return Transition::Call(subtree_id.to_string());
+        }
+        match next_id.as_str() {
+            EXIT_ID => Transition::Exit,
+            START_ID => Transition::Restart,
+            "back" => Transition::Back,
+            _ => Transition::Node(next_id),
+        }
+    }
+}
+
+impl From<Transition> for String {
+    fn from(transition: Transition) -> Self {
+        transition.to_string()
+    }
+}
+
+impl Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transition::Node(id) => write!(f, "{}", id),
+            Transition::Exit => write!(f, "{}", EXIT_ID),
+            Transition::Restart => write!(f, "{}", START_ID),
+            Transition::Back => write!(f, "back"),
+            Transition::Call(id) => write!(f, "call:{}", id),
+        }
+    }
+}
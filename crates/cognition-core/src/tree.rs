@@ -0,0 +1,336 @@
+use crate::{PredictionPolicy, Transition};
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// YAML decision node structure, unset fields are left out when written back
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Decision {
+    pub id: String,
+    pub text: String,
+    // Generate nodes have the model answer the user, with `text` as its instructions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<NodeKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub predicted_text: Option<String>,
+    // Condition on the session's template variables for entering the node,
+    // e.g. `vars.authenticated`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requires: Option<String>,
+    // Where entering the node leads when `requires` is false, instead of
+    // staying on the node the session came from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_fail: Option<Transition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool: Option<ToolIds>,
+    // Tools run one after the other once `tool` ran, each step getting the
+    // previous step's output. Only the last step's response is the node's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<Vec<PipelineStep>>,
+    // Prediction of the node's choice, the session's policy if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub predict: Option<PredictionPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<Choice>>,
+    // How the choice is made, by the user's answer unless set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choices_mode: Option<ChoicesMode>,
+    // Where a node without choices moves on to once its text is shown and its
+    // tools ran, without waiting for the user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<Transition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analytics_event: Option<AnalyticsEvent>,
+    #[serde(rename = "return", skip_serializing_if = "Option::is_none")]
+    pub return_to_caller: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_next_id: Option<Transition>,
+    // Where to go when one of the node's tools fails, instead of staying on the node
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_tool_error: Option<Transition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_latency_ms: Option<u64>,
+    // Rhai script run on entering the node, see the `rhai` feature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    // Kind of answer the node expects, plain text if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<InputKind>,
+    // Longest answer the node takes, the config's `input_limit` if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_limit: Option<InputLimit>,
+    // Agent from the config's `agents` that says the node, the default agent if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+}
+
+impl Decision {
+    pub fn choices(&self) -> Vec<&Choice> {
+        self.choices.iter().flatten().collect()
+    }
+
+    pub fn expects_secret(&self) -> bool {
+        self.input == Some(InputKind::Secret)
+    }
+
+    // Whether the model composes the node's answer
+    pub fn generates(&self) -> bool {
+        self.kind == Some(NodeKind::Generate)
+    }
+
+    // A/B split node, taking one of its choices by their weights
+    pub fn picks_randomly(&self) -> bool {
+        self.choices_mode == Some(ChoicesMode::WeightedRandom) && !self.choices().is_empty()
+    }
+
+    // Informational node, moving on to `next` by itself
+    pub fn passes_through(&self) -> bool {
+        self.choices().is_empty() && self.next.is_some()
+    }
+
+    // IDs of the tools the node runs
+    pub fn tools(&self) -> Vec<&String> {
+        match &self.tool {
+            Some(ToolIds::One(id)) => vec![id],
+            Some(ToolIds::Many(ids)) => ids.iter().collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn pipeline(&self) -> Vec<&PipelineStep> {
+        self.pipeline.iter().flatten().collect()
+    }
+}
+
+// What the engine does with a node's text
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    // Show the text and route the answer to a choice
+    #[default]
+    Choose,
+    // Show an answer the model composes from the text, the history and the
+    // node's tool responses
+    Generate,
+}
+
+// How a node's choice is made
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ChoicesMode {
+    // By the user's answer
+    #[default]
+    Input,
+    // At random by the choices' `weight`, without input, once per session.
+    // The choice taken is the session's variant of the node.
+    WeightedRandom,
+}
+
+// Answer a node expects
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum InputKind {
+    #[default]
+    Text,
+    // Codes or account numbers, kept out of the history, prompts and logs
+    Secret,
+}
+
+// Longest answer in characters and/or estimated tokens, and what is done with
+// longer ones before they reach the history
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct InputLimit {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_chars: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    #[serde(default)]
+    pub strategy: InputLimitStrategy,
+    // Refusal of rejected answers, can refer to `{{max_chars}}`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl InputLimit {
+    // Longest answer in characters, tokens estimated at four characters each
+    pub fn char_limit(&self) -> Option<usize> {
+        let from_tokens = self.max_tokens.map(|tokens| tokens * 4);
+        match (self.max_chars, from_tokens) {
+            (Some(chars), Some(tokens)) => Some(chars.min(tokens)),
+            (chars, tokens) => chars.or(tokens),
+        }
+    }
+}
+
+// What happens to an answer over its node's input limit
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum InputLimitStrategy {
+    // Refuse the answer and ask the node again
+    #[default]
+    Reject,
+    // Drop the beginning, keeping the end of the answer
+    TruncateHead,
+    // Drop the end, keeping the beginning of the answer
+    TruncateTail,
+    // Have the model summarize the answer within the limit
+    Summarize,
+}
+
+// `tool:` is either a single tool ID or a list of tools run concurrently
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum ToolIds {
+    One(String),
+    Many(Vec<String>),
+}
+
+// Step of a tool pipeline, a tool ID or a tool with its parameters
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum PipelineStep {
+    Tool(String),
+    Step {
+        tool: String,
+        // Template of the tool's input, can refer to `{{previous}}`, the
+        // previous step's output, `{{steps.<tool>}}` and the fields of JSON
+        // outputs like `{{steps.geocode.lat}}`. The previous output if unset.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        params: Option<String>,
+        #[serde(default, skip_serializing_if = "is_default")]
+        on_error: StepFailure,
+    },
+}
+
+impl PipelineStep {
+    pub fn tool(&self) -> &str {
+        match self {
+            PipelineStep::Tool(tool) | PipelineStep::Step { tool, .. } => tool,
+        }
+    }
+
+    pub fn params(&self) -> Option<&str> {
+        match self {
+            PipelineStep::Tool(_) => None,
+            PipelineStep::Step { params, .. } => params.as_deref(),
+        }
+    }
+
+    pub fn on_error(&self) -> StepFailure {
+        match self {
+            PipelineStep::Tool(_) => StepFailure::default(),
+            PipelineStep::Step { on_error, .. } => *on_error,
+        }
+    }
+}
+
+// What a failing pipeline step does to the rest of the pipeline
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum StepFailure {
+    // Stop the pipeline, failing the node's tools
+    #[default]
+    Stop,
+    // Log the failure and pass the previous output on to the next step
+    Skip,
+}
+
+// Choice structure within a decision node
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Choice {
+    #[serde(rename = "choice")]
+    pub text: String,
+    pub next_id: Transition,
+    // Node the session continues at when the subtree called with
+    // `next_id: call:<id>` returns, the calling node if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub return_id: Option<String>,
+    // Condition on the session's template variables for offering the choice,
+    // e.g. `vars.order_id`. Hidden choices are left out of prompts, results
+    // and matching.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requires: Option<String>,
+    // Yes/no question asked before the transition is committed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm: Option<String>,
+    // Confirmed with the config's `confirm.destructive` question unless `confirm` is set
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub destructive: bool,
+    // Seconds before the choice can be taken again in the same session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown_secs: Option<u64>,
+    // Refusal while cooling down, can refer to `{{cooldown}}`, the seconds left
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown_message: Option<String>,
+    // Other answers that select the choice without a model call
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    // Regex an answer matching selects the choice without a model call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    // Relative chance of the choice on `weighted_random` nodes, 1 if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+}
+
+impl Choice {
+    pub fn new(text: &str, next_id: Transition) -> Self {
+        Self {
+            text: text.to_string(),
+            next_id,
+            return_id: None,
+            requires: None,
+            confirm: None,
+            destructive: false,
+            cooldown_secs: None,
+            cooldown_message: None,
+            aliases: vec![],
+            pattern: None,
+            weight: None,
+        }
+    }
+}
+
+// Analytics event declared on a decision node, emitted when the node is entered
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AnalyticsEvent {
+    pub name: String,
+    #[serde(default)]
+    pub props: BTreeMap<String, Value>,
+}
+
+// Per-node prompt compression settings, overriding `compression.max_tokens`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Compression {
+    pub enabled: Option<bool>,
+    pub max_tokens: Option<usize>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
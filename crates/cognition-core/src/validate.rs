@@ -0,0 +1,84 @@
+use crate::{Decision, Transition, START_ID};
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
+use core::fmt::{self, Display};
+
+// Problem found in a decision tree
+#[derive(Clone, Debug, PartialEq)]
+pub enum TreeError {
+    MissingStart,
+    DuplicateId(String),
+    UnknownTarget { node_id: String, target: String },
+}
+
+impl Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeError::MissingStart => write!(f, "No '{}' node", START_ID),
+            TreeError::DuplicateId(id) => write!(f, "Duplicate node ID '{}'", id),
+            TreeError::UnknownTarget { node_id, target } => {
+                write!(f, "Node '{}' leads to unknown node '{}'", node_id, target)
+            }
+        }
+    }
+}
+
+// Check that node IDs are unique, a start node exists and every transition
+// leads to an existing node. Returns all problems found.
+pub fn validate_tree(decision_nodes: &[Decision]) -> Result<(), Vec<TreeError>> {
+    let mut errors = Vec::new();
+    let mut ids = BTreeSet::new();
+
+    for decision_node in decision_nodes {
+        if !ids.insert(decision_node.id.as_str()) {
+            errors.push(TreeError::DuplicateId(decision_node.id.clone()));
+        }
+    }
+
+    if !ids.contains(START_ID) {
+        errors.push(TreeError::MissingStart);
+    }
+
+    for decision_node in decision_nodes {
+        let transitions = decision_node
+            .choices()
+            .into_iter()
+            .map(|choice| &choice.next_id)
+            .chain(decision_node.next.iter())
+            .chain(decision_node.default_next_id.iter())
+            .chain(decision_node.on_tool_error.iter())
+            .chain(decision_node.on_fail.iter());
+
+        for transition in transitions {
+            // Exit, restart and back are always valid, exit nodes are optional
+            let target = match transition {
+                Transition::Node(id) | Transition::Call(id) => id,
+                Transition::Exit | Transition::Restart | Transition::Back => continue,
+            };
+            if !ids.contains(target.as_str()) {
+                errors.push(TreeError::UnknownTarget {
+                    node_id: decision_node.id.clone(),
+                    target: target.clone(),
+                });
+            }
+        }
+
+        let return_ids = decision_node
+            .choices()
+            .into_iter()
+            .filter_map(|choice| choice.return_id.as_ref());
+        for return_id in return_ids {
+            if !ids.contains(return_id.as_str()) {
+                errors.push(TreeError::UnknownTarget {
+                    node_id: decision_node.id.clone(),
+                    target: return_id.clone(),
+                });
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
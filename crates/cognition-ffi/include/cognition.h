@@ -0,0 +1,69 @@
+/* Generated with cbindgen from crates/cognition-ffi, do not edit */
+
+#ifndef COGNITION_H
+#define COGNITION_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct CognitionSession CognitionSession;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create a session from the YAML config, decision prompt template and
+ * decision tree. Returns null on failure, see `cognition_last_error`.
+ *
+ * # Safety
+ *
+ * The arguments must be null or valid NUL-terminated strings.
+ */
+struct CognitionSession *cognition_state_new(const char *config,
+                                             const char *prompt_template,
+                                             const char *tree);
+
+/**
+ * Run a turn with the user's answer, null for the first turn. Returns the
+ * turn's `DecisionResult` as JSON, `null` once the session has ended, or a
+ * null pointer on failure, see `cognition_last_error`.
+ *
+ * # Safety
+ *
+ * `session` must come from `cognition_state_new` and not be freed.
+ * `user_input` must be null or a valid NUL-terminated string.
+ */
+char *cognition_run(struct CognitionSession *session, const char *user_input);
+
+/**
+ * Free a session. Null is ignored.
+ *
+ * # Safety
+ *
+ * `session` must come from `cognition_state_new` and be freed only once.
+ */
+void cognition_free(struct CognitionSession *session);
+
+/**
+ * Free a string returned by the library. Null is ignored.
+ *
+ * # Safety
+ *
+ * `value` must come from this library and be freed only once.
+ */
+void cognition_string_free(char *value);
+
+/**
+ * Message of the last failure on this thread, null if the last call
+ * succeeded. Free it with `cognition_string_free`.
+ */
+char *cognition_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* COGNITION_H */
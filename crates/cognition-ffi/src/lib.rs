@@ -0,0 +1,176 @@
+// C ABI of the engine for Unity, Unreal and C++ simulators. A session is
+// created from YAML config, prompt template and tree text, runs a turn per
+// answer, and returns each turn as a JSON `DecisionResult`. The header is
+// `include/cognition.h`, generated with cbindgen:
+//
+//     cbindgen --config cbindgen.toml --output include/cognition.h
+//
+// Strings passed in are UTF-8 and borrowed. Strings returned are owned by the
+// caller and released with `cognition_string_free`. A session must only be
+// used from one thread at a time.
+use cognition::{
+    models::ModelRegistry, run_decision, validate_tree, CognitionError, Decision,
+    DecisionPromptTemplate, DecisionState,
+};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use tokio::runtime::{Builder, Runtime};
+
+// Engine session with the runtime its turns run on
+pub struct Session {
+    runtime: Runtime,
+    state: DecisionState,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
+
+// Run `call`, turning errors and panics into the thread's last error and
+// `failed`, as panics must not unwind into the caller
+fn guard<T>(failed: T, call: impl FnOnce() -> Result<T, CognitionError>) -> T {
+    LAST_ERROR.with(|error| *error.borrow_mut() = None);
+    match catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(err)) => {
+            set_error(err.0);
+            failed
+        }
+        Err(_) => {
+            set_error("Panic in the cognition engine".to_string());
+            failed
+        }
+    }
+}
+
+// Borrowed UTF-8 string, None for null
+unsafe fn string_arg(value: *const c_char, name: &str) -> Result<Option<String>, CognitionError> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map(|value| Some(value.to_string()))
+        .map_err(|_| CognitionError(format!("{} is not UTF-8", name)))
+}
+
+fn owned_string(value: String) -> *mut c_char {
+    CString::new(value.replace('\0', " "))
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
+
+fn new_session(config: &str, template: &str, tree: &str) -> Result<Session, CognitionError> {
+    let decision_nodes: Vec<Decision> = serde_yaml::from_str(tree)
+        .map_err(|err| CognitionError(format!("Invalid tree: {}", err)))?;
+    if let Err(errors) = validate_tree(&decision_nodes) {
+        let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        return Err(CognitionError(errors.join("\n")));
+    }
+
+    let runtime = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| CognitionError(format!("Failed to start runtime: {}", err)))?;
+    let state = DecisionState::with_registry(
+        config,
+        DecisionPromptTemplate::new(template.to_string()),
+        decision_nodes,
+        &ModelRegistry::default(),
+    )?;
+    Ok(Session { runtime, state })
+}
+
+/// Create a session from the YAML config, decision prompt template and
+/// decision tree. Returns null on failure, see `cognition_last_error`.
+///
+/// # Safety
+///
+/// The arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn cognition_state_new(
+    config: *const c_char,
+    prompt_template: *const c_char,
+    tree: *const c_char,
+) -> *mut Session {
+    guard(ptr::null_mut(), || {
+        let config = string_arg(config, "config")?.unwrap_or_default();
+        let template = string_arg(prompt_template, "prompt_template")?
+            .ok_or_else(|| CognitionError("Missing prompt template".to_string()))?;
+        let tree =
+            string_arg(tree, "tree")?.ok_or_else(|| CognitionError("Missing tree".to_string()))?;
+        let session = new_session(&config, &template, &tree)?;
+        Ok(Box::into_raw(Box::new(session)))
+    })
+}
+
+/// Run a turn with the user's answer, null for the first turn. Returns the
+/// turn's `DecisionResult` as JSON, `null` once the session has ended, or a
+/// null pointer on failure, see `cognition_last_error`.
+///
+/// # Safety
+///
+/// `session` must come from `cognition_state_new` and not be freed.
+/// `user_input` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn cognition_run(
+    session: *mut Session,
+    user_input: *const c_char,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let session = session
+            .as_mut()
+            .ok_or_else(|| CognitionError("Missing session".to_string()))?;
+        let user_input = string_arg(user_input, "user_input")?;
+        let result = session
+            .runtime
+            .block_on(run_decision(user_input, &mut session.state))?;
+        let json = serde_json::to_string(&result)
+            .map_err(|err| CognitionError(format!("Failed to serialize result: {}", err)))?;
+        Ok(owned_string(json))
+    })
+}
+
+/// Free a session. Null is ignored.
+///
+/// # Safety
+///
+/// `session` must come from `cognition_state_new` and be freed only once.
+#[no_mangle]
+pub unsafe extern "C" fn cognition_free(session: *mut Session) {
+    if !session.is_null() {
+        drop(Box::from_raw(session));
+    }
+}
+
+/// Free a string returned by the library. Null is ignored.
+///
+/// # Safety
+///
+/// `value` must come from this library and be freed only once.
+#[no_mangle]
+pub unsafe extern "C" fn cognition_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+/// Message of the last failure on this thread, null if the last call
+/// succeeded. Free it with `cognition_string_free`.
+#[no_mangle]
+pub extern "C" fn cognition_last_error() -> *mut c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map(|message| message.clone().into_raw())
+            .unwrap_or(ptr::null_mut())
+    })
+}
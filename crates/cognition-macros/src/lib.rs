@@ -0,0 +1,42 @@
+// Compile-time tree embedding, see `cognition::include_tree!`
+use cognition_core::{validate_tree, Decision};
+use proc_macro::TokenStream;
+use quote::quote;
+use std::path::Path;
+use syn::{parse_macro_input, LitStr};
+
+// The YAML tree at a path relative to the calling crate's manifest, parsed and
+// validated while compiling and expanded to its nodes as JSON. An invalid tree
+// fails the build. The file is also included so changes to it rebuild the crate.
+#[proc_macro]
+pub fn tree_json(input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(input as LitStr);
+    match load(&path.value()) {
+        Ok((file, json)) => quote! {
+            {
+                const _: &str = include_str!(#file);
+                #json
+            }
+        }
+        .into(),
+        Err(message) => syn::Error::new(path.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+fn load(path: &str) -> Result<(String, String), String> {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let file = Path::new(&manifest_dir).join(path);
+    let yaml = std::fs::read_to_string(&file)
+        .map_err(|err| format!("Failed to read {}: {}", file.display(), err))?;
+    let decision_nodes: Vec<Decision> = serde_yaml::from_str(&yaml)
+        .map_err(|err| format!("Failed to parse {}: {}", file.display(), err))?;
+    if let Err(errors) = validate_tree(&decision_nodes) {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        return Err(format!("Invalid tree {}: {}", path, errors.join(", ")));
+    }
+    let json = serde_json::to_string(&decision_nodes)
+        .map_err(|err| format!("Failed to serialize {}: {}", file.display(), err))?;
+    Ok((file.display().to_string(), json))
+}
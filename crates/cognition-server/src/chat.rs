@@ -0,0 +1,376 @@
+// OpenAI-compatible chat endpoint, so chat UIs made for OpenAI models talk to
+// the decision tree unchanged:
+//
+//     POST /v1/chat/completions
+//     GET  /v1/models
+//
+// Chat clients send the whole conversation with every request, so a session
+// is found by the messages before the last one, which must be the user's.
+// A conversation the server has not seen starts a new session, taking its
+// first user message as the answer to the start node. Replies are the turn's
+// text with the choices listed below it. `stream: true` is answered as a
+// single chunk. Listens on `CHAT_ADDR` (`0.0.0.0:8081` by default) and, when
+// `CHAT_API_KEY` is set, requires it as the bearer token. See `inbound` for
+// HTTPS and signed requests.
+use crate::inbound;
+use crate::setup::{SessionCache, SessionFactory};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use cognition::{CognitionError, ConcurrencyPolicy, DecisionResult, Session, Usage};
+use futures::channel::{
+    mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::convert::Infallible;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::task::{spawn_local, LocalSet};
+
+const MODEL_ID: &str = "cognition";
+
+#[derive(Deserialize)]
+struct ChatRequest {
+    messages: Vec<Message>,
+    #[serde(default)]
+    stream: bool,
+    // End user, keeping identical conversations of different users apart
+    user: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    role: String,
+    // A string, or text and image parts
+    #[serde(default)]
+    content: Value,
+}
+
+impl Message {
+    fn text(&self) -> String {
+        match &self.content {
+            Value::String(text) => text.trim().to_string(),
+            Value::Array(parts) => parts
+                .iter()
+                .filter_map(|part| part["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+// A turn to run on the session thread
+struct Turn {
+    user: Option<String>,
+    // Role and text of every message, the user's answer last
+    conversation: Vec<(String, String)>,
+    reply: oneshot::Sender<Result<Reply, CognitionError>>,
+}
+
+struct Reply {
+    session_id: String,
+    text: String,
+    usage: Usage,
+}
+
+#[derive(Clone)]
+struct Api {
+    turns: UnboundedSender<Turn>,
+    api_key: Option<String>,
+}
+
+// Sessions by the conversation that leads to their next answer
+type Conversations = Rc<SessionCache<u64>>;
+
+pub async fn serve() -> Result<(), CognitionError> {
+    let factory = SessionFactory::from_env()?;
+    let verifier = factory.verifier()?;
+    let (turns, requests) = unbounded();
+    // Sessions are not `Send`, so they run on a thread of their own
+    std::thread::spawn(move || run_sessions(factory, requests));
+
+    let api = Api {
+        turns,
+        api_key: std::env::var("CHAT_API_KEY").ok(),
+    };
+    let app = Router::new()
+        .route("/v1/chat/completions", post(chat_completions))
+        .route("/v1/models", get(models))
+        .with_state(api);
+    let addr = std::env::var("CHAT_ADDR").unwrap_or_else(|_| "0.0.0.0:8081".to_string());
+    inbound::serve(&addr, inbound::signed(app, verifier), |url| {
+        println!("Serving chat completions on {}/v1", url)
+    })
+    .await
+}
+
+async fn models(State(api): State<Api>, headers: HeaderMap) -> Response {
+    if !api.authorized(&headers) {
+        return unauthorized();
+    }
+    Json(json!({
+        "object": "list",
+        "data": [{ "id": MODEL_ID, "object": "model", "created": 0, "owned_by": MODEL_ID }],
+    }))
+    .into_response()
+}
+
+async fn chat_completions(
+    State(api): State<Api>,
+    headers: HeaderMap,
+    Json(request): Json<ChatRequest>,
+) -> Response {
+    if !api.authorized(&headers) {
+        return unauthorized();
+    }
+    let conversation: Vec<(String, String)> = request
+        .messages
+        .iter()
+        .map(|message| (message.role.clone(), message.text()))
+        .collect();
+    if conversation.last().is_none_or(|(role, _)| role != "user") {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            "The last message must be the user's",
+        );
+    }
+
+    let (reply, receiver) = oneshot::channel();
+    let turn = Turn {
+        user: request.user,
+        conversation,
+        reply,
+    };
+    if api.turns.unbounded_send(turn).is_err() {
+        return error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "server_error",
+            "Sessions stopped",
+        );
+    }
+    let reply = match receiver.await {
+        Ok(Ok(reply)) => reply,
+        Ok(Err(err)) => return error(StatusCode::INTERNAL_SERVER_ERROR, "server_error", &err.0),
+        Err(_) => {
+            return error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "server_error",
+                "Sessions stopped",
+            )
+        }
+    };
+
+    let id = format!("chatcmpl-{}", reply.session_id);
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    if request.stream {
+        let chunk = |delta: Value, finish_reason: Value| {
+            Event::default().data(
+                json!({
+                    "id": id,
+                    "object": "chat.completion.chunk",
+                    "created": created,
+                    "model": MODEL_ID,
+                    "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+                })
+                .to_string(),
+            )
+        };
+        let events = vec![
+            chunk(
+                json!({ "role": "assistant", "content": reply.text }),
+                Value::Null,
+            ),
+            chunk(json!({}), json!("stop")),
+            Event::default().data("[DONE]"),
+        ];
+        let events = futures::stream::iter(events.into_iter().map(Ok::<_, Infallible>));
+        return Sse::new(events).into_response();
+    }
+    Json(json!({
+        "id": id,
+        "object": "chat.completion",
+        "created": created,
+        "model": MODEL_ID,
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": reply.text },
+            "finish_reason": "stop",
+        }],
+        "usage": {
+            "prompt_tokens": reply.usage.prompt_tokens,
+            "completion_tokens": reply.usage.completion_tokens,
+            "total_tokens": reply.usage.total_tokens(),
+        },
+    }))
+    .into_response()
+}
+
+impl Api {
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(api_key) = &self.api_key else {
+            return true;
+        };
+        let bearer = headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        bearer == Some(api_key.as_str())
+    }
+}
+
+fn unauthorized() -> Response {
+    error(
+        StatusCode::UNAUTHORIZED,
+        "invalid_api_key",
+        "Invalid API key",
+    )
+}
+
+// Error in OpenAI's shape, which chat clients show to the user
+fn error(status: StatusCode, kind: &str, message: &str) -> Response {
+    let body = json!({ "error": { "message": message, "type": kind } });
+    (status, Json(body)).into_response()
+}
+
+fn run_sessions(factory: SessionFactory, mut requests: UnboundedReceiver<Turn>) {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("[!] Failed to start the session runtime: {}", err);
+            return;
+        }
+    };
+    let factory = Rc::new(factory);
+    let conversations: Conversations = Rc::new(factory.cache());
+    // Turns of different conversations run concurrently
+    LocalSet::new().block_on(&runtime, async move {
+        while let Some(turn) = requests.next().await {
+            let factory = factory.clone();
+            let conversations = conversations.clone();
+            spawn_local(async move {
+                let result = run_turn(&factory, &conversations, &turn).await;
+                let _ = turn.reply.send(result);
+            });
+        }
+    });
+}
+
+async fn run_turn(
+    factory: &SessionFactory,
+    conversations: &Conversations,
+    turn: &Turn,
+) -> Result<Reply, CognitionError> {
+    let Some(((_, answer), before)) = turn.conversation.split_last() else {
+        return Err(CognitionError("No messages".to_string()));
+    };
+    let key = conversation_key(turn.user.as_deref(), before);
+    let session = conversations.remove(&key);
+    let session = match session {
+        Some(session) => session,
+        None => {
+            let session = Rc::new(Session::new(factory.new_state()?, ConcurrencyPolicy::Queue));
+            session
+                .run(None)
+                .await
+                .map_err(|err| CognitionError(err.to_string()))?;
+            session
+        }
+    };
+
+    let result = session
+        .run(Some(answer.clone()))
+        .await
+        .map_err(|err| CognitionError(err.to_string()))?;
+    let text = match &result {
+        Some(result) => reply_text(result),
+        None => "Goodbye!".to_string(),
+    };
+    let usage = result
+        .as_ref()
+        .map(|result| result.usage)
+        .unwrap_or_default();
+
+    if result.as_ref().is_some_and(|result| !has_ended(result)) {
+        // Found by the next request, which repeats the reply
+        let mut conversation = turn.conversation.clone();
+        conversation.push(("assistant".to_string(), text.clone()));
+        let key = conversation_key(turn.user.as_deref(), &conversation);
+        conversations.insert(key, session.clone());
+    } else if let Err(err) = factory.forget(session.session_id()).await {
+        eprintln!("[!] {}", err);
+    }
+    Ok(Reply {
+        session_id: session.session_id().to_string(),
+        text,
+        usage,
+    })
+}
+
+fn conversation_key(user: Option<&str>, messages: &[(String, String)]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    user.hash(&mut hasher);
+    messages.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Informational nodes, refusal and question of a turn, then its choices or
+// yes and no for a confirmation
+fn reply_text(result: &DecisionResult) -> String {
+    let mut paragraphs: Vec<String> = result
+        .interstitials
+        .iter()
+        .map(|interstitial| interstitial.text.clone())
+        .collect();
+    paragraphs.extend(result.refusal.clone());
+    paragraphs.push(
+        result
+            .confirm
+            .clone()
+            .unwrap_or_else(|| result.decision_node.text.clone()),
+    );
+
+    let choices: Vec<String> = match result.confirm {
+        Some(_) => vec!["Yes".to_string(), "No".to_string()],
+        None => result
+            .decision_node
+            .choices()
+            .iter()
+            .map(|choice| choice.text.clone())
+            .collect(),
+    };
+    if !choices.is_empty() {
+        let list: Vec<String> = choices
+            .iter()
+            .map(|choice| format!("- {}", choice))
+            .collect();
+        paragraphs.push(list.join("\n"));
+    }
+    paragraphs.join("\n\n")
+}
+
+// Nothing left to answer
+fn has_ended(result: &DecisionResult) -> bool {
+    result.confirm.is_none() && result.decision_node.choices().is_empty()
+}
@@ -0,0 +1,182 @@
+// Listening for the HTTP endpoints, over HTTPS with `TLS_CERT` and `TLS_KEY`
+// (PEM files) when built with the `tls` feature. With `TLS_CLIENT_CA` too,
+// only clients presenting a certificate it signed are served (mTLS).
+//
+// When the config has `webhook.secret`, requests must also be signed like the
+// engine's outgoing webhooks: `X-Cognition-Timestamp` and an
+// `X-Cognition-Signature` of the body, or of the path and query for requests
+// without a body, such as `GET /stream?session=...`. Unsigned, stale and
+// replayed requests get a 401, see `WebhookVerifier`.
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use cognition::{webhook::WebhookVerifier, CognitionError};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+
+// Largest body buffered to check its signature
+const MAX_SIGNED_BODY: usize = 2 * 1024 * 1024;
+
+// Serve `app` on `addr`, calling `listening` with its URL once bound
+pub async fn serve(
+    addr: &str,
+    app: Router,
+    listening: impl FnOnce(&str),
+) -> Result<(), CognitionError> {
+    let failed = |err: std::io::Error| CognitionError(format!("Server failed: {}", err));
+    #[cfg(feature = "tls")]
+    let tls = crate::setup::tls_from_env()?;
+    #[cfg(not(feature = "tls"))]
+    if std::env::var("TLS_CERT").is_ok() {
+        return Err(CognitionError(
+            "TLS_CERT needs the `tls` feature".to_string(),
+        ));
+    }
+
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|err| CognitionError(format!("Failed to listen on {}: {}", addr, err)))?;
+    #[cfg(feature = "tls")]
+    if let Some(acceptor) = tls {
+        listening(&format!("https://{}", addr));
+        let listener = tls::TlsListener::new(listener, acceptor).map_err(failed)?;
+        return axum::serve(listener, app).await.map_err(failed);
+    }
+    listening(&format!("http://{}", addr));
+    axum::serve(listener, app).await.map_err(failed)
+}
+
+// `app` refusing requests without a valid signature, when there is a verifier
+pub fn signed(app: Router, verifier: Option<WebhookVerifier>) -> Router {
+    match verifier {
+        Some(verifier) => app.layer(middleware::from_fn_with_state(
+            Arc::new(verifier),
+            verify_signature,
+        )),
+        None => app,
+    }
+}
+
+async fn verify_signature(
+    State(verifier): State<Arc<WebhookVerifier>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let Ok(body) = to_bytes(body, MAX_SIGNED_BODY).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let header = |name: &str| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    let signed: &[u8] = match parts.uri.path_and_query() {
+        Some(path) if body.is_empty() => path.as_str().as_bytes(),
+        _ => &body,
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    if let Err(err) = verifier.verify(
+        header("x-cognition-timestamp"),
+        signed,
+        header("x-cognition-signature"),
+        now,
+    ) {
+        return (StatusCode::UNAUTHORIZED, err.0).into_response();
+    }
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use openssl::ssl::{Ssl, SslAcceptor};
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::time::Duration;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+    use tokio_openssl::SslStream;
+
+    // Clients failing to complete the handshake in time are dropped
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+    const ACCEPT_RETRY_DELAY: Duration = Duration::from_secs(1);
+    // Connections done with their handshake, waiting to be served
+    const PENDING_CONNECTIONS: usize = 64;
+
+    // Connections of a TCP listener after their TLS handshake, done
+    // concurrently so slow clients do not hold up the others
+    pub struct TlsListener {
+        connections: mpsc::Receiver<(SslStream<TcpStream>, SocketAddr)>,
+        local_addr: SocketAddr,
+    }
+
+    impl TlsListener {
+        pub fn new(listener: TcpListener, acceptor: SslAcceptor) -> std::io::Result<Self> {
+            let local_addr = listener.local_addr()?;
+            let (sender, connections) = mpsc::channel(PENDING_CONNECTIONS);
+            tokio::spawn(async move {
+                loop {
+                    let (stream, addr) = match listener.accept().await {
+                        Ok(accepted) => accepted,
+                        Err(err) => {
+                            eprintln!("[!] Failed to accept a connection: {}", err);
+                            tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                            continue;
+                        }
+                    };
+                    let Ok(ssl) = Ssl::new(acceptor.context()) else {
+                        continue;
+                    };
+                    let sender = sender.clone();
+                    tokio::spawn(async move {
+                        let Ok(mut stream) = SslStream::new(ssl, stream) else {
+                            return;
+                        };
+                        let handshake = Pin::new(&mut stream).accept();
+                        match tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake).await {
+                            Ok(Ok(())) => {
+                                let _ = sender.send((stream, addr)).await;
+                            }
+                            Ok(Err(err)) => {
+                                eprintln!("[!] TLS handshake with {} failed: {}", addr, err)
+                            }
+                            Err(_) => eprintln!("[!] TLS handshake with {} timed out", addr),
+                        }
+                    });
+                }
+            });
+            Ok(Self {
+                connections,
+                local_addr,
+            })
+        }
+    }
+
+    impl axum::serve::Listener for TlsListener {
+        type Io = SslStream<TcpStream>;
+        type Addr = SocketAddr;
+
+        async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+            match self.connections.recv().await {
+                Some(connection) => connection,
+                // The accept loop never ends
+                None => std::future::pending().await,
+            }
+        }
+
+        fn local_addr(&self) -> std::io::Result<Self::Addr> {
+            Ok(self.local_addr)
+        }
+    }
+}
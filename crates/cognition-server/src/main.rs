@@ -1,3 +1,70 @@
-fn main() {
+#[cfg(feature = "chat-api")]
+mod chat;
+#[cfg(any(feature = "chat-api", feature = "sse"))]
+mod inbound;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(any(
+    feature = "chat-api",
+    feature = "mqtt",
+    feature = "slack",
+    feature = "sse"
+))]
+mod setup;
+#[cfg(feature = "slack")]
+mod slack;
+#[cfg(feature = "sse")]
+mod sse;
+
+#[tokio::main]
+async fn main() {
     println!("Hello, world!");
+
+    // Prometheus scrape endpoint, listening on METRICS_ADDR
+    #[cfg(feature = "metrics")]
+    let metrics = tokio::spawn(async {
+        if let Err(err) = metrics::serve().await {
+            eprintln!("[!] {}", err);
+        }
+    });
+
+    // Turns streamed to browsers, listening on SERVER_ADDR
+    #[cfg(feature = "sse")]
+    let sse = tokio::spawn(async {
+        if let Err(err) = sse::serve().await {
+            eprintln!("[!] {}", err);
+        }
+    });
+
+    // OpenAI-compatible chat completions, listening on CHAT_ADDR
+    #[cfg(feature = "chat-api")]
+    let chat = tokio::spawn(async {
+        if let Err(err) = chat::serve().await {
+            eprintln!("[!] {}", err);
+        }
+    });
+
+    // Sessions driven over MQTT, on a thread of their own
+    #[cfg(feature = "mqtt")]
+    let mqtt = tokio::task::spawn_blocking(|| {
+        if let Err(err) = mqtt::run_from_env() {
+            eprintln!("[!] {}", err);
+        }
+    });
+
+    #[cfg(feature = "slack")]
+    if let Err(err) = slack::run_from_env().await {
+        eprintln!("[!] {}", err);
+    }
+
+    #[cfg(feature = "chat-api")]
+    chat.await.unwrap();
+    #[cfg(feature = "mqtt")]
+    mqtt.await.unwrap();
+    #[cfg(feature = "sse")]
+    sse.await.unwrap();
+    #[cfg(feature = "metrics")]
+    metrics.await.unwrap();
 }
@@ -0,0 +1,17 @@
+// Prometheus scrape endpoint, `GET /metrics`, listening on `METRICS_ADDR`
+// (`0.0.0.0:9100` by default)
+use axum::{routing::get, Router};
+use cognition::CognitionError;
+use tokio::net::TcpListener;
+
+pub async fn serve() -> Result<(), CognitionError> {
+    let addr = std::env::var("METRICS_ADDR").unwrap_or_else(|_| "0.0.0.0:9100".to_string());
+    let app = Router::new().route("/metrics", get(|| async { cognition::metrics::gather() }));
+    let listener = TcpListener::bind(&addr)
+        .await
+        .map_err(|err| CognitionError(format!("Failed to listen on {}: {}", addr, err)))?;
+    println!("Serving metrics on http://{}/metrics", addr);
+    axum::serve(listener, app)
+        .await
+        .map_err(|err| CognitionError(format!("Metrics server failed: {}", err)))
+}
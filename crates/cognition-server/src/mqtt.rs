@@ -0,0 +1,212 @@
+// MQTT bridge for robots and embedded devices, with one session per agent.
+// Answers published to `<prefix>/<agent>/in` run a turn of the agent's
+// session, starting one if it has none; the first turn of a new session runs
+// before the message's answer, if any. Each turn's `DecisionResult` is
+// published to `<prefix>/<agent>/out` as `{ "session_id", "result" }`, with a
+// null result when the session ended, every tool call to
+// `<prefix>/<agent>/tools` as a `ToolOutcome` and failed turns to
+// `<prefix>/<agent>/error`. Connects to `MQTT_HOST` (localhost) on
+// `MQTT_PORT` (1883) as `MQTT_CLIENT_ID` (cognition), optionally with
+// `MQTT_USERNAME` and `MQTT_PASSWORD`, and uses `MQTT_QOS` (1) both ways.
+// `MQTT_PREFIX` is `cognition` by default. Sessions see their agent as
+// `{{vars.mqtt_agent}}`.
+use crate::setup::{env, SessionCache, SessionFactory};
+use cognition::{CognitionError, ConcurrencyPolicy, DecisionResult, Session};
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, Publish, QoS};
+use serde_json::json;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::task::{spawn_local, LocalSet};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+// Requests queued for the event loop before publishing waits
+const CHANNEL_CAPACITY: usize = 64;
+
+struct Bridge {
+    client: AsyncClient,
+    qos: QoS,
+    prefix: String,
+    factory: SessionFactory,
+    sessions: SessionCache<String>,
+}
+
+// Run the bridge until the process ends, on a thread of its own as sessions
+// are not `Send`. Turns of different agents run concurrently.
+pub fn run_from_env() -> Result<(), CognitionError> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| CognitionError(format!("Failed to start the MQTT runtime: {}", err)))?;
+    let (bridge, event_loop) = Bridge::from_env()?;
+    LocalSet::new().block_on(&runtime, Rc::new(bridge).listen(event_loop))
+}
+
+impl Bridge {
+    fn from_env() -> Result<(Self, EventLoop), CognitionError> {
+        let host = std::env::var("MQTT_HOST").unwrap_or_else(|_| "localhost".to_string());
+        let port = match std::env::var("MQTT_PORT") {
+            Ok(port) => port
+                .parse()
+                .map_err(|_| CognitionError(format!("Invalid MQTT_PORT: {}", port)))?,
+            Err(_) => 1883,
+        };
+        let client_id = std::env::var("MQTT_CLIENT_ID").unwrap_or_else(|_| "cognition".to_string());
+        let mut options = MqttOptions::new(client_id, host, port);
+        options.set_keep_alive(KEEP_ALIVE);
+        if let Ok(username) = std::env::var("MQTT_USERNAME") {
+            options.set_credentials(username, env("MQTT_PASSWORD")?);
+        }
+        let qos = match std::env::var("MQTT_QOS").as_deref() {
+            Ok("0") => QoS::AtMostOnce,
+            Ok("1") | Err(_) => QoS::AtLeastOnce,
+            Ok("2") => QoS::ExactlyOnce,
+            Ok(qos) => return Err(CognitionError(format!("Invalid MQTT_QOS: {}", qos))),
+        };
+
+        let factory = SessionFactory::from_env()?;
+        let (client, event_loop) = AsyncClient::new(options, CHANNEL_CAPACITY);
+        let bridge = Self {
+            client,
+            qos,
+            prefix: std::env::var("MQTT_PREFIX").unwrap_or_else(|_| "cognition".to_string()),
+            sessions: factory.cache(),
+            factory,
+        };
+        Ok((bridge, event_loop))
+    }
+
+    // Poll the connection forever, subscribing again after every reconnect
+    async fn listen(self: Rc<Self>, mut event_loop: EventLoop) -> Result<(), CognitionError> {
+        let topic = format!("{}/+/in", self.prefix);
+        loop {
+            match event_loop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    println!("Listening on MQTT topic {}", topic);
+                    self.client
+                        .subscribe(&topic, self.qos)
+                        .await
+                        .map_err(|err| CognitionError(format!("MQTT subscribe failed: {}", err)))?;
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    spawn_local(self.clone().on_message(publish));
+                }
+                Ok(_) => {}
+                // The next poll reconnects
+                Err(err) => {
+                    eprintln!("[!] MQTT connection failed: {}", err);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    }
+
+    async fn on_message(self: Rc<Self>, publish: Publish) {
+        let Some(agent) = publish
+            .topic
+            .strip_prefix(&format!("{}/", self.prefix))
+            .and_then(|topic| topic.strip_suffix("/in"))
+            .map(str::to_string)
+        else {
+            return;
+        };
+        let answer = String::from_utf8_lossy(&publish.payload).trim().to_string();
+        if let Err(err) = self.answer(&agent, answer).await {
+            eprintln!("[!] {}", err);
+            let _ = self.publish(&agent, "error", err.0).await;
+        }
+    }
+
+    async fn answer(&self, agent: &str, answer: String) -> Result<(), CognitionError> {
+        let session = match self.session(agent).await? {
+            Some(session) => session,
+            None => {
+                let session = self.start(agent)?;
+                self.turn(agent, &session, None).await?;
+                if answer.is_empty() {
+                    return Ok(());
+                }
+                session
+            }
+        };
+        self.turn(
+            agent,
+            &session,
+            Some(answer).filter(|answer| !answer.is_empty()),
+        )
+        .await
+    }
+
+    // Session of the agent, resumed from the store when another bridge or an
+    // earlier run of this one started it
+    async fn session(&self, agent: &str) -> Result<Option<Rc<Session>>, CognitionError> {
+        if let Some(session) = self.sessions.get(agent).await? {
+            return Ok(Some(session));
+        }
+        let Some(state) = self.factory.resume(&session_id(agent)).await? else {
+            return Ok(None);
+        };
+        let session = Rc::new(Session::new(state, ConcurrencyPolicy::Queue));
+        Ok(Some(self.sessions.insert(agent.to_string(), session)))
+    }
+
+    fn start(&self, agent: &str) -> Result<Rc<Session>, CognitionError> {
+        let mut state = self.factory.new_state()?;
+        state.session_id = session_id(agent);
+        state
+            .vars
+            .insert("mqtt_agent".to_string(), agent.to_string());
+        let session = Rc::new(Session::new(state, ConcurrencyPolicy::Queue));
+        Ok(self.sessions.insert(agent.to_string(), session))
+    }
+
+    async fn turn(
+        &self,
+        agent: &str,
+        session: &Session,
+        answer: Option<String>,
+    ) -> Result<(), CognitionError> {
+        let result = session
+            .run(answer)
+            .await
+            .map_err(|err| CognitionError(err.to_string()))?;
+        if let Some(result) = &result {
+            for outcome in &result.tool_outcomes {
+                self.publish(agent, "tools", json!(outcome).to_string())
+                    .await?;
+            }
+        }
+        if result.as_ref().is_none_or(has_ended) {
+            self.sessions.remove(agent);
+            self.factory.forget(session.session_id()).await?;
+        }
+        let out = json!({ "session_id": session.session_id(), "result": result });
+        self.publish(agent, "out", out.to_string()).await
+    }
+
+    async fn publish(
+        &self,
+        agent: &str,
+        topic: &str,
+        payload: String,
+    ) -> Result<(), CognitionError> {
+        self.client
+            .publish(
+                format!("{}/{}/{}", self.prefix, agent, topic),
+                self.qos,
+                false,
+                payload,
+            )
+            .await
+            .map_err(|err| CognitionError(format!("MQTT publish failed: {}", err)))
+    }
+}
+
+// Nothing left to answer
+fn has_ended(result: &DecisionResult) -> bool {
+    result.confirm.is_none() && result.decision_node.choices().is_empty()
+}
+
+fn session_id(agent: &str) -> String {
+    format!("mqtt-{}", agent)
+}
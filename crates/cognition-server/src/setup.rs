@@ -0,0 +1,259 @@
+// Sessions of the server's channels, from the tree and prompt template in the
+// working directory and the config at `COGNITION_CONFIG`. Their state is kept
+// in Redis at `REDIS_URL` when set, so any server can continue them, and in
+// memory otherwise, for `SESSION_TTL_SECS` (a day by default) after their last
+// turn. Channels cache the sessions they run in a `SessionCache`.
+#[cfg(any(feature = "chat-api", feature = "sse"))]
+use cognition::webhook::WebhookVerifier;
+#[cfg(feature = "redis")]
+use cognition::RedisStore;
+use cognition::{
+    models::ModelRegistry, tools, validate_tree, CognitionError, Decision, DecisionPromptTemplate,
+    DecisionState, MemoryStore, Session, SessionStore,
+};
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub struct SessionFactory {
+    config: String,
+    template: String,
+    nodes: Vec<Decision>,
+    store: Store,
+    ttl: Duration,
+}
+
+// Store shared by the sessions of the factory
+#[derive(Clone)]
+enum Store {
+    Memory(MemoryStore),
+    #[cfg(feature = "redis")]
+    Redis(RedisStore),
+}
+
+impl Store {
+    fn from_env(ttl: Duration) -> Result<Self, CognitionError> {
+        match std::env::var("REDIS_URL") {
+            #[cfg(feature = "redis")]
+            Ok(url) => Ok(Store::Redis(RedisStore::new(
+                &url,
+                "cognition:",
+                Some(ttl),
+            )?)),
+            #[cfg(not(feature = "redis"))]
+            Ok(_) => Err(CognitionError(
+                "REDIS_URL needs the `redis` feature".to_string(),
+            )),
+            Err(_) => Ok(Store::Memory(MemoryStore::new(Some(ttl)))),
+        }
+    }
+
+    // Whether other servers run turns of the sessions too
+    #[cfg(any(feature = "mqtt", feature = "slack", feature = "sse"))]
+    fn shared(&self) -> bool {
+        match self {
+            Store::Memory(_) => false,
+            #[cfg(feature = "redis")]
+            Store::Redis(_) => true,
+        }
+    }
+
+    fn boxed(&self) -> Box<dyn SessionStore> {
+        match self {
+            Store::Memory(store) => Box::new(store.clone()),
+            #[cfg(feature = "redis")]
+            Store::Redis(store) => Box::new(store.clone()),
+        }
+    }
+}
+
+// TLS of the HTTP endpoints from the PEM files at `TLS_CERT` and `TLS_KEY`,
+// requiring client certificates signed by the CAs at `TLS_CLIENT_CA` when set
+#[cfg(feature = "tls")]
+pub fn tls_from_env() -> Result<Option<openssl::ssl::SslAcceptor>, CognitionError> {
+    use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
+    use openssl::x509::X509Name;
+
+    let Ok(cert) = std::env::var("TLS_CERT") else {
+        return Ok(None);
+    };
+    let key = env("TLS_KEY")?;
+    let failed =
+        |err: openssl::error::ErrorStack| CognitionError(format!("Invalid TLS setup: {}", err));
+    let mut builder =
+        SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).map_err(failed)?;
+    builder.set_certificate_chain_file(&cert).map_err(failed)?;
+    builder
+        .set_private_key_file(&key, SslFiletype::PEM)
+        .map_err(failed)?;
+    builder.check_private_key().map_err(failed)?;
+    if let Ok(client_ca) = std::env::var("TLS_CLIENT_CA") {
+        builder.set_ca_file(&client_ca).map_err(failed)?;
+        builder.set_client_ca_list(X509Name::load_client_ca_file(&client_ca).map_err(failed)?);
+        builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    }
+    Ok(Some(builder.build()))
+}
+
+pub fn env(name: &str) -> Result<String, CognitionError> {
+    std::env::var(name).map_err(|_| CognitionError(format!("Missing {}", name)))
+}
+
+fn read(path: &str) -> Result<String, CognitionError> {
+    std::fs::read_to_string(path)
+        .map_err(|err| CognitionError(format!("Failed to read {}: {}", path, err)))
+}
+
+impl SessionFactory {
+    pub fn from_env() -> Result<Self, CognitionError> {
+        let nodes: Vec<Decision> = serde_yaml::from_str(&read("decision_tree.yaml")?)
+            .map_err(|err| CognitionError(format!("Invalid tree: {}", err)))?;
+        if let Err(errors) = validate_tree(&nodes) {
+            let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+            return Err(CognitionError(errors.join("\n")));
+        }
+        // The CLI's davinci-003 setup without a config file
+        let config = match std::env::var("COGNITION_CONFIG") {
+            Ok(path) => read(&path)?,
+            Err(_) => format!(
+                "models:\n  davinci003:\n    api_key: {}\n",
+                env("OPENAI_API_KEY")?
+            ),
+        };
+        let ttl = match std::env::var("SESSION_TTL_SECS") {
+            Ok(secs) => Duration::from_secs(
+                secs.parse()
+                    .map_err(|_| CognitionError(format!("Invalid SESSION_TTL_SECS: {}", secs)))?,
+            ),
+            Err(_) => DEFAULT_SESSION_TTL,
+        };
+        Ok(Self {
+            config,
+            template: read("decision_prompt_template.yaml")?,
+            nodes,
+            store: Store::from_env(ttl)?,
+            ttl,
+        })
+    }
+
+    pub fn new_state(&self) -> Result<DecisionState, CognitionError> {
+        let mut state = DecisionState::with_registry(
+            &self.config,
+            DecisionPromptTemplate::new(self.template.clone()),
+            self.nodes.clone(),
+            &ModelRegistry::default(),
+        )?;
+        if let Ok(app_id) = std::env::var("WOLFRAM_APP_ID") {
+            state.add_tool(Box::new(tools::WolframAlpha::new(app_id)));
+        }
+        state.set_store(self.store.boxed());
+        Ok(state)
+    }
+
+    // Session `session_id` as of its last turn, if still stored
+    #[cfg(any(feature = "mqtt", feature = "slack", feature = "sse"))]
+    pub async fn resume(&self, session_id: &str) -> Result<Option<DecisionState>, CognitionError> {
+        let mut state = self.new_state()?;
+        Ok(state.resume(session_id).await?.then_some(state))
+    }
+
+    // Verifier of signed requests when the config has `webhook.secret`
+    #[cfg(any(feature = "chat-api", feature = "sse"))]
+    pub fn verifier(&self) -> Result<Option<WebhookVerifier>, CognitionError> {
+        let config: serde_yaml::Value = serde_yaml::from_str(&self.config)
+            .map_err(|err| CognitionError(format!("Invalid config: {}", err)))?;
+        if config["webhook"]["secret"].is_null() {
+            return Ok(None);
+        }
+        WebhookVerifier::from_config(&self.config).map(Some)
+    }
+
+    // Drop the stored state of an ended session
+    pub async fn forget(&self, session_id: &str) -> Result<(), CognitionError> {
+        self.store.boxed().delete_state(session_id).await
+    }
+
+    pub fn cache<K>(&self) -> SessionCache<K> {
+        SessionCache {
+            sessions: RefCell::new(HashMap::new()),
+            ttl: self.ttl,
+            #[cfg(any(feature = "mqtt", feature = "slack", feature = "sse"))]
+            shared: self.store.shared(),
+        }
+    }
+}
+
+// Sessions a channel runs, by its key for them. Sessions idle for the session
+// TTL are dropped like their stored state. With a shared store another server
+// may have run turns of a cached session since, so it is reloaded from the
+// store before it is used.
+pub struct SessionCache<K> {
+    sessions: RefCell<HashMap<K, (Rc<Session>, Instant)>>,
+    ttl: Duration,
+    #[cfg(any(feature = "mqtt", feature = "slack", feature = "sse"))]
+    shared: bool,
+}
+
+impl<K: Eq + Hash + Clone> SessionCache<K> {
+    // Session of `key` as of its last turn on any server, `None` once it ended
+    // or expired elsewhere
+    #[cfg(any(feature = "mqtt", feature = "slack", feature = "sse"))]
+    pub async fn get<Q>(&self, key: &Q) -> Result<Option<Rc<Session>>, CognitionError>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.evict();
+        let session = match self.sessions.borrow_mut().get_mut(key) {
+            Some((session, used)) => {
+                *used = Instant::now();
+                session.clone()
+            }
+            None => return Ok(None),
+        };
+        if self.shared {
+            let mut state = session
+                .lock()
+                .await
+                .map_err(|err| CognitionError(err.to_string()))?;
+            let session_id = state.session_id.clone();
+            if !state.resume(&session_id).await? {
+                drop(state);
+                self.remove(key);
+                return Ok(None);
+            }
+        }
+        Ok(Some(session))
+    }
+
+    // Cache `session` unless `key` has one already, returning the cached one
+    pub fn insert(&self, key: K, session: Rc<Session>) -> Rc<Session> {
+        self.evict();
+        let mut sessions = self.sessions.borrow_mut();
+        let (session, used) = sessions.entry(key).or_insert((session, Instant::now()));
+        *used = Instant::now();
+        session.clone()
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<Rc<Session>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.sessions
+            .borrow_mut()
+            .remove(key)
+            .map(|(session, _)| session)
+    }
+
+    fn evict(&self) {
+        self.sessions
+            .borrow_mut()
+            .retain(|_, (_, used)| used.elapsed() < self.ttl);
+    }
+}
@@ -0,0 +1,371 @@
+// Slack bot over Socket Mode, with one session per thread. Mentioning the app,
+// or messaging it directly, starts a session in the message's thread; every
+// turn is posted there with a button per choice, and replies typed in the
+// thread are answers too. Needs `SLACK_APP_TOKEN` (`xapp-`, with
+// `connections:write`) and `SLACK_BOT_TOKEN` (`xoxb-`, with `chat:write`), and
+// the `app_mention`, `message.channels` and `message.im` events. Sessions see who they run for as `{{vars.slack_user}}`
+// and `{{vars.slack_channel}}`, so tools can act on their behalf. Threads
+// outlive restarts of the bot as long as their session is stored, see `setup`.
+use crate::setup::{env, SessionCache, SessionFactory};
+use cognition::{CognitionError, ConcurrencyPolicy, DecisionResult, Session};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::future::Future;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::task::LocalSet;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const SLACK_API: &str = "https://slack.com/api";
+// Slack's limits on buttons per actions block and on button text
+const MAX_BUTTONS: usize = 25;
+const MAX_BUTTON_TEXT: usize = 75;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+// Channel ID and timestamp of the thread a session runs in
+type ThreadKey = (String, String);
+
+struct Bot {
+    http: reqwest::Client,
+    bot_token: String,
+    factory: SessionFactory,
+    sessions: SessionCache<ThreadKey>,
+}
+
+// Run the bot until the process ends, reconnecting whenever Slack drops the
+// connection. Turns of different threads run concurrently.
+pub async fn run_from_env() -> Result<(), CognitionError> {
+    let app_token = env("SLACK_APP_TOKEN")?;
+    let bot = Rc::new(Bot::from_env()?);
+    LocalSet::new()
+        .run_until(async move {
+            loop {
+                if let Err(err) = bot.clone().listen(&app_token).await {
+                    eprintln!("[!] Slack connection failed: {}", err);
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        })
+        .await
+}
+
+impl Bot {
+    fn from_env() -> Result<Self, CognitionError> {
+        let factory = SessionFactory::from_env()?;
+        Ok(Self {
+            http: reqwest::Client::new(),
+            bot_token: env("SLACK_BOT_TOKEN")?,
+            sessions: factory.cache(),
+            factory,
+        })
+    }
+
+    // Serve one Socket Mode connection until Slack closes it
+    async fn listen(self: Rc<Self>, app_token: &str) -> Result<(), CognitionError> {
+        let opened = self
+            .call(app_token, "apps.connections.open", json!({}))
+            .await?;
+        let url = opened["url"]
+            .as_str()
+            .ok_or_else(|| CognitionError("Missing Socket Mode URL".to_string()))?;
+        let (mut socket, _) = connect_async(url)
+            .await
+            .map_err(|err| CognitionError(format!("Failed to connect to Slack: {}", err)))?;
+
+        while let Some(message) = socket.next().await {
+            let envelope: Value = match message {
+                Ok(Message::Text(text)) => serde_json::from_str(&text).unwrap_or_default(),
+                Ok(Message::Close(_)) => break,
+                Ok(_) => continue,
+                Err(err) => return Err(CognitionError(format!("Slack socket error: {}", err))),
+            };
+            // Acknowledged before the turn runs, as Slack retries after 3 seconds
+            if let Some(envelope_id) = envelope["envelope_id"].as_str() {
+                let ack = json!({ "envelope_id": envelope_id }).to_string();
+                socket
+                    .send(Message::Text(ack))
+                    .await
+                    .map_err(|err| CognitionError(format!("Slack socket error: {}", err)))?;
+            }
+            let payload = envelope["payload"].clone();
+            match envelope["type"].as_str() {
+                Some("events_api") => spawn(self.clone().on_event(payload["event"].clone())),
+                Some("interactive") => spawn(self.clone().on_action(payload)),
+                Some("disconnect") => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_event(self: Rc<Self>, event: Value) -> Result<(), CognitionError> {
+        // The bot's own posts and edits
+        if event["bot_id"].is_string() || event["subtype"].is_string() {
+            return Ok(());
+        }
+        let (Some(channel), Some(ts), Some(user)) = (
+            event["channel"].as_str(),
+            event["ts"].as_str(),
+            event["user"].as_str(),
+        ) else {
+            return Ok(());
+        };
+        let reply_to = event["thread_ts"].as_str().filter(|thread| *thread != ts);
+        let text = without_mentions(event["text"].as_str().unwrap_or_default());
+
+        match (event["type"].as_str(), reply_to) {
+            // Answers, also delivered as `app_mention` when they mention the app
+            (Some("message"), Some(thread)) => {
+                let key = (channel.to_string(), thread.to_string());
+                match self.session(&key).await? {
+                    Some(session) => self.turn(&key, &session, Some(text)).await,
+                    None => Ok(()),
+                }
+            }
+            (Some("message"), None) if event["channel_type"] == "im" => {
+                self.start(channel, ts, user).await
+            }
+            (Some("app_mention"), None) => self.start(channel, ts, user).await,
+            (Some("app_mention"), Some(thread)) => {
+                let key = (channel.to_string(), thread.to_string());
+                if self.session(&key).await?.is_some() {
+                    return Ok(());
+                }
+                self.start(channel, thread, user).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // A choice button clicked, answering with the choice's text
+    async fn on_action(self: Rc<Self>, payload: Value) -> Result<(), CognitionError> {
+        if payload["type"] != "block_actions" {
+            return Ok(());
+        }
+        let message = &payload["message"];
+        let (Some(channel), Some(ts), Some(answer)) = (
+            payload["channel"]["id"].as_str(),
+            message["ts"].as_str(),
+            payload["actions"][0]["value"].as_str(),
+        ) else {
+            return Ok(());
+        };
+        let thread = message["thread_ts"].as_str().unwrap_or(ts);
+        let key = (channel.to_string(), thread.to_string());
+        let Some(session) = self.session(&key).await? else {
+            return self
+                .post(&key, "This conversation has ended.", vec![])
+                .await;
+        };
+
+        // Replace the buttons with the answer, so they are not clicked twice
+        let text = message["text"].as_str().unwrap_or_default();
+        let user = payload["user"]["id"].as_str().unwrap_or_default();
+        let chosen = format!("<@{}> chose *{}*", user, answer);
+        self.call(
+            &self.bot_token,
+            "chat.update",
+            json!({
+                "channel": channel,
+                "ts": ts,
+                "text": text,
+                "blocks": [
+                    section(text),
+                    { "type": "context", "elements": [{ "type": "mrkdwn", "text": chosen }] },
+                ],
+            }),
+        )
+        .await?;
+        self.turn(&key, &session, Some(answer.to_string())).await
+    }
+
+    // New session in the thread, posting its first turn
+    async fn start(&self, channel: &str, thread: &str, user: &str) -> Result<(), CognitionError> {
+        let mut state = self.factory.new_state()?;
+        state
+            .vars
+            .insert("slack_user".to_string(), user.to_string());
+        state
+            .vars
+            .insert("slack_channel".to_string(), channel.to_string());
+
+        let key = (channel.to_string(), thread.to_string());
+        state.session_id = session_id(&key);
+        let session = Rc::new(Session::new(state, ConcurrencyPolicy::Queue));
+        let session = self.sessions.insert(key.clone(), session);
+        self.turn(&key, &session, None).await
+    }
+
+    // Session of the thread, resumed from the store when another server or an
+    // earlier run of the bot started it
+    async fn session(&self, key: &ThreadKey) -> Result<Option<Rc<Session>>, CognitionError> {
+        if let Some(session) = self.sessions.get(key).await? {
+            return Ok(Some(session));
+        }
+        let Some(state) = self.factory.resume(&session_id(key)).await? else {
+            return Ok(None);
+        };
+        let session = Rc::new(Session::new(state, ConcurrencyPolicy::Queue));
+        Ok(Some(self.sessions.insert(key.clone(), session)))
+    }
+
+    async fn end(&self, key: &ThreadKey) -> Result<(), CognitionError> {
+        self.sessions.remove(key);
+        self.factory.forget(&session_id(key)).await
+    }
+
+    async fn turn(
+        &self,
+        key: &ThreadKey,
+        session: &Session,
+        answer: Option<String>,
+    ) -> Result<(), CognitionError> {
+        let result = session
+            .run(answer)
+            .await
+            .map_err(|err| CognitionError(err.to_string()));
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                self.post(key, "Sorry, something went wrong.", vec![])
+                    .await?;
+                return Err(err);
+            }
+        };
+        let Some(result) = result else {
+            self.end(key).await?;
+            return self.post(key, "Goodbye!", vec![]).await;
+        };
+
+        let (text, buttons) = turn_message(&result);
+        if buttons.is_empty() {
+            // Nothing left to answer
+            self.end(key).await?;
+        }
+        let mut blocks = vec![section(&text)];
+        for buttons in buttons.chunks(MAX_BUTTONS) {
+            blocks.push(json!({ "type": "actions", "elements": buttons }));
+        }
+        self.post(key, &text, blocks).await
+    }
+
+    async fn post(
+        &self,
+        (channel, thread): &ThreadKey,
+        text: &str,
+        blocks: Vec<Value>,
+    ) -> Result<(), CognitionError> {
+        let mut message = json!({ "channel": channel, "thread_ts": thread, "text": text });
+        if !blocks.is_empty() {
+            message["blocks"] = blocks.into();
+        }
+        self.call(&self.bot_token, "chat.postMessage", message)
+            .await
+            .map(|_| ())
+    }
+
+    // Web API method, failing on Slack's `ok: false`
+    async fn call(&self, token: &str, method: &str, body: Value) -> Result<Value, CognitionError> {
+        let response: Value = self
+            .http
+            .post(format!("{}/{}", SLACK_API, method))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| CognitionError(format!("Slack {} failed: {}", method, err)))?
+            .json()
+            .await
+            .map_err(|err| CognitionError(format!("Invalid Slack {} response: {}", method, err)))?;
+        if response["ok"] != true {
+            return Err(CognitionError(format!(
+                "Slack {} failed: {}",
+                method, response["error"]
+            )));
+        }
+        Ok(response)
+    }
+}
+
+fn session_id((channel, thread): &ThreadKey) -> String {
+    format!("slack-{}-{}", channel, thread)
+}
+
+fn spawn(task: impl Future<Output = Result<(), CognitionError>> + 'static) {
+    tokio::task::spawn_local(async move {
+        if let Err(err) = task.await {
+            eprintln!("[!] {}", err);
+        }
+    });
+}
+
+// Text of a turn, its informational nodes, refusal and question, and a button
+// per choice, or yes and no for a confirmation
+fn turn_message(result: &DecisionResult) -> (String, Vec<Value>) {
+    let mut lines: Vec<&str> = result
+        .interstitials
+        .iter()
+        .map(|interstitial| interstitial.text.as_str())
+        .collect();
+    lines.extend(result.refusal.as_deref());
+    lines.push(
+        result
+            .confirm
+            .as_deref()
+            .unwrap_or(&result.decision_node.text),
+    );
+
+    let choices: Vec<&str> = match result.confirm {
+        Some(_) => vec!["Yes", "No"],
+        None => result
+            .decision_node
+            .choices()
+            .iter()
+            .map(|choice| choice.text.as_str())
+            .collect(),
+    };
+    let buttons = choices
+        .iter()
+        .enumerate()
+        .map(|(i, choice)| {
+            json!({
+                "type": "button",
+                "action_id": format!("choice_{}", i),
+                "text": { "type": "plain_text", "text": button_text(choice) },
+                "value": choice,
+            })
+        })
+        .collect();
+    (lines.join("\n\n"), buttons)
+}
+
+fn section(text: &str) -> Value {
+    json!({ "type": "section", "text": { "type": "mrkdwn", "text": text } })
+}
+
+fn button_text(choice: &str) -> String {
+    if choice.chars().count() <= MAX_BUTTON_TEXT {
+        return choice.to_string();
+    }
+    let mut text: String = choice.chars().take(MAX_BUTTON_TEXT - 1).collect();
+    text.push('…');
+    text
+}
+
+// Message text without `<@U123>` mentions
+fn without_mentions(text: &str) -> String {
+    let mut rest = text;
+    let mut result = String::new();
+    while let Some(start) = rest.find("<@") {
+        result.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    result.push_str(rest);
+    result.trim().to_string()
+}
@@ -0,0 +1,151 @@
+// Server-Sent Events endpoint for browsers:
+//
+//     GET /stream?session=<id>&input=<answer>
+//
+// runs a turn of the session, or the first turn of a new session without
+// `session`, and streams its `DecisionEvent`s as they happen, model output and
+// tool progress included. Each SSE event is named after the event and carries
+// its JSON, with the `session_id` to pass on the next turn. The stream ends
+// after `awaiting_input`, `turn_failed` or `session_ended`. Listens on
+// `SERVER_ADDR` (`0.0.0.0:8080` by default), see `inbound` for HTTPS and
+// signed requests.
+use crate::inbound;
+use crate::setup::{SessionCache, SessionFactory};
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Router,
+};
+use cognition::{decision_stream, CognitionError, ConcurrencyPolicy, DecisionEvent, Session};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::rc::Rc;
+use tokio::task::{spawn_local, LocalSet};
+
+// A turn to run, streaming its events to `events`
+struct TurnRequest {
+    session_id: Option<String>,
+    input: Option<String>,
+    events: UnboundedSender<DecisionEvent>,
+}
+
+#[derive(Deserialize)]
+struct StreamQuery {
+    session: Option<String>,
+    input: Option<String>,
+}
+
+type Sessions = Rc<SessionCache<String>>;
+
+pub async fn serve() -> Result<(), CognitionError> {
+    let factory = SessionFactory::from_env()?;
+    let verifier = factory.verifier()?;
+    let (turns, requests) = unbounded();
+    // Sessions are not `Send`, so they run on a thread of their own
+    std::thread::spawn(move || run_sessions(factory, requests));
+
+    let app = Router::new()
+        .route("/stream", get(stream))
+        .with_state(turns);
+    let addr = std::env::var("SERVER_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
+    inbound::serve(&addr, inbound::signed(app, verifier), |url| {
+        println!("Streaming turns on {}/stream", url)
+    })
+    .await
+}
+
+async fn stream(
+    State(turns): State<UnboundedSender<TurnRequest>>,
+    Query(query): Query<StreamQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (events, receiver) = unbounded();
+    let request = TurnRequest {
+        session_id: query.session,
+        input: query.input,
+        events,
+    };
+    // Without the session thread the receiver ends right away
+    let _ = turns.unbounded_send(request);
+    Sse::new(receiver.map(|event| Ok(sse_event(&event)))).keep_alive(KeepAlive::default())
+}
+
+fn sse_event(event: &DecisionEvent) -> Event {
+    let json = serde_json::to_value(event).unwrap_or_default();
+    Event::default()
+        .event(json["event"].as_str().unwrap_or("message"))
+        .data(json.to_string())
+}
+
+fn run_sessions(factory: SessionFactory, mut requests: UnboundedReceiver<TurnRequest>) {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("[!] Failed to start the session runtime: {}", err);
+            return;
+        }
+    };
+    let factory = Rc::new(factory);
+    let sessions: Sessions = Rc::new(factory.cache());
+    // Turns of different sessions run concurrently
+    LocalSet::new().block_on(&runtime, async move {
+        while let Some(request) = requests.next().await {
+            spawn_local(run_turn(factory.clone(), sessions.clone(), request));
+        }
+    });
+}
+
+async fn run_turn(factory: Rc<SessionFactory>, sessions: Sessions, request: TurnRequest) {
+    let fail = |session_id: &str, message: String| {
+        let _ = request.events.unbounded_send(DecisionEvent::TurnFailed {
+            session_id: session_id.to_string(),
+            message,
+        });
+    };
+    let session = match &request.session_id {
+        Some(session_id) => match sessions.get(session_id).await {
+            Ok(Some(session)) => session,
+            // Started on another server, or before a restart
+            Ok(None) => match factory.resume(session_id).await {
+                Ok(Some(state)) => {
+                    let session = Rc::new(Session::new(state, ConcurrencyPolicy::Queue));
+                    sessions.insert(session_id.clone(), session)
+                }
+                Ok(None) => return fail(session_id, "Unknown session".to_string()),
+                Err(err) => return fail(session_id, err.0),
+            },
+            Err(err) => return fail(session_id, err.0),
+        },
+        None => match factory.new_state() {
+            Ok(state) => {
+                let session = Rc::new(Session::new(state, ConcurrencyPolicy::Queue));
+                sessions.insert(session.session_id().to_string(), session)
+            }
+            Err(err) => return fail("", err.0),
+        },
+    };
+
+    let mut ended = false;
+    match session.lock().await {
+        Ok(mut state) => {
+            let mut events = std::pin::pin!(decision_stream(request.input, &mut state));
+            while let Some(event) = events.next().await {
+                ended |= matches!(event, DecisionEvent::SessionEnded { .. });
+                // The turn completes even when the browser stopped listening
+                let _ = request.events.unbounded_send(event);
+            }
+        }
+        Err(err) => fail(session.session_id(), err.to_string()),
+    }
+    if ended {
+        sessions.remove(session.session_id());
+        if let Err(err) = factory.forget(session.session_id()).await {
+            eprintln!("[!] {}", err);
+        }
+    }
+}
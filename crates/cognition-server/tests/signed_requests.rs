@@ -0,0 +1,97 @@
+// Requests to the SSE endpoint of a server whose config has `webhook.secret`:
+// only requests signed with the secret are served, once.
+#![cfg(feature = "sse")]
+use cognition::webhook::WebhookVerifier;
+use reqwest::StatusCode;
+use std::net::TcpListener;
+use std::process::{Child, Command};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECRET: &str = "test-secret";
+
+// Server process, killed at the end of the test
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn start_server() -> (Server, String) {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let config = std::env::temp_dir().join(format!("cognition-signed-{}.yaml", port));
+    std::fs::write(
+        &config,
+        format!(
+            "models:\n  davinci003:\n    api_key: test\nwebhook:\n  secret: {}\n",
+            SECRET
+        ),
+    )
+    .unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_cognition-server"))
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../.."))
+        .env("COGNITION_CONFIG", &config)
+        .env("SERVER_ADDR", format!("127.0.0.1:{}", port))
+        .env_remove("REDIS_URL")
+        .env_remove("TLS_CERT")
+        .spawn()
+        .unwrap();
+    (Server(child), format!("http://127.0.0.1:{}", port))
+}
+
+async fn get(
+    url: &str,
+    path: &str,
+    signature: Option<(u64, String)>,
+) -> Result<StatusCode, reqwest::Error> {
+    let mut request = reqwest::Client::new().get(format!("{}{}", url, path));
+    if let Some((timestamp, signature)) = signature {
+        request = request
+            .header("X-Cognition-Timestamp", timestamp.to_string())
+            .header("X-Cognition-Signature", signature);
+    }
+    Ok(request.send().await?.status())
+}
+
+#[tokio::test]
+async fn only_signed_requests_are_served() {
+    let (_server, url) = start_server();
+    // Up once it answers
+    let mut unsigned = None;
+    for _ in 0..100 {
+        if let Ok(status) = get(&url, "/stream", None).await {
+            unsigned = Some(status);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(unsigned, Some(StatusCode::UNAUTHORIZED));
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let forged = WebhookVerifier::new("another-secret").sign(now, b"/stream");
+    let status = get(&url, "/stream", Some((now, forged))).await.unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Signed for another path
+    let signature = WebhookVerifier::new(SECRET).sign(now, b"/stream?session=other");
+    let status = get(&url, "/stream", Some((now, signature))).await.unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let signature = WebhookVerifier::new(SECRET).sign(now, b"/stream");
+    let status = get(&url, "/stream", Some((now, signature.clone())))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    // Replayed
+    let status = get(&url, "/stream", Some((now, signature))).await.unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,168 @@
+// Browser bindings of the engine, built with wasm-pack:
+//
+//     wasm-pack build crates/cognition-wasm --target web
+//
+// A session is created from YAML config, prompt template and tree text and
+// runs a turn per answer, resolving to the turn's `DecisionResult`. Models
+// reach their servers through `fetch`, the global one unless another is
+// passed, e.g. to add credentials through a proxy.
+use async_trait::async_trait;
+use cognition::{
+    models::{openai_compatible::OpenAICompatible, LargeLanguageModel, ModelRegistry},
+    run_decision, validate_tree, CognitionError, Decision, DecisionPromptTemplate, DecisionState,
+    HttpClient,
+};
+use js_sys::{Function, Object, Promise, Reflect, JSON};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+// HTTP client calling a JS `fetch(url, init)`
+struct JsFetch(Function);
+
+fn js_error(value: JsValue) -> CognitionError {
+    CognitionError(
+        value
+            .as_string()
+            .or_else(|| {
+                JSON::stringify(&value)
+                    .ok()
+                    .and_then(|text| text.as_string())
+            })
+            .unwrap_or_else(|| "JavaScript error".to_string()),
+    )
+}
+
+fn set(target: &Object, key: &str, value: &JsValue) -> Result<(), CognitionError> {
+    Reflect::set(target, &key.into(), value)
+        .map(|_| ())
+        .map_err(js_error)
+}
+
+fn get(target: &JsValue, key: &str) -> Result<JsValue, CognitionError> {
+    Reflect::get(target, &key.into()).map_err(js_error)
+}
+
+#[async_trait(?Send)]
+impl HttpClient for JsFetch {
+    async fn post_json(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+        body: &Value,
+    ) -> Result<Value, CognitionError> {
+        let init = Object::new();
+        set(&init, "method", &"POST".into())?;
+        let init_headers = Object::new();
+        set(&init_headers, "Content-Type", &"application/json".into())?;
+        for (name, value) in headers {
+            set(&init_headers, name, &value.into())?;
+        }
+        set(&init, "headers", &init_headers)?;
+        set(&init, "body", &body.to_string().into())?;
+
+        let response = self
+            .0
+            .call2(&JsValue::NULL, &url.into(), &init)
+            .map_err(js_error)?;
+        let response = JsFuture::from(Promise::resolve(&response))
+            .await
+            .map_err(js_error)?;
+        if !get(&response, "ok")?.is_truthy() {
+            let status = get(&response, "status")?.as_f64().unwrap_or_default();
+            return Err(CognitionError(format!(
+                "HTTP request error: {} returned {}",
+                url, status
+            )));
+        }
+        let text = get(&response, "text")?
+            .dyn_into::<Function>()
+            .map_err(js_error)?
+            .call0(&response)
+            .map_err(js_error)?;
+        let text = JsFuture::from(Promise::resolve(&text))
+            .await
+            .map_err(js_error)?
+            .as_string()
+            .unwrap_or_default();
+        serde_json::from_str(&text)
+            .map_err(|err| CognitionError(format!("JSON parsing error: {}", err)))
+    }
+}
+
+// Models able to run in the browser, sending requests through `fetch`
+fn registry(fetch: Function) -> ModelRegistry {
+    let mut registry = ModelRegistry::empty();
+    registry.register(
+        "openai_compatible",
+        Box::new(move |config| {
+            let client = Box::new(JsFetch(fetch.clone()));
+            Ok(Box::new(OpenAICompatible::with_client(config, client)?)
+                as Box<dyn LargeLanguageModel>)
+        }),
+    );
+    registry
+}
+
+#[wasm_bindgen]
+pub struct Session {
+    // Taken out while a turn runs
+    state: Rc<RefCell<Option<DecisionState>>>,
+}
+
+#[wasm_bindgen]
+impl Session {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        config: &str,
+        prompt_template: &str,
+        tree: &str,
+        fetch: Option<Function>,
+    ) -> Result<Session, JsError> {
+        let decision_nodes: Vec<Decision> = serde_yaml::from_str(tree)
+            .map_err(|err| JsError::new(&format!("Invalid tree: {}", err)))?;
+        if let Err(errors) = validate_tree(&decision_nodes) {
+            let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+            return Err(JsError::new(&errors.join("\n")));
+        }
+
+        let fetch = match fetch {
+            Some(fetch) => fetch,
+            None => get(&js_sys::global(), "fetch")
+                .and_then(|fetch| fetch.dyn_into::<Function>().map_err(js_error))
+                .map_err(|err| JsError::new(&err.0))?,
+        };
+        let state = DecisionState::with_registry(
+            config,
+            DecisionPromptTemplate::new(prompt_template.to_string()),
+            decision_nodes,
+            &registry(fetch),
+        )
+        .map_err(|err| JsError::new(&err.0))?;
+        Ok(Session {
+            state: Rc::new(RefCell::new(Some(state))),
+        })
+    }
+
+    // Run a turn with the user's answer, none for the first turn. Resolves to
+    // the turn's `DecisionResult`, or null once the session has ended.
+    pub fn run(&self, user_input: Option<String>) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let mut decision_state = state
+                .borrow_mut()
+                .take()
+                .ok_or_else(|| JsValue::from_str("A turn is already running"))?;
+            let result = run_decision(user_input, &mut decision_state).await;
+            *state.borrow_mut() = Some(decision_state);
+
+            let result = result.map_err(|err| JsValue::from_str(&err.0))?;
+            let json = serde_json::to_string(&result).map_err(|err| {
+                JsValue::from_str(&format!("Failed to serialize result: {}", err))
+            })?;
+            JSON::parse(&json)
+        })
+    }
+}
@@ -0,0 +1,53 @@
+// Node lookups as done on every turn, by linear search and through the index
+// of `DecisionTree`. Run with `cargo bench -p cognition --bench node_lookup`.
+use cognition::{Decision, DecisionTree, NodeId};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const LOOKUPS: usize = 100_000;
+
+fn time(name: &str, mut run: impl FnMut(usize)) -> Duration {
+    let started = Instant::now();
+    for index in 0..LOOKUPS {
+        run(index);
+    }
+    let elapsed = started.elapsed();
+    println!(
+        "{:<28} {:>10.1} ns/lookup",
+        name,
+        elapsed.as_nanos() as f64 / LOOKUPS as f64
+    );
+    elapsed
+}
+
+fn main() {
+    for size in [10, 100, 1000] {
+        let nodes: Vec<Decision> = (0..size)
+            .map(|index| Decision {
+                id: format!("node_{}", index),
+                text: "Text".to_string(),
+                ..Decision::default()
+            })
+            .collect();
+        let ids: Vec<String> = nodes.iter().map(|node| node.id.clone()).collect();
+        let tree = DecisionTree::new(nodes.clone());
+        let node_ids: Vec<NodeId> = ids.iter().map(|id| tree.id(id)).collect();
+
+        println!("{} nodes", size);
+        time("linear find", |index| {
+            let id = &ids[index % size];
+            black_box(nodes.iter().find(|node| node.id == *id));
+        });
+        time("index get", |index| {
+            black_box(tree.get(&ids[index % size]));
+        });
+        time("String clone and compare", |index| {
+            let id = ids[index % size].clone();
+            black_box(id == ids[(index + 1) % size]);
+        });
+        time("NodeId clone and compare", |index| {
+            let id = node_ids[index % size].clone();
+            black_box(id == node_ids[(index + 1) % size]);
+        });
+    }
+}
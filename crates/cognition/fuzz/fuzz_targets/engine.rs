@@ -0,0 +1,68 @@
+#![no_main]
+
+// Arbitrary config, model output and user input through the engine loop, with
+// a scripted model instead of a real backend. The input is split on NUL bytes
+// into the config, the model output and the user inputs.
+use async_trait::async_trait;
+use cognition::{
+    models::{InferenceResult, LargeLanguageModel, ModelError, ModelRegistry},
+    run_decision, Decision, DecisionPromptTemplate, DecisionState,
+};
+use libfuzzer_sys::fuzz_target;
+
+const TREE: &str = include_str!("../../../../decision_tree.yaml");
+const TEMPLATE: &str = include_str!("../../../../decision_prompt_template.yaml");
+
+struct Scripted(String);
+
+#[async_trait(?Send)]
+impl LargeLanguageModel for Scripted {
+    fn new(_config: &String) -> Result<Self, ModelError> {
+        Ok(Self(String::new()))
+    }
+
+    async fn generate(
+        &self,
+        _prompt: &str,
+        _max_length: usize,
+        _temperature: f32,
+    ) -> Result<InferenceResult, ModelError> {
+        Ok(InferenceResult {
+            text: self.0.clone(),
+            probabilities: vec![],
+        })
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let mut parts = text.split('\0');
+    let config = parts.next().unwrap_or_default().to_string();
+    let output = parts.next().unwrap_or_default().to_string();
+    let inputs: Vec<String> = parts.map(|input| input.to_string()).collect();
+
+    let mut registry = ModelRegistry::empty();
+    registry.register(
+        "scripted",
+        Box::new(move |_| Ok(Box::new(Scripted(output.clone())) as Box<dyn LargeLanguageModel>)),
+    );
+
+    // Always use the scripted model, whatever the fuzzed config says
+    let config = format!("model: scripted\n{}", config);
+    let decision_nodes: Vec<Decision> = serde_yaml::from_str(TREE).unwrap();
+    let template = DecisionPromptTemplate::new(TEMPLATE.to_string());
+    let mut state = match DecisionState::with_registry(&config, template, decision_nodes, &registry)
+    {
+        Ok(state) => state,
+        Err(_) => return,
+    };
+
+    futures::executor::block_on(async {
+        let _ = run_decision(None, &mut state).await;
+        for input in inputs {
+            if !matches!(run_decision(Some(input), &mut state).await, Ok(Some(_))) {
+                break;
+            }
+        }
+    });
+});
@@ -0,0 +1,22 @@
+#![no_main]
+
+// Arbitrary templates and variable values through prompt rendering. The input
+// is split on NUL bytes into the template and the four prompt values.
+use cognition::{render_variables, DecisionPromptTemplate};
+use libfuzzer_sys::fuzz_target;
+use std::collections::BTreeMap;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let mut parts = text.split('\0');
+    let template = parts.next().unwrap_or_default();
+    let values: Vec<&str> = parts.collect();
+    let value = |index: usize| values.get(index).copied().unwrap_or_default();
+
+    let template = DecisionPromptTemplate::new(template.to_string());
+    let prompt = template.format(value(0), value(1), value(2), value(3));
+
+    let mut variables = BTreeMap::new();
+    variables.insert(value(0).to_string(), value(1).to_string());
+    let _ = render_variables(&prompt, &variables);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+// Arbitrary YAML through the decision tree parser and validator
+use cognition::{validate_tree, Decision};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(yaml) = std::str::from_utf8(data) {
+        if let Ok(decision_nodes) = serde_yaml::from_str::<Vec<Decision>>(yaml) {
+            let _ = validate_tree(&decision_nodes);
+        }
+    }
+});
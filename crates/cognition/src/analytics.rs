@@ -0,0 +1,211 @@
+use crate::{config::object_by_path, CognitionError, TurnRecord};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Per-node statistics across sessions
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct NodeStats {
+    // Times the node was entered
+    pub visits: usize,
+    // Sessions whose last turn ended on the node
+    pub drop_offs: usize,
+    // Answers that matched a choice
+    pub matched: usize,
+    // Answers that matched none of the choices
+    pub unmatched: usize,
+    // Unmatched answers per matched one
+    pub average_retries: f64,
+}
+
+// Named ordered node sequence, `analytics.funnels` in the config:
+//
+// analytics:
+//   funnels:
+//     purchase: [start, buy_something, buy_book]
+#[derive(Clone, Debug)]
+pub struct Funnel {
+    pub name: String,
+    pub steps: Vec<String>,
+}
+
+impl Funnel {
+    pub fn from_config(config: &str) -> Vec<Self> {
+        let funnels: BTreeMap<String, Vec<String>> = object_by_path(config, "analytics.funnels")
+            .and_then(|funnels| serde_json::from_value(funnels).ok())
+            .unwrap_or_default();
+        funnels
+            .into_iter()
+            .map(|(name, steps)| Self { name, steps })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct FunnelStep {
+    pub node_id: String,
+    // Sessions that reached the step after every previous one
+    pub sessions: usize,
+    // Share of the sessions of the previous step, or of all sessions for the first
+    pub conversion: f64,
+    // Median seconds from the previous step
+    pub median_secs: Option<f64>,
+}
+
+// Aggregates of recorded turn logs (`logging.turns`), for funnel analysis
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SessionAnalytics {
+    pub sessions: usize,
+    pub nodes: BTreeMap<String, NodeStats>,
+    // Node paths, e.g. `start -> buy_something -> buy_book`, by number of sessions
+    pub paths: BTreeMap<String, usize>,
+    pub funnels: BTreeMap<String, Vec<FunnelStep>>,
+}
+
+impl SessionAnalytics {
+    pub fn from_turns(turns: &[TurnRecord], funnels: &[Funnel]) -> Self {
+        // Turns by session, in order
+        let mut sessions: BTreeMap<&str, Vec<&TurnRecord>> = BTreeMap::new();
+        for turn in turns {
+            sessions.entry(&turn.session_id).or_default().push(turn);
+        }
+
+        let mut analytics = Self {
+            sessions: sessions.len(),
+            ..Self::default()
+        };
+        let mut session_paths = vec![];
+        for turns in sessions.values_mut() {
+            turns.sort_by_key(|turn| (turn.turn, turn.timestamp));
+
+            // Nodes entered, with the time they were entered
+            let mut path = vec![turns[0].node_id.as_str()];
+            let mut entered = vec![turns[0].timestamp];
+            for turn in turns.iter() {
+                let stats = analytics.nodes.entry(turn.node_id.clone()).or_default();
+                if turn.choices.is_empty() {
+                    stats.unmatched += 1;
+                } else {
+                    stats.matched += 1;
+                }
+                if turn.next_id != turn.node_id {
+                    path.push(&turn.next_id);
+                    entered.push(turn.timestamp);
+                }
+            }
+
+            for node_id in &path {
+                analytics
+                    .nodes
+                    .entry(node_id.to_string())
+                    .or_default()
+                    .visits += 1;
+            }
+            if let Some(last) = path.last() {
+                analytics
+                    .nodes
+                    .entry(last.to_string())
+                    .or_default()
+                    .drop_offs += 1;
+            }
+            *analytics.paths.entry(path.join(" -> ")).or_default() += 1;
+            session_paths.push((path, entered));
+        }
+
+        for funnel in funnels {
+            analytics
+                .funnels
+                .insert(funnel.name.clone(), funnel_steps(funnel, &session_paths));
+        }
+
+        for stats in analytics.nodes.values_mut() {
+            if stats.matched > 0 {
+                stats.average_retries = stats.unmatched as f64 / stats.matched as f64;
+            }
+        }
+        analytics
+    }
+
+    // Aggregate every `*.jsonl` turn log in `dir`
+    pub fn load_dir(dir: &str, funnels: &[Funnel]) -> Result<Self, CognitionError> {
+        Ok(Self::from_turns(&TurnRecord::load_dir(dir)?, funnels))
+    }
+
+    pub fn to_json(&self) -> Result<String, CognitionError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|err| CognitionError(format!("Failed to serialize analytics: {}", err)))
+    }
+
+    // One row per node
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("node,visits,drop_offs,matched,unmatched,average_retries\n");
+        for (node_id, stats) in &self.nodes {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{:.2}\n",
+                node_id,
+                stats.visits,
+                stats.drop_offs,
+                stats.matched,
+                stats.unmatched,
+                stats.average_retries
+            ));
+        }
+        csv
+    }
+}
+
+// Steps reached by each session, in order, each after the previous one
+fn funnel_steps(funnel: &Funnel, session_paths: &[(Vec<&str>, Vec<u64>)]) -> Vec<FunnelStep> {
+    let mut reached: Vec<usize> = vec![0; funnel.steps.len()];
+    let mut durations: Vec<Vec<u64>> = vec![vec![]; funnel.steps.len()];
+    for (path, entered) in session_paths {
+        let mut start = 0;
+        let mut previous: Option<u64> = None;
+        for (step, node_id) in funnel.steps.iter().enumerate() {
+            let position = match path[start..].iter().position(|id| id == node_id) {
+                Some(offset) => start + offset,
+                None => break,
+            };
+            reached[step] += 1;
+            if let Some(previous) = previous {
+                durations[step].push(entered[position].saturating_sub(previous));
+            }
+            previous = Some(entered[position]);
+            start = position + 1;
+        }
+    }
+
+    let mut previous = session_paths.len();
+    funnel
+        .steps
+        .iter()
+        .zip(reached)
+        .zip(durations)
+        .map(|((node_id, sessions), durations)| {
+            let conversion = if previous > 0 {
+                sessions as f64 / previous as f64
+            } else {
+                0.0
+            };
+            previous = sessions;
+            FunnelStep {
+                node_id: node_id.clone(),
+                sessions,
+                conversion,
+                median_secs: median(durations),
+            }
+        })
+        .collect()
+}
+
+fn median(mut values: Vec<u64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[middle - 1] + values[middle]) as f64 / 2.0)
+    } else {
+        Some(values[middle] as f64)
+    }
+}
@@ -0,0 +1,37 @@
+// Prompt of generate nodes: the model answers the user's last message in the
+// history following the node's instructions, grounded in what its tools returned
+
+use crate::{history::unindent, ToolResponse};
+
+pub fn prompt(history: &str, instructions: &str, tool_responses: &[ToolResponse]) -> String {
+    let mut prompt = String::new();
+    let history = unindent(history);
+    if !history.is_empty() {
+        prompt.push_str(&format!("Conversation so far:\n{}\n\n", history));
+    }
+    if !tool_responses.is_empty() {
+        prompt.push_str("Information to base the answer on:\n");
+        for tool_response in tool_responses {
+            prompt.push_str(&format!(
+                "[{}] {}\n",
+                tool_response.id, tool_response.response
+            ));
+        }
+        prompt.push('\n');
+    }
+    prompt.push_str(&format!(
+        "Instructions: {}\nRespond to the user with the answer only.\n\nAnswer:",
+        instructions
+    ));
+    prompt
+}
+
+// Model output without a repeated label
+pub fn clean(output: &str) -> String {
+    let output = output.trim();
+    output
+        .strip_prefix("Answer:")
+        .unwrap_or(output)
+        .trim()
+        .to_string()
+}
@@ -0,0 +1,12 @@
+// Clock and timeouts of the engine. Browsers have neither std's clock nor
+// tokio's timer, so wasm builds use the JavaScript clock and timers instead.
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(not(target_arch = "wasm32"))]
+pub use tokio::time::{timeout, Instant};
+
+#[cfg(target_arch = "wasm32")]
+pub use wasmtimer::{
+    std::{Instant, SystemTime, UNIX_EPOCH},
+    tokio::timeout,
+};
@@ -0,0 +1,77 @@
+use crate::{History, HistoryEntry};
+
+// Low-information words dropped first, in the spirit of LLMLingua
+const FILLER_WORDS: &[&str] = &[
+    "a",
+    "an",
+    "the",
+    "um",
+    "uh",
+    "well",
+    "so",
+    "just",
+    "really",
+    "very",
+    "actually",
+    "basically",
+    "like",
+    "please",
+    "okay",
+    "ok",
+    "that",
+    "which",
+    "is",
+    "are",
+    "was",
+    "were",
+    "be",
+    "of",
+    "to",
+];
+
+// Recent entries kept verbatim, routing depends on them the most
+const KEEP_RECENT: usize = 2;
+
+// Cheap token estimate, roughly four characters per token for English text
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+fn history_tokens(entries: &[HistoryEntry]) -> usize {
+    entries
+        .iter()
+        .map(|entry| estimate_tokens(&entry.text))
+        .sum()
+}
+
+fn strip_filler(text: &str) -> String {
+    text.split_whitespace()
+        .filter(|word| {
+            let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+            !FILLER_WORDS.contains(&word.to_lowercase().as_str())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Compress the history to fit in `max_tokens`: first strip filler words from
+// older entries, then drop the oldest entries. The most recent entries are
+// always kept as they are.
+pub fn compress_history(history: &History, max_tokens: usize) -> History {
+    let mut compressed = history.clone();
+    if history_tokens(&compressed.entries) <= max_tokens {
+        return compressed;
+    }
+
+    let older = compressed.entries.len().saturating_sub(KEEP_RECENT);
+    for entry in compressed.entries.iter_mut().take(older) {
+        entry.text = strip_filler(&entry.text);
+    }
+
+    while history_tokens(&compressed.entries) > max_tokens && compressed.entries.len() > KEEP_RECENT
+    {
+        compressed.entries.remove(0);
+    }
+
+    compressed
+}
@@ -1,11 +1,11 @@
 use serde_json::Value;
-use serde_yaml;
+use std::time::Duration;
 
-pub fn object_by_path(config: &String, search_path: &str) -> Option<Value> {
-    let yaml_value: Value =
-        serde_yaml::from_str(&config).expect("Unable to parse the YAML content.");
+pub fn object_by_path(config: &str, search_path: &str) -> Option<Value> {
+    // Invalid YAML has no values
+    let yaml_value: Value = serde_yaml::from_str(config).ok()?;
     let mut current_value = &yaml_value;
-    let path_parts: Vec<&str> = search_path.split(".").collect();
+    let path_parts: Vec<&str> = search_path.split('.').collect();
 
     for part in path_parts {
         if let Some(map) = current_value.as_object() {
@@ -22,7 +22,7 @@ pub fn object_by_path(config: &String, search_path: &str) -> Option<Value> {
     Some(current_value.clone())
 }
 
-pub fn string_by_path(config: &String, search_path: &str) -> Option<String> {
+pub fn string_by_path(config: &str, search_path: &str) -> Option<String> {
     let value = object_by_path(config, search_path);
     if let Some(value) = value {
         if let Some(value) = value.as_str() {
@@ -31,3 +31,30 @@ pub fn string_by_path(config: &String, search_path: &str) -> Option<String> {
     }
     None
 }
+
+pub fn f64_by_path(config: &str, search_path: &str) -> Option<f64> {
+    object_by_path(config, search_path).and_then(|value| value.as_f64())
+}
+
+// Duration such as `500ms`, `30s`, `5m` or `1h`, a bare number is in seconds
+pub fn duration_by_path(config: &str, search_path: &str) -> Option<Duration> {
+    let value = object_by_path(config, search_path)?;
+    if let Some(seconds) = value.as_f64() {
+        return Some(Duration::from_secs_f64(seconds));
+    }
+
+    let value = value.as_str()?.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: f64 = amount.parse().ok()?;
+    let seconds = match unit.trim() {
+        "ms" => amount / 1000.0,
+        "" | "s" => amount,
+        "m" => amount * 60.0,
+        "h" => amount * 3600.0,
+        _ => return None,
+    };
+    Some(Duration::from_secs_f64(seconds))
+}
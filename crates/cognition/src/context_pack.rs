@@ -0,0 +1,140 @@
+// Compact description of what a tree is for, its steps and the tools it can
+// use, put ahead of prompts after the persona so chat backends route with the
+// whole conversation in mind. From the config's `context_pack` section:
+//
+// context_pack:
+//   purpose: Book trips to destinations Acme sells
+//   max_nodes: 20
+use crate::{tools::Tool, Decision, Transition, START_ID};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+
+const DEFAULT_MAX_NODES: usize = 20;
+// Characters of node text kept per step
+const MAX_STEP_CHARS: usize = 80;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ContextPack {
+    // What the conversation is for, the start node's text if unset
+    pub purpose: Option<String>,
+    // Nodes listed as steps, nearest to the start first
+    pub max_nodes: usize,
+    pub include_tools: bool,
+}
+
+impl Default for ContextPack {
+    fn default() -> Self {
+        Self {
+            purpose: None,
+            max_nodes: DEFAULT_MAX_NODES,
+            include_tools: true,
+        }
+    }
+}
+
+impl ContextPack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_config(context_pack: &Value) -> Self {
+        serde_json::from_value(context_pack.clone()).unwrap_or_default()
+    }
+
+    pub fn purpose(mut self, purpose: &str) -> Self {
+        self.purpose = Some(purpose.to_string());
+        self
+    }
+
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    pub fn include_tools(mut self, include_tools: bool) -> Self {
+        self.include_tools = include_tools;
+        self
+    }
+
+    // The preamble of a tree and its tools, without the persona
+    pub fn render(&self, decision_nodes: &[Decision], tools: &[Box<dyn Tool>]) -> String {
+        let mut sections = vec![];
+
+        let start = decision_nodes.iter().find(|node| node.id == START_ID);
+        let purpose = self
+            .purpose
+            .clone()
+            .or_else(|| start.map(|node| summarize(&node.text)));
+        if let Some(purpose) = purpose.filter(|purpose| !purpose.is_empty()) {
+            sections.push(format!("Purpose: {}", purpose.trim()));
+        }
+
+        let steps: Vec<String> = ordered(decision_nodes)
+            .into_iter()
+            .filter(|node| !node.text.trim().is_empty())
+            .take(self.max_nodes)
+            .map(|node| format!("  - {}: {}", node.id, summarize(&node.text)))
+            .collect();
+        if !steps.is_empty() {
+            sections.push(format!("Conversation steps:\n{}", steps.join("\n")));
+        }
+
+        if self.include_tools && !tools.is_empty() {
+            let tools: Vec<String> = tools
+                .iter()
+                .map(|tool| {
+                    let mut line = format!("  - {}: {}", tool.name(), tool.description());
+                    if let Some(args_prompt) = tool.args_prompt() {
+                        line.push_str(&format!(" Parameters: {}", args_prompt.trim()));
+                    }
+                    line
+                })
+                .collect();
+            sections.push(format!("Available tools:\n{}", tools.join("\n")));
+        }
+
+        sections.join("\n\n")
+    }
+}
+
+// Nodes in breadth-first order from the start node, then the unreachable ones
+// in tree order
+fn ordered(decision_nodes: &[Decision]) -> Vec<&Decision> {
+    let mut ordered: Vec<&Decision> = vec![];
+    let mut queue = VecDeque::from([START_ID]);
+    while let Some(id) = queue.pop_front() {
+        if ordered.iter().any(|node| node.id == id) {
+            continue;
+        }
+        if let Some(node) = decision_nodes.iter().find(|node| node.id == id) {
+            ordered.push(node);
+            queue.extend(
+                node.choices()
+                    .iter()
+                    .filter_map(|choice| match &choice.next_id {
+                        Transition::Node(id) | Transition::Call(id) => Some(id.as_str()),
+                        _ => None,
+                    }),
+            );
+        }
+    }
+    for node in decision_nodes {
+        if !ordered.iter().any(|ordered| ordered.id == node.id) {
+            ordered.push(node);
+        }
+    }
+    ordered
+}
+
+// First line of a text, shortened to a few words
+fn summarize(text: &str) -> String {
+    let line = text.trim().lines().next().unwrap_or_default().trim();
+    if line.chars().count() <= MAX_STEP_CHARS {
+        line.to_string()
+    } else {
+        let cut: String = line.chars().take(MAX_STEP_CHARS).collect();
+        format!("{}...", cut.trim_end())
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{tree::NodeId, HistoryEntry};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// What changed in a session between two turns, see `DecisionState::diff`
+#[derive(Clone, Debug, Serialize)]
+pub struct TurnDiff {
+    pub from_turn: usize,
+    pub to_turn: usize,
+    pub from_node: String,
+    pub to_node: String,
+    // Callers of subtrees, innermost last
+    pub from_call_stack: Vec<String>,
+    pub to_call_stack: Vec<String>,
+    // Template variables added, changed or removed, by name
+    pub variables: BTreeMap<String, VariableChange>,
+    // History entries dropped from the end, by a reset node or going back,
+    // and entries added after them
+    pub history_removed: Vec<HistoryEntry>,
+    pub history_added: Vec<HistoryEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct VariableChange {
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+// Session state at a point of its timeline
+pub(crate) struct TurnState<'a> {
+    pub turn: usize,
+    pub node_id: &'a str,
+    pub call_stack: &'a [NodeId],
+    pub variables: &'a BTreeMap<String, String>,
+    pub history: &'a [HistoryEntry],
+}
+
+impl TurnDiff {
+    pub(crate) fn between(from: TurnState, to: TurnState) -> Self {
+        let names = from.variables.keys().chain(to.variables.keys());
+        let variables = names
+            .filter_map(|name| {
+                let before = from.variables.get(name);
+                let after = to.variables.get(name);
+                (before != after).then(|| {
+                    let change = VariableChange {
+                        before: before.cloned(),
+                        after: after.cloned(),
+                    };
+                    (name.clone(), change)
+                })
+            })
+            .collect();
+
+        let common = from
+            .history
+            .iter()
+            .zip(to.history)
+            .take_while(|(before, after)| before.role == after.role && before.text == after.text)
+            .count();
+
+        Self {
+            from_turn: from.turn,
+            to_turn: to.turn,
+            from_node: from.node_id.to_string(),
+            to_node: to.node_id.to_string(),
+            from_call_stack: from.call_stack.iter().map(NodeId::to_string).collect(),
+            to_call_stack: to.call_stack.iter().map(NodeId::to_string).collect(),
+            variables,
+            history_removed: from.history[common..].to_vec(),
+            history_added: to.history[common..].to_vec(),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+// Choice matching by meaning, `choices.embedding_threshold`. Answers are
+// embedded and compared with the embeddings of every choice's text and
+// aliases, computed once per tree version rather than on every turn.
+use crate::{tree::DecisionTree, Choice};
+use std::collections::{BTreeSet, HashMap};
+
+// How much closer the best choice must be than the next one to be taken
+// without the model
+const MARGIN: f32 = 0.05;
+
+// Embeddings of a tree's choice texts and aliases
+#[derive(Default)]
+pub struct ChoiceEmbeddings {
+    version: Option<u64>,
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+impl ChoiceEmbeddings {
+    pub fn is_current(&self, tree: &DecisionTree) -> bool {
+        self.version == Some(tree.version())
+    }
+
+    pub fn replace(&mut self, tree: &DecisionTree, texts: Vec<String>, vectors: Vec<Vec<f32>>) {
+        self.version = Some(tree.version());
+        self.vectors = texts.into_iter().zip(vectors).collect();
+    }
+
+    // Index of the choice closest to `answer`, if it is at least `threshold`
+    // similar and clearly ahead of the others
+    pub fn best_choice(
+        &self,
+        answer: &[f32],
+        choices: &[&Choice],
+        threshold: f32,
+    ) -> Option<usize> {
+        let mut scores: Vec<(usize, f32)> = choices
+            .iter()
+            .enumerate()
+            .filter_map(|(index, choice)| {
+                std::iter::once(&choice.text)
+                    .chain(&choice.aliases)
+                    .filter_map(|text| self.vectors.get(text))
+                    .map(|vector| cosine(answer, vector))
+                    .reduce(f32::max)
+                    .map(|score| (index, score))
+            })
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let (index, best) = *scores.first()?;
+        let runner_up = scores.get(1).map(|(_, score)| *score).unwrap_or(f32::MIN);
+        (best >= threshold && best - runner_up >= MARGIN).then_some(index)
+    }
+}
+
+// Every choice text and alias in the tree, once
+pub fn choice_texts(tree: &DecisionTree) -> Vec<String> {
+    let texts: BTreeSet<&String> = tree
+        .nodes()
+        .iter()
+        .flat_map(|decision_node| decision_node.choices())
+        .flat_map(|choice| std::iter::once(&choice.text).chain(&choice.aliases))
+        .collect();
+    texts.into_iter().cloned().collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
@@ -1,95 +1,2219 @@
 use crate::{
-    models::{self, LargeLanguageModel},
-    CognitionError, DecisionPromptTemplate, Tool, ToolResponse,
+    answer,
+    clock::{timeout, Instant, SystemTime, UNIX_EPOCH},
+    compression::{compress_history, estimate_tokens},
+    config::{duration_by_path, f64_by_path, object_by_path, string_by_path},
+    context_pack::ContextPack,
+    diff::{TurnDiff, TurnState},
+    embeddings::{self, ChoiceEmbeddings},
+    guardrails::{Guardrail, GuardrailStage},
+    i18n::Localization,
+    input_limit,
+    interrupts::{keyword_interrupt, Interrupt},
+    language,
+    log_sinks::{self, LogSink},
+    metrics,
+    models::{InferenceResult, LargeLanguageModel, ModelRegistry},
+    persona::{self, Persona},
+    plugins,
+    privacy::PromptPrivacy,
+    redaction::Redactor,
+    scripting, storage,
+    store::{InvocationStatus, SessionRecord, SessionStore, ToolInvocation},
+    templates::{self, TemplateProvider},
+    tools, translation,
+    tree::{DecisionTree, NodeId},
+    tree_stats::TreeStats,
+    turn_log::{self, TurnRecord},
+    usage::{Pricing, Usage},
+    webhook, CognitionError, DecisionEvent, History, HistoryEntry, HistoryStyle, Observer, Role,
+    SessionOutcome, Tool, ToolOutcome, ToolProgress, ToolResponse,
 };
+use async_trait::async_trait;
+use cognition_core::{
+    condition, render_variables, Choice, Decision, DecisionPromptTemplate, InputLimit,
+    InputLimitStrategy, PredictionPolicy, StepFailure, Transition, EXIT_ID, START_ID,
+};
+use futures::future::join_all;
 use log::*;
-use serde::{Deserialize, Serialize};
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+// Dialogue memory of a node within the session
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NodeVisits {
+    pub count: usize,
+    // Seconds since the Unix epoch
+    pub last_visit: u64,
+}
 
-// YAML decision node structure
+// Choice selected by the model in JSON mode
+#[derive(Deserialize)]
+struct ChoiceSelection {
+    choice: String,
+}
+
+// Choice waiting for the user's yes/no before its transition is committed
 #[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Decision {
-    pub id: String,
-    pub text: String,
-    pub predicted_text: Option<String>,
-    pub tool: Option<String>,
-    pub predict: Option<bool>,
-    pub reset: Option<bool>,
-    pub choices: Option<Vec<Choice>>,
+struct PendingConfirm {
+    choice: Choice,
+    prompt: String,
+    user_input: Option<String>,
+}
+
+// State restored by undo, and compared by `DecisionState::diff`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Checkpoint {
+    current_id: NodeId,
+    history: Vec<HistoryEntry>,
+    call_stack: Vec<NodeId>,
+    variables: BTreeMap<String, String>,
+    // Session variables and counters, as the turn left them before undo
+    #[serde(default)]
+    vars: BTreeMap<String, String>,
+    #[serde(default)]
+    turns: usize,
+    #[serde(default)]
+    visits: HashMap<String, NodeVisits>,
+    #[serde(default)]
+    last_entered: Option<String>,
+    #[serde(default)]
+    repeats: usize,
+}
+
+// Conversation state of a session between turns, so another process can
+// continue it, see `DecisionState::snapshot`. Cooldowns, model translations
+// and secret answers are not kept.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionSnapshot {
+    pub session_id: String,
+    pub tags: BTreeMap<String, String>,
+    pub vars: BTreeMap<String, String>,
+    pub locale: Option<String>,
+    pub user: String,
+    pub history: Vec<HistoryEntry>,
+    pub current_id: String,
+    pub call_stack: Vec<String>,
+    pub visits: HashMap<String, NodeVisits>,
+    pub usage: Usage,
+    pub outcome: Option<SessionOutcome>,
+    // Seconds since the Unix epoch
+    pub started_at: u64,
+    pub started: bool,
+    pub finished: bool,
+    answer: Option<(NodeId, String)>,
+    checkpoints: Vec<Checkpoint>,
+    pending_confirm: Option<PendingConfirm>,
+    retries: usize,
+    turns: usize,
+    nudged: bool,
+    last_entered: Option<String>,
+    repeats: usize,
+}
+
+// Named agent of a multi-agent tree, said nodes with `speaker: <id>`
+struct Agent {
+    persona: Persona,
+    // Own model backend, the session's model if unset
+    model: Option<Box<dyn LargeLanguageModel>>,
+}
+
+// Tool responses by tool ID and input, with when they were received
+type ToolCache = HashMap<(String, String), (Instant, Option<ToolResponse>)>;
+
+pub struct DecisionState {
+    model: Box<dyn LargeLanguageModel>,
+    agents: BTreeMap<String, Agent>,
+    // Nodes as defined, and as served in the session's locale
+    source_nodes: Vec<Decision>,
+    decision_tree: DecisionTree,
+    localization: Localization,
+    locale: Option<String>,
+    // Computed on first request, cleared when the tree changes
+    tree_stats: OnceLock<Arc<TreeStats>>,
+    // Embedded choice texts, computed again when the tree version changes
+    choice_embeddings: Mutex<ChoiceEmbeddings>,
+    // Summary of the tree and tools ahead of prompts, see `ContextPack`
+    context_pack: Option<ContextPack>,
+    // Rendered context pack, cleared when the tree or tools change
+    context_preamble: OnceLock<String>,
+    decision_prompt_template: DecisionPromptTemplate,
+    template_provider: Option<Box<dyn TemplateProvider>>,
+    tools: Vec<Box<dyn Tool>>,
+    observers: Vec<Box<dyn Observer>>,
+    store: Option<Box<dyn SessionStore>>,
+    guardrails: Vec<Box<dyn Guardrail>>,
+    log_sinks: Vec<Box<dyn LogSink>>,
+    redactor: Option<Redactor>,
+    pub session_id: String,
+    // Searchable session labels (tenant, channel, campaign), see `SessionStore::find_sessions`
+    pub tags: BTreeMap<String, String>,
+    pub persona: Persona,
+    pub user: String,
+    pub output_language: Option<String>,
+    // Language the tree is written in, enables translating sessions whose
+    // locale has no string bundle
+    pub base_language: Option<String>,
+    pub confidence_threshold: Option<f32>,
+    // Whether and how many choices are predicted after the user's, nodes can
+    // override the policy with `predict`
+    pub prediction: PredictionPolicy,
+    pub max_predictions: usize,
+    pub json_choices: bool,
+    // Choices listed as `1. Yes` in prompts
+    pub numbered_choices: bool,
+    // Answers that are a choice number are taken as the choice's text
+    pub number_input: bool,
+    // Select choices matching an answer unambiguously without the model, see
+    // `fast_choice`. Disabled to evaluate the model on every answer.
+    pub fast_path: bool,
+    // Similarity from which answers select the closest choice by embedding,
+    // without the model. Unset disables embedding matching.
+    pub embedding_threshold: Option<f32>,
+    // Longest answer of nodes without their own `input_limit`
+    pub input_limit: Option<InputLimit>,
+    pub max_retries: usize,
+    pub max_history_tokens: Option<usize>,
+    pub persona_traits: BTreeMap<String, String>,
+    // Host values available to templates as `{{vars.<name>}}`
+    pub vars: BTreeMap<String, String>,
+    // Jumps out of any node, checked before the node's choices
+    pub interrupts: Vec<Interrupt>,
+    pub model_timeout: Option<Duration>,
+    pub tool_timeout: Option<Duration>,
+    pub tool_retries: usize,
+    // How long responses of each tool are reused for the same input
+    pub tool_cache_ttls: HashMap<String, Duration>,
+    pub destructive_confirm: String,
+    // Length and temperature of generate nodes' answers
+    pub answer_max_tokens: usize,
+    pub answer_temperature: f32,
+    // Rhai operations a node script may run before it is stopped
+    pub script_max_operations: u64,
+    pub idle_after: Option<Duration>,
+    pub idle_node: Option<String>,
+    pub blocked_node: Option<String>,
+    // Safety limits on answers per session and entries of a node in a row,
+    // leading to `limit_node` or failing the turn without one
+    pub max_turns: Option<usize>,
+    pub max_node_repeats: Option<usize>,
+    pub limit_node: Option<String>,
+    // JSONL file every turn is appended to
+    pub turn_log: Option<String>,
+    pub prompt_privacy: PromptPrivacy,
+    // Cost of model tokens, see `Usage`
+    pub pricing: Option<Pricing>,
+    // Model calls of the session, counted by `generate` which only borrows the state
+    usage: Mutex<Usage>,
+    tool_cache: Mutex<ToolCache>,
+    history: History,
+    // Model translations by target language and text
+    translations: HashMap<(String, String), String>,
+    // Answer composed for the current generate node, until another node is entered
+    answer: Option<(NodeId, String)>,
+    current_id: NodeId,
+    call_stack: Vec<NodeId>,
+    checkpoints: Vec<Checkpoint>,
+    retries: usize,
+    turns: usize,
+    // Last time each (node, choice) with a cooldown was taken
+    cooldowns: HashMap<(String, String), Instant>,
+    pending_confirm: Option<PendingConfirm>,
+    // Answers to `input: secret` nodes by node ID, never rendered or serialized
+    secrets: HashMap<String, String>,
+    visits: HashMap<String, NodeVisits>,
+    last_activity: Instant,
+    nudged: bool,
+    // Node entered last and how many times in a row
+    last_entered: Option<String>,
+    repeats: usize,
+    outcome: Option<SessionOutcome>,
+    started_at: u64,
+    started: bool,
+    finished: bool,
+}
+
+impl DecisionState {
+    // Create the state with the built-in models, failing when the model named
+    // in the config is not compiled in
+    #[allow(clippy::ptr_arg)] // `&String` is part of the public API
+    pub fn new(
+        config: &String,
+        decision_prompt_template: DecisionPromptTemplate,
+        decision_nodes: Vec<Decision>,
+    ) -> Result<Self, CognitionError> {
+        Self::with_registry(
+            config,
+            decision_prompt_template,
+            decision_nodes,
+            &ModelRegistry::default(),
+        )
+    }
+
+    // Create the state with the model named by `model:` in the config, looked up
+    // in `registry` so downstream crates can provide their own models
+    pub fn with_registry(
+        config: &str,
+        decision_prompt_template: DecisionPromptTemplate,
+        decision_nodes: Vec<Decision>,
+        registry: &ModelRegistry,
+    ) -> Result<Self, CognitionError> {
+        // Models and tools of native plugins, e.g. `plugins.dir: plugins`,
+        // with plugin models taking precedence over the registry's
+        let (plugin_models, plugin_tools) = plugins::from_config(config)?;
+        let create_model = |name: &str| {
+            if plugin_models.contains(name) {
+                plugin_models.create(name, config)
+            } else {
+                registry.create(name, config)
+            }
+        };
+
+        // LLM model
+        let model_name = string_by_path(config, "model").unwrap_or(DEFAULT_MODEL.to_string());
+        let model = create_model(&model_name)
+            .map_err(|err| CognitionError(format!("Failed to create model: {}", err)))?;
+
+        // Named agents, e.g. `agents: { critic: { name: Critic, tone: blunt,
+        // model: openai_compatible } }`, with the persona fields and an optional
+        // model backend configured like the session's
+        let mut agents = BTreeMap::new();
+        let agent_configs = object_by_path(config, "agents")
+            .and_then(|agents| agents.as_object().cloned())
+            .unwrap_or_default();
+        for (id, agent_config) in agent_configs {
+            let mut persona = Persona::from_config(&agent_config);
+            if agent_config.get("name").is_none() {
+                persona.name = id.clone();
+            }
+            let model = match agent_config.get("model").and_then(|model| model.as_str()) {
+                Some(model_name) => Some(create_model(model_name).map_err(|err| {
+                    CognitionError(format!("Failed to create model of agent {}: {}", id, err))
+                })?),
+                None => None,
+            };
+            agents.insert(id, Agent { persona, model });
+        }
+        for decision_node in &decision_nodes {
+            if let Some(speaker) = &decision_node.speaker {
+                if !agents.contains_key(speaker) {
+                    warn!(
+                        "Node {} is said by unknown agent {}, using the default agent",
+                        decision_node.id, speaker
+                    );
+                }
+            }
+        }
+
+        // Interrupts, e.g. `interrupts: [{ intent: Talk to a human, keywords:
+        // [human, agent], next_id: handoff }]`
+        let interrupts = match object_by_path(config, "interrupts") {
+            Some(interrupts) => Interrupt::from_config(&interrupts)?,
+            None => vec![],
+        };
+        for interrupt in &interrupts {
+            if let Transition::Node(id) | Transition::Call(id) = &interrupt.next_id {
+                if !decision_nodes.iter().any(|node| node.id == *id) {
+                    return Err(CognitionError(format!(
+                        "Interrupt {} leads to unknown node {}",
+                        interrupt.intent, id
+                    )));
+                }
+            }
+        }
+
+        // Unique enough for correlating events, hosts can override it
+        let session_id = format!(
+            "{:x}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos())
+                .unwrap_or_default()
+        );
+
+        // Session tags, e.g. `session.tags: { channel: web }`
+        let tags = object_by_path(config, "session.tags")
+            .and_then(|tags| serde_json::from_value(tags).ok())
+            .unwrap_or_default();
+
+        // Agent name, role, tone and instructions, see `Persona`
+        let persona = object_by_path(config, "persona")
+            .map(|persona| Persona::from_config(&persona))
+            .unwrap_or_default();
+        let user = "User".into();
+
+        // String bundles for `t:<key>` node text, e.g.
+        // `i18n: { bundles: locales, locale: es, default_locale: en }`
+        let mut localization = match string_by_path(config, "i18n.bundles") {
+            Some(dir) => Localization::load_dir(dir)?,
+            None => Localization::new(),
+        };
+        localization.default_locale = string_by_path(config, "i18n.default_locale");
+        let locale = string_by_path(config, "i18n.locale");
+
+        // Language generated text must be in, e.g. `session.output_language: es`
+        let output_language = string_by_path(config, "session.output_language");
+
+        // Translation bridge, e.g. `translation.base_language: en`
+        let base_language = string_by_path(config, "translation.base_language");
+
+        // Minimum confidence to auto-advance a predicted choice
+        let confidence_threshold =
+            f64_by_path(config, "prediction.confidence_threshold").map(|value| value as f32);
+
+        // Prediction after the user's choice, e.g. `prediction: { policy: conservative, max_chain: 2 }`
+        let prediction = match string_by_path(config, "prediction.policy") {
+            Some(value) => PredictionPolicy::parse(&value)
+                .ok_or_else(|| CognitionError(format!("Unknown prediction policy: {}", value)))?,
+            None => PredictionPolicy::default(),
+        };
+        let max_predictions = f64_by_path(config, "prediction.max_chain")
+            .map(|value| value as usize)
+            .unwrap_or(DEFAULT_MAX_PREDICTIONS);
+
+        // Unmatched answers on a node before falling back to its `default_next_id`
+        let max_retries = f64_by_path(config, "session.max_retries")
+            .map(|value| value as usize)
+            .unwrap_or(DEFAULT_MAX_RETRIES);
+
+        // Token budget for the history in prompts, compressed when over
+        let max_history_tokens =
+            f64_by_path(config, "compression.max_tokens").map(|value| value as usize);
+
+        // Select choices with structured JSON output instead of free text
+        let json_choices = object_by_path(config, "prediction.json")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+
+        // Number the choices in prompts, `choices.numbered: true`
+        let numbered_choices = object_by_path(config, "choices.numbered")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+        // Kiosk-style selection by number, `choices.number_input: true`
+        let number_input = object_by_path(config, "choices.number_input")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+        // `choices.fast_path: false` sends every answer to the model
+        let fast_path = object_by_path(config, "choices.fast_path")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(true);
+        // `choices.embedding_threshold: 0.8` matches answers by meaning
+        let embedding_threshold =
+            f64_by_path(config, "choices.embedding_threshold").map(|value| value as f32);
+        // e.g. `input_limit: { max_chars: 2000, strategy: summarize }`
+        let input_limit = object_by_path(config, "input_limit")
+            .and_then(|limit| serde_json::from_value(limit).ok());
+
+        // Per-call timeouts, e.g. `timeouts.model_ms: 30000`
+        let model_timeout = f64_by_path(config, "timeouts.model_ms")
+            .map(|value| Duration::from_millis(value as u64));
+        let tool_timeout = f64_by_path(config, "timeouts.tool_ms")
+            .map(|value| Duration::from_millis(value as u64));
+
+        // Failed tool calls are retried, see `run_tool`
+        let tool_retries = f64_by_path(config, "tools.max_retries")
+            .map(|value| value as usize)
+            .unwrap_or(0);
+        // Responses reused for the same input, e.g. `tools.cache: { weather: 10m }`
+        let tool_cache_ttls = object_by_path(config, "tools.cache")
+            .and_then(|cache| cache.as_object().cloned())
+            .unwrap_or_default()
+            .keys()
+            .filter_map(|tool_id| {
+                let ttl = duration_by_path(config, &format!("tools.cache.{}", tool_id))?;
+                Some((tool_id.clone(), ttl))
+            })
+            .collect();
+
+        // Question asked before choices marked `destructive: true`
+        let destructive_confirm = string_by_path(config, "confirm.destructive")
+            .unwrap_or_else(|| DEFAULT_DESTRUCTIVE_CONFIRM.to_string());
+
+        // Answers of generate nodes, e.g. `generate: { max_tokens: 200, temperature: 0.3 }`
+        let answer_max_tokens = f64_by_path(config, "generate.max_tokens")
+            .map(|value| value as usize)
+            .unwrap_or(DEFAULT_ANSWER_MAX_TOKENS);
+        let answer_temperature = f64_by_path(config, "generate.temperature")
+            .map(|value| value as f32)
+            .unwrap_or(DEFAULT_ANSWER_TEMPERATURE);
+
+        // Node scripts, e.g. `scripting: { max_operations: 10000 }`
+        let script_max_operations = f64_by_path(config, "scripting.max_operations")
+            .map(|value| value as u64)
+            .unwrap_or(DEFAULT_SCRIPT_MAX_OPERATIONS);
+
+        // Abandonment detection, e.g. `on_idle: { after: 5m, node: nudge }`
+        let idle_after = duration_by_path(config, "on_idle.after");
+        let idle_node = node_by_path(config, "on_idle.node", &decision_nodes)?;
+
+        // Personal data redaction, `redaction.enabled: true`
+        let redactor = object_by_path(config, "redaction.enabled")
+            .and_then(|value| value.as_bool())
+            .filter(|enabled| *enabled)
+            .map(|_| Redactor::new());
+
+        // Per-turn log for offline analysis, e.g. `logging.turns: turns.jsonl`
+        let turn_log = string_by_path(config, "logging.turns");
+        // and streamed to external log systems, e.g. `logging.syslog.address`
+        let mut log_sinks = log_sinks::from_config(config)?;
+        // Sessions and turns in a database, e.g. `storage.url: sqlite://cognition.db`
+        let (store, storage_sink) = storage::from_config(config)?.unzip();
+        log_sinks.extend(storage_sink);
+
+        // Events posted to integrations, e.g. `webhooks: [{ url, secret, events }]`
+        let observers = webhook::from_config(config)?;
+
+        // Local commands, e.g. `tools.shell.commands`
+        let mut tools = tools::from_config(config)?;
+        tools.extend(plugin_tools);
+
+        // Prompt template kept outside the deployment, e.g. `prompt_template.url`
+        let template_provider = templates::from_config(config)?;
+
+        // Prompt capture in logs and exports, e.g. `privacy.prompts: hash`
+        let prompt_privacy = string_by_path(config, "privacy.prompts")
+            .and_then(|value| PromptPrivacy::parse(&value))
+            .unwrap_or_default();
+
+        // Node shown when a guardrail blocks the user's input
+        let blocked_node = node_by_path(config, "guardrails.blocked_node", &decision_nodes)?;
+
+        // Safety limits, e.g. `limits: { max_turns: 50, max_node_repeats: 5,
+        // fallback_node: human }`
+        let max_turns = f64_by_path(config, "limits.max_turns").map(|value| value as usize);
+        let max_node_repeats =
+            f64_by_path(config, "limits.max_node_repeats").map(|value| value as usize);
+        let limit_node = node_by_path(config, "limits.fallback_node", &decision_nodes)?;
+
+        // Tree and tool summary ahead of prompts, e.g. `context_pack: { purpose:
+        // Book trips }`
+        let context_pack =
+            object_by_path(config, "context_pack").map(|pack| ContextPack::from_config(&pack));
+
+        // Persona traits sampled once per session, see `persona::sample_traits`
+        let persona_traits = object_by_path(config, "persona.traits")
+            .map(|traits| persona::sample_traits(&traits, &mut rand::thread_rng()))
+            .unwrap_or_default();
+
+        // History rendering, e.g. `history.style: chat_ml` for ChatML-tuned models
+        let history_style = string_by_path(config, "history.style")
+            .and_then(|style| HistoryStyle::parse(&style))
+            .unwrap_or_default();
+        let history = History::new(history_style);
+
+        // Initialize the decision loop
+        let current_id = NodeId::from(START_ID);
+
+        let mut state = Self {
+            model,
+            agents,
+            source_nodes: decision_nodes,
+            decision_tree: DecisionTree::default(),
+            localization,
+            locale,
+            tree_stats: OnceLock::new(),
+            choice_embeddings: Mutex::default(),
+            context_pack,
+            context_preamble: OnceLock::new(),
+            decision_prompt_template,
+            template_provider,
+            tools,
+            observers,
+            store,
+            guardrails: vec![],
+            log_sinks,
+            redactor,
+            session_id,
+            tags,
+            persona,
+            user,
+            output_language,
+            base_language,
+            confidence_threshold,
+            prediction,
+            max_predictions,
+            json_choices,
+            numbered_choices,
+            number_input,
+            fast_path,
+            embedding_threshold,
+            input_limit,
+            max_retries,
+            max_history_tokens,
+            persona_traits,
+            vars: BTreeMap::new(),
+            interrupts,
+            model_timeout,
+            tool_timeout,
+            tool_retries,
+            tool_cache_ttls,
+            destructive_confirm,
+            answer_max_tokens,
+            answer_temperature,
+            script_max_operations,
+            idle_after,
+            idle_node,
+            blocked_node,
+            max_turns,
+            max_node_repeats,
+            limit_node,
+            turn_log,
+            prompt_privacy,
+            pricing: Pricing::from_config(config),
+            usage: Mutex::new(Usage::default()),
+            tool_cache: Mutex::new(HashMap::new()),
+            history,
+            translations: HashMap::new(),
+            answer: None,
+            current_id,
+            call_stack: vec![],
+            checkpoints: vec![],
+            retries: 0,
+            turns: 0,
+            cooldowns: HashMap::new(),
+            pending_confirm: None,
+            secrets: HashMap::new(),
+            visits: HashMap::new(),
+            last_activity: Instant::now(),
+            nudged: false,
+            last_entered: None,
+            repeats: 0,
+            outcome: None,
+            started_at: unix_now(),
+            started: false,
+            finished: false,
+        };
+        state.localize_tree();
+        Ok(state)
+    }
+
+    // add tool
+    pub fn add_tool(&mut self, tool: Box<dyn Tool>) {
+        self.tools.push(tool);
+        self.context_preamble = OnceLock::new();
+    }
+
+    // add guardrail, checking user input and generated text
+    pub fn add_guardrail(&mut self, guardrail: Box<dyn Guardrail>) {
+        self.guardrails.push(guardrail);
+    }
+
+    // add log sink, receiving every completed turn
+    pub fn add_log_sink(&mut self, log_sink: Box<dyn LogSink>) {
+        self.log_sinks.push(log_sink);
+    }
+
+    // Fetch the decision prompt template from `provider` at the start of turns
+    pub fn set_template_provider(&mut self, provider: Box<dyn TemplateProvider>) {
+        self.template_provider = Some(provider);
+    }
+
+    // Replace the decision prompt template with the provider's current one
+    pub async fn refresh_template(&mut self) -> Result<(), CognitionError> {
+        let Some(provider) = &self.template_provider else {
+            return Ok(());
+        };
+        let template = provider.fetch().await?;
+        if template != self.decision_prompt_template.content() {
+            info!("Prompt template updated");
+            self.decision_prompt_template = DecisionPromptTemplate::new(template);
+        }
+        Ok(())
+    }
+
+    // Persist side-effecting tool invocations, see `Tool::side_effecting`, and
+    // the session's record and state after every turn
+    pub fn set_store(&mut self, store: Box<dyn SessionStore>) {
+        self.store = Some(store);
+    }
+
+    // Conversation state to continue the session later, with `restore`
+    pub fn snapshot(&self) -> SessionSnapshot {
+        SessionSnapshot {
+            session_id: self.session_id.clone(),
+            tags: self.tags.clone(),
+            vars: self.vars.clone(),
+            locale: self.locale.clone(),
+            user: self.user.clone(),
+            history: self.history.entries.clone(),
+            current_id: self.current_id.to_string(),
+            call_stack: self.call_stack.iter().map(NodeId::to_string).collect(),
+            visits: self.visits.clone(),
+            usage: self.usage(),
+            outcome: self.outcome,
+            started_at: self.started_at,
+            started: self.started,
+            finished: self.finished,
+            answer: self.answer.clone(),
+            checkpoints: self.checkpoints.clone(),
+            pending_confirm: self.pending_confirm.clone(),
+            retries: self.retries,
+            turns: self.turns,
+            nudged: self.nudged,
+            last_entered: self.last_entered.clone(),
+            repeats: self.repeats,
+        }
+    }
+
+    // Continue the session of a snapshot, taken from a state with the same tree
+    pub fn restore(&mut self, snapshot: SessionSnapshot) {
+        self.session_id = snapshot.session_id;
+        self.tags = snapshot.tags;
+        self.vars = snapshot.vars;
+        self.user = snapshot.user;
+        self.history.entries = snapshot.history;
+        self.visits = snapshot.visits;
+        if let Ok(mut usage) = self.usage.lock() {
+            *usage = snapshot.usage;
+        }
+        self.outcome = snapshot.outcome;
+        self.started_at = snapshot.started_at;
+        self.started = snapshot.started;
+        self.finished = snapshot.finished;
+        self.answer = snapshot.answer;
+        self.checkpoints = snapshot.checkpoints;
+        self.pending_confirm = snapshot.pending_confirm;
+        self.retries = snapshot.retries;
+        self.turns = snapshot.turns;
+        self.nudged = snapshot.nudged;
+        self.last_entered = snapshot.last_entered;
+        self.repeats = snapshot.repeats;
+        self.last_activity = Instant::now();
+        self.current_id = NodeId::from(snapshot.current_id.as_str());
+        self.set_locale(snapshot.locale.as_deref());
+        self.call_stack = snapshot
+            .call_stack
+            .iter()
+            .map(|id| self.decision_tree.id(id))
+            .collect();
+    }
+
+    // Restore session `session_id` from the store, false if the store has no
+    // state for it
+    pub async fn resume(&mut self, session_id: &str) -> Result<bool, CognitionError> {
+        let Some(store) = &self.store else {
+            return Err(CognitionError("No session store set".to_string()));
+        };
+        match store.get_state(session_id).await? {
+            Some(snapshot) => {
+                self.restore(snapshot);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // add subtree, its nodes can be reached with `next_id: "call:<id>"`
+    pub fn add_subtree(&mut self, decision_nodes: Vec<Decision>) {
+        self.source_nodes.extend(decision_nodes);
+        self.localize_tree();
+    }
+
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    // Switch the language of node text and choices, e.g. `es-MX`, from the
+    // next node shown on
+    pub fn set_locale(&mut self, locale: Option<&str>) {
+        self.locale = locale.map(str::to_string);
+        self.localize_tree();
+    }
+
+    // Replace the string bundles, e.g. with `Localization::load_dir`
+    pub fn set_localization(&mut self, localization: Localization) {
+        self.localization = localization;
+        self.localize_tree();
+    }
+
+    fn localize_tree(&mut self) {
+        self.decision_tree = DecisionTree::new(
+            self.source_nodes
+                .iter()
+                .map(|decision_node| {
+                    self.localization
+                        .localize_node(self.locale.as_deref(), decision_node)
+                })
+                .collect(),
+        );
+        self.current_id = self.decision_tree.id(&self.current_id);
+        self.tree_stats = OnceLock::new();
+        self.context_preamble = OnceLock::new();
+    }
+
+    pub fn context_pack(&self) -> Option<&ContextPack> {
+        self.context_pack.as_ref()
+    }
+
+    // Summarize the tree and tools ahead of prompts, or stop with `None`
+    pub fn set_context_pack(&mut self, context_pack: Option<ContextPack>) {
+        self.context_pack = context_pack;
+        self.context_preamble = OnceLock::new();
+    }
+
+    // The rendered context pack, empty without one. Only rendered again after
+    // the tree, its locale or the tools change.
+    pub fn context_preamble(&self) -> &str {
+        match &self.context_pack {
+            Some(context_pack) => self
+                .context_preamble
+                .get_or_init(|| context_pack.render(self.decision_tree.nodes(), &self.tools)),
+            None => "",
+        }
+    }
+
+    // Depths, reachability, lint errors and token estimates of the tree's
+    // nodes, cheap to poll as they are only computed again after a change
+    pub fn tree_stats(&self) -> Arc<TreeStats> {
+        self.tree_stats
+            .get_or_init(|| Arc::new(TreeStats::new(self.decision_tree.nodes())))
+            .clone()
+    }
+
+    // add observer
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    // The observer added last, e.g. by `decision_stream` after its turn
+    pub(crate) fn remove_last_observer(&mut self) -> Option<Box<dyn Observer>> {
+        self.observers.pop()
+    }
+
+    async fn notify(&self, event: DecisionEvent) {
+        for observer in &self.observers {
+            observer.notify(&event).await;
+        }
+    }
+
+    // Record how the session ended and notify observers
+    async fn end_session(&mut self, outcome: SessionOutcome) {
+        if self.outcome.is_some() {
+            return;
+        }
+        self.outcome = Some(outcome);
+        self.notify(DecisionEvent::SessionEnded {
+            session_id: self.session_id.clone(),
+            outcome,
+        })
+        .await;
+        if let Err(err) = self.save_session().await {
+            warn!("{}", err.0);
+        }
+    }
+
+    // Write the session record with its tags and outcome, and the session's
+    // state, to the store, if any
+    async fn save_session(&self) -> Result<(), CognitionError> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        store
+            .put_session(&SessionRecord {
+                session_id: self.session_id.clone(),
+                tags: self.tags.clone(),
+                outcome: self.outcome,
+                started_at: self.started_at,
+                updated_at: unix_now(),
+            })
+            .await?;
+        store.put_state(&self.snapshot()).await
+    }
+
+    // Run the guardrails over `text`, notifying observers of the first violation
+    async fn violates_guardrails(
+        &self,
+        stage: GuardrailStage,
+        text: &str,
+    ) -> Result<bool, CognitionError> {
+        for guardrail in &self.guardrails {
+            let violation = match stage {
+                GuardrailStage::Input => guardrail.check_input(text).await?,
+                GuardrailStage::Output => guardrail.check_output(text).await?,
+            };
+            if let Some(violation) = violation {
+                warn!(
+                    "Guardrail {} blocked {:?}: {}",
+                    violation.guardrail, stage, violation.reason
+                );
+                self.notify(DecisionEvent::GuardrailViolation {
+                    session_id: self.session_id.clone(),
+                    node_id: self.current_id.to_string(),
+                    stage,
+                    violation,
+                })
+                .await;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // Result showing the current node, for turns that make no choice, using
+    // the model after `usage_before`
+    async fn node_result(
+        &mut self,
+        user_input: Option<String>,
+        usage_before: Usage,
+    ) -> Result<DecisionResult, CognitionError> {
+        let decision_node = self.current_node()?.clone();
+        let decision_node = self.shown_node(&decision_node, &[]).await?;
+        let mut result = DecisionResult {
+            user_input,
+            decision_prompt: None,
+            choice: None,
+            current_id: self.current_id.to_string(),
+            decision_node,
+            predictions: vec![],
+            tool_responses: vec![],
+            tool_outcomes: vec![],
+            confirm: self
+                .pending_confirm
+                .as_ref()
+                .map(|pending| pending.prompt.clone()),
+            refusal: None,
+            interstitials: vec![],
+            prediction: self.prediction,
+            usage: Usage::default(),
+            variants: self.variants(),
+        };
+        self.translate_result(&mut result).await?;
+        result.usage = self.usage().since(&usage_before);
+        Ok(result)
+    }
+
+    // Node as shown to the user, with its variables rendered. Generate nodes
+    // show the model's answer, composed once per visit.
+    async fn shown_node(
+        &mut self,
+        decision_node: &Decision,
+        tool_responses: &[ToolResponse],
+    ) -> Result<Decision, CognitionError> {
+        let mut shown = self.available(decision_node);
+        shown.text = render_variables(&shown.text, &self.template_variables());
+        if decision_node.generates() {
+            shown.text = self.compose_answer(&shown, tool_responses).await?;
+        }
+        Ok(shown)
+    }
+
+    async fn compose_answer(
+        &mut self,
+        decision_node: &Decision,
+        tool_responses: &[ToolResponse],
+    ) -> Result<String, CognitionError> {
+        if let Some((id, answer)) = &self.answer {
+            if **id == decision_node.id {
+                return Ok(answer.clone());
+            }
+        }
+        let history = self.prompt_history(decision_node);
+        let prompt = answer::prompt(&history, &decision_node.text, tool_responses);
+        self.notify(DecisionEvent::PromptBuilt {
+            session_id: self.session_id.clone(),
+            node_id: decision_node.id.clone(),
+            prompt: prompt.clone(),
+        })
+        .await;
+        let answer = self
+            .generate_text(&prompt, self.answer_max_tokens, self.answer_temperature)
+            .await?;
+        let answer = answer::clean(&answer);
+        self.notify(DecisionEvent::TokensStreamed {
+            session_id: self.session_id.clone(),
+            node_id: decision_node.id.clone(),
+            text: answer.clone(),
+        })
+        .await;
+        self.answer = Some((self.decision_tree.id(&decision_node.id), answer.clone()));
+        Ok(answer)
+    }
+
+    // Text of the current node's choice an answer gives the number of, if any.
+    // A pending confirmation is answered as it is.
+    fn numbered_choice(&self, answer: &str) -> Result<Option<String>, CognitionError> {
+        if self.pending_confirm.is_some() {
+            return Ok(None);
+        }
+        let decision_node = self.available(self.current_node()?);
+        let choices = decision_node.choices();
+        Ok(choice_number(answer, &choices).map(|index| choices[index].text.clone()))
+    }
+
+    // A node with only the choices whose `requires` holds
+    pub fn available(&self, decision_node: &Decision) -> Decision {
+        let mut available = decision_node.clone();
+        if let Some(choices) = &mut available.choices {
+            if choices.iter().any(|choice| choice.requires.is_some()) {
+                let variables = self.template_variables();
+                choices.retain(|choice| match &choice.requires {
+                    Some(requires) => condition(requires, &variables) == Ok(true),
+                    None => true,
+                });
+            }
+        }
+        available
+    }
+
+    // The session's variant of an A/B split node: the choice taken on its first
+    // visit, kept in the `variant.<node_id>` tag. Hosts set the tag to force one.
+    fn variant(&mut self, decision_node: &Decision) -> Choice {
+        let tag = format!("{}{}", VARIANT_TAG_PREFIX, decision_node.id);
+        let choices = decision_node.choices();
+        let assigned = self
+            .tags
+            .get(&tag)
+            .and_then(|text| choices.iter().find(|choice| choice.text == *text));
+        if let Some(choice) = assigned {
+            return (*choice).clone();
+        }
+        let choice = weighted_choice(&choices, rand::random::<f64>()).clone();
+        info!(
+            "Session {} gets variant {} of {}",
+            self.session_id, choice.text, decision_node.id
+        );
+        self.tags.insert(tag, choice.text.clone());
+        choice
+    }
+
+    // Variants of the A/B splits the session went through, by node ID
+    pub fn variants(&self) -> BTreeMap<String, String> {
+        self.tags
+            .iter()
+            .filter_map(|(name, value)| {
+                let node_id = name.strip_prefix(VARIANT_TAG_PREFIX)?;
+                Some((node_id.to_string(), value.clone()))
+            })
+            .collect()
+    }
+
+    // Prediction policy of a node, the session's unless the node overrides it
+    pub fn prediction_policy(&self, decision_node: &Decision) -> PredictionPolicy {
+        decision_node.predict.unwrap_or(self.prediction)
+    }
+
+    // Text of the node the user answered, the answer of a generate node
+    fn answered_text(&self, decision_node: &Decision) -> String {
+        match &self.answer {
+            Some((id, answer)) if decision_node.generates() && **id == decision_node.id => {
+                answer.clone()
+            }
+            _ => decision_node.text.clone(),
+        }
+    }
+
+    // Base language of the tree and the session's locale, when the locale has
+    // no string bundle and is bridged by translation
+    fn bridge(&self) -> Option<(String, String)> {
+        let base_language = self.base_language.as_deref()?;
+        let locale = self.locale.as_deref()?;
+        if translation::same_language(locale, base_language) || self.localization.supports(locale) {
+            return None;
+        }
+        Some((base_language.to_string(), locale.to_string()))
+    }
+
+    // Translate with the model, cached as node text repeats within a session
+    async fn translate(
+        &mut self,
+        text: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<String, CognitionError> {
+        if text.trim().is_empty() {
+            return Ok(text.to_string());
+        }
+        let key = (to.to_string(), text.to_string());
+        if let Some(translated) = self.translations.get(&key) {
+            return Ok(translated.clone());
+        }
+        let prompt = translation::prompt(text, from, to);
+        let response = self
+            .generate(
+                &prompt,
+                translation::max_length(text),
+                0.0,
+                &CancellationToken::new(),
+            )
+            .await?;
+        let translated = translation::clean(&response.text);
+        self.translations.insert(key, translated.clone());
+        Ok(translated)
+    }
+
+    // Node text, choices and confirmation of a result in the user's language
+    async fn translate_result(
+        &mut self,
+        result: &mut DecisionResult,
+    ) -> Result<(), CognitionError> {
+        let Some((base_language, locale)) = self.bridge() else {
+            return Ok(());
+        };
+        let decision_node = &mut result.decision_node;
+        decision_node.text = self
+            .translate(&decision_node.text, &base_language, &locale)
+            .await?;
+        for choice in decision_node.choices.iter_mut().flatten() {
+            choice.text = self
+                .translate(&choice.text, &base_language, &locale)
+                .await?;
+        }
+        if let Some(confirm) = &result.confirm {
+            result.confirm = Some(self.translate(confirm, &base_language, &locale).await?);
+        }
+        for interstitial in &mut result.interstitials {
+            interstitial.text = self
+                .translate(&interstitial.text, &base_language, &locale)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Mapping of redacted placeholders to personal data, if redaction is enabled
+    pub fn redactor(&self) -> Option<&Redactor> {
+        self.redactor.as_ref()
+    }
+
+    // How the session ended, if it has
+    pub fn outcome(&self) -> Option<SessionOutcome> {
+        self.outcome
+    }
+
+    // When the session counts as idle, for the host's scheduler to call `run_idle`
+    pub fn idle_deadline(&self) -> Option<Instant> {
+        if self.outcome.is_some() || self.finished {
+            return None;
+        }
+        self.idle_after.map(|after| self.last_activity + after)
+    }
+
+    // Record the visit and notify observers that a node was entered, including
+    // its analytics event
+    async fn enter_node(&mut self, decision_node: &Decision) {
+        metrics::node_entered(&decision_node.id);
+        self.answer = None;
+        let visits = self.visits.entry(decision_node.id.clone()).or_default();
+        visits.count += 1;
+        visits.last_visit = unix_now();
+        if self.last_entered.as_ref() == Some(&decision_node.id) {
+            self.repeats += 1;
+        } else {
+            self.last_entered = Some(decision_node.id.clone());
+            self.repeats = 1;
+        }
+
+        self.notify(DecisionEvent::NodeEntered {
+            session_id: self.session_id.clone(),
+            node_id: decision_node.id.clone(),
+        })
+        .await;
+
+        if let Some(analytics_event) = &decision_node.analytics_event {
+            self.notify(DecisionEvent::Analytics {
+                session_id: self.session_id.clone(),
+                node_id: decision_node.id.clone(),
+                name: analytics_event.name.clone(),
+                props: analytics_event.props.clone(),
+            })
+            .await;
+        }
+    }
+
+    // Run the script of the entered node, following the nodes scripts return,
+    // and give the tools' input the last script set. A failing script is
+    // skipped.
+    async fn run_scripts(&mut self, input: Option<&str>) -> Result<Option<String>, CognitionError> {
+        for _ in 0..=self.decision_tree.len() {
+            let decision_node = self.current_node()?.clone();
+            let Some(script) = &decision_node.script else {
+                return Ok(None);
+            };
+            let outcome =
+                match scripting::run(script, &mut self.vars, input, self.script_max_operations) {
+                    Ok(outcome) => outcome,
+                    Err(err) => {
+                        warn!("Script of node {} skipped: {}", decision_node.id, err.0);
+                        return Ok(None);
+                    }
+                };
+            let Some(next_id) = outcome.next else {
+                return Ok(outcome.tool_input);
+            };
+            info!("Script of node {} moves to {}", decision_node.id, next_id);
+            self.transition(&decision_node.id, &next_id, None)?;
+            if next_id == Transition::Exit {
+                self.end_session(SessionOutcome::Completed).await;
+            }
+            if self.finished {
+                return Ok(None);
+            }
+            let next_node = self.current_node()?.clone();
+            self.enter_node(&next_node).await;
+        }
+        Err(CognitionError(format!(
+            "Node scripts loop at {}",
+            self.current_id
+        )))
+    }
+
+    // The last answer to an `input: secret` node, for the host to use
+    pub fn secret(&self, node_id: &str) -> Option<&str> {
+        self.secrets.get(node_id).map(String::as_str)
+    }
+
+    // Continue the session at a node, e.g. to evaluate answers given there
+    pub(crate) fn go_to(&mut self, node_id: &str) -> Result<(), CognitionError> {
+        self.decision_node(node_id)?;
+        self.current_id = self.decision_tree.id(node_id);
+        Ok(())
+    }
+
+    // The safety limit the session ran into, if any
+    fn exceeded_limit(&self) -> Option<String> {
+        if let Some(max_turns) = self.max_turns.filter(|max_turns| self.turns > *max_turns) {
+            return Some(format!("turn limit of {} reached", max_turns));
+        }
+        self.max_node_repeats
+            .filter(|max_node_repeats| self.repeats > *max_node_repeats)
+            .map(|_| {
+                format!(
+                    "node {} entered {} times in a row",
+                    self.current_id, self.repeats
+                )
+            })
+    }
+
+    // Move to the limits' fallback node, or fail the turn without one
+    async fn hit_limit(&mut self, reason: String) -> Result<(), CognitionError> {
+        let Some(limit_node) = self.limit_node.clone() else {
+            return Err(CognitionError::loop_detected(&reason));
+        };
+        warn!("Loop detected: {}, going to {}", reason, limit_node);
+        self.pending_confirm = None;
+        self.current_id = self.decision_tree.id(&limit_node);
+        let decision_node = self.current_node()?.clone();
+        self.enter_node(&decision_node).await;
+        Ok(())
+    }
+
+    // Visits to a node in this session, if it was entered
+    pub fn visits(&self, id: &str) -> Option<&NodeVisits> {
+        self.visits.get(id)
+    }
+
+    fn decision_node(&self, id: &str) -> Result<&Decision, CognitionError> {
+        self.decision_tree
+            .get(id)
+            .ok_or_else(|| CognitionError(format!("Decision node with ID '{}' not found", id)))
+    }
+
+    pub fn current_node(&self) -> Result<&Decision, CognitionError> {
+        self.decision_node(&self.current_id)
+    }
+
+    // Generate free text in the session's output language, failing if a
+    // guardrail blocks it
+    pub async fn generate_text(
+        &self,
+        prompt: &str,
+        max_length: usize,
+        temperature: f32,
+    ) -> Result<String, CognitionError> {
+        let text = self
+            .generate_in_language(&self.with_persona(prompt), max_length, temperature)
+            .await?;
+        if self
+            .violates_guardrails(GuardrailStage::Output, &text)
+            .await?
+        {
+            return Err(CognitionError(
+                "Generated text blocked by a guardrail".to_string(),
+            ));
+        }
+        Ok(text)
+    }
+
+    // Generate free text, regenerating if the model drifts out of the output language
+    async fn generate_in_language(
+        &self,
+        prompt: &str,
+        max_length: usize,
+        temperature: f32,
+    ) -> Result<String, CognitionError> {
+        let cancel = CancellationToken::new();
+        let language = match &self.output_language {
+            Some(language) => language,
+            None => {
+                let response = self
+                    .generate(prompt, max_length, temperature, &cancel)
+                    .await?;
+                return Ok(response.text);
+            }
+        };
+
+        let prompt = format!("Always respond in {}.\n\n{}", language, prompt);
+        let mut text = String::new();
+        for attempt in 0..=LANGUAGE_RETRIES {
+            text = self
+                .generate(&prompt, max_length, temperature, &cancel)
+                .await?
+                .text;
+            if language::matches_language(&text, language) {
+                break;
+            }
+            warn!(
+                "Output drifted from {} (attempt {}): {}",
+                language,
+                attempt + 1,
+                self.prompt_privacy.apply(&text)
+            );
+        }
+        Ok(text)
+    }
+
+    // Add a named agent, for nodes with `speaker: <id>`. Without a model the
+    // agent uses the session's model.
+    pub fn add_agent(
+        &mut self,
+        id: &str,
+        persona: Persona,
+        model: Option<Box<dyn LargeLanguageModel>>,
+    ) {
+        self.agents.insert(id.to_string(), Agent { persona, model });
+    }
+
+    // Persona of the agent that says a node
+    pub fn speaker_persona(&self, decision_node: &Decision) -> &Persona {
+        self.speaker(decision_node)
+            .map_or(&self.persona, |agent| &agent.persona)
+    }
+
+    fn speaker(&self, decision_node: &Decision) -> Option<&Agent> {
+        self.agents.get(decision_node.speaker.as_deref()?)
+    }
+
+    // Persona and model of the agent saying the current node
+    fn current_persona(&self) -> &Persona {
+        match self.current_node() {
+            Ok(decision_node) => self.speaker_persona(decision_node),
+            Err(_) => &self.persona,
+        }
+    }
+
+    fn current_model(&self) -> &dyn LargeLanguageModel {
+        self.current_node()
+            .ok()
+            .and_then(|decision_node| self.speaker(decision_node))
+            .and_then(|agent| agent.model.as_deref())
+            .unwrap_or(self.model.as_ref())
+    }
+
+    // Agent text of the current node into the history, under the name of a
+    // named speaker
+    fn push_agent_history(&mut self, text: &str) {
+        let speaker = self
+            .current_node()
+            .ok()
+            .and_then(|decision_node| self.speaker(decision_node))
+            .map(|agent| agent.persona.name.clone());
+        self.history.push_agent(speaker.as_deref(), text);
+    }
+
+    // Prompt led by the persona's instructions and the context pack, if any
+    fn with_persona(&self, prompt: &str) -> String {
+        let preamble = self.preamble();
+        if preamble.is_empty() {
+            prompt.to_string()
+        } else {
+            format!("{}\n\n{}", preamble, prompt)
+        }
+    }
+
+    // The persona's instructions followed by the context pack
+    fn preamble(&self) -> String {
+        [
+            self.current_persona().render(),
+            self.context_preamble().to_string(),
+        ]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+    }
+
+    // Call the model, bounded by the model timeout and the cancellation token
+    async fn generate(
+        &self,
+        prompt: &str,
+        max_length: usize,
+        temperature: f32,
+        cancel: &CancellationToken,
+    ) -> Result<InferenceResult, CognitionError> {
+        let started = Instant::now();
+        let response = guarded(
+            "Model call",
+            self.current_model()
+                .generate(prompt, max_length, temperature),
+            self.model_timeout,
+            cancel,
+        )
+        .await
+        .and_then(|response| {
+            response.map_err(|err| CognitionError(format!("Failed to generate: {}", err)))
+        });
+
+        let tokens = response
+            .as_ref()
+            .ok()
+            .map(|response| (estimate_tokens(prompt), estimate_tokens(&response.text)));
+        metrics::model_called(started.elapsed(), tokens);
+        if let Ok(response) = &response {
+            self.record_usage(prompt, &response.text);
+        }
+        response
+    }
+
+    fn record_usage(&self, prompt: &str, completion: &str) {
+        if let Ok(mut usage) = self.usage.lock() {
+            usage.record(prompt, completion, self.pricing);
+        }
+    }
+
+    // Model calls, tokens and cost of the session so far, see
+    // `DecisionResult::usage` for a turn's
+    pub fn usage(&self) -> Usage {
+        self.usage.lock().map(|usage| *usage).unwrap_or_default()
+    }
+
+    // Forget cached tool responses, e.g. after the data behind them changed
+    pub fn clear_tool_cache(&self) {
+        if let Ok(mut cache) = self.tool_cache.lock() {
+            cache.clear();
+        }
+    }
+
+    fn cached_tool_response(
+        &self,
+        key: &(String, String),
+        ttl: Duration,
+    ) -> Option<Option<ToolResponse>> {
+        let cache = self.tool_cache.lock().ok()?;
+        let (received, response) = cache.get(key)?;
+        (received.elapsed() < ttl).then(|| response.clone())
+    }
+
+    fn cache_tool_response(
+        &self,
+        key: (String, String),
+        response: Option<ToolResponse>,
+        ttl: Duration,
+    ) {
+        if let Ok(mut cache) = self.tool_cache.lock() {
+            cache.retain(|(tool_id, _), (received, _)| {
+                let ttl = self.tool_cache_ttls.get(tool_id).copied().unwrap_or(ttl);
+                received.elapsed() < ttl
+            });
+            cache.insert(key, (Instant::now(), response));
+        }
+    }
+
+    // Generate JSON matching `schema` parsed into `T`, bounded like `generate`
+    async fn generate_typed<T: DeserializeOwned>(
+        &self,
+        prompt: &str,
+        schema: &Value,
+        cancel: &CancellationToken,
+    ) -> Result<T, CognitionError> {
+        let value = guarded(
+            "Model call",
+            self.current_model().generate_json(prompt, schema),
+            self.model_timeout,
+            cancel,
+        )
+        .await?
+        .map_err(|err| CognitionError(format!("Failed to generate JSON: {}", err)))?;
+        self.record_usage(prompt, &value.to_string());
+        serde_json::from_value(value).map_err(|err| {
+            CognitionError(format!(
+                "Failed to generate JSON: JSON does not match the schema: {}",
+                err
+            ))
+        })
+    }
+
+    // Extract slots from the model's answer to `prompt` into `T`, e.g. the title
+    // and format of a book order, with `schema` describing `T` as a JSON schema
+    pub async fn extract<T: DeserializeOwned>(
+        &self,
+        prompt: &str,
+        schema: &Value,
+    ) -> Result<T, CognitionError> {
+        self.generate_typed(prompt, schema, &CancellationToken::new())
+            .await
+    }
+
+    // Parameters of a tool as JSON, written by the model from the conversation
+    async fn tool_args(
+        &self,
+        tool: &dyn Tool,
+        args_prompt: &str,
+        decision_node: &Decision,
+        cancel: &CancellationToken,
+    ) -> Result<String, CognitionError> {
+        let prompt = tools::args_prompt(&self.prompt_history(decision_node), tool, args_prompt);
+        let args: Value = self
+            .generate_typed(&prompt, &json!({ "type": "object" }), cancel)
+            .await
+            .map_err(|err| CognitionError(format!("Arguments of {}: {}", tool.id(), err.0)))?;
+        debug!(
+            "{} arguments: {}",
+            tool.id(),
+            self.prompt_privacy.apply(&args.to_string())
+        );
+        Ok(args.to_string())
+    }
+
+    // Registered tool with the given ID
+    fn tool(&self, id: &str) -> Result<&dyn Tool, CognitionError> {
+        self.tools
+            .iter()
+            .find(|tool| tool.id() == id)
+            .map(|tool| tool.as_ref())
+            .ok_or_else(|| CognitionError(format!("Could not find tool: {}", id)))
+    }
+
+    // Tell listeners a node started running a tool
+    async fn notify_tool_started(&self, decision_node: &Decision, tool: &dyn Tool) {
+        self.notify(DecisionEvent::ToolStarted {
+            session_id: self.session_id.clone(),
+            node_id: decision_node.id.clone(),
+            tool_id: tool.id().clone(),
+        })
+        .await;
+    }
+
+    // Ask the model which of `choices` the user made, as free text or, with
+    // `prediction.json`, as a JSON object restricted to the choices
+    async fn select_choice(
+        &self,
+        prompt: &str,
+        choices: &[String],
+        cancel: &CancellationToken,
+    ) -> Result<InferenceResult, CognitionError> {
+        if !self.json_choices {
+            return self.generate(prompt, 200, 0.5, cancel).await;
+        }
+
+        let schema = json!({
+            "type": "object",
+            "properties": { "choice": { "type": "string", "enum": choices } },
+            "required": ["choice"],
+        });
+        let selection: ChoiceSelection = self.generate_typed(prompt, &schema, cancel).await?;
+        Ok(InferenceResult {
+            text: selection.choice,
+            probabilities: vec![],
+        })
+    }
+
+    // Reduce first-turn latency: open connections, pre-tokenize the template and
+    // every node and choice text, and with `prime` send a tiny model request
+    pub async fn warmup(&self, prime: bool) -> Result<(), CognitionError> {
+        let mut texts = vec![self.decision_prompt_template.content().to_string()];
+        for decision_node in self.decision_tree.nodes() {
+            texts.push(decision_node.text.clone());
+            texts.extend(
+                decision_node
+                    .choices()
+                    .iter()
+                    .map(|choice| choice.text.clone()),
+            );
+        }
+
+        let agent_models = self
+            .agents
+            .values()
+            .filter_map(|agent| agent.model.as_deref());
+        for model in std::iter::once(self.model.as_ref()).chain(agent_models) {
+            model
+                .warmup(&texts, prime)
+                .await
+                .map_err(|err| CognitionError(format!("Failed to warm up model: {}", err)))?;
+        }
+        self.embed_choices().await
+    }
+
+    // Embed the tree's choice texts and aliases for embedding matching, unless
+    // they are for the current tree version. Done by `warmup`, or else on the
+    // first answer after the tree changes.
+    pub async fn embed_choices(&self) -> Result<(), CognitionError> {
+        if self.embedding_threshold.is_none()
+            || self.choice_embeddings()?.is_current(&self.decision_tree)
+        {
+            return Ok(());
+        }
+
+        let texts = embeddings::choice_texts(&self.decision_tree);
+        info!("Embedding {} choice texts", texts.len());
+        let vectors = self
+            .model
+            .embed(&texts)
+            .await
+            .map_err(|err| CognitionError(format!("Failed to embed choices: {}", err)))?;
+        self.choice_embeddings()?
+            .replace(&self.decision_tree, texts, vectors);
+        Ok(())
+    }
+
+    // Embedded choice texts, failing if a panic poisoned their lock
+    fn choice_embeddings(&self) -> Result<MutexGuard<'_, ChoiceEmbeddings>, CognitionError> {
+        self.choice_embeddings
+            .lock()
+            .map_err(|_| CognitionError("Choice embeddings are unavailable".to_string()))
+    }
+
+    // Index of the choice an answer means, if embedding matching is enabled and
+    // one choice is clearly closest. Failures leave the answer to the model.
+    async fn embedding_choice(&self, answer: Option<&str>, choices: &[&Choice]) -> Option<usize> {
+        let (answer, threshold) = (answer?, self.embedding_threshold?);
+        if let Err(err) = self.embed_choices().await {
+            warn!("{}", err);
+            return None;
+        }
+        let answer = match self.model.embed(&[answer.to_string()]).await {
+            Ok(mut vectors) if vectors.len() == 1 => vectors.remove(0),
+            Ok(_) => return None,
+            Err(err) => {
+                warn!("Failed to embed the answer: {}", err);
+                return None;
+            }
+        };
+        // A poisoned lock means no match, like any other embedding failure
+        self.choice_embeddings
+            .lock()
+            .ok()?
+            .best_choice(&answer, choices, threshold)
+    }
+
+    // Variables available to prompt templates as `{{name}}`
+    pub fn template_variables(&self) -> BTreeMap<String, String> {
+        let mut variables = BTreeMap::new();
+        variables.insert(
+            "output_language".to_string(),
+            self.output_language.clone().unwrap_or_default(),
+        );
+        variables.insert(
+            "locale".to_string(),
+            self.locale.clone().unwrap_or_default(),
+        );
+        let persona = self.current_persona();
+        variables.insert("persona".to_string(), persona.render());
+        variables.insert(
+            "context_pack".to_string(),
+            self.context_preamble().to_string(),
+        );
+        variables.insert("persona.name".to_string(), persona.name.clone());
+        let persona_fields = [("role", &persona.role), ("tone", &persona.tone)];
+        for (name, value) in persona_fields {
+            variables.insert(
+                format!("persona.{}", name),
+                value.clone().unwrap_or_default(),
+            );
+        }
+        for (name, value) in &self.persona_traits {
+            variables.insert(format!("persona.{}", name), value.clone());
+        }
+        for (name, value) in &self.vars {
+            variables.insert(format!("vars.{}", name), value.clone());
+        }
+        let visits = self.visits(&self.current_id).cloned().unwrap_or_default();
+        variables.insert("node.visits".to_string(), visits.count.to_string());
+        variables.insert("node.last_visit".to_string(), visits.last_visit.to_string());
+        variables
+    }
+
+    // Render the history for a node prompt, compressed if over the token budget
+    fn prompt_history(&self, decision_node: &Decision) -> String {
+        let compression = decision_node.compression.as_ref();
+        let enabled = compression
+            .and_then(|compression| compression.enabled)
+            .unwrap_or(true);
+        let max_tokens = compression
+            .and_then(|compression| compression.max_tokens)
+            .or(self.max_history_tokens);
+
+        match max_tokens {
+            Some(max_tokens) if enabled => {
+                compress_history(&self.history, max_tokens).render(&self.persona.name, &self.user)
+            }
+            _ => self.history.render(&self.persona.name, &self.user),
+        }
+    }
+
+    // Save the state before a user turn so it can be undone
+    fn checkpoint(&mut self) {
+        self.checkpoints.push(Checkpoint {
+            current_id: self.current_id.clone(),
+            history: self.history.entries.clone(),
+            call_stack: self.call_stack.clone(),
+            variables: self.template_variables(),
+            vars: self.vars.clone(),
+            turns: self.turns,
+            visits: self.visits.clone(),
+            last_entered: self.last_entered.clone(),
+            repeats: self.repeats,
+        });
+    }
+
+    // Turns in the session's timeline, undone turns excluded
+    pub fn turn_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    // Changes in node position, template variables and history between two
+    // points of the timeline, where turn 0 is before the first user input and
+    // `turn_count()` is now
+    pub fn diff(&self, from: usize, to: usize) -> Result<TurnDiff, CognitionError> {
+        let variables = self.template_variables();
+        let turn_state = |turn: usize| match self.checkpoints.get(turn) {
+            Some(checkpoint) => Ok(TurnState {
+                turn,
+                node_id: &checkpoint.current_id,
+                call_stack: &checkpoint.call_stack,
+                variables: &checkpoint.variables,
+                history: &checkpoint.history,
+            }),
+            None if turn == self.checkpoints.len() => Ok(TurnState {
+                turn,
+                node_id: &self.current_id,
+                call_stack: &self.call_stack,
+                variables: &variables,
+                history: &self.history.entries,
+            }),
+            None => Err(CognitionError(format!(
+                "No turn {}, the session has {} turns",
+                turn,
+                self.checkpoints.len()
+            ))),
+        };
+        Ok(TurnDiff::between(turn_state(from)?, turn_state(to)?))
+    }
+
+    // Revert to the previous node, trimming the history of the undone turn and
+    // restoring the variables and counters it changed. Returns false if there
+    // is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if let Some(checkpoint) = self.checkpoints.pop() {
+            self.current_id = checkpoint.current_id;
+            self.history.entries = checkpoint.history;
+            self.call_stack = checkpoint.call_stack;
+            self.vars = checkpoint.vars;
+            self.turns = checkpoint.turns;
+            self.visits = checkpoint.visits;
+            self.last_entered = checkpoint.last_entered;
+            self.repeats = checkpoint.repeats;
+            self.pending_confirm = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Refusal message if `choice` of node `node_id` is still cooling down
+    fn cooldown_refusal(&self, node_id: &str, choice: &Choice) -> Option<String> {
+        let cooldown = Duration::from_secs(choice.cooldown_secs?);
+        let taken = self
+            .cooldowns
+            .get(&(node_id.to_string(), choice.text.clone()))?;
+        let remaining = cooldown.checked_sub(taken.elapsed())?;
+
+        let message = choice
+            .cooldown_message
+            .as_deref()
+            .unwrap_or(DEFAULT_COOLDOWN_MESSAGE);
+        let mut variables = self.template_variables();
+        variables.insert(
+            "cooldown".to_string(),
+            remaining.as_secs().max(1).to_string(),
+        );
+        Some(render_variables(message, &variables))
+    }
+
+    // A node's input limit and its length in characters, if it has one
+    fn input_limit(&self, decision_node: &Decision) -> Option<(InputLimit, usize)> {
+        let limit = decision_node
+            .input_limit
+            .as_ref()
+            .or(self.input_limit.as_ref())?;
+        Some((limit.clone(), limit.char_limit()?))
+    }
+
+    // Answer over its node's limit cut to `max_chars`, or None when refused
+    async fn limit_input(
+        &self,
+        input: &str,
+        limit: &InputLimit,
+        max_chars: usize,
+    ) -> Result<Option<String>, CognitionError> {
+        let limited = match limit.strategy {
+            InputLimitStrategy::Reject => return Ok(None),
+            InputLimitStrategy::TruncateHead => input_limit::truncate_head(input, max_chars),
+            InputLimitStrategy::TruncateTail => input_limit::truncate_tail(input, max_chars),
+            InputLimitStrategy::Summarize => {
+                let prompt = input_limit::summary_prompt(input, max_chars);
+                let response = self
+                    .generate(&prompt, max_chars / 4 + 16, 0.0, &CancellationToken::new())
+                    .await?;
+                let summary = response.text.trim().to_string();
+                // Summaries running long are cut like any answer
+                if input_limit::exceeds(&summary, max_chars) {
+                    input_limit::truncate_tail(&summary, max_chars)
+                } else {
+                    summary
+                }
+            }
+        };
+        Ok(Some(limited))
+    }
+
+    fn input_limit_refusal(&self, limit: &InputLimit, max_chars: usize) -> String {
+        let message = limit
+            .message
+            .as_deref()
+            .unwrap_or(DEFAULT_INPUT_LIMIT_MESSAGE);
+        let mut variables = self.template_variables();
+        variables.insert("max_chars".to_string(), max_chars.to_string());
+        render_variables(message, &variables)
+    }
+
+    // Confirmation question for `choice`, if it needs one
+    fn confirm_prompt(&self, choice: &Choice) -> Option<String> {
+        let prompt = match (&choice.confirm, choice.destructive) {
+            (Some(confirm), _) => confirm,
+            (None, true) => &self.destructive_confirm,
+            (None, false) => return None,
+        };
+        let mut variables = self.template_variables();
+        variables.insert("choice".to_string(), choice.text.clone());
+        Some(render_variables(prompt, &variables))
+    }
+
+    // Move from node `from_id` along `next_id`, handling subtree calls, returns,
+    // `back` and `exit`. A subtree called returns to `return_id`, or to
+    // `from_id` without one. Returns true if control went back to an earlier
+    // node or returned from a subtree.
+    fn transition(
+        &mut self,
+        from_id: &str,
+        next_id: &Transition,
+        return_id: Option<&str>,
+    ) -> Result<bool, CognitionError> {
+        // Nodes whose `requires` is false lead on to their `on_fail`, at most
+        // once per node of the tree
+        let mut from_id = from_id.to_string();
+        let mut next_id = next_id.clone();
+        let mut return_id = return_id.map(str::to_string);
+        let mut returned = false;
+        for _ in 0..=self.decision_tree.len() {
+            returned |= self.move_to(&from_id, &next_id, return_id.take().as_deref())?;
+            // Going back returns to a node the session was already on
+            if self.finished || next_id == Transition::Back {
+                return Ok(returned);
+            }
+            let decision_node = self.current_node()?;
+            let Some(requires) = &decision_node.requires else {
+                return Ok(returned);
+            };
+            if condition(requires, &self.template_variables()) == Ok(true) {
+                return Ok(returned);
+            }
+            info!("Node {} requires {}", decision_node.id, requires);
+            match decision_node.on_fail.clone() {
+                Some(on_fail) => {
+                    from_id = decision_node.id.clone();
+                    next_id = on_fail;
+                }
+                None => {
+                    // Stay where the session was and let the user choose again
+                    self.current_id = self.decision_tree.id(&from_id);
+                    return Ok(true);
+                }
+            }
+        }
+        Err(CognitionError(format!(
+            "Node requirements loop at {}",
+            self.current_id
+        )))
+    }
+
+    fn move_to(
+        &mut self,
+        from_id: &str,
+        next_id: &Transition,
+        return_id: Option<&str>,
+    ) -> Result<bool, CognitionError> {
+        self.retries = 0;
+
+        match next_id {
+            Transition::Node(id) => {
+                self.current_id = self.decision_tree.id(id);
+            }
+            Transition::Exit => {
+                // Show the exit node if the tree has one, otherwise end the session
+                if self.decision_node(EXIT_ID).is_ok() {
+                    self.current_id = self.decision_tree.id(EXIT_ID);
+                } else {
+                    self.finished = true;
+                    return Ok(false);
+                }
+            }
+            Transition::Restart => {
+                self.current_id = self.decision_tree.id(START_ID);
+                self.call_stack.clear();
+            }
+            Transition::Back => {
+                // Drop the checkpoint of the current turn, then undo the previous one.
+                // With no previous turn, undo the current one.
+                let current = self.checkpoints.pop();
+                if !self.undo() {
+                    self.checkpoints.extend(current);
+                    self.undo();
+                }
+                return Ok(true);
+            }
+            Transition::Call(subtree_id) => {
+                // Remember where the caller continues and enter the subtree
+                let continuation = return_id.unwrap_or(from_id);
+                self.decision_node(subtree_id)?;
+                self.decision_node(continuation)?;
+                self.call_stack.push(self.decision_tree.id(continuation));
+                self.current_id = self.decision_tree.id(subtree_id);
+            }
+        }
+
+        // A return node hands control back to the caller's continuation
+        let mut returned = false;
+        while let Some(true) = self.current_node()?.return_to_caller {
+            let continuation = self.call_stack.pop().ok_or_else(|| {
+                CognitionError(format!(
+                    "Return node '{}' reached without a caller",
+                    self.current_id
+                ))
+            })?;
+            self.current_id = continuation;
+            returned = true;
+        }
+
+        Ok(returned)
+    }
 }
 
-impl Decision {
-    pub fn choices(&self) -> Vec<&Choice> {
-        self.choices.iter().flat_map(|choice| choice).collect()
+// Model used when the config doesn't name one
+const DEFAULT_MODEL: &str = "davinci003";
+
+// Choices predicted after the user's in one turn
+const DEFAULT_MAX_PREDICTIONS: usize = 4;
+
+// Confidence conservative prediction requires without `prediction.confidence_threshold`
+const DEFAULT_CONSERVATIVE_THRESHOLD: f32 = 0.8;
+
+// Unmatched answers on a node before its default choice is taken
+const DEFAULT_MAX_RETRIES: usize = 2;
+
+// Times generated text is regenerated when it is not in the output language
+const LANGUAGE_RETRIES: usize = 2;
+
+const DEFAULT_COOLDOWN_MESSAGE: &str = "Please wait {{cooldown}} seconds before trying that again.";
+
+const DEFAULT_INPUT_LIMIT_MESSAGE: &str =
+    "That answer is too long. Please keep it under {{max_chars}} characters.";
+
+const DEFAULT_DESTRUCTIVE_CONFIRM: &str = "You chose \"{{choice}}\". Is that right?";
+
+const DEFAULT_ANSWER_MAX_TOKENS: usize = 256;
+const DEFAULT_ANSWER_TEMPERATURE: f32 = 0.7;
+
+const DEFAULT_SCRIPT_MAX_OPERATIONS: u64 = 100_000;
+
+// Stands in for secret answers in the history, prompts, logs and results
+const SECRET_MASK: &str = "[secret]";
+
+// Session tags holding the variant of each A/B split node
+const VARIANT_TAG_PREFIX: &str = "variant.";
+
+// Answers taken as a yes to a confirmation question
+const AFFIRMATIVE: &[&str] = &[
+    "yes", "y", "yeah", "yep", "sure", "correct", "right", "ok", "okay", "confirm",
+];
+
+fn is_affirmative(answer: &str) -> bool {
+    let answer = answer.trim().to_lowercase();
+    let first_word = answer
+        .split(|c: char| !c.is_alphanumeric())
+        .find(|word| !word.is_empty())
+        .unwrap_or_default();
+    AFFIRMATIVE.contains(&first_word)
+}
+
+// Choice a `sample` in [0, 1) falls on, each taking a share of the range by
+// its weight
+fn weighted_choice<'a>(choices: &[&'a Choice], sample: f64) -> &'a Choice {
+    let weight = |choice: &Choice| choice.weight.unwrap_or(1.0).max(0.0);
+    let total: f64 = choices.iter().map(|choice| weight(choice)).sum();
+    let mut target = sample * total;
+    for choice in choices {
+        if target < weight(choice) {
+            return choice;
+        }
+        target -= weight(choice);
+    }
+    choices[choices.len() - 1]
+}
+
+fn choice_texts(choices: &[&Choice]) -> Vec<String> {
+    choices
+        .iter()
+        .map(|choice| choice.text.trim().to_string())
+        .collect()
+}
+
+// Answer as compared with choice texts and aliases, without surrounding
+// quotes, extra spaces and trailing punctuation
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(['"', '\'', '`'])
+        .trim_end_matches(['.', '!', '?', ',', ';', ':'])
+        .trim()
+        .to_lowercase()
+}
+
+// Index of the only choice an answer selects without the model: by its text,
+// an alias or its pattern, or else by its number, like `2`, `#2` or `2)`. Answers
+// matching several choices are left to the model.
+fn fast_choice(answer: &str, choices: &[&Choice]) -> Option<usize> {
+    let normalized = normalize(answer);
+    let matching: Vec<usize> = choices
+        .iter()
+        .enumerate()
+        .filter(|(_, choice)| {
+            normalize(&choice.text) == normalized
+                || choice
+                    .aliases
+                    .iter()
+                    .any(|alias| normalize(alias) == normalized)
+                || choice
+                    .pattern
+                    .as_ref()
+                    .is_some_and(|pattern| match Regex::new(pattern) {
+                        Ok(pattern) => pattern.is_match(answer.trim()),
+                        Err(err) => {
+                            warn!("Invalid pattern of choice {}: {}", choice.text, err);
+                            false
+                        }
+                    })
+        })
+        .map(|(index, _)| index)
+        .collect();
+    match matching[..] {
+        [index] => return Some(index),
+        [] => {}
+        _ => return None,
+    }
+
+    choice_number(answer, choices)
+}
+
+// Index of the choice an answer gives the number of, as in `2`, `#2`, `2)` or `(2)`
+fn choice_number(answer: &str, choices: &[&Choice]) -> Option<usize> {
+    let number: usize = normalize(answer)
+        .trim_start_matches(['#', '('])
+        .trim_end_matches(')')
+        .parse()
+        .ok()?;
+    number.checked_sub(1).filter(|index| *index < choices.len())
+}
+
+// Index of the choice a model response names, by its text or, for numbered
+// choices, by its number with or without the text
+fn response_choice(response: &str, choice_texts: &[String]) -> Option<usize> {
+    let response = response.trim();
+    if let Some(index) = choice_texts.iter().position(|text| text == response) {
+        return Some(index);
+    }
+    let digits = response
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(response.len());
+    let index = response[..digits].parse::<usize>().ok()?.checked_sub(1)?;
+    let text = response[digits..].trim_start_matches(['.', ')']).trim();
+    choice_texts
+        .get(index)
+        .filter(|choice| text.is_empty() || **choice == text)
+        .map(|_| index)
+}
+
+// Node ID set at `search_path` of the config, failing if the tree has no such node
+fn node_by_path(
+    config: &str,
+    search_path: &str,
+    decision_nodes: &[Decision],
+) -> Result<Option<String>, CognitionError> {
+    let Some(id) = string_by_path(config, search_path) else {
+        return Ok(None);
+    };
+    if !decision_nodes.iter().any(|node| node.id == id) {
+        return Err(CognitionError(format!(
+            "{} is unknown node {}",
+            search_path, id
+        )));
+    }
+    Ok(Some(id))
+}
+
+// Seconds since the Unix epoch
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+// Await `future`, failing if it takes longer than `limit` or `cancel` is cancelled
+async fn guarded<T>(
+    what: &str,
+    future: impl Future<Output = T>,
+    limit: Option<Duration>,
+    cancel: &CancellationToken,
+) -> Result<T, CognitionError> {
+    let limited = async {
+        match limit {
+            Some(limit) => timeout(limit, future)
+                .await
+                .map_err(|_| CognitionError(format!("{} timed out after {:?}", what, limit))),
+            None => Ok(future.await),
+        }
+    };
+
+    tokio::select! {
+        result = limited => result,
+        _ = cancel.cancelled() => Err(CognitionError(format!("{} cancelled", what))),
     }
 }
 
-// Choice structure within a decision node
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Choice {
-    #[serde(rename = "choice")]
-    pub text: String,
-    next_id: String,
-}
-
-pub struct DecisionState {
-    model: Box<dyn LargeLanguageModel>,
-    decision_nodes: Vec<Decision>,
-    decision_prompt_template: DecisionPromptTemplate,
-    tools: Vec<Box<dyn Tool>>,
-    pub agent: String,
-    pub user: String,
-    history: String,
-    current_id: String,
-}
+// Run a tool. Side-effecting tools are persisted as pending before they run and
+// as completed after, so on replay a completed invocation returns its recorded
+// response and a pending one, interrupted by a crash, is only run again if the
+// tool supports idempotency.
+async fn invoke_tool(
+    store: Option<&dyn SessionStore>,
+    mut invocation: ToolInvocation,
+    tool: &dyn Tool,
+    progress: &dyn ToolProgress,
+    retries: usize,
+) -> Result<Option<ToolResponse>, CognitionError> {
+    let store = match store {
+        Some(store) if tool.side_effecting() => store,
+        _ => return run_tool(&invocation, tool, progress, retries).await,
+    };
 
-impl DecisionState {
-    pub fn new(
-        config: &String,
-        decision_prompt_template: DecisionPromptTemplate,
-        decision_nodes: Vec<Decision>,
-    ) -> Self {
-        // LLM model
-        let model = models::davinci003::Davinci003::new(config).unwrap();
-        // let model = models::textgen::Textgen::new("").unwrap();
+    if let Some(recorded) = store.get_invocation(&invocation.id).await? {
+        match recorded.status {
+            InvocationStatus::Completed => {
+                info!("Tool invocation {} already completed", recorded.id);
+                return Ok(recorded.response);
+            }
+            InvocationStatus::Pending if tool.supports_idempotency() => {
+                info!("Retrying interrupted tool invocation {}", recorded.id);
+                invocation = recorded;
+            }
+            InvocationStatus::Pending => {
+                warn!(
+                    "Tool invocation {} was interrupted, not running it again",
+                    recorded.id
+                );
+                return Ok(None);
+            }
+        }
+    } else {
+        store.put_invocation(&invocation).await?;
+    }
 
-        let agent = "Agent".into();
-        let user = "User".into();
+    let response = run_tool(&invocation, tool, progress, retries).await?;
 
-        let history = String::new();
+    invocation.status = InvocationStatus::Completed;
+    invocation.response = response.clone();
+    store.put_invocation(&invocation).await?;
+    Ok(response)
+}
 
-        // Initialize the decision loop
-        let current_id = "start".to_string();
+// Run a tool, retrying failures up to `retries` times. Side-effecting tools are
+// only retried when they support idempotency, with the same key every attempt.
+async fn run_tool(
+    invocation: &ToolInvocation,
+    tool: &dyn Tool,
+    progress: &dyn ToolProgress,
+    retries: usize,
+) -> Result<Option<ToolResponse>, CognitionError> {
+    let idempotent = tool.supports_idempotency();
+    let retries = if idempotent || !tool.side_effecting() {
+        retries
+    } else {
+        0
+    };
 
-        Self {
-            model: Box::new(model),
-            decision_nodes,
-            decision_prompt_template,
-            tools: vec![],
-            agent,
-            user,
-            history,
-            current_id,
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        let result = if idempotent {
+            tool.run_idempotent(&invocation.input, &invocation.idempotency_key, progress)
+                .await
+        } else {
+            tool.run_with_progress(&invocation.input, progress).await
+        };
+        metrics::tool_called(&invocation.tool_id, started.elapsed(), result.is_ok());
+        match result {
+            Err(err) if attempt < retries => {
+                attempt += 1;
+                warn!(
+                    "Tool {} failed, retrying ({}/{}): {}",
+                    invocation.tool_id, attempt, retries, err.0
+                );
+            }
+            result => return result,
         }
     }
+}
 
-    // add tool
-    pub fn add_tool(&mut self, tool: Box<dyn Tool>) {
-        self.tools.push(tool);
-    }
-
-    fn decision_node(&self, id: &str) -> Result<&Decision, CognitionError> {
-        self.decision_nodes
-            .iter()
-            .find(|node| node.id == id)
-            .ok_or_else(|| CognitionError(format!("Decision node with ID '{}' not found", id)))
-    }
+// Forwards tool progress to the observers as events
+struct ObserverProgress<'a> {
+    session_id: &'a str,
+    tool_id: &'a str,
+    observers: &'a [Box<dyn Observer>],
+}
 
-    pub fn current_node(&self) -> Result<&Decision, CognitionError> {
-        self.decision_node(&self.current_id)
+#[async_trait(?Send)]
+impl ToolProgress for ObserverProgress<'_> {
+    async fn on_tool_progress(&self, message: &str, percent: Option<f32>) {
+        let event = DecisionEvent::ToolProgress {
+            session_id: self.session_id.to_string(),
+            tool_id: self.tool_id.to_string(),
+            message: message.to_string(),
+            percent,
+        };
+        for observer in self.observers {
+            observer.notify(&event).await;
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct DecisionResult {
     pub user_input: Option<String>,
     pub decision_prompt: Option<String>,
@@ -97,134 +2221,457 @@ pub struct DecisionResult {
     pub current_id: String,
     pub decision_node: Decision,
     pub predictions: Vec<Prediction>,
-    pub tool_response: Option<ToolResponse>,
+    pub tool_responses: Vec<ToolResponse>,
+    // Every tool call of the turn, including failed ones
+    #[serde(default)]
+    pub tool_outcomes: Vec<ToolOutcome>,
+    // Confirmation question the user has to answer before the choice is made
+    #[serde(default)]
+    pub confirm: Option<String>,
+    // Why the user's choice was refused, e.g. a choice cooling down
+    #[serde(default)]
+    pub refusal: Option<String>,
+    // Informational nodes passed on the way to the current node, to show first
+    #[serde(default)]
+    pub interstitials: Vec<Decision>,
+    // Prediction policy of the session, see `DecisionState::prediction_policy`
+    // for nodes overriding it
+    #[serde(default)]
+    pub prediction: PredictionPolicy,
+    // Model calls and tokens of the turn
+    #[serde(default)]
+    pub usage: Usage,
+    // A/B variants of the session, see `DecisionState::variants`
+    #[serde(default)]
+    pub variants: BTreeMap<String, String>,
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Prediction {
     pub choice: String,
     pub id: String,
-    pub tool_response: Option<ToolResponse>,
+    pub tool_responses: Vec<ToolResponse>,
+    pub confidence: Option<f32>,
 }
 
 // Run the decision-making process using the decision tree
 pub async fn run_decision(
     user_input: Option<String>,
     state: &mut DecisionState,
+) -> Result<Option<DecisionResult>, CognitionError> {
+    run_decision_with_cancel(user_input, state, &CancellationToken::new()).await
+}
+
+// Run the decision-making process, aborting model and tool calls as soon as
+// `cancel` is cancelled by the host
+pub async fn run_decision_with_cancel(
+    user_input: Option<String>,
+    state: &mut DecisionState,
+    cancel: &CancellationToken,
 ) -> Result<Option<DecisionResult>, CognitionError> {
     let mut predicting_choice = false;
-    let mut tool_response = None;
+    let mut tool_responses = vec![];
+    let mut tool_outcomes = vec![];
     let mut decision_prompt = None;
     let choice: Option<String> = None;
     let mut predictions = vec![];
-    let mut max_depth = 5;
+    let mut interstitials = vec![];
+    let mut auto_steps = 0;
+    let mut max_depth = state.max_predictions + 1;
+    let mut refusal = None;
+    let turn_started = Instant::now();
+    let usage_before = state.usage();
+    let mut model_outputs = vec![];
+    let mut tokens = 0;
+
+    if state.finished {
+        return Ok(None);
+    }
+
+    // Prompt copy edited at its source applies from the next turn on
+    if let Err(err) = state.refresh_template().await {
+        warn!("Keeping the current prompt template: {}", err);
+    }
+
+    // The first run enters the start node
+    if !state.started {
+        state.started = true;
+        let decision_node = state.current_node()?.clone();
+        state.enter_node(&decision_node).await;
+        state.run_scripts(None).await?;
+        if state.finished {
+            return Ok(None);
+        }
+    }
+
+    // Secret answers are kept aside and masked from here on
+    let expects_secret = state.pending_confirm.is_none() && state.current_node()?.expects_secret();
+    let (user_input, secret_answer) = match user_input {
+        Some(input) if expects_secret => (Some(SECRET_MASK.to_string()), Some(input)),
+        user_input => (user_input, None),
+    };
+
+    // A choice number stands for the choice, in the history and the result
+    // too, and selects it without the model
+    let numbered = match &user_input {
+        Some(input) if state.number_input && secret_answer.is_none() => {
+            state.numbered_choice(input)?
+        }
+        _ => None,
+    };
+    let user_input = numbered.clone().or(user_input);
+
+    // Personal data is replaced before input reaches guardrails, the history,
+    // the model or the logs
+    let user_input = match (user_input, &mut state.redactor) {
+        (Some(input), Some(redactor)) => Some(redactor.redact(&input)),
+        (user_input, _) => user_input,
+    };
+
+    // Input in a bridged locale is translated to the tree's language
+    let user_input = match (user_input, state.bridge()) {
+        (Some(input), Some((base_language, locale))) if secret_answer.is_none() => {
+            Some(state.translate(&input, &locale, &base_language).await?)
+        }
+        (user_input, _) => user_input,
+    };
+
+    // Answers over the node's input limit are refused, truncated or
+    // summarized before guardrails and the history see them
+    let limit = state.input_limit(state.current_node()?);
+    let user_input = match (user_input, limit) {
+        (Some(input), Some((limit, max_chars)))
+            if secret_answer.is_none() && input_limit::exceeds(&input, max_chars) =>
+        {
+            info!(
+                "Answer of {} characters over the input limit of {}",
+                input.chars().count(),
+                max_chars
+            );
+            match state.limit_input(&input, &limit, max_chars).await? {
+                Some(limited) => Some(limited),
+                None => {
+                    let mut result = state.node_result(None, usage_before).await?;
+                    result.refusal = Some(state.input_limit_refusal(&limit, max_chars));
+                    return Ok(Some(result));
+                }
+            }
+        }
+        (user_input, _) => user_input,
+    };
+
+    // Blocked input never reaches the history or the model
+    if let Some(input) = &user_input {
+        if state
+            .violates_guardrails(GuardrailStage::Input, input)
+            .await?
+        {
+            if let Some(blocked_node) = state.blocked_node.clone() {
+                state.checkpoint();
+                state.pending_confirm = None;
+                state.current_id = state.decision_tree.id(&blocked_node);
+                let decision_node = state.current_node()?.clone();
+                state.enter_node(&decision_node).await;
+            }
+            return state.node_result(user_input, usage_before).await.map(Some);
+        }
+    }
+
+    if let Some(secret) = &secret_answer {
+        state
+            .secrets
+            .insert(state.current_node()?.id.clone(), secret.clone());
+    }
+
+    if user_input.is_some() {
+        state.checkpoint();
+        state.turns += 1;
+        state.last_activity = Instant::now();
+        state.nudged = false;
+
+        if let Some(reason) = state.exceeded_limit() {
+            state.hit_limit(reason).await?;
+            return state.node_result(user_input, usage_before).await.map(Some);
+        }
+    }
+
+    // Settle a pending confirmation: a yes commits the choice with the original
+    // answer, anything else asks the node again
+    let mut input = user_input.clone();
+    let mut confirmed = None;
+    let pending = user_input
+        .as_ref()
+        .and_then(|answer| Some((state.pending_confirm.take()?, answer)));
+    if let Some((pending, answer)) = pending {
+        state.push_agent_history(&pending.prompt);
+        state.history.push(Role::User, answer);
+        if is_affirmative(answer) {
+            input = pending.user_input;
+            confirmed = Some(pending.choice);
+        } else {
+            info!("Choice not confirmed: {}", pending.choice.text);
+            input = None;
+        }
+    }
 
+    let answered_id = state.current_id.clone();
     loop {
-        let decision_node = state.decision_node(&state.current_id)?.clone();
+        // Choices whose `requires` does not hold are left out from here on
+        let decision_node = state.available(state.decision_node(&state.current_id)?);
 
         // Map choices to choices.choice
         let choices: Vec<&Choice> = decision_node.choices();
 
-        // If there are no choices, we're done
-        if choices.len() == 0 {
+        // If there are no choices, we're done, unless the node moves on by
+        // itself. Loops of such nodes stop once every node was passed.
+        let mut passing = decision_node.passes_through();
+        let mut splitting = decision_node.picks_randomly();
+        if (passing || splitting) && auto_steps >= state.decision_tree.len() {
+            warn!("Nodes moving on by themselves loop at {}", decision_node.id);
+            passing = false;
+            splitting = false;
+        }
+        if passing || splitting {
+            auto_steps += 1;
+        }
+        if choices.is_empty() && !passing {
+            break;
+        }
+
+        // Informational nodes as shown, with their variables rendered
+        let shown = if passing {
+            let shown = state.shown_node(&decision_node, &tool_responses).await?;
+            Some(shown)
+        } else {
+            None
+        };
+
+        // Without an exit node, the last informational node is the turn's
+        // result and the session ends
+        if passing
+            && decision_node.next == Some(Transition::Exit)
+            && state.decision_node(EXIT_ID).is_err()
+        {
+            state.push_agent_history(&shown.unwrap_or_default().text);
+            state.end_session(SessionOutcome::Completed).await;
+            state.finished = true;
             break;
         }
 
+        // Model confidence in the selected choice, if reported
+        let mut confidence = None;
+        let mut timed_out = false;
+
         // Select next choice
-        let next_choice = if user_input.is_none() {
+        let confirming = confirmed.is_some();
+        let mut next_choice = if let Some(choice) = confirmed.take() {
+            Some(choice)
+        } else if let Some(next_id) = decision_node.next.as_ref().filter(|_| passing) {
+            // Informational nodes move on without input or a model call
+            Some(Choice::new(&decision_node.text, next_id.clone()))
+        } else if splitting {
+            // So do A/B splits, to the session's variant
+            Some(state.variant(&decision_node))
+        } else if input.is_none() {
             // If user has not provided input, do not make a choice
             None
+        } else if let Some(interrupt) = input
+            .as_deref()
+            .filter(|_| !predicting_choice && secret_answer.is_none())
+            .and_then(|answer| keyword_interrupt(&state.interrupts, answer))
+        {
+            // Interrupt keywords leave the node ahead of its choices
+            info!("Interrupt: {}", interrupt.intent);
+            Some(Choice::new(&interrupt.intent, interrupt.next_id.clone()))
+        } else if let Some(secret) = &secret_answer {
+            // Secret answers never reach the model: they select the only choice
+            // or one they match, and later nodes are not predicted from them
+            if predicting_choice {
+                None
+            } else if choices.len() == 1 {
+                choices.first().map(|choice| (*choice).clone())
+            } else {
+                fast_choice(secret, &choices).map(|index| choices[index].clone())
+            }
         } else if choices.len() == 1 {
             // If there is only one choice, select it
             debug!("Only one choice, skip prediction");
-            choices.first()
-        } else if let Some(user_input) = &user_input {
+            choices.first().map(|choice| (*choice).clone())
+        } else if let Some(index) = input
+            .as_deref()
+            .filter(|_| !predicting_choice && (state.fast_path || numbered.is_some()))
+            .and_then(|answer| fast_choice(answer, &choices))
+        {
+            // Unambiguous answers select the choice without the model
+            debug!("Fast path match, skip prediction");
+            metrics::choice_fast_matched(&decision_node.id);
+            Some(choices[index].clone())
+        } else if let Some(index) = state
+            .embedding_choice(input.as_deref().filter(|_| !predicting_choice), &choices)
+            .await
+        {
+            // So do answers clearly closest to one choice in meaning
+            debug!("Embedding match, skip prediction");
+            Some(choices[index].clone())
+        } else if let Some(user_input) = &input {
             // If many choices, predict best choice
-            info!("User input: {:?}", user_input);
-
-            // Map choices to choice string
-            let choice_texts: Vec<String> = choices
-                .iter()
-                .map(|choice| choice.text.trim().to_string())
-                .collect();
-
-            let choices_str = choice_texts.join("\n  - ");
-
-            // Create the decision prompt
-            let prompt = decision_node.text.clone();
-            let mut prompt = state.decision_prompt_template.format(
-                &state.history,
-                &prompt,
-                &choices_str,
-                &user_input,
-            );
-
-            // Few shot prediction
-            let response = state
-                .model
-                .generate(&prompt, 200, 0.5)
-                .await
-                .map_err(|err| CognitionError(format!("Failed to generate choice: {}", err)))?;
-            let response = response.text;
-            prompt.push_str(&response);
-            debug!("{}", &prompt);
-
-            // Set current prompt
-            decision_prompt = Some(prompt);
-
-            // Try to match the user's response with one of the choices
-            choice_texts
-                .iter()
-                .position(|choice| *choice == response)
-                .and_then(|index| choices.get(index))
+            info!("User input: {:?}", state.prompt_privacy.apply(user_input));
+            let prepared = prepare_prompt(
+                state,
+                &decision_node,
+                user_input,
+                &tool_responses,
+                !predicting_choice,
+            )
+            .await?;
+            let inference = infer_choice(state, &decision_node, &prepared, cancel).await?;
+            confidence = inference.confidence;
+            timed_out = inference.timed_out;
+            if let Some(response) = &inference.response {
+                tokens += estimate_tokens(&prepared.prompt) + estimate_tokens(response);
+                model_outputs.push(response.clone());
+            }
+            decision_prompt = Some(inference.prompt);
+            inference.choice
         } else {
             None
         };
 
+        // Only auto-advance a prediction the node's policy accepts
+        if predicting_choice && !passing {
+            let policy = state.prediction_policy(&decision_node);
+            let threshold = match policy {
+                PredictionPolicy::Conservative => Some(
+                    state
+                        .confidence_threshold
+                        .unwrap_or(DEFAULT_CONSERVATIVE_THRESHOLD),
+                ),
+                _ => state.confidence_threshold,
+            };
+            match (threshold, confidence) {
+                (Some(threshold), Some(confidence)) if confidence < threshold => {
+                    info!(
+                        "Prediction confidence {} below {}, asking the user",
+                        confidence, threshold
+                    );
+                    next_choice = None;
+                }
+                (Some(_), None) if policy == PredictionPolicy::Conservative => {
+                    info!("Prediction confidence unknown, asking the user");
+                    next_choice = None;
+                }
+                _ => {}
+            }
+        }
+
+        // Count unmatched answers, falling back to the node's default choice
+        if let (None, false, Some(user_input)) = (&next_choice, predicting_choice, &input) {
+            state.retries += 1;
+            metrics::choice_unmatched(&decision_node.id);
+            if let Some(default_next_id) = &decision_node.default_next_id {
+                if state.retries >= state.max_retries || timed_out {
+                    info!(
+                        "No choice matched after {} attempts, defaulting to {}",
+                        state.retries, default_next_id
+                    );
+                    next_choice = Some(Choice::new(user_input, default_next_id.clone()));
+                }
+            }
+        }
+
         // Update the history with the agent-user interaction
-        if let Some(user_input) = &user_input {
-            if !predicting_choice {
+        if let Some(shown) = &shown {
+            state.push_agent_history(&shown.text);
+        } else if let Some(user_input) = input.as_ref().filter(|_| !splitting) {
+            if !predicting_choice && !confirming {
                 // Update the history with the current text
-                if state.history.len() > 0 {
-                    state.history.push_str(&format!("\n  "));
-                }
-                state
-                    .history
-                    .push_str(&format!("- {}: {}", state.agent, decision_node.text));
+                state.push_agent_history(&state.answered_text(&decision_node));
                 // Update the history with the user's response
-                state
-                    .history
-                    .push_str(&format!("\n  - {}: {}", state.user, user_input));
+                state.history.push(Role::User, user_input);
+            }
+        }
+
+        // Refuse choices that are still cooling down
+        if let Some(choice) = &next_choice {
+            if let Some(message) = state.cooldown_refusal(&decision_node.id, choice) {
+                info!("Choice cooling down: {}", choice.text);
+                refusal = Some(message);
+                break;
+            }
+        }
+
+        // Ask before committing a choice that needs confirmation
+        if let Some(choice) = next_choice.as_ref().filter(|_| !confirming) {
+            if let Some(prompt) = state.confirm_prompt(choice) {
+                info!("Confirming choice: {}", choice.text);
+                state.pending_confirm = Some(PendingConfirm {
+                    choice: choice.clone(),
+                    prompt,
+                    user_input: input.clone(),
+                });
+                break;
             }
         }
 
         // If there is a choice, get the next decision node ID
-        if let Some(choice) = next_choice {
+        if let Some(choice) = &next_choice {
             info!(
                 "Predicting the user's next choice... {} {}",
                 decision_node.id, decision_node.text
             );
-            predictions.push(Prediction {
-                choice: choice.text.clone(),
-                id: choice.next_id.clone(),
-                tool_response: tool_response.clone(),
-            });
+            if let Some(shown) = shown {
+                interstitials.push(shown);
+            } else if !splitting {
+                predictions.push(Prediction {
+                    choice: choice.text.clone(),
+                    id: choice.next_id.to_string(),
+                    tool_responses: tool_responses.clone(),
+                    confidence,
+                });
+            }
 
-            predicting_choice = true;
+            // Passing an informational node keeps predicting as it was
+            predicting_choice |= !passing && !splitting;
             // Continue to the next decision node
-            state.current_id = choice.next_id.clone();
+            if apply_transition(state, &decision_node, choice).await? {
+                // Back at an earlier node, let the user choose again
+                predicting_choice = false;
+            }
+
+            // The session ended without an exit node
+            if state.finished {
+                return Ok(None);
+            }
         }
 
         // Find the current decision node
-        let decision_node = state.decision_node(&state.current_id)?.clone();
+        let mut decision_node = state.decision_node(&state.current_id)?.clone();
+
+        let mut script_input = None;
+        if next_choice.is_some() {
+            state.enter_node(&decision_node).await;
+            if let Some(reason) = state.exceeded_limit() {
+                state.hit_limit(reason).await?;
+                break;
+            }
+            // Node scripts may set variables and the tools' input, and move on
+            script_input = state.run_scripts(input.as_deref()).await?;
+            if state.finished {
+                return Ok(None);
+            }
+            decision_node = state.current_node()?.clone();
+        }
 
         // If node has reset, reset the history
         if let Some(true) = decision_node.reset {
-            state.history = String::new();
+            state.history.clear();
         }
 
-        // If node doesn't support prediction, disable prediction
-        if let Some(false) = decision_node.predict {
+        // If prediction is off for the node, or it waits for a secret, let the
+        // user choose
+        if state.prediction_policy(&decision_node) == PredictionPolicy::Off
+            || decision_node.expects_secret()
+        {
             predicting_choice = false;
         }
 
@@ -233,35 +2680,621 @@ pub async fn run_decision(
             predicting_choice = false;
         }
 
-        // If there is a tool, run the tool and get the response
-        if let Some(user_input) = &user_input {
-            // If node has a tool, run the tool
-            if let Some(tool_id) = &decision_node.tool {
-                // Find the tool
-                let tool = state
-                    .tools
-                    .iter()
-                    .find(|obj| *obj.id() == *tool_id)
-                    .ok_or_else(|| CognitionError(format!("Could not find tool: {}", tool_id)))?;
-                tool_response = tool.run(user_input).await?;
+        // If there are tools, run them concurrently and get the responses
+        if let Some(user_input) = &input {
+            if !decision_node.tools().is_empty() || !decision_node.pipeline().is_empty() {
+                let input = script_input.as_ref().unwrap_or(user_input);
+                let outcomes = run_tools(
+                    state,
+                    &decision_node,
+                    input,
+                    secret_answer.as_deref(),
+                    cancel,
+                )
+                .await?;
+                tool_responses = vec![];
+                let mut failed = false;
+                for outcome in outcomes {
+                    match &outcome {
+                        ToolOutcome::Ok(response) => tool_responses.push(response.clone()),
+                        ToolOutcome::Error { .. } => failed = true,
+                    }
+                    state
+                        .notify(DecisionEvent::ToolFinished {
+                            session_id: state.session_id.clone(),
+                            node_id: decision_node.id.clone(),
+                            outcome: outcome.clone(),
+                        })
+                        .await;
+                    tool_outcomes.push(outcome);
+                }
+
+                // A failed tool leads to the node's error handler if it has one,
+                // otherwise the conversation stays on the node
+                if let (true, Some(next_id)) = (failed, &decision_node.on_tool_error) {
+                    info!(
+                        "Tool failed on node {}, going to {}",
+                        decision_node.id, next_id
+                    );
+                    predicting_choice = false;
+                    state.transition(&decision_node.id, next_id, None)?;
+                    if *next_id == Transition::Exit {
+                        state.end_session(SessionOutcome::Completed).await;
+                    }
+                    if state.finished {
+                        return Ok(None);
+                    }
+                    let error_node = state.current_node()?.clone();
+                    state.enter_node(&error_node).await;
+                }
             }
         }
 
+        // Nodes moving on by themselves do not count towards the prediction depth
+        let current_node = state.available(state.current_node()?);
+        if current_node.passes_through() || current_node.picks_randomly() {
+            continue;
+        }
         max_depth -= 1;
         if !predicting_choice || max_depth == 0 {
             break;
         }
     }
 
-    let result = DecisionResult {
+    // Node text can refer to template variables, e.g. `{{node.visits}}`
+    let decision_node = state.current_node()?.clone();
+    let decision_node = state.shown_node(&decision_node, &tool_responses).await?;
+
+    let mut result = DecisionResult {
         user_input,
         decision_prompt,
         choice,
-        current_id: state.current_id.clone(),
-        decision_node: state.current_node()?.clone(),
+        current_id: state.current_id.to_string(),
+        decision_node,
         predictions,
-        tool_response,
+        tool_responses,
+        tool_outcomes,
+        confirm: state
+            .pending_confirm
+            .as_ref()
+            .map(|pending| pending.prompt.clone()),
+        refusal,
+        interstitials,
+        prediction: state.prediction,
+        usage: Usage::default(),
+        variants: state.variants(),
     };
+    state.translate_result(&mut result).await?;
+    result.usage = state.usage().since(&usage_before);
+
+    let logged = state.turn_log.is_some() || !state.log_sinks.is_empty();
+    if let (true, Some(_)) = (logged, &result.user_input) {
+        let privacy = state.prompt_privacy;
+        let exported = privacy.result(&result);
+        let record = TurnRecord {
+            session_id: state.session_id.clone(),
+            turn: state.turns,
+            timestamp: unix_now(),
+            node_id: answered_id.to_string(),
+            next_id: result.current_id.clone(),
+            user_input: exported.user_input,
+            prompt: exported.decision_prompt,
+            model_outputs: model_outputs
+                .iter()
+                .map(|output| privacy.apply(output))
+                .collect(),
+            choices: result
+                .predictions
+                .iter()
+                .map(|prediction| prediction.choice.clone())
+                .collect(),
+            tool_responses: result.tool_responses.clone(),
+            latency_ms: turn_started.elapsed().as_millis() as u64,
+            tokens,
+            variants: result.variants.clone(),
+        };
+        if let Some(path) = &state.turn_log {
+            turn_log::append(path, &record)?;
+        }
+        for sink in &state.log_sinks {
+            if let Err(err) = sink.send(&record).await {
+                warn!("Log sink {} failed: {}", sink.id(), err.0);
+            }
+        }
+    }
+
+    state.save_session().await?;
 
     Ok(Some(result))
 }
+
+// A node's decision prompt, rendered for the user's answer
+#[derive(Clone, Debug)]
+pub struct PreparedPrompt {
+    pub prompt: String,
+    // The node's choices, then the interrupts offered with them
+    pub choices: Vec<Choice>,
+    pub interrupts: Vec<Interrupt>,
+    // As listed in the prompt, in the same order
+    pub choice_texts: Vec<String>,
+}
+
+impl PreparedPrompt {
+    // The choice or interrupt at an index of `choice_texts`
+    pub fn choice(&self, index: usize) -> Option<Choice> {
+        match index.checked_sub(self.choices.len()) {
+            None => Some(self.choices[index].clone()),
+            Some(index) => {
+                let interrupt = self.interrupts.get(index)?;
+                info!("Interrupt: {}", interrupt.intent);
+                Some(Choice::new(&interrupt.intent, interrupt.next_id.clone()))
+            }
+        }
+    }
+}
+
+// What the model made of an answer
+#[derive(Clone, Debug, Default)]
+pub struct Inference {
+    pub choice: Option<Choice>,
+    // The model's output, none if it exceeded the node's latency budget
+    pub response: Option<String>,
+    // The prompt followed by the response
+    pub prompt: String,
+    pub confidence: Option<f32>,
+    pub timed_out: bool,
+}
+
+// Render the decision prompt of a node for the user's answer, listing the
+// interrupts' intents after the node's choices with `with_interrupts`
+pub async fn prepare_prompt(
+    state: &mut DecisionState,
+    decision_node: &Decision,
+    user_input: &str,
+    tool_responses: &[ToolResponse],
+    with_interrupts: bool,
+) -> Result<PreparedPrompt, CognitionError> {
+    // Map choices to choice string, followed by the interrupts' intents
+    let choices = decision_node.choices();
+    let mut choice_texts = choice_texts(&choices);
+    let interrupts: Vec<Interrupt> = state
+        .interrupts
+        .iter()
+        .filter(|interrupt| with_interrupts && !choice_texts.contains(&interrupt.intent))
+        .cloned()
+        .collect();
+    choice_texts.extend(interrupts.iter().map(|interrupt| interrupt.intent.clone()));
+
+    let choices_str = if state.numbered_choices {
+        choice_texts
+            .iter()
+            .enumerate()
+            .map(|(index, text)| format!("{}. {}", index + 1, text))
+            .collect::<Vec<_>>()
+            .join("\n  - ")
+    } else {
+        choice_texts.join("\n  - ")
+    };
+
+    // Create the decision prompt
+    // Generate nodes are asked with their answer
+    let decision_prompt_text = state.shown_node(decision_node, tool_responses).await?.text;
+    let mut variables = state.template_variables();
+    variables.insert("history".to_string(), state.prompt_history(decision_node));
+    let style = state.history.style;
+    variables.insert(
+        "decision_prompt".to_string(),
+        style.template_value(&decision_prompt_text),
+    );
+    variables.insert("choices".to_string(), choices_str);
+    variables.insert("user_input".to_string(), style.template_value(user_input));
+    let mut prompt = state.decision_prompt_template.render(&variables);
+    // Templates without `{{persona}}` or `{{context_pack}}` get them
+    // ahead of the prompt
+    let template = state.decision_prompt_template.content().replace(' ', "");
+    let mut preamble = vec![];
+    if !template.contains("{{persona}}") {
+        preamble.push(state.current_persona().render());
+    }
+    if !template.contains("{{context_pack}}") {
+        preamble.push(state.context_preamble().to_string());
+    }
+    preamble.retain(|part| !part.is_empty());
+    if !preamble.is_empty() {
+        prompt = format!("{}\n\n{}", preamble.join("\n\n"), prompt);
+    }
+
+    state
+        .notify(DecisionEvent::PromptBuilt {
+            session_id: state.session_id.clone(),
+            node_id: decision_node.id.clone(),
+            prompt: prompt.clone(),
+        })
+        .await;
+
+    Ok(PreparedPrompt {
+        prompt,
+        choices: choices.into_iter().cloned().collect(),
+        interrupts,
+        choice_texts,
+    })
+}
+
+// Ask the model which choice of a prepared prompt the user made, within the
+// node's latency budget
+pub async fn infer_choice(
+    state: &DecisionState,
+    decision_node: &Decision,
+    prepared: &PreparedPrompt,
+    cancel: &CancellationToken,
+) -> Result<Inference, CognitionError> {
+    // Few shot prediction, within the node's latency budget
+    let generation = state.select_choice(&prepared.prompt, &prepared.choice_texts, cancel);
+    let response = match decision_node.max_latency_ms {
+        Some(max_latency_ms) => timeout(Duration::from_millis(max_latency_ms), generation)
+            .await
+            .ok(),
+        None => Some(generation.await),
+    };
+
+    let response = match response {
+        Some(response) => response?,
+        None => {
+            // Too slow, fall back to the node's static text and default choice
+            warn!(
+                "Model exceeded the latency budget of node {}",
+                decision_node.id
+            );
+            return Ok(Inference {
+                prompt: prepared.prompt.clone(),
+                timed_out: true,
+                ..Inference::default()
+            });
+        }
+    };
+    let confidence = response.confidence();
+    let response = response.text;
+    state
+        .notify(DecisionEvent::TokensStreamed {
+            session_id: state.session_id.clone(),
+            node_id: decision_node.id.clone(),
+            text: response.clone(),
+        })
+        .await;
+    let prompt = format!("{}{}", prepared.prompt, response);
+    debug!("{}", state.prompt_privacy.apply(&prompt));
+
+    // Try to match the user's response with one of the choices
+    let choice =
+        response_choice(&response, &prepared.choice_texts).and_then(|index| prepared.choice(index));
+    Ok(Inference {
+        choice,
+        response: Some(response),
+        prompt,
+        confidence,
+        timed_out: false,
+    })
+}
+
+// Commit a choice made at a node: start its cooldown and move to its next
+// node, ending the session at `exit`. Returns whether the session went back to
+// an earlier node.
+pub async fn apply_transition(
+    state: &mut DecisionState,
+    decision_node: &Decision,
+    choice: &Choice,
+) -> Result<bool, CognitionError> {
+    state
+        .notify(DecisionEvent::ChoiceResolved {
+            session_id: state.session_id.clone(),
+            node_id: decision_node.id.clone(),
+            choice: choice.text.clone(),
+            next_id: choice.next_id.to_string(),
+        })
+        .await;
+
+    if choice.cooldown_secs.is_some() {
+        state.cooldowns.insert(
+            (decision_node.id.clone(), choice.text.clone()),
+            Instant::now(),
+        );
+    }
+
+    let back = state.transition(
+        &decision_node.id,
+        &choice.next_id,
+        choice.return_id.as_deref(),
+    )?;
+    if choice.next_id == Transition::Exit {
+        state.end_session(SessionOutcome::Completed).await;
+    }
+    Ok(back)
+}
+
+// Run a node's tools concurrently with the user's input, or a secret answer
+// for tools trusted with personal data, then its pipeline. A failed tool is an
+// error outcome, a cancelled turn an error.
+pub async fn run_tools(
+    state: &DecisionState,
+    decision_node: &Decision,
+    input: &str,
+    secret: Option<&str>,
+    cancel: &CancellationToken,
+) -> Result<Vec<ToolOutcome>, CognitionError> {
+    // Find the tools
+    let tool_ids = decision_node.tools();
+    let tools = tool_ids
+        .iter()
+        .map(|tool_id| state.tool(tool_id))
+        .collect::<Result<Vec<_>, _>>()?;
+    let progresses: Vec<ObserverProgress> = tool_ids
+        .iter()
+        .map(|tool_id| ObserverProgress {
+            session_id: &state.session_id,
+            tool_id,
+            observers: &state.observers,
+        })
+        .collect();
+
+    let mut inputs = vec![];
+    for tool in &tools {
+        inputs.push(tool_input(state, *tool, decision_node, input, secret, cancel).await);
+    }
+
+    for tool in &tools {
+        state.notify_tool_started(decision_node, *tool).await;
+    }
+
+    let calls = tools.iter().zip(&progresses).zip(inputs);
+    let responses = join_all(calls.map(|((tool, progress), input)| async move {
+        call_tool(
+            state,
+            decision_node,
+            *tool,
+            tool.id(),
+            input?,
+            progress,
+            cancel,
+        )
+        .await
+    }))
+    .await;
+
+    let mut outcomes = vec![];
+    for (tool, response) in tools.iter().zip(responses) {
+        match response {
+            Ok(Some(response)) => outcomes.push(ToolOutcome::Ok(response)),
+            Ok(None) => {}
+            // The host gave up on the turn
+            Err(err) if cancel.is_cancelled() => return Err(err),
+            Err(err) => outcomes.push(tool_error(*tool, err)),
+        }
+    }
+
+    if !decision_node.pipeline().is_empty() {
+        outcomes.extend(run_pipeline(state, decision_node, input, secret, cancel).await?);
+    }
+    Ok(outcomes)
+}
+
+// Run a node's pipeline one step at a time. Each step gets its rendered
+// `params`, or else the previous step's output, the first step the input
+// like any tool. Only the last output is the node's tool response, so
+// intermediate results stay out of the history. A failed step stops the
+// pipeline as an error outcome, unless it is skipped.
+async fn run_pipeline(
+    state: &DecisionState,
+    decision_node: &Decision,
+    input: &str,
+    secret: Option<&str>,
+    cancel: &CancellationToken,
+) -> Result<Vec<ToolOutcome>, CognitionError> {
+    let mut variables = state.template_variables();
+    variables.insert("input".to_string(), input.to_string());
+    let mut outcomes = vec![];
+    let mut last: Option<ToolResponse> = None;
+
+    for (index, step) in decision_node.pipeline().into_iter().enumerate() {
+        let tool = state.tool(step.tool())?;
+        let previous = last.as_ref().map(|response| response.response.clone());
+        variables.insert(
+            "previous".to_string(),
+            previous.clone().unwrap_or_else(|| input.to_string()),
+        );
+        let step_input = match (step.params(), previous) {
+            (Some(params), _) => Ok(render_variables(params, &variables)),
+            (None, Some(previous)) => Ok(previous),
+            (None, None) => tool_input(state, tool, decision_node, input, secret, cancel).await,
+        };
+
+        state.notify_tool_started(decision_node, tool).await;
+        let progress = ObserverProgress {
+            session_id: &state.session_id,
+            tool_id: step.tool(),
+            observers: &state.observers,
+        };
+        let call_id = format!("pipeline.{}.{}", index, tool.id());
+        let response = match step_input {
+            Ok(step_input) => {
+                call_tool(
+                    state,
+                    decision_node,
+                    tool,
+                    &call_id,
+                    step_input,
+                    &progress,
+                    cancel,
+                )
+                .await
+            }
+            Err(err) => Err(err),
+        };
+
+        match response {
+            Ok(Some(response)) => {
+                insert_output(&mut variables, step.tool(), &response.response);
+                last = Some(response);
+            }
+            Ok(None) => {}
+            Err(err) if cancel.is_cancelled() => return Err(err),
+            Err(err) if step.on_error() == StepFailure::Skip => {
+                warn!("Pipeline step {} skipped: {}", tool.id(), err.0);
+            }
+            Err(err) => {
+                info!("Pipeline of {} stopped at {}", decision_node.id, tool.id());
+                outcomes.push(tool_error(tool, err));
+                return Ok(outcomes);
+            }
+        }
+    }
+
+    outcomes.extend(last.map(ToolOutcome::Ok));
+    Ok(outcomes)
+}
+
+// A step's output as `steps.<tool>`, and the fields of JSON output as
+// `steps.<tool>.<field>`, nested fields joined with dots
+fn insert_output(variables: &mut BTreeMap<String, String>, tool_id: &str, output: &str) {
+    fn flatten(variables: &mut BTreeMap<String, String>, name: String, value: &Value) {
+        match value {
+            Value::Object(fields) => {
+                for (field, value) in fields {
+                    flatten(variables, format!("{}.{}", name, field), value);
+                }
+            }
+            Value::String(text) => {
+                variables.insert(name, text.clone());
+            }
+            value => {
+                variables.insert(name, value.to_string());
+            }
+        }
+    }
+
+    let name = format!("steps.{}", tool_id);
+    variables.insert(name.clone(), output.to_string());
+    if let Ok(value @ Value::Object(_)) = serde_json::from_str::<Value>(output) {
+        flatten(variables, name, &value);
+    }
+}
+
+// Input of a tool run on the user's answer. Tools with an `args_prompt` get
+// parameters the model writes from the conversation instead, and only tools
+// trusted with personal data see a secret answer.
+async fn tool_input(
+    state: &DecisionState,
+    tool: &dyn Tool,
+    decision_node: &Decision,
+    input: &str,
+    secret: Option<&str>,
+    cancel: &CancellationToken,
+) -> Result<String, CognitionError> {
+    match tool.args_prompt() {
+        Some(args_prompt) => {
+            state
+                .tool_args(tool, args_prompt, decision_node, cancel)
+                .await
+        }
+        None => Ok(match secret {
+            Some(secret) if tool.needs_personal_data() => secret.to_string(),
+            _ => input.to_string(),
+        }),
+    }
+}
+
+// Call a tool with the retries, timeout and persistence of the session.
+// `call_id` names the call within the node's turn.
+async fn call_tool(
+    state: &DecisionState,
+    decision_node: &Decision,
+    tool: &dyn Tool,
+    call_id: &str,
+    input: String,
+    progress: &dyn ToolProgress,
+    cancel: &CancellationToken,
+) -> Result<Option<ToolResponse>, CognitionError> {
+    let invocation = ToolInvocation {
+        id: format!(
+            "{}:{}:{}:{}",
+            state.session_id, state.turns, decision_node.id, call_id
+        ),
+        session_id: state.session_id.clone(),
+        node_id: decision_node.id.clone(),
+        tool_id: tool.id().clone(),
+        input: match &state.redactor {
+            Some(redactor) if tool.needs_personal_data() => redactor.restore(&input),
+            _ => input.clone(),
+        },
+        idempotency_key: format!("{:032x}", rand::random::<u128>()),
+        status: InvocationStatus::Pending,
+        response: None,
+    };
+    let call = guarded(
+        "Tool call",
+        invoke_tool(
+            state.store.as_deref(),
+            invocation,
+            tool,
+            progress,
+            state.tool_retries,
+        ),
+        state.tool_timeout,
+        cancel,
+    );
+
+    // Tools without side effects can reuse a response to the same input
+    let ttl = match state.tool_cache_ttls.get(tool.id()) {
+        Some(ttl) if !tool.side_effecting() => *ttl,
+        _ => return call.await?,
+    };
+    // Keyed by the input before personal data is restored
+    let key = (tool.id().clone(), input);
+    if let Some(response) = state.cached_tool_response(&key, ttl) {
+        debug!("Tool {} answered from the cache", tool.id());
+        return Ok(response);
+    }
+    let response = call.await??;
+    state.cache_tool_response(key, response.clone(), ttl);
+    Ok(response)
+}
+
+fn tool_error(tool: &dyn Tool, err: CognitionError) -> ToolOutcome {
+    warn!("Tool {} failed: {}", tool.id(), err.0);
+    ToolOutcome::Error {
+        tool_id: tool.id().clone(),
+        message: err.0,
+        retryable: tool.supports_idempotency() || !tool.side_effecting(),
+    }
+}
+
+// Handle an idle user once `DecisionState::idle_deadline` has passed: the first
+// time, move to the `on_idle.node` nudge if there is one, otherwise close the
+// session as abandoned
+pub async fn run_idle(state: &mut DecisionState) -> Result<Option<DecisionResult>, CognitionError> {
+    match state.idle_deadline() {
+        Some(deadline) if deadline <= Instant::now() => {}
+        _ => return Ok(None),
+    }
+
+    let idle_node = state.idle_node.clone().filter(|_| !state.nudged);
+    let Some(idle_node) = idle_node else {
+        info!("Session {} abandoned", state.session_id);
+        state.end_session(SessionOutcome::Abandoned).await;
+        state.finished = true;
+        return Ok(None);
+    };
+
+    info!(
+        "Session {} idle, nudging with {}",
+        state.session_id, idle_node
+    );
+    let usage_before = state.usage();
+    state.nudged = true;
+    state.last_activity = Instant::now();
+    state.pending_confirm = None;
+    state.current_id = state.decision_tree.id(&idle_node);
+    let decision_node = state.current_node()?.clone();
+    state.enter_node(&decision_node).await;
+
+    state.node_result(None, usage_before).await.map(Some)
+}
@@ -0,0 +1,178 @@
+// Offline routing evaluation: labeled answers run against a model backend and
+// prompt template, scored for accuracy, latency and cost. Reports are appended
+// to a JSONL history to track regressions across model updates.
+use crate::{
+    clock::{Instant, SystemTime, UNIX_EPOCH},
+    run_decision, CognitionError, DecisionState, Usage,
+};
+use cognition_core::{PredictionPolicy, START_ID};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+
+// An answer given at a node and the node it should lead to
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EvalCase {
+    #[serde(default = "start_id")]
+    pub node: String,
+    pub input: String,
+    // Node ID, `exit` or `call:<node_id>`, or the answered node for answers
+    // that should not match any choice
+    pub expected: String,
+}
+
+fn start_id() -> String {
+    START_ID.to_string()
+}
+
+impl EvalCase {
+    // Load a YAML list of cases
+    pub fn load(path: &str) -> Result<Vec<Self>, CognitionError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| CognitionError(format!("Failed to read dataset: {}", err)))?;
+        serde_yaml::from_str(&content)
+            .map_err(|err| CognitionError(format!("Failed to parse dataset: {}", err)))
+    }
+}
+
+// A case routed elsewhere, or failed with `error`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EvalMiss {
+    pub node: String,
+    pub input: String,
+    pub expected: String,
+    pub actual: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Scores of one variant, a backend and prompt template, over a dataset
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EvalReport {
+    pub variant: String,
+    // Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub cases: usize,
+    pub correct: usize,
+    pub accuracy: f64,
+    pub mean_latency_ms: f64,
+    pub max_latency_ms: u64,
+    pub usage: Usage,
+    pub misses: Vec<EvalMiss>,
+}
+
+impl EvalReport {
+    // Append the report as one JSON line
+    pub fn append(&self, path: &str) -> Result<(), CognitionError> {
+        let line = serde_json::to_string(self)
+            .map_err(|err| CognitionError(format!("Failed to serialize report: {}", err)))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| CognitionError(format!("Failed to open eval history: {}", err)))?;
+        writeln!(file, "{}", line)
+            .map_err(|err| CognitionError(format!("Failed to write eval history: {}", err)))
+    }
+
+    // Reports appended to a history file, oldest first. A missing file has none.
+    pub fn load_history(path: &str) -> Result<Vec<Self>, CognitionError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => {
+                return Err(CognitionError(format!(
+                    "Failed to read eval history: {}",
+                    err
+                )))
+            }
+        };
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|err| CognitionError(format!("Failed to parse eval history: {}", err)))
+            })
+            .collect()
+    }
+}
+
+// Run every case in a fresh session from `new_state`, counting the answer as
+// correct when its first choice leads to the expected node. Predictions after
+// that choice are turned off.
+pub async fn evaluate(
+    variant: &str,
+    cases: &[EvalCase],
+    mut new_state: impl FnMut() -> Result<DecisionState, CognitionError>,
+) -> Result<EvalReport, CognitionError> {
+    let mut correct = 0;
+    let mut latencies = vec![];
+    let mut usage = Usage::default();
+    let mut misses = vec![];
+
+    for case in cases {
+        let mut state = new_state()?;
+        state.prediction = PredictionPolicy::Off;
+        state.go_to(&case.node)?;
+
+        let started = Instant::now();
+        let result = run_decision(Some(case.input.clone()), &mut state).await;
+        latencies.push(started.elapsed().as_millis() as u64);
+        usage.add(&state.usage());
+
+        let actual = match result {
+            Ok(Some(result)) => Some(match result.predictions.first() {
+                Some(prediction) => prediction.id.clone(),
+                None => result.current_id,
+            }),
+            // The answer ended the session without an exit node
+            Ok(None) => Some("exit".to_string()),
+            Err(err) => {
+                misses.push(EvalMiss {
+                    node: case.node.clone(),
+                    input: case.input.clone(),
+                    expected: case.expected.clone(),
+                    actual: None,
+                    error: Some(err.0),
+                });
+                continue;
+            }
+        };
+        if actual.as_deref() == Some(case.expected.as_str()) {
+            correct += 1;
+        } else {
+            misses.push(EvalMiss {
+                node: case.node.clone(),
+                input: case.input.clone(),
+                expected: case.expected.clone(),
+                actual,
+                error: None,
+            });
+        }
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    Ok(EvalReport {
+        variant: variant.to_string(),
+        timestamp,
+        cases: cases.len(),
+        correct,
+        accuracy: if cases.is_empty() {
+            0.0
+        } else {
+            correct as f64 / cases.len() as f64
+        },
+        mean_latency_ms: if latencies.is_empty() {
+            0.0
+        } else {
+            latencies.iter().sum::<u64>() as f64 / latencies.len() as f64
+        },
+        max_latency_ms: latencies.iter().copied().max().unwrap_or_default(),
+        usage,
+        misses,
+    })
+}
@@ -0,0 +1,53 @@
+use crate::{Decision, Transition, START_ID};
+use serde_json::{json, Value};
+
+// Dialogflow CX flow as one JSON document: the flow, whose only route enters
+// the `start` page, a page per node with its text as entry fulfillment, and an
+// intent per choice trained on the choice text and its aliases. `exit` ends
+// the session and `back` returns to the previous page. Subtree calls become
+// plain transitions.
+pub fn to_dialogflow(decision_nodes: &[Decision]) -> Value {
+    let mut pages = vec![];
+    let mut intents = vec![];
+
+    for node in decision_nodes {
+        let mut routes = vec![];
+        for (index, choice) in node.choices().into_iter().enumerate() {
+            let intent = format!("{}_{}", node.id, index + 1);
+            let phrases: Vec<Value> = std::iter::once(&choice.text)
+                .chain(&choice.aliases)
+                .map(|text| json!({ "parts": [{ "text": text }], "repeatCount": 1 }))
+                .collect();
+            intents.push(json!({ "displayName": intent, "trainingPhrases": phrases }));
+            routes.push(json!({ "intent": intent, "targetPage": target_page(&choice.next_id) }));
+        }
+        if let Some(next_id) = node.next.iter().chain(&node.default_next_id).next() {
+            routes.push(json!({ "condition": "true", "targetPage": target_page(next_id) }));
+        }
+
+        pages.push(json!({
+            "name": node.id,
+            "displayName": node.id,
+            "entryFulfillment": { "messages": [{ "text": { "text": [node.text] } }] },
+            "transitionRoutes": routes,
+        }));
+    }
+
+    json!({
+        "flow": {
+            "displayName": "Default Start Flow",
+            "transitionRoutes": [{ "condition": "true", "targetPage": START_ID }],
+        },
+        "pages": pages,
+        "intents": intents,
+    })
+}
+
+fn target_page(next_id: &Transition) -> String {
+    match next_id {
+        Transition::Exit => "END_SESSION".to_string(),
+        Transition::Back => "PREVIOUS_PAGE".to_string(),
+        Transition::Restart => START_ID.to_string(),
+        Transition::Node(id) | Transition::Call(id) => id.clone(),
+    }
+}
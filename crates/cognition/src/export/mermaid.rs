@@ -0,0 +1,66 @@
+use crate::{Decision, Transition, EXIT_ID};
+
+// Mermaid flowchart of the tree: nodes with their text, choices as labelled
+// edges, default transitions as dotted `default` edges and subtree calls as
+// dotted edges. `exit` and `back` are drawn as terminal nodes.
+pub fn to_mermaid(decision_nodes: &[Decision]) -> String {
+    let mut lines = vec!["flowchart TD".to_string()];
+    let mut exit = false;
+    let mut back = false;
+
+    for node in decision_nodes {
+        lines.push(format!("    {}[\"{}\"]", node.id, escape(&node.text)));
+    }
+    for node in decision_nodes {
+        for choice in node.choices() {
+            let (arrow, target) = edge(&choice.next_id, &mut exit, &mut back);
+            lines.push(format!(
+                "    {} {}|\"{}\"| {}",
+                node.id,
+                arrow,
+                escape(&choice.text),
+                target
+            ));
+        }
+        if let Some(next_id) = &node.next {
+            let (arrow, target) = edge(next_id, &mut exit, &mut back);
+            lines.push(format!("    {} {}|next| {}", node.id, arrow, target));
+        }
+        if let Some(next_id) = &node.default_next_id {
+            let (_, target) = edge(next_id, &mut exit, &mut back);
+            lines.push(format!("    {} -.->|default| {}", node.id, target));
+        }
+    }
+    // Terminal nodes, unless the tree has nodes with these IDs
+    let defined = |id: &str| decision_nodes.iter().any(|node| node.id == id);
+    if exit && !defined(EXIT_ID) {
+        lines.push(format!("    {}([\"{}\"])", EXIT_ID, EXIT_ID));
+    }
+    if back && !defined("back") {
+        lines.push("    back([\"back\"])".to_string());
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+// Arrow and target node of a transition
+fn edge(next_id: &Transition, exit: &mut bool, back: &mut bool) -> (&'static str, String) {
+    match next_id {
+        Transition::Exit => {
+            *exit = true;
+            ("-->", EXIT_ID.to_string())
+        }
+        Transition::Back => {
+            *back = true;
+            ("-->", "back".to_string())
+        }
+        Transition::Call(id) => ("-.->", id.clone()),
+        transition => ("-->", transition.to_string()),
+    }
+}
+
+// Mermaid labels are quoted, quotes inside them are written as entities
+fn escape(text: &str) -> String {
+    text.replace('"', "#quot;").replace('\n', "<br>")
+}
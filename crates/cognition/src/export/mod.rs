@@ -0,0 +1,5 @@
+// Converters from decision nodes to other formats, for visualization and for
+// migrating to and from other dialogue stacks
+pub mod dialogflow;
+pub mod mermaid;
+pub mod rasa;
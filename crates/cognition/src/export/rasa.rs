@@ -0,0 +1,56 @@
+use crate::{Decision, Transition, START_ID};
+use serde_json::{json, Value};
+
+// Rasa training data and responses as one YAML document, to split into the
+// domain and data files: an `utter_<node>` response per node, an intent per
+// choice with the choice text and its aliases as examples, and a rule per
+// choice from the node's response through the intent to the next node's.
+// `exit` restarts the conversation and `back` undoes the last turn.
+pub fn to_rasa(decision_nodes: &[Decision]) -> Value {
+    let mut intents = vec![];
+    let mut nlu = vec![];
+    let mut responses = serde_json::Map::new();
+    let mut rules = vec![json!({
+        "rule": "Start",
+        "conversation_start": true,
+        "steps": [{ "action": format!("utter_{}", START_ID) }],
+    })];
+
+    for node in decision_nodes {
+        responses.insert(format!("utter_{}", node.id), json!([{ "text": node.text }]));
+        for (index, choice) in node.choices().into_iter().enumerate() {
+            let intent = format!("{}_{}", node.id, index + 1);
+            let examples: String = std::iter::once(&choice.text)
+                .chain(&choice.aliases)
+                .map(|text| format!("- {}\n", text))
+                .collect();
+            nlu.push(json!({ "intent": intent, "examples": examples }));
+            rules.push(json!({
+                "rule": format!("{}: {}", node.id, choice.text),
+                "steps": [
+                    { "action": format!("utter_{}", node.id) },
+                    { "intent": intent },
+                    { "action": action(&choice.next_id) },
+                ],
+            }));
+            intents.push(intent);
+        }
+    }
+
+    json!({
+        "version": "3.1",
+        "intents": intents,
+        "responses": responses,
+        "nlu": nlu,
+        "rules": rules,
+    })
+}
+
+fn action(next_id: &Transition) -> String {
+    match next_id {
+        Transition::Exit => "action_restart".to_string(),
+        Transition::Back => "action_back".to_string(),
+        Transition::Restart => format!("utter_{}", START_ID),
+        Transition::Node(id) | Transition::Call(id) => format!("utter_{}", id),
+    }
+}
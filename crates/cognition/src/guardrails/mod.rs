@@ -0,0 +1,38 @@
+use crate::CognitionError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "openai")]
+pub use openai_moderation::OpenAIModeration;
+
+#[cfg(feature = "openai")]
+mod openai_moderation;
+
+// Where a guardrail caught a violation
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailStage {
+    // User input, before it reaches the history or the model
+    Input,
+    // Generated text, before it reaches the user
+    Output,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Violation {
+    pub guardrail: String,
+    pub reason: String,
+}
+
+// Moderation check run on user input and model output
+#[async_trait(?Send)]
+pub trait Guardrail {
+    fn id(&self) -> &String;
+
+    async fn check_input(&self, text: &str) -> Result<Option<Violation>, CognitionError>;
+
+    // Output is held to the same rules as input unless overridden
+    async fn check_output(&self, text: &str) -> Result<Option<Violation>, CognitionError> {
+        self.check_input(text).await
+    }
+}
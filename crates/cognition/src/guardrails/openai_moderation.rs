@@ -0,0 +1,93 @@
+use super::*;
+use crate::config::string_by_path;
+use log::debug;
+use reqwest::Client;
+use std::collections::BTreeMap;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+// OpenAI moderation endpoint, configured with `guardrails.openai_moderation.api_key`
+// and optionally `base_url`
+pub struct OpenAIModeration {
+    pub id: String,
+    client: Client,
+    api_key: String,
+    base_url: String,
+}
+
+#[derive(Serialize)]
+struct ModerationRequest<'a> {
+    input: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ModerationResponse {
+    results: Vec<ModerationResult>,
+}
+
+#[derive(Deserialize)]
+struct ModerationResult {
+    flagged: bool,
+    categories: BTreeMap<String, bool>,
+}
+
+impl OpenAIModeration {
+    pub fn new(config: &str) -> Result<Self, CognitionError> {
+        let api_key =
+            string_by_path(config, "guardrails.openai_moderation.api_key").ok_or_else(|| {
+                CognitionError("Missing guardrails.openai_moderation.api_key".to_string())
+            })?;
+        let base_url = string_by_path(config, "guardrails.openai_moderation.base_url")
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+        Ok(Self {
+            id: "openai_moderation".to_string(),
+            client: Client::new(),
+            api_key,
+            base_url,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Guardrail for OpenAIModeration {
+    fn id(&self) -> &String {
+        &self.id
+    }
+
+    async fn check_input(&self, text: &str) -> Result<Option<Violation>, CognitionError> {
+        let response = self
+            .client
+            .post(format!("{}/moderations", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&ModerationRequest { input: text })
+            .send()
+            .await
+            .map_err(|err| CognitionError(format!("Failed to send moderation request: {}", err)))?
+            .json::<ModerationResponse>()
+            .await
+            .map_err(|err| CognitionError(format!("Failed to parse moderation: {}", err)))?;
+
+        let flagged: Vec<String> = response
+            .results
+            .iter()
+            .filter(|result| result.flagged)
+            .flat_map(|result| {
+                result
+                    .categories
+                    .iter()
+                    .filter(|(_, flagged)| **flagged)
+                    .map(|(category, _)| category.clone())
+            })
+            .collect();
+        if response.results.iter().all(|result| !result.flagged) {
+            return Ok(None);
+        }
+
+        debug!("{}: flagged {:?}", self.id, flagged);
+        Ok(Some(Violation {
+            guardrail: self.id.clone(),
+            reason: flagged.join(", "),
+        }))
+    }
+}
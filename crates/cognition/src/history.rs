@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Agent,
+    User,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub role: Role,
+    pub text: String,
+    // Name of the agent that said it in multi-agent trees
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+}
+
+// How the history is rendered into the prompt. Instruction-tuned local models
+// work best with the format they were trained on.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryStyle {
+    // `- Agent: text` items, indented to sit inside the YAML prompt template
+    #[default]
+    Yaml,
+    // `Agent: text` lines
+    Prefix,
+    // `<|im_start|>assistant ... <|im_end|>` blocks
+    ChatMl,
+    // User turns wrapped in `[INST] ... [/INST]`
+    Llama,
+}
+
+impl HistoryStyle {
+    pub fn parse(style: &str) -> Option<Self> {
+        serde_yaml::from_str(style).ok()
+    }
+
+    // Text for a single-line value of the prompt template. In YAML templates
+    // continuation lines are indented so pasted logs or addresses stay inside
+    // their value instead of reading as new keys.
+    pub fn template_value(&self, text: &str) -> String {
+        match self {
+            HistoryStyle::Yaml => indent_lines(text, "  "),
+            _ => text.to_string(),
+        }
+    }
+}
+
+fn indent_lines(text: &str, indent: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\n', &format!("\n{}", indent))
+}
+
+// Rendered history without the indentation it has for templates, for prompts
+// the engine writes itself
+pub(crate) fn unindent(history: &str) -> String {
+    history
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+    pub style: HistoryStyle,
+}
+
+impl History {
+    pub fn new(style: HistoryStyle) -> Self {
+        Self {
+            entries: vec![],
+            style,
+        }
+    }
+
+    pub fn push(&mut self, role: Role, text: &str) {
+        self.entries.push(HistoryEntry {
+            role,
+            text: text.to_string(),
+            speaker: None,
+        });
+    }
+
+    // Agent text said by a named agent, shown under its name instead of the
+    // default agent's
+    pub fn push_agent(&mut self, speaker: Option<&str>, text: &str) {
+        self.entries.push(HistoryEntry {
+            role: Role::Agent,
+            text: text.to_string(),
+            speaker: speaker.map(str::to_string),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // Render the history for the prompt, using the agent and user names
+    pub fn render(&self, agent: &str, user: &str) -> String {
+        let name = |entry: &HistoryEntry| match entry.role {
+            Role::Agent => entry.speaker.clone().unwrap_or(agent.to_string()),
+            Role::User => user.to_string(),
+        };
+        // Chat formats have one assistant role, named agents are told apart
+        // by a prefix
+        let text = |entry: &HistoryEntry| match &entry.speaker {
+            Some(speaker) => format!("{}: {}", speaker, entry.text),
+            None => entry.text.clone(),
+        };
+
+        let lines: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| match self.style {
+                HistoryStyle::Yaml => {
+                    format!("- {}: {}", name(entry), indent_lines(&entry.text, "    "))
+                }
+                HistoryStyle::Prefix => format!("{}: {}", name(entry), entry.text),
+                HistoryStyle::ChatMl => {
+                    let role = match entry.role {
+                        Role::Agent => "assistant",
+                        Role::User => "user",
+                    };
+                    format!("<|im_start|>{}\n{}<|im_end|>", role, text(entry))
+                }
+                HistoryStyle::Llama => match entry.role {
+                    Role::Agent => text(entry),
+                    Role::User => format!("[INST] {} [/INST]", entry.text),
+                },
+            })
+            .collect();
+
+        match self.style {
+            HistoryStyle::Yaml => lines.join("\n  "),
+            _ => lines.join("\n"),
+        }
+    }
+}
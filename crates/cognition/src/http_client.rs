@@ -0,0 +1,67 @@
+// HTTP transport of model backends, so they run wherever requests can be sent:
+// reqwest natively, the browser's fetch in wasm builds, or a host's own client
+use crate::CognitionError;
+use async_trait::async_trait;
+use serde_json::Value;
+
+#[async_trait(?Send)]
+pub trait HttpClient {
+    // POST `body` as JSON to `url` with extra `headers`, returning the JSON
+    // response. Error statuses are errors.
+    async fn post_json(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+        body: &Value,
+    ) -> Result<Value, CognitionError>;
+
+    // Open a connection to `url` ahead of the first request, for transports
+    // that pool connections
+    async fn connect(
+        &self,
+        _url: &str,
+        _headers: &[(String, String)],
+    ) -> Result<(), CognitionError> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "reqwest")]
+pub struct ReqwestClient(pub reqwest::Client);
+
+#[cfg(feature = "reqwest")]
+#[async_trait(?Send)]
+impl HttpClient for ReqwestClient {
+    async fn post_json(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+        body: &Value,
+    ) -> Result<Value, CognitionError> {
+        let mut request = self.0.post(url).json(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        request
+            .send()
+            .await
+            .map_err(|err| CognitionError(format!("HTTP request error: {}", err)))?
+            .error_for_status()
+            .map_err(|err| CognitionError(format!("HTTP status error: {}", err)))?
+            .json()
+            .await
+            .map_err(|err| CognitionError(format!("JSON parsing error: {}", err)))
+    }
+
+    async fn connect(&self, url: &str, headers: &[(String, String)]) -> Result<(), CognitionError> {
+        let mut request = self.0.get(url);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        request
+            .send()
+            .await
+            .map_err(|err| CognitionError(format!("HTTP request error: {}", err)))?;
+        Ok(())
+    }
+}
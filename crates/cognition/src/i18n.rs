@@ -0,0 +1,128 @@
+use crate::{CognitionError, Decision};
+use log::warn;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Node and choice text of the form `t:<key>` refers to a string in the bundles
+const KEY_PREFIX: &str = "t:";
+
+// Per-locale string bundles, loaded from `<locale>.yaml` files of flat or
+// nested keys, e.g. `greeting: { text: Hola, yes: Sí }` defines `greeting.text`
+// and `greeting.yes`
+#[derive(Clone, Debug, Default)]
+pub struct Localization {
+    bundles: BTreeMap<String, BTreeMap<String, String>>,
+    // Used for keys missing from the session's locale
+    pub default_locale: Option<String>,
+}
+
+impl Localization {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Every `<locale>.yaml` in a directory, e.g. `locales/es.yaml`
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self, CognitionError> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir)
+            .map_err(|err| CognitionError(format!("Failed to read {}: {}", dir.display(), err)))?;
+
+        let mut localization = Self::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(locale) = path
+                .file_stem()
+                .filter(|_| path.extension().is_some_and(|ext| ext == "yaml"))
+                .and_then(|stem| stem.to_str())
+            else {
+                continue;
+            };
+            let source = std::fs::read_to_string(&path).map_err(|err| {
+                CognitionError(format!("Failed to read {}: {}", path.display(), err))
+            })?;
+            localization.add_yaml(locale, &source)?;
+        }
+        Ok(localization)
+    }
+
+    pub fn add_yaml(&mut self, locale: &str, source: &str) -> Result<(), CognitionError> {
+        let value: Value = serde_yaml::from_str(source)
+            .map_err(|err| CognitionError(format!("Failed to parse {} bundle: {}", locale, err)))?;
+        let bundle = self.bundles.entry(locale.to_string()).or_default();
+        flatten("", &value, bundle);
+        Ok(())
+    }
+
+    pub fn locales(&self) -> Vec<&str> {
+        self.bundles.keys().map(String::as_str).collect()
+    }
+
+    // Whether there is a bundle for the locale or its language
+    pub fn supports(&self, locale: &str) -> bool {
+        let language = locale.split(['-', '_']).next().unwrap_or_default();
+        self.bundles.contains_key(locale) || self.bundles.contains_key(language)
+    }
+
+    // Look a key up in the locale, its language (`es` for `es-MX`), then the
+    // default locale
+    pub fn get(&self, locale: Option<&str>, key: &str) -> Option<&str> {
+        let language = locale.and_then(|locale| locale.split(['-', '_']).next());
+        [locale, language, self.default_locale.as_deref()]
+            .into_iter()
+            .flatten()
+            .find_map(|locale| self.bundles.get(locale)?.get(key))
+            .map(String::as_str)
+    }
+
+    // Text with its key resolved, unchanged if it is not a key. Missing keys
+    // are left as they are so the gap shows up in testing.
+    pub fn localize(&self, locale: Option<&str>, text: &str) -> String {
+        let Some(key) = text
+            .strip_prefix(KEY_PREFIX)
+            .filter(|key| !key.is_empty() && !key.contains(char::is_whitespace))
+        else {
+            return text.to_string();
+        };
+        match self.get(locale, key) {
+            Some(text) => text.to_string(),
+            None => {
+                warn!("No string for key {} in locale {:?}", key, locale);
+                text.to_string()
+            }
+        }
+    }
+
+    // Node with its text, choices and choice aliases localized
+    pub fn localize_node(&self, locale: Option<&str>, decision_node: &Decision) -> Decision {
+        let mut decision_node = decision_node.clone();
+        decision_node.text = self.localize(locale, &decision_node.text);
+        for choice in decision_node.choices.iter_mut().flatten() {
+            choice.text = self.localize(locale, &choice.text);
+            for alias in &mut choice.aliases {
+                *alias = self.localize(locale, alias);
+            }
+        }
+        decision_node
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, bundle: &mut BTreeMap<String, String>) {
+    let text = match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, bundle);
+            }
+            return;
+        }
+        Value::String(text) => text.clone(),
+        Value::Null => return,
+        value => value.to_string(),
+    };
+    bundle.insert(prefix.to_string(), text);
+}
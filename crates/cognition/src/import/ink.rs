@@ -0,0 +1,193 @@
+use super::{choice, node_id};
+use crate::{CognitionError, Decision, EXIT_ID, START_ID};
+
+// Choice of a node when a knot just moves on to another
+const CONTINUE: &str = "Continue";
+
+#[derive(Default)]
+struct Knot {
+    name: String,
+    text: Vec<String>,
+    choices: Vec<InkChoice>,
+    // Divert after the text when there are no choices
+    divert: Option<String>,
+}
+
+#[derive(Default)]
+struct InkChoice {
+    text: String,
+    // Lines between the choice and its divert
+    body: Vec<String>,
+    divert: Option<String>,
+}
+
+// Compile a subset of Ink to decision nodes: knots (`=== name ===`) become
+// nodes, choices (`* [text] -> target`, `+ text`) become choices, and diverts
+// (`-> target`, `-> END`, `-> DONE`) their transitions. Content before the first
+// knot is the `start` node, or just points to it with a lone divert. Text after
+// a choice becomes a node of its own, and choices without a divert end the
+// story. Stitches, gathers, nested choices, variables and logic are not
+// supported and their lines are skipped.
+pub fn from_ink(source: &str) -> Result<Vec<Decision>, CognitionError> {
+    let mut knots = vec![Knot {
+        name: START_ID.to_string(),
+        ..Knot::default()
+    }];
+
+    for line in source.lines() {
+        let line = strip_comment(line).trim();
+        let line = line.split(" #").next().unwrap_or_default().trim();
+        if line.is_empty() || line.starts_with('#') || is_skipped(line) {
+            continue;
+        }
+        let knot = knots.last_mut().unwrap();
+
+        if let Some(name) = knot_header(line) {
+            knots.push(Knot {
+                name,
+                ..Knot::default()
+            });
+        } else if let Some(content) = line.strip_prefix(['*', '+']) {
+            let content = content.trim_start_matches(['*', '+', ' ', '\t']);
+            // Labels, `* (greet) Hello`
+            let content = match content.strip_prefix('(') {
+                Some(labelled) => labelled.split_once(')').map_or(content, |(_, rest)| rest),
+                None => content,
+            };
+            let (content, divert) = split_divert(content);
+            knot.choices.push(InkChoice {
+                text: choice_text(content),
+                body: vec![],
+                divert,
+            });
+        } else if let Some(target) = line.strip_prefix("->") {
+            let target = Some(target.trim().to_string());
+            match knot.choices.last_mut() {
+                Some(choice) if choice.divert.is_none() => choice.divert = target,
+                Some(_) => {}
+                None => knot.divert = target,
+            }
+        } else {
+            let (text, divert) = split_divert(line);
+            match knot.choices.last_mut() {
+                Some(choice) => {
+                    choice.body.push(text.to_string());
+                    if divert.is_some() {
+                        choice.divert = divert;
+                    }
+                }
+                None => {
+                    knot.text.push(text.to_string());
+                    if divert.is_some() {
+                        knot.divert = divert;
+                    }
+                }
+            }
+        }
+    }
+
+    // A preamble that only diverts makes its target the start node
+    let mut start = START_ID.to_string();
+    if knots[0].text.is_empty() && knots[0].choices.is_empty() {
+        let preamble = knots.remove(0);
+        match preamble.divert {
+            Some(target) => start = target,
+            None if knots.is_empty() => {
+                return Err(CognitionError("No content in Ink story".to_string()))
+            }
+            None => start = knots[0].name.clone(),
+        }
+    }
+    let id = |name: &str| match name {
+        "END" | "DONE" => EXIT_ID.to_string(),
+        _ if name == start => START_ID.to_string(),
+        _ => node_id(name),
+    };
+
+    let mut nodes = vec![];
+    for knot in knots {
+        let knot_id = id(&knot.name);
+        let mut choices = vec![];
+        let mut branches = vec![];
+        for (index, ink_choice) in knot.choices.into_iter().enumerate() {
+            let target = ink_choice.divert.as_deref().map(id);
+            let body: Vec<String> = ink_choice
+                .body
+                .into_iter()
+                .filter(|line| !line.is_empty())
+                .collect();
+            if body.is_empty() {
+                choices.push(choice(
+                    &ink_choice.text,
+                    target.unwrap_or(EXIT_ID.to_string()),
+                ));
+                continue;
+            }
+
+            // Text after the choice, then on to its divert
+            let branch_id = format!("{}_{}", knot_id, index + 1);
+            choices.push(choice(&ink_choice.text, branch_id.clone()));
+            branches.push(Decision {
+                id: branch_id,
+                text: body.join("\n"),
+                choices: target.map(|target| vec![choice(CONTINUE, target)]),
+                ..Decision::default()
+            });
+        }
+        if choices.is_empty() {
+            if let Some(target) = &knot.divert {
+                choices.push(choice(CONTINUE, id(target)));
+            }
+        }
+
+        nodes.push(Decision {
+            id: knot_id,
+            text: knot.text.join("\n"),
+            choices: (!choices.is_empty()).then_some(choices),
+            ..Decision::default()
+        });
+        nodes.extend(branches);
+    }
+    Ok(nodes)
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split("//").next().unwrap_or_default()
+}
+
+// Logic, declarations and stitches this importer does not compile
+fn is_skipped(line: &str) -> bool {
+    [
+        "VAR ", "CONST ", "INCLUDE ", "LIST ", "~", "- ", "{", "/*", "*/",
+    ]
+    .iter()
+    .any(|prefix| line.starts_with(prefix))
+        || (line.starts_with('=') && !line.starts_with("=="))
+}
+
+// `=== name ===`, `== name` or `=== name(x) ===`
+fn knot_header(line: &str) -> Option<String> {
+    if !line.starts_with("==") {
+        return None;
+    }
+    let name = line.trim_matches('=').trim();
+    let name = name.split('(').next().unwrap_or_default().trim();
+    Some(name.to_string())
+}
+
+// Content and divert target of `content -> target`
+fn split_divert(line: &str) -> (&str, Option<String>) {
+    match line.split_once("->") {
+        Some((content, target)) => (content.trim(), Some(target.trim().to_string())),
+        None => (line.trim(), None),
+    }
+}
+
+// Choice text as shown to the player: `Hello [back!] he said` shows `Hello back!`
+fn choice_text(content: &str) -> String {
+    let shown = match content.find(']') {
+        Some(end) => &content[..end],
+        None => content,
+    };
+    shown.replace('[', "").trim().to_string()
+}
@@ -0,0 +1,164 @@
+use super::choice;
+use crate::{CognitionError, Decision, Transition, EXIT_ID, START_ID};
+use regex::Regex;
+use std::collections::BTreeMap;
+
+// Statements other than nodes and edges
+const KEYWORDS: &[&str] = &[
+    "flowchart",
+    "graph",
+    "subgraph",
+    "end",
+    "direction",
+    "style",
+    "linkStyle",
+    "classDef",
+    "class",
+    "click",
+];
+
+// Convert a Mermaid flowchart to decision nodes, best effort. Node labels
+// (`id[text]`, `id(text)`, `id{text}`...) become node text and edges become
+// choices labelled with the edge text (`A -->|text| B`, `A -- text --> B`),
+// or with the target's text when unlabelled. Edges labelled `next` move on
+// without a choice, dotted edges labelled `default` set the node's default
+// transition and other dotted edges call a subtree.
+// Unlabelled `exit` and `back` nodes are transitions rather than nodes, and
+// without a `start` node the first node is the start.
+pub fn from_mermaid(source: &str) -> Result<Vec<Decision>, CognitionError> {
+    let arrow = Regex::new(
+        r#"^\s*(?:--\s*([^>|]+?)\s*-->|-\.\s*([^>|]+?)\s*\.->|==\s*([^>|]+?)\s*==>|(-->|---|==>|-\.->|-\.-))\s*(?:\|"([^"]*)"\||\|([^|]*)\|)?\s*"#,
+    )
+    .unwrap();
+
+    // Node IDs in order of appearance, their labels and edges
+    let mut ids: Vec<String> = vec![];
+    let mut labels: BTreeMap<String, String> = BTreeMap::new();
+    let mut edges: Vec<(String, String, Option<String>, bool)> = vec![];
+
+    for line in source.lines() {
+        let line = line.trim();
+        let keyword = line.split_whitespace().next().unwrap_or_default();
+        if line.is_empty() || line.starts_with("%%") || KEYWORDS.contains(&keyword) {
+            continue;
+        }
+
+        let mut rest = line.trim_end_matches(';');
+        let mut previous: Option<String> = None;
+        let mut pending: Option<(Option<String>, bool)> = None;
+        while let Some((id, label, after)) = parse_node(rest) {
+            if !ids.contains(&id) {
+                ids.push(id.clone());
+            }
+            if let Some(label) = label {
+                labels.insert(id.clone(), label);
+            }
+            if let (Some(from), Some((text, dotted))) = (previous.take(), pending.take()) {
+                edges.push((from, id.clone(), text, dotted));
+            }
+            previous = Some(id);
+
+            let Some(captures) = arrow.captures(after) else {
+                break;
+            };
+            let text = [1, 2, 3, 5, 6]
+                .into_iter()
+                .find_map(|group| captures.get(group))
+                .map(|text| unescape(text.as_str().trim()));
+            let dotted = captures.get(2).is_some()
+                || captures
+                    .get(4)
+                    .is_some_and(|arrow| arrow.as_str().contains('.'));
+            pending = Some((text, dotted));
+            rest = &after[captures.get(0).unwrap().end()..];
+        }
+    }
+
+    if ids.is_empty() {
+        return Err(CognitionError("No nodes in Mermaid flowchart".to_string()));
+    }
+    let start = if ids.iter().any(|id| id == START_ID) {
+        START_ID.to_string()
+    } else {
+        ids[0].clone()
+    };
+    let transition = |id: &str| {
+        if id == start {
+            START_ID.to_string()
+        } else {
+            id.to_string()
+        }
+    };
+
+    let mut nodes = vec![];
+    let terminal = |id: &str| {
+        (id == EXIT_ID || id == "back") && labels.get(id).is_none_or(|label| label == id)
+    };
+    for id in ids.iter().filter(|id| !terminal(id)) {
+        let mut node = Decision {
+            id: transition(id),
+            text: labels.get(id).cloned().unwrap_or_else(|| id.clone()),
+            ..Decision::default()
+        };
+        for (_, to, text, dotted) in edges.iter().filter(|(from, ..)| from == id) {
+            match (text.as_deref(), dotted) {
+                (Some("next"), false) => node.next = Some(Transition::from(transition(to))),
+                (Some("default"), true) => {
+                    node.default_next_id = Some(Transition::from(transition(to)))
+                }
+                (text, dotted) => {
+                    let text = text
+                        .map(str::to_string)
+                        .or_else(|| labels.get(to).cloned())
+                        .unwrap_or_else(|| to.clone());
+                    let next_id = if *dotted {
+                        format!("call:{}", to)
+                    } else {
+                        transition(to)
+                    };
+                    node.choices
+                        .get_or_insert_with(Vec::new)
+                        .push(choice(&text, next_id));
+                }
+            }
+        }
+        nodes.push(node);
+    }
+    Ok(nodes)
+}
+
+// Node ID, its label if any, and the rest of the line
+fn parse_node(text: &str) -> Option<(String, Option<String>, &str)> {
+    let text = text.trim_start();
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    if end == 0 {
+        return None;
+    }
+    let (id, rest) = text.split_at(end);
+
+    let opening = rest
+        .find(|c: char| !"[({>/\\".contains(c))
+        .unwrap_or(rest.len());
+    if opening == 0 {
+        return Some((id.to_string(), None, rest));
+    }
+    let inner = &rest[opening..];
+    let (label, after) = match inner.strip_prefix('"') {
+        Some(quoted) => {
+            let close = quoted.find('"')?;
+            (&quoted[..close], &quoted[close + 1..])
+        }
+        None => {
+            let close = inner.find([']', ')', '}', '/', '\\'])?;
+            (&inner[..close], &inner[close..])
+        }
+    };
+    let after = after.trim_start_matches([']', ')', '}', '/', '\\']);
+    Some((id.to_string(), Some(unescape(label.trim())), after))
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("#quot;", "\"").replace("<br>", "\n")
+}
@@ -0,0 +1,25 @@
+// Converters from other story and dialogue formats to decision nodes. Imported
+// trees are not validated, run `validate_tree` on the result.
+pub mod ink;
+pub mod mermaid;
+pub mod twine;
+
+use crate::{Choice, Transition};
+
+// Node ID for a passage or knot name: lowercase, with runs of anything but
+// letters and digits replaced by `_`
+fn node_id(name: &str) -> String {
+    let mut id = String::new();
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() {
+            id.extend(c.to_lowercase());
+        } else if !id.ends_with('_') {
+            id.push('_');
+        }
+    }
+    id.trim_matches('_').to_string()
+}
+
+fn choice(text: &str, next_id: String) -> Choice {
+    Choice::new(text.trim(), Transition::from(next_id))
+}
@@ -0,0 +1,143 @@
+use super::{choice, node_id};
+use crate::{CognitionError, Decision, START_ID};
+use regex::Regex;
+use serde_json::Value;
+
+// Passages that hold story metadata, scripts or styles rather than story text
+const SPECIAL_PASSAGES: &[&str] = &["StoryTitle", "StoryData"];
+const SPECIAL_TAGS: &[&str] = &["script", "stylesheet"];
+
+struct Passage {
+    name: String,
+    tags: Vec<String>,
+    body: String,
+}
+
+// Convert a Twee 3 story to decision nodes: passages become nodes and their
+// links, `[[text->target]]`, `[[target<-text]]`, `[[text|target]]`, `[[target]]`
+// and Harlowe's `(link-goto: "text", "target")`, become choices. The start
+// passage, from `StoryData` or named `Start`, becomes the `start` node.
+pub fn from_twee(source: &str) -> Result<Vec<Decision>, CognitionError> {
+    let passages = passages(source);
+    if passages.is_empty() {
+        return Err(CognitionError("No passages in Twee story".to_string()));
+    }
+
+    let start = passages
+        .iter()
+        .find(|passage| passage.name == "StoryData")
+        .and_then(|passage| serde_json::from_str::<Value>(&passage.body).ok())
+        .and_then(|data| data["start"].as_str().map(str::to_string))
+        .unwrap_or_else(|| "Start".to_string());
+    let id = |name: &str| {
+        if name == start {
+            START_ID.to_string()
+        } else {
+            node_id(name)
+        }
+    };
+
+    let link = Regex::new(r"\[\[(.+?)\]\]").unwrap();
+    let link_goto = Regex::new(r#"\(link-goto:\s*"([^"]*)"\s*(?:,\s*"([^"]*)"\s*)?\)"#).unwrap();
+
+    let mut nodes = vec![];
+    for passage in &passages {
+        if SPECIAL_PASSAGES.contains(&passage.name.as_str())
+            || passage
+                .tags
+                .iter()
+                .any(|tag| SPECIAL_TAGS.contains(&tag.as_str()))
+        {
+            continue;
+        }
+
+        // Links in the order they appear
+        let mut links = vec![];
+        for captures in link.captures_iter(&passage.body) {
+            let (text, target) = parse_link(captures.get(1).unwrap().as_str());
+            links.push((captures.get(0).unwrap().start(), text, target));
+        }
+        for captures in link_goto.captures_iter(&passage.body) {
+            let text = captures.get(1).unwrap().as_str();
+            let target = captures.get(2).map_or(text, |target| target.as_str());
+            links.push((captures.get(0).unwrap().start(), text, target));
+        }
+        links.sort_by_key(|(start, _, _)| *start);
+        let mut choices: Vec<_> = links
+            .into_iter()
+            .map(|(_, text, target)| choice(text, id(target)))
+            .collect();
+        choices.dedup_by(|a, b| a.text == b.text);
+
+        let text = link.replace_all(&passage.body, "");
+        let text = link_goto.replace_all(&text, "");
+        let text = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        nodes.push(Decision {
+            id: id(&passage.name),
+            text,
+            choices: (!choices.is_empty()).then_some(choices),
+            ..Decision::default()
+        });
+    }
+    Ok(nodes)
+}
+
+// Text and target passage of the inside of a `[[...]]` link
+fn parse_link(link: &str) -> (&str, &str) {
+    if let Some((text, target)) = link.rsplit_once("->") {
+        (text, target)
+    } else if let Some((target, text)) = link.split_once("<-") {
+        (text, target)
+    } else if let Some((text, target)) = link.split_once('|') {
+        (text, target)
+    } else {
+        (link, link)
+    }
+}
+
+fn passages(source: &str) -> Vec<Passage> {
+    let mut passages: Vec<Passage> = vec![];
+    for line in source.lines() {
+        match line.strip_prefix("::") {
+            Some(header) => passages.push(parse_header(header)),
+            None => {
+                if let Some(passage) = passages.last_mut() {
+                    passage.body.push_str(line);
+                    passage.body.push('\n');
+                }
+            }
+        }
+    }
+    passages
+}
+
+// `Name [tag1 tag2] {"position":"100,100"}`
+fn parse_header(header: &str) -> Passage {
+    let mut header = header.trim();
+    if header.ends_with('}') {
+        if let Some(start) = header.rfind('{') {
+            header = header[..start].trim_end();
+        }
+    }
+    let mut tags = vec![];
+    if header.ends_with(']') {
+        if let Some(start) = header.rfind('[') {
+            tags = header[start + 1..header.len() - 1]
+                .split_whitespace()
+                .map(str::to_string)
+                .collect();
+            header = header[..start].trim_end();
+        }
+    }
+    Passage {
+        name: header.to_string(),
+        tags,
+        body: String::new(),
+    }
+}
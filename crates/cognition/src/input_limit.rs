@@ -0,0 +1,31 @@
+// Answers over a node's input limit, cut down before they reach the history so
+// a pasted document does not eat the prompt budget
+
+// Whether `text` is longer than `max_chars` characters
+pub fn exceeds(text: &str, max_chars: usize) -> bool {
+    text.chars().count() > max_chars
+}
+
+// The last `max_chars` characters, marked as cut
+pub fn truncate_head(text: &str, max_chars: usize) -> String {
+    let count = text.chars().count();
+    let kept: String = text
+        .chars()
+        .skip((count + 1).saturating_sub(max_chars.max(1)))
+        .collect();
+    format!("…{}", kept.trim_start())
+}
+
+// The first `max_chars` characters, marked as cut
+pub fn truncate_tail(text: &str, max_chars: usize) -> String {
+    let kept: String = text.chars().take(max_chars.max(1) - 1).collect();
+    format!("{}…", kept.trim_end())
+}
+
+pub fn summary_prompt(text: &str, max_chars: usize) -> String {
+    format!(
+        "Summarize the user's message in under {} characters, keeping names, numbers \
+         and what they ask for. Respond with the summary only.\n\nMessage: {}\nSummary:",
+        max_chars, text
+    )
+}
@@ -0,0 +1,60 @@
+// Global jumps out of any node, e.g. to a human agent or to cancel, from the
+// config's `interrupts` list
+use crate::CognitionError;
+use cognition_core::Transition;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Interrupt {
+    // Offered to the model next to a node's choices, e.g. `Talk to a human`
+    pub intent: String,
+    // Words or phrases triggering the interrupt without the model
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    pub next_id: Transition,
+}
+
+impl Interrupt {
+    pub fn from_config(interrupts: &Value) -> Result<Vec<Self>, CognitionError> {
+        serde_json::from_value(interrupts.clone())
+            .map_err(|err| CognitionError(format!("Invalid interrupts: {}", err)))
+    }
+
+    // Whether an answer is the intent itself or contains one of the keywords
+    // as whole words
+    pub fn matches(&self, answer: &str) -> bool {
+        let answer = words(answer);
+        !answer.is_empty()
+            && (words(&self.intent) == answer
+                || self.keywords.iter().any(|keyword| {
+                    let keyword = words(keyword);
+                    !keyword.is_empty()
+                        && answer
+                            .windows(keyword.len())
+                            .any(|window| window == keyword)
+                }))
+    }
+}
+
+// The only interrupt an answer triggers without the model, if any
+pub(crate) fn keyword_interrupt<'a>(
+    interrupts: &'a [Interrupt],
+    answer: &str,
+) -> Option<&'a Interrupt> {
+    match interrupts
+        .iter()
+        .filter(|interrupt| interrupt.matches(answer))
+        .collect::<Vec<_>>()[..]
+    {
+        [interrupt] => Some(interrupt),
+        _ => None,
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
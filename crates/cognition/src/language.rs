@@ -0,0 +1,143 @@
+// Rough language identification, used to catch a model drifting out of the
+// session's output language. Non-Latin languages are recognized by script,
+// Latin ones by common stopwords.
+
+// (code, name, script or stopwords)
+const LANGUAGES: &[(&str, &str, Detect)] = &[
+    (
+        "en",
+        "english",
+        Detect::Words(&["the", "is", "and", "you", "to", "of", "it"]),
+    ),
+    (
+        "es",
+        "spanish",
+        Detect::Words(&["el", "la", "es", "y", "que", "de", "los", "usted"]),
+    ),
+    (
+        "fr",
+        "french",
+        Detect::Words(&["le", "la", "est", "et", "vous", "les", "des", "une"]),
+    ),
+    (
+        "de",
+        "german",
+        Detect::Words(&["der", "die", "das", "ist", "und", "sie", "nicht", "ein"]),
+    ),
+    (
+        "it",
+        "italian",
+        Detect::Words(&["il", "la", "è", "e", "che", "di", "non", "per"]),
+    ),
+    (
+        "pt",
+        "portuguese",
+        Detect::Words(&["o", "a", "é", "e", "que", "não", "você", "os"]),
+    ),
+    ("ru", "russian", Detect::Script(Script::Cyrillic)),
+    ("uk", "ukrainian", Detect::Script(Script::Cyrillic)),
+    ("el", "greek", Detect::Script(Script::Greek)),
+    ("ar", "arabic", Detect::Script(Script::Arabic)),
+    ("he", "hebrew", Detect::Script(Script::Hebrew)),
+    ("hi", "hindi", Detect::Script(Script::Devanagari)),
+    ("ja", "japanese", Detect::Script(Script::Japanese)),
+    ("zh", "chinese", Detect::Script(Script::Han)),
+    ("ko", "korean", Detect::Script(Script::Hangul)),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Japanese,
+    Han,
+    Hangul,
+}
+
+enum Detect {
+    Script(Script),
+    Words(&'static [&'static str]),
+}
+
+fn script(c: char) -> Option<Script> {
+    match c as u32 {
+        0x0041..=0x024F if c.is_alphabetic() => Some(Script::Latin),
+        0x0370..=0x03FF => Some(Script::Greek),
+        0x0400..=0x04FF => Some(Script::Cyrillic),
+        0x0590..=0x05FF => Some(Script::Hebrew),
+        0x0600..=0x06FF => Some(Script::Arabic),
+        0x0900..=0x097F => Some(Script::Devanagari),
+        0x3040..=0x30FF => Some(Script::Japanese),
+        0x4E00..=0x9FFF => Some(Script::Han),
+        0xAC00..=0xD7AF => Some(Script::Hangul),
+        _ => None,
+    }
+}
+
+// Most frequent script in the text
+fn dominant_script(text: &str) -> Option<Script> {
+    let mut counts: Vec<(Script, usize)> = vec![];
+    for script in text.chars().filter_map(script) {
+        match counts.iter_mut().find(|(s, _)| *s == script) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((script, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(script, _)| script)
+}
+
+// Stopword hits for a Latin language
+fn word_score(text: &str, words: &[&str]) -> usize {
+    text.split(|c: char| !c.is_alphabetic())
+        .filter(|word| words.contains(&word.to_lowercase().as_str()))
+        .count()
+}
+
+// Returns false only when the text is clearly not in `language`. Unknown
+// languages and text too short to judge are accepted.
+pub fn matches_language(text: &str, language: &str) -> bool {
+    let language = language.trim().to_lowercase();
+    let detect = match LANGUAGES
+        .iter()
+        .find(|(code, name, _)| *code == language || *name == language)
+    {
+        Some((_, _, detect)) => detect,
+        None => return true,
+    };
+
+    let dominant = match dominant_script(text) {
+        Some(script) => script,
+        None => return true,
+    };
+
+    match detect {
+        // Japanese text mixes kana with Han characters
+        Detect::Script(Script::Japanese) => dominant == Script::Japanese || dominant == Script::Han,
+        Detect::Script(expected) => dominant == *expected,
+        Detect::Words(words) => {
+            if dominant != Script::Latin {
+                return false;
+            }
+            // The expected language must score at least as well as any other
+            let expected = word_score(text, words);
+            let best_other = LANGUAGES
+                .iter()
+                .filter_map(|(_, _, detect)| match detect {
+                    Detect::Words(other) if !std::ptr::eq(*other, *words) => {
+                        Some(word_score(text, other))
+                    }
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0);
+            expected >= best_other
+        }
+    }
+}
@@ -1,12 +1,101 @@
+mod analytics;
+mod answer;
+mod clock;
+mod compression;
 mod config;
+mod context_pack;
+mod diff;
+mod embeddings;
 mod engine;
-mod models;
-mod templates;
+mod eval;
+pub mod export;
+pub mod guardrails;
+mod history;
+pub mod http_client;
+mod i18n;
+pub mod import;
+mod input_limit;
+mod interrupts;
+mod language;
+pub mod log_sinks;
+pub mod metrics;
+pub mod models;
+mod observer;
+mod persona;
+pub mod plugins;
+mod privacy;
+mod redaction;
+#[cfg(feature = "schema")]
+mod schema;
+mod scripting;
+mod session;
+#[cfg(feature = "speech")]
+pub mod speech;
+pub mod storage;
+mod store;
+mod stream;
+mod suggestions;
+mod template_test;
+pub mod templates;
 pub mod tools;
+mod transcript;
+mod translation;
+mod tree;
+mod tree_stats;
+mod turn_log;
+mod usage;
+pub mod webhook;
 
-pub use engine::{run_decision, Decision, DecisionResult, DecisionState};
-pub use templates::decision::DecisionPromptTemplate;
-pub use tools::{Tool, ToolResponse};
+pub use analytics::{Funnel, FunnelStep, NodeStats, SessionAnalytics};
+pub use cognition_core::{
+    condition, decision_tree, embedded_tree, render_variables, validate_tree, AnalyticsEvent,
+    Choice, ChoicesMode, Compression, Decision, DecisionPromptTemplate, ExpressionError, InputKind,
+    InputLimit, InputLimitStrategy, NodeKind, PipelineStep, PredictionPolicy, StepFailure,
+    Transition, Tree, TreeBuilder, TreeError, EXIT_ID, START_ID,
+};
+#[doc(hidden)]
+pub use cognition_macros::tree_json as __tree_json;
+pub use context_pack::ContextPack;
+pub use diff::{TurnDiff, VariableChange};
+pub use engine::{
+    apply_transition, infer_choice, prepare_prompt, run_decision, run_decision_with_cancel,
+    run_idle, run_tools, DecisionResult, DecisionState, Inference, NodeVisits, PreparedPrompt,
+    SessionSnapshot,
+};
+pub use eval::{evaluate, EvalCase, EvalMiss, EvalReport};
+pub use guardrails::{Guardrail, GuardrailStage, Violation};
+pub use history::{History, HistoryEntry, HistoryStyle, Role};
+pub use http_client::HttpClient;
+pub use i18n::Localization;
+pub use interrupts::Interrupt;
+pub use log_sinks::LogSink;
+pub use observer::{DecisionEvent, Observer, SessionOutcome};
+pub use persona::Persona;
+pub use privacy::PromptPrivacy;
+pub use redaction::Redactor;
+#[cfg(feature = "schema")]
+pub use schema::{schema, SchemaKind};
+pub use session::{ConcurrencyPolicy, Session, SessionError};
+#[cfg(feature = "redis")]
+pub use store::RedisStore;
+pub use store::{
+    FileStore, InvocationStatus, MemoryStore, SessionFilter, SessionRecord, SessionStore,
+    ToolInvocation,
+};
+pub use stream::decision_stream;
+pub use suggestions::{
+    apply_suggestions, suggest_improvements, tree_diff, ChoiceGroup, SuggestionOptions,
+    TreeSuggestion,
+};
+pub use template_test::{test_template, TemplateFailure, TemplateFixture};
+pub use templates::TemplateProvider;
+pub use tokio_util::sync::CancellationToken;
+pub use tools::{Tool, ToolOutcome, ToolProgress, ToolResponse};
+pub use transcript::{replay, Transcript};
+pub use tree::{DecisionTree, NodeId};
+pub use tree_stats::{NodeInfo, TreeStats};
+pub use turn_log::TurnRecord;
+pub use usage::{Pricing, Usage};
 
 #[derive(Debug)]
 pub struct CognitionError(pub String);
@@ -16,3 +105,18 @@ impl std::fmt::Display for CognitionError {
         write!(f, "Cognition error: {}", self.0)
     }
 }
+
+// Start of the message of errors made by `CognitionError::loop_detected`
+const LOOP_DETECTED: &str = "Loop detected: ";
+
+impl CognitionError {
+    // A session past one of its `limits` without a fallback node
+    pub fn loop_detected(reason: &str) -> Self {
+        CognitionError(format!("{}{}", LOOP_DETECTED, reason))
+    }
+
+    // Whether the turn failed on one of the session's `limits`
+    pub fn is_loop_detected(&self) -> bool {
+        self.0.starts_with(LOOP_DETECTED)
+    }
+}
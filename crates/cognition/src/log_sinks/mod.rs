@@ -0,0 +1,37 @@
+use crate::{config::object_by_path, turn_log::TurnRecord, CognitionError};
+use async_trait::async_trait;
+
+#[cfg(feature = "otlp")]
+pub use otlp::OtlpLogs;
+pub use syslog::Syslog;
+
+#[cfg(feature = "otlp")]
+mod otlp;
+mod syslog;
+
+// External log system receiving every completed turn as it happens, next to
+// the `logging.turns` file and independent of the session store. Records have
+// `privacy.prompts` applied. A failing sink is logged and never fails the turn.
+#[async_trait(?Send)]
+pub trait LogSink {
+    fn id(&self) -> &String;
+
+    async fn send(&self, record: &TurnRecord) -> Result<(), CognitionError>;
+}
+
+// Sinks configured under `logging`, e.g. `logging.syslog.address`
+pub(crate) fn from_config(config: &str) -> Result<Vec<Box<dyn LogSink>>, CognitionError> {
+    let mut sinks: Vec<Box<dyn LogSink>> = vec![];
+    if object_by_path(config, "logging.syslog").is_some() {
+        sinks.push(Box::new(Syslog::new(config)?));
+    }
+    if object_by_path(config, "logging.otlp").is_some() {
+        #[cfg(feature = "otlp")]
+        sinks.push(Box::new(OtlpLogs::new(config)?));
+        #[cfg(not(feature = "otlp"))]
+        return Err(CognitionError(
+            "logging.otlp needs the `otlp` feature".to_string(),
+        ));
+    }
+    Ok(sinks)
+}
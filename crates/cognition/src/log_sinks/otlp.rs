@@ -0,0 +1,98 @@
+use super::*;
+use crate::config::string_by_path;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const DEFAULT_ENDPOINT: &str = "http://localhost:4318";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// OpenTelemetry logs over OTLP/HTTP JSON, posted to `<endpoint>/v1/logs` from
+// `logging.otlp.endpoint`, with `logging.otlp.headers` such as an API key and
+// `logging.otlp.service_name` as the resource's `service.name`
+pub struct OtlpLogs {
+    pub id: String,
+    client: Client,
+    endpoint: String,
+    service_name: String,
+}
+
+impl OtlpLogs {
+    pub fn new(config: &str) -> Result<Self, CognitionError> {
+        let endpoint = string_by_path(config, "logging.otlp.endpoint")
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string());
+        let service_name = string_by_path(config, "logging.otlp.service_name")
+            .unwrap_or_else(|| "cognition".to_string());
+
+        let mut builder = Client::builder().timeout(TIMEOUT);
+        if let Some(headers) = object_by_path(config, "logging.otlp.headers") {
+            let headers: std::collections::HashMap<String, String> =
+                serde_json::from_value(headers).map_err(|err| {
+                    CognitionError(format!("Invalid logging.otlp.headers: {}", err))
+                })?;
+            builder =
+                builder.default_headers((&headers).try_into().map_err(|err| {
+                    CognitionError(format!("Invalid logging.otlp.headers: {}", err))
+                })?);
+        }
+        let client = builder
+            .build()
+            .map_err(|err| CognitionError(format!("HTTP client error: {}", err)))?;
+
+        Ok(Self {
+            id: "otlp".to_string(),
+            client,
+            endpoint,
+            service_name,
+        })
+    }
+}
+
+fn string_value(value: &str) -> Value {
+    json!({ "stringValue": value })
+}
+
+#[async_trait(?Send)]
+impl LogSink for OtlpLogs {
+    fn id(&self) -> &String {
+        &self.id
+    }
+
+    async fn send(&self, record: &TurnRecord) -> Result<(), CognitionError> {
+        let body = serde_json::to_string(record)
+            .map_err(|err| CognitionError(format!("Failed to serialize turn: {}", err)))?;
+        let request = json!({
+            "resourceLogs": [{
+                "resource": {
+                    "attributes": [
+                        { "key": "service.name", "value": string_value(&self.service_name) },
+                    ],
+                },
+                "scopeLogs": [{
+                    "scope": { "name": "cognition" },
+                    "logRecords": [{
+                        "timeUnixNano": (record.timestamp as u128 * 1_000_000_000).to_string(),
+                        "severityNumber": 9,
+                        "severityText": "INFO",
+                        "body": string_value(&body),
+                        "attributes": [
+                            { "key": "session.id", "value": string_value(&record.session_id) },
+                            { "key": "turn", "value": { "intValue": record.turn.to_string() } },
+                            { "key": "node.id", "value": string_value(&record.node_id) },
+                            { "key": "next.id", "value": string_value(&record.next_id) },
+                        ],
+                    }],
+                }],
+            }],
+        });
+        self.client
+            .post(format!("{}/v1/logs", self.endpoint))
+            .json(&request)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|err| CognitionError(format!("Failed to send to OTLP: {}", err)))
+    }
+}
@@ -0,0 +1,63 @@
+use super::*;
+use crate::config::{f64_by_path, string_by_path};
+use std::net::UdpSocket;
+
+// Local use 0
+const DEFAULT_FACILITY: u8 = 16;
+// Informational
+const SEVERITY: u8 = 6;
+
+// RFC 5424 messages over UDP to `logging.syslog.address`, like
+// `127.0.0.1:514`, with the turn as JSON. The facility is `logging.syslog.facility`,
+// 16 (local0) by default. Timestamp and host are left to the receiver.
+pub struct Syslog {
+    pub id: String,
+    socket: UdpSocket,
+    address: String,
+    facility: u8,
+}
+
+impl Syslog {
+    pub fn new(config: &str) -> Result<Self, CognitionError> {
+        let address = string_by_path(config, "logging.syslog.address")
+            .ok_or_else(|| CognitionError("Missing logging.syslog.address".to_string()))?;
+        let facility = f64_by_path(config, "logging.syslog.facility")
+            .map(|facility| facility as u8)
+            .unwrap_or(DEFAULT_FACILITY);
+        if facility > 23 {
+            return Err(CognitionError(format!(
+                "Invalid syslog facility: {}",
+                facility
+            )));
+        }
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|err| CognitionError(format!("Failed to open syslog socket: {}", err)))?;
+        Ok(Self {
+            id: "syslog".to_string(),
+            socket,
+            address,
+            facility,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl LogSink for Syslog {
+    fn id(&self) -> &String {
+        &self.id
+    }
+
+    async fn send(&self, record: &TurnRecord) -> Result<(), CognitionError> {
+        let json = serde_json::to_string(record)
+            .map_err(|err| CognitionError(format!("Failed to serialize turn: {}", err)))?;
+        let message = format!(
+            "<{}>1 - - cognition - turn - {}",
+            self.facility * 8 + SEVERITY,
+            json
+        );
+        self.socket
+            .send_to(message.as_bytes(), &self.address)
+            .map(|_| ())
+            .map_err(|err| CognitionError(format!("Failed to send to syslog: {}", err)))
+    }
+}
@@ -0,0 +1,160 @@
+// Engine metrics in the Prometheus text format. Without the `metrics` feature
+// recording is a no-op and `gather` returns an empty string.
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+mod prometheus_metrics {
+    use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+    use std::sync::OnceLock;
+
+    pub struct Metrics {
+        pub registry: Registry,
+        pub node_entries: IntCounterVec,
+        pub choice_match_failures: IntCounterVec,
+        pub choice_fast_matches: IntCounterVec,
+        pub model_latency: HistogramVec,
+        pub model_tokens: IntCounterVec,
+        pub tool_latency: HistogramVec,
+        pub tool_errors: IntCounterVec,
+    }
+
+    fn counter(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+        let counter = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        counter
+    }
+
+    fn histogram(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> HistogramVec {
+        let histogram = HistogramVec::new(HistogramOpts::new(name, help), labels).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        histogram
+    }
+
+    pub fn metrics() -> &'static Metrics {
+        static METRICS: OnceLock<Metrics> = OnceLock::new();
+        METRICS.get_or_init(|| {
+            let registry = Registry::new();
+            Metrics {
+                node_entries: counter(
+                    &registry,
+                    "cognition_node_entries_total",
+                    "Decision nodes entered",
+                    &["node"],
+                ),
+                choice_match_failures: counter(
+                    &registry,
+                    "cognition_choice_match_failures_total",
+                    "User answers that matched none of the node's choices",
+                    &["node"],
+                ),
+                choice_fast_matches: counter(
+                    &registry,
+                    "cognition_choice_fast_matches_total",
+                    "User answers matched to a choice without a model call",
+                    &["node"],
+                ),
+                model_latency: histogram(
+                    &registry,
+                    "cognition_model_latency_seconds",
+                    "Model call latency",
+                    &["outcome"],
+                ),
+                model_tokens: counter(
+                    &registry,
+                    "cognition_model_tokens_total",
+                    "Estimated tokens sent to and generated by the model",
+                    &["kind"],
+                ),
+                tool_latency: histogram(
+                    &registry,
+                    "cognition_tool_latency_seconds",
+                    "Tool call latency",
+                    &["tool"],
+                ),
+                tool_errors: counter(
+                    &registry,
+                    "cognition_tool_errors_total",
+                    "Failed tool calls",
+                    &["tool"],
+                ),
+                registry,
+            }
+        })
+    }
+}
+
+#[cfg(feature = "metrics")]
+use prometheus_metrics::metrics;
+
+pub(crate) fn node_entered(_node_id: &str) {
+    #[cfg(feature = "metrics")]
+    metrics().node_entries.with_label_values(&[_node_id]).inc();
+}
+
+pub(crate) fn choice_unmatched(_node_id: &str) {
+    #[cfg(feature = "metrics")]
+    metrics()
+        .choice_match_failures
+        .with_label_values(&[_node_id])
+        .inc();
+}
+
+pub(crate) fn choice_fast_matched(_node_id: &str) {
+    #[cfg(feature = "metrics")]
+    metrics()
+        .choice_fast_matches
+        .with_label_values(&[_node_id])
+        .inc();
+}
+
+// A model call, with the estimated prompt and completion tokens if it succeeded
+pub(crate) fn model_called(_latency: Duration, _tokens: Option<(usize, usize)>) {
+    #[cfg(feature = "metrics")]
+    {
+        let metrics = metrics();
+        let outcome = if _tokens.is_some() { "ok" } else { "error" };
+        metrics
+            .model_latency
+            .with_label_values(&[outcome])
+            .observe(_latency.as_secs_f64());
+        if let Some((prompt, completion)) = _tokens {
+            metrics
+                .model_tokens
+                .with_label_values(&["prompt"])
+                .inc_by(prompt as u64);
+            metrics
+                .model_tokens
+                .with_label_values(&["completion"])
+                .inc_by(completion as u64);
+        }
+    }
+}
+
+pub(crate) fn tool_called(_tool_id: &str, _latency: Duration, _ok: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        let metrics = metrics();
+        metrics
+            .tool_latency
+            .with_label_values(&[_tool_id])
+            .observe(_latency.as_secs_f64());
+        if !_ok {
+            metrics.tool_errors.with_label_values(&[_tool_id]).inc();
+        }
+    }
+}
+
+// All metrics in the Prometheus text exposition format
+pub fn gather() -> String {
+    #[cfg(feature = "metrics")]
+    {
+        use prometheus::{Encoder, TextEncoder};
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&metrics().registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+    #[cfg(not(feature = "metrics"))]
+    String::new()
+}
@@ -1,6 +1,6 @@
 use crate::{
     config::string_by_path,
-    models::{InferenceResult, LargeLanguageModel, ModelError},
+    models::{http, InferenceResult, LargeLanguageModel, ModelError},
 };
 use async_trait::async_trait;
 use reqwest::{
@@ -10,9 +10,12 @@ use reqwest::{
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
 pub struct Davinci003 {
     client: Client,
     api_key: String,
+    base_url: String,
 }
 
 #[derive(Serialize)]
@@ -25,6 +28,7 @@ struct OpenAIRequestBody<'a> {
     top_p: f32,
     frequency_penalty: f32,
     presence_penalty: f32,
+    logprobs: usize,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -46,18 +50,23 @@ struct OpenAIChoice {
 
 #[derive(Serialize, Deserialize)]
 struct OpenAILogprobs {
-    top_logprobs: HashMap<String, f64>,
+    tokens: Vec<String>,
+    token_logprobs: Vec<Option<f64>>,
+    top_logprobs: Option<Vec<HashMap<String, f64>>>,
     text_offset: Vec<usize>,
 }
 
 #[async_trait(?Send)]
 impl LargeLanguageModel for Davinci003 {
     fn new(config: &String) -> Result<Self, ModelError> {
-        let client = Client::new();
+        let client = http::client(config, "davinci003")?;
         let api_key = string_by_path(config, "models.davinci003.api_key").unwrap();
+        let base_url =
+            http::base_url(config, "davinci003").unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
         Ok(Self {
             client,
             api_key: api_key.to_string(),
+            base_url,
         })
     }
 
@@ -84,11 +93,12 @@ impl LargeLanguageModel for Davinci003 {
             top_p: 1.0,
             frequency_penalty: 0.0,
             presence_penalty: 0.0,
+            logprobs: 1,
         };
 
         let response = self
             .client
-            .post("https://api.openai.com/v1/completions")
+            .post(format!("{}/completions", self.base_url))
             .headers(headers)
             .json(&request_body)
             .send()
@@ -100,13 +110,41 @@ impl LargeLanguageModel for Davinci003 {
 
         let choice = response
             .choices
-            .get(0)
+            .first()
             .ok_or_else(|| ModelError::new("No choices found"))?;
+        // Probability of each sampled token
+        let probabilities = choice
+            .logprobs
+            .as_ref()
+            .map(|logprobs| {
+                logprobs
+                    .token_logprobs
+                    .iter()
+                    .flatten()
+                    .map(|logprob| logprob.exp() as f32)
+                    .collect()
+            })
+            .unwrap_or_default();
         let result = InferenceResult {
             text: choice.text.clone(),
-            probabilities: vec![], // You may want to calculate probabilities based on your requirements
+            probabilities,
         };
 
         Ok(result)
     }
+
+    async fn warmup(&self, _texts: &[String], prime: bool) -> Result<(), ModelError> {
+        // Open a pooled HTTPS connection to the API
+        self.client
+            .get(format!("{}/models", self.base_url))
+            .bearer_auth(&self.api_key)
+            .send()
+            .await
+            .map_err(|e| ModelError::new(&format!("HTTP request error: {}", e)))?;
+
+        if prime {
+            self.generate(".", 1, 0.0).await?;
+        }
+        Ok(())
+    }
 }
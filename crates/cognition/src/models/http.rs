@@ -0,0 +1,56 @@
+use crate::{
+    config::{object_by_path, string_by_path},
+    models::ModelError,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, Proxy,
+};
+
+// Setting for `model`, `models.<model>.<key>` first, then the shared `http.<key>`
+fn setting(config: &str, model: &str, key: &str) -> Option<serde_json::Value> {
+    object_by_path(config, &format!("models.{}.{}", model, key))
+        .or_else(|| object_by_path(config, &format!("http.{}", key)))
+}
+
+// HTTP client for a backend, with the configured `proxy` and extra `headers`
+pub fn client(config: &str, model: &str) -> Result<Client, ModelError> {
+    let mut builder = Client::builder();
+
+    if let Some(proxy) = setting(config, model, "proxy") {
+        let proxy = proxy
+            .as_str()
+            .ok_or_else(|| ModelError::new("Proxy must be a URL"))?;
+        builder = builder
+            .proxy(Proxy::all(proxy).map_err(|e| ModelError::new(&format!("Proxy error: {}", e)))?);
+    }
+
+    if let Some(headers) = setting(config, model, "headers") {
+        let headers = headers
+            .as_object()
+            .ok_or_else(|| ModelError::new("Headers must be a map of name to value"))?;
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            let value = value
+                .as_str()
+                .ok_or_else(|| ModelError::new(&format!("Header {} must be a string", name)))?;
+            header_map.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| ModelError::new(&format!("Header name error: {}", e)))?,
+                HeaderValue::from_str(value)
+                    .map_err(|e| ModelError::new(&format!("Header value error: {}", e)))?,
+            );
+        }
+        builder = builder.default_headers(header_map);
+    }
+
+    builder
+        .build()
+        .map_err(|e| ModelError::new(&format!("HTTP client error: {}", e)))
+}
+
+// Backend base URL from `models.<model>.base_url`, without a trailing slash
+pub fn base_url(config: &str, model: &str) -> Option<String> {
+    string_by_path(config, &format!("models.{}.base_url", model))
+        .map(|url| url.trim_end_matches('/').to_string())
+}
@@ -0,0 +1,131 @@
+use crate::{
+    config::string_by_path,
+    models::{InferenceResult, LargeLanguageModel, ModelError},
+};
+use async_trait::async_trait;
+use candle_core::{quantized::gguf_file, Device, Tensor};
+use candle_transformers::{generation::LogitsProcessor, models::quantized_llama::ModelWeights};
+use std::{fs::File, sync::Mutex};
+use tokenizers::Tokenizer;
+
+// LLaMA-family model loaded from a local GGUF file and run in-process, for
+// offline and air-gapped deployments
+pub struct LocalLlm {
+    // Generation mutates the model's KV cache
+    model: Mutex<ModelWeights>,
+    tokenizer: Tokenizer,
+    device: Device,
+    eos_token: Option<u32>,
+}
+
+fn model_error(context: &str, err: impl std::fmt::Display) -> ModelError {
+    ModelError::new(&format!("{}: {}", context, err))
+}
+
+#[async_trait(?Send)]
+impl LargeLanguageModel for LocalLlm {
+    fn new(config: &String) -> Result<Self, ModelError> {
+        let model_path = string_by_path(config, "models.local_llm.model_path")
+            .ok_or_else(|| ModelError::new("Missing models.local_llm.model_path"))?;
+        let tokenizer_path = string_by_path(config, "models.local_llm.tokenizer_path")
+            .ok_or_else(|| ModelError::new("Missing models.local_llm.tokenizer_path"))?;
+        let device = Device::Cpu;
+
+        let mut file =
+            File::open(&model_path).map_err(|e| model_error("Cannot open GGUF model", e))?;
+        let content = gguf_file::Content::read(&mut file)
+            .map_err(|e| model_error("Cannot read GGUF model", e))?;
+        let model = ModelWeights::from_gguf(content, &mut file, &device)
+            .map_err(|e| model_error("Cannot load GGUF model", e))?;
+
+        let tokenizer = Tokenizer::from_file(&tokenizer_path)
+            .map_err(|e| model_error("Cannot load tokenizer", e))?;
+        let eos_token = tokenizer.token_to_id("</s>");
+
+        Ok(Self {
+            model: Mutex::new(model),
+            tokenizer,
+            device,
+            eos_token,
+        })
+    }
+
+    async fn generate(
+        &self,
+        prompt: &str,
+        max_length: usize,
+        temperature: f32,
+    ) -> Result<InferenceResult, ModelError> {
+        let prompt_tokens = self
+            .tokenizer
+            .encode(prompt, true)
+            .map_err(|e| model_error("Tokenizer error", e))?
+            .get_ids()
+            .to_vec();
+
+        let mut model = self
+            .model
+            .lock()
+            .map_err(|e| model_error("Model lock error", e))?;
+        let mut logits_processor = LogitsProcessor::new(299792458, Some(temperature as f64), None);
+
+        let mut tokens: Vec<u32> = vec![];
+        let mut probabilities = vec![];
+        let mut input = prompt_tokens.clone();
+        for index in 0..max_length {
+            let position = if index == 0 {
+                0
+            } else {
+                prompt_tokens.len() + index - 1
+            };
+            let input_tensor = Tensor::new(input.as_slice(), &self.device)
+                .and_then(|tensor| tensor.unsqueeze(0))
+                .map_err(|e| model_error("Tensor error", e))?;
+            let logits = model
+                .forward(&input_tensor, position)
+                .and_then(|logits| logits.squeeze(0))
+                .map_err(|e| model_error("Inference error", e))?;
+
+            let next_token = logits_processor
+                .sample(&logits)
+                .map_err(|e| model_error("Sampling error", e))?;
+            if Some(next_token) == self.eos_token {
+                break;
+            }
+
+            // Probability of the sampled token, for confidence scoring
+            let probability = logits.to_vec1::<f32>().ok().map(|logits| {
+                let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                let sum: f32 = logits.iter().map(|logit| (logit - max).exp()).sum();
+                (logits[next_token as usize] - max).exp() / sum
+            });
+            probabilities.extend(probability);
+
+            tokens.push(next_token);
+            input = vec![next_token];
+        }
+
+        let text = self
+            .tokenizer
+            .decode(&tokens, true)
+            .map_err(|e| model_error("Tokenizer error", e))?;
+
+        Ok(InferenceResult {
+            text,
+            probabilities,
+        })
+    }
+
+    async fn warmup(&self, texts: &[String], prime: bool) -> Result<(), ModelError> {
+        // Exercise the tokenizer on every prompt fragment
+        let texts: Vec<&str> = texts.iter().map(|text| text.as_str()).collect();
+        self.tokenizer
+            .encode_batch(texts, true)
+            .map_err(|e| model_error("Tokenizer error", e))?;
+
+        if prime {
+            self.generate(".", 1, 0.0).await?;
+        }
+        Ok(())
+    }
+}
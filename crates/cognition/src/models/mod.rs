@@ -1,10 +1,22 @@
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 use std::error::Error;
 use std::fmt::{self, Display};
 
+#[cfg(feature = "openai")]
 pub mod davinci003;
+#[cfg(any(feature = "openai", feature = "textgen"))]
+mod http;
+#[cfg(feature = "local-llm")]
+pub mod local_llm;
+pub mod openai_compatible;
+mod registry;
+#[cfg(feature = "textgen")]
 pub mod textgen;
 
+pub use registry::{ModelFactory, ModelRegistry};
+
 #[derive(Debug)]
 pub struct ModelError {
     message: String,
@@ -32,9 +44,21 @@ pub struct InferenceResult {
     pub probabilities: Vec<f32>,
 }
 
+impl InferenceResult {
+    /// Probability of the whole generated text, if the model reports token probabilities.
+    pub fn confidence(&self) -> Option<f32> {
+        if self.probabilities.is_empty() {
+            None
+        } else {
+            Some(self.probabilities.iter().product())
+        }
+    }
+}
+
 #[async_trait(?Send)]
 pub trait LargeLanguageModel {
     /// Initializes the model with the given configuration.
+    #[allow(clippy::ptr_arg)] // `&String` is part of the public trait
     fn new(config: &String) -> Result<Self, ModelError>
     where
         Self: Sized;
@@ -46,4 +70,63 @@ pub trait LargeLanguageModel {
         max_length: usize,
         temperature: f32,
     ) -> Result<InferenceResult, ModelError>;
+
+    /// Prepares the model ahead of the first turn. `texts` are the strings prompts
+    /// are built from. If `prime` is set, a tiny request is sent to the model.
+    async fn warmup(&self, _texts: &[String], prime: bool) -> Result<(), ModelError> {
+        if prime {
+            self.generate(".", 1, 0.0).await?;
+        }
+        Ok(())
+    }
+
+    /// Generates a JSON value matching `schema`, a JSON schema. Backends with a
+    /// JSON mode or constrained decoding should override this; by default the
+    /// schema is added to the prompt and the first JSON value in the answer is parsed.
+    async fn generate_json(&self, prompt: &str, schema: &Value) -> Result<Value, ModelError> {
+        let response = self
+            .generate(&json_prompt(prompt, schema), 400, 0.0)
+            .await?;
+        parse_json(&response.text)
+    }
+
+    /// Embeds each text as a vector, in order, for matching answers to choices
+    /// by meaning. Backends without an embeddings endpoint return an error.
+    async fn embed(&self, _texts: &[String]) -> Result<Vec<Vec<f32>>, ModelError> {
+        Err(ModelError::new(
+            "Embeddings are not supported by this model",
+        ))
+    }
+}
+
+/// Generates JSON matching `schema` and parses it into `T`.
+pub async fn generate_typed<T: DeserializeOwned>(
+    model: &dyn LargeLanguageModel,
+    prompt: &str,
+    schema: &Value,
+) -> Result<T, ModelError> {
+    let value = model.generate_json(prompt, schema).await?;
+    serde_json::from_value(value)
+        .map_err(|e| ModelError::new(&format!("JSON does not match the schema: {}", e)))
+}
+
+/// Prompt asking for an answer in JSON matching `schema`.
+pub fn json_prompt(prompt: &str, schema: &Value) -> String {
+    format!(
+        "{}\n\nAnswer with only a JSON value matching this JSON schema:\n{}\n",
+        prompt, schema
+    )
+}
+
+/// Parses the first JSON object or array in `text`, ignoring any text around it.
+pub fn parse_json(text: &str) -> Result<Value, ModelError> {
+    let start = text
+        .find(['{', '['])
+        .ok_or_else(|| ModelError::new("No JSON found in the model output"))?;
+    let mut values = serde_json::Deserializer::from_str(&text[start..]).into_iter::<Value>();
+    match values.next() {
+        Some(Ok(value)) => Ok(value),
+        Some(Err(e)) => Err(ModelError::new(&format!("JSON parsing error: {}", e))),
+        None => Err(ModelError::new("No JSON found in the model output")),
+    }
 }
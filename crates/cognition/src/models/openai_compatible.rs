@@ -0,0 +1,221 @@
+use crate::{
+    config::string_by_path,
+    http_client::HttpClient,
+    models::{json_prompt, parse_json, InferenceResult, LargeLanguageModel, ModelError},
+    CognitionError,
+};
+#[cfg(feature = "openai")]
+use crate::{http_client::ReqwestClient, models::http};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+// Any server speaking the OpenAI chat completions API: vLLM, LM Studio,
+// llamafile, Groq, OpenRouter...
+pub struct OpenAICompatible {
+    client: Box<dyn HttpClient>,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    // Model of the `/embeddings` endpoint, `models.openai_compatible.embedding_model`
+    embedding_model: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ChatRequestBody<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    temperature: f32,
+    max_tokens: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatResponseMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatResponseMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+fn model_error(err: CognitionError) -> ModelError {
+    ModelError::new(&err.0)
+}
+
+impl OpenAICompatible {
+    // Backend sending its requests through `client`, e.g. the browser's fetch
+    // in wasm builds
+    pub fn with_client(config: &str, client: Box<dyn HttpClient>) -> Result<Self, ModelError> {
+        let base_url = string_by_path(config, "models.openai_compatible.base_url")
+            .map(|url| url.trim_end_matches('/').to_string())
+            .ok_or_else(|| ModelError::new("Missing models.openai_compatible.base_url"))?;
+        let model = string_by_path(config, "models.openai_compatible.model")
+            .ok_or_else(|| ModelError::new("Missing models.openai_compatible.model"))?;
+        // Local servers usually do not need a key
+        let api_key = string_by_path(config, "models.openai_compatible.api_key");
+        let embedding_model = string_by_path(config, "models.openai_compatible.embedding_model");
+        Ok(Self {
+            client,
+            base_url,
+            api_key,
+            model,
+            embedding_model,
+        })
+    }
+
+    fn headers(&self) -> Vec<(String, String)> {
+        self.api_key
+            .iter()
+            .map(|api_key| ("Authorization".to_string(), format!("Bearer {}", api_key)))
+            .collect()
+    }
+
+    async fn post<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        body: &Value,
+    ) -> Result<T, ModelError> {
+        let response = self
+            .client
+            .post_json(&format!("{}{}", self.base_url, path), &self.headers(), body)
+            .await
+            .map_err(model_error)?;
+        serde_json::from_value(response)
+            .map_err(|e| ModelError::new(&format!("JSON parsing error: {}", e)))
+    }
+
+    // Single-message chat completion, returning the answer text
+    async fn chat(
+        &self,
+        prompt: &str,
+        max_length: usize,
+        temperature: f32,
+        response_format: Option<Value>,
+    ) -> Result<String, ModelError> {
+        let request_body = ChatRequestBody {
+            model: &self.model,
+            messages: vec![ChatMessage {
+                role: "user",
+                content: prompt,
+            }],
+            temperature,
+            max_tokens: max_length,
+            response_format,
+        };
+        let request_body = serde_json::to_value(&request_body)
+            .map_err(|e| ModelError::new(&format!("JSON error: {}", e)))?;
+
+        let response: ChatResponse = self.post("/chat/completions", &request_body).await?;
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| ModelError::new("No choices found"))?;
+
+        Ok(choice.message.content.unwrap_or_default())
+    }
+}
+
+#[async_trait(?Send)]
+impl LargeLanguageModel for OpenAICompatible {
+    #[cfg(feature = "openai")]
+    fn new(config: &String) -> Result<Self, ModelError> {
+        let client = http::client(config, "openai_compatible")?;
+        Self::with_client(config, Box::new(ReqwestClient(client)))
+    }
+
+    // Without reqwest, hosts provide the HTTP client
+    #[cfg(not(feature = "openai"))]
+    fn new(_config: &String) -> Result<Self, ModelError> {
+        Err(ModelError::new(
+            "openai_compatible needs the `openai` feature or OpenAICompatible::with_client",
+        ))
+    }
+
+    async fn generate(
+        &self,
+        prompt: &str,
+        max_length: usize,
+        temperature: f32,
+    ) -> Result<InferenceResult, ModelError> {
+        let text = self.chat(prompt, max_length, temperature, None).await?;
+        Ok(InferenceResult {
+            text,
+            probabilities: vec![],
+        })
+    }
+
+    async fn generate_json(&self, prompt: &str, schema: &Value) -> Result<Value, ModelError> {
+        // Structured outputs, supported by most OpenAI-compatible servers
+        let response_format = json!({
+            "type": "json_schema",
+            "json_schema": { "name": "response", "schema": schema },
+        });
+        let text = self
+            .chat(
+                &json_prompt(prompt, schema),
+                400,
+                0.0,
+                Some(response_format),
+            )
+            .await?;
+        parse_json(&text)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ModelError> {
+        let model = self
+            .embedding_model
+            .as_deref()
+            .ok_or_else(|| ModelError::new("Missing models.openai_compatible.embedding_model"))?;
+        let mut response: EmbeddingResponse = self
+            .post("/embeddings", &json!({ "model": model, "input": texts }))
+            .await?;
+        if response.data.len() != texts.len() {
+            return Err(ModelError::new("Wrong number of embeddings"));
+        }
+        response.data.sort_by_key(|data| data.index);
+        Ok(response
+            .data
+            .into_iter()
+            .map(|data| data.embedding)
+            .collect())
+    }
+
+    async fn warmup(&self, _texts: &[String], prime: bool) -> Result<(), ModelError> {
+        // Open a pooled connection to the server
+        self.client
+            .connect(&format!("{}/models", self.base_url), &self.headers())
+            .await
+            .map_err(model_error)?;
+
+        if prime {
+            self.generate(".", 1, 0.0).await?;
+        }
+        Ok(())
+    }
+}
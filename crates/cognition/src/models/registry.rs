@@ -0,0 +1,75 @@
+use crate::models::{LargeLanguageModel, ModelError};
+use std::collections::HashMap;
+
+/// Creates a model from the configuration.
+pub type ModelFactory = Box<dyn Fn(&str) -> Result<Box<dyn LargeLanguageModel>, ModelError>>;
+
+/// Models available by name, selected at runtime with `model: <name>` in the config.
+pub struct ModelRegistry {
+    factories: HashMap<String, ModelFactory>,
+}
+
+impl ModelRegistry {
+    /// Registry without any models.
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Registers a factory under `name`, replacing any model with the same name.
+    pub fn register(&mut self, name: &str, factory: ModelFactory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    /// Registers a model type using its `LargeLanguageModel::new`.
+    pub fn register_model<M: LargeLanguageModel + 'static>(&mut self, name: &str) {
+        self.register(
+            name,
+            Box::new(|config| {
+                Ok(Box::new(M::new(&config.to_string())?) as Box<dyn LargeLanguageModel>)
+            }),
+        );
+    }
+
+    /// Creates the model registered under `name`.
+    pub fn create(
+        &self,
+        name: &str,
+        config: &str,
+    ) -> Result<Box<dyn LargeLanguageModel>, ModelError> {
+        let factory = self
+            .factories
+            .get(name)
+            .ok_or_else(|| ModelError::new(&format!("Unknown model: {}", name)))?;
+        factory(config)
+    }
+
+    /// Whether a model is registered under `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// Names of the registered models.
+    pub fn names(&self) -> Vec<&String> {
+        self.factories.keys().collect()
+    }
+}
+
+impl Default for ModelRegistry {
+    /// Registry with the built-in models.
+    fn default() -> Self {
+        // Every built-in model can be disabled by features
+        #[allow(unused_mut)]
+        let mut registry = Self::empty();
+        #[cfg(feature = "openai")]
+        registry.register_model::<super::davinci003::Davinci003>("davinci003");
+        #[cfg(feature = "openai")]
+        registry.register_model::<super::openai_compatible::OpenAICompatible>("openai_compatible");
+        #[cfg(feature = "textgen")]
+        registry.register_model::<super::textgen::Textgen>("textgen");
+        #[cfg(feature = "local-llm")]
+        registry.register_model::<super::local_llm::LocalLlm>("local_llm");
+        registry
+    }
+}
@@ -1,4 +1,4 @@
-use crate::models::{InferenceResult, LargeLanguageModel, ModelError};
+use crate::models::{http, InferenceResult, LargeLanguageModel, ModelError};
 use async_trait::async_trait;
 use reqwest::{
     header::{HeaderMap, HeaderValue, CONTENT_TYPE},
@@ -66,12 +66,16 @@ pub struct TextgenResponse {
 
 #[async_trait(?Send)]
 impl LargeLanguageModel for Textgen {
-    fn new(_config: &String) -> Result<Self, ModelError> {
-        Ok(Textgen {
-            server: std::env::var("TEXTGEN_SERVER").map_err(|e| {
+    fn new(config: &String) -> Result<Self, ModelError> {
+        let server = match http::base_url(config, "textgen") {
+            Some(server) => server,
+            None => std::env::var("TEXTGEN_SERVER").map_err(|e| {
                 ModelError::new(&format!("Cannot get TEXTGEN_SERVER from env var: {}", e))
             })?,
-            client: Client::new(),
+        };
+        Ok(Textgen {
+            server,
+            client: http::client(config, "textgen")?,
         })
     }
 
@@ -138,4 +142,18 @@ impl LargeLanguageModel for Textgen {
 
         Ok(result)
     }
+
+    async fn warmup(&self, _texts: &[String], prime: bool) -> Result<(), ModelError> {
+        // Open a pooled connection to the server
+        self.client
+            .get(&self.server)
+            .send()
+            .await
+            .map_err(|e| ModelError::new(&format!("HTTP request error: {}", e)))?;
+
+        if prime {
+            self.generate(".", 1, 0.0).await?;
+        }
+        Ok(())
+    }
 }
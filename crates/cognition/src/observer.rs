@@ -0,0 +1,95 @@
+use crate::guardrails::{GuardrailStage, Violation};
+use crate::{DecisionResult, ToolOutcome};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// Events emitted by the engine to registered observers
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DecisionEvent {
+    NodeEntered {
+        session_id: String,
+        node_id: String,
+    },
+    // A prompt about to be sent to the model, to choose or to answer
+    PromptBuilt {
+        session_id: String,
+        node_id: String,
+        prompt: String,
+    },
+    // Model output for the last prompt. Backends answer in one piece, so this
+    // is the whole output for now.
+    TokensStreamed {
+        session_id: String,
+        node_id: String,
+        text: String,
+    },
+    ToolStarted {
+        session_id: String,
+        node_id: String,
+        tool_id: String,
+    },
+    // A tool call of the node was answered or failed
+    ToolFinished {
+        session_id: String,
+        node_id: String,
+        outcome: ToolOutcome,
+    },
+    // A choice was made on a node, by the user's answer or a prediction
+    ChoiceResolved {
+        session_id: String,
+        node_id: String,
+        choice: String,
+        next_id: String,
+    },
+    Analytics {
+        session_id: String,
+        node_id: String,
+        name: String,
+        props: BTreeMap<String, Value>,
+    },
+    ToolProgress {
+        session_id: String,
+        tool_id: String,
+        message: String,
+        percent: Option<f32>,
+    },
+    GuardrailViolation {
+        session_id: String,
+        node_id: String,
+        stage: GuardrailStage,
+        violation: Violation,
+    },
+    SessionEnded {
+        session_id: String,
+        outcome: SessionOutcome,
+    },
+    // The turn is over, only in `decision_stream`
+    AwaitingInput {
+        session_id: String,
+        node_id: String,
+        result: Box<DecisionResult>,
+    },
+    // The turn failed, only in `decision_stream`
+    TurnFailed {
+        session_id: String,
+        message: String,
+    },
+}
+
+// How a session ended
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOutcome {
+    // The user reached an exit
+    Completed,
+    // The user went idle, see `on_idle` in the config
+    Abandoned,
+}
+
+#[async_trait(?Send)]
+pub trait Observer {
+    async fn notify(&self, event: &DecisionEvent);
+}
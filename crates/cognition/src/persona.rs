@@ -0,0 +1,104 @@
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// Who the agent is, from the `persona` section of the config:
+//
+// persona:
+//   name: Ada
+//   role: a travel agent at Acme
+//   tone: warm and concise
+//   system_prompt: Only recommend destinations Acme sells.
+//   forbidden_topics: [ politics, competitors ]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Persona {
+    // Speaker name in the history and transcripts
+    pub name: String,
+    pub role: Option<String>,
+    pub tone: Option<String>,
+    pub system_prompt: Option<String>,
+    pub forbidden_topics: Vec<String>,
+}
+
+impl Default for Persona {
+    fn default() -> Self {
+        Self {
+            name: "Agent".to_string(),
+            role: None,
+            tone: None,
+            system_prompt: None,
+            forbidden_topics: vec![],
+        }
+    }
+}
+
+impl Persona {
+    pub fn from_config(persona: &Value) -> Self {
+        serde_json::from_value(persona.clone()).unwrap_or_default()
+    }
+
+    // Instructions injected into prompts, empty for the default persona
+    pub fn render(&self) -> String {
+        let mut lines = vec![];
+        match &self.role {
+            Some(role) => lines.push(format!("You are {}, {}.", self.name, role)),
+            None if *self != Self::default() => lines.push(format!("You are {}.", self.name)),
+            None => {}
+        }
+        if let Some(tone) = &self.tone {
+            lines.push(format!("Your tone is {}.", tone));
+        }
+        if let Some(system_prompt) = &self.system_prompt {
+            lines.push(system_prompt.trim().to_string());
+        }
+        if !self.forbidden_topics.is_empty() {
+            lines.push(format!(
+                "Never discuss these topics: {}.",
+                self.forbidden_topics.join(", ")
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+// Sample one value per persona trait, so every session driven by the same tree
+// gets a slightly different character. Traits are configured as either a list
+// of equally likely values or a map of value to weight:
+//
+// persona:
+//   traits:
+//     tone: { cheerful: 3, grumpy: 1 }
+//     quirk: [ "hums while thinking", "uses nautical slang" ]
+pub fn sample_traits<R: Rng>(traits: &Value, rng: &mut R) -> BTreeMap<String, String> {
+    let mut sampled = BTreeMap::new();
+    let traits = match traits.as_object() {
+        Some(traits) => traits,
+        None => return sampled,
+    };
+
+    for (name, distribution) in traits {
+        let (values, weights): (Vec<String>, Vec<f64>) = match distribution {
+            Value::Array(values) => values.iter().map(|value| (text(value), 1.0)).unzip(),
+            Value::Object(weighted) => weighted
+                .iter()
+                .map(|(value, weight)| (value.clone(), weight.as_f64().unwrap_or(0.0)))
+                .unzip(),
+            value => (vec![text(value)], vec![1.0]),
+        };
+
+        if let Ok(index) = WeightedIndex::new(&weights) {
+            sampled.insert(name.clone(), values[index.sample(rng)].clone());
+        }
+    }
+
+    sampled
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
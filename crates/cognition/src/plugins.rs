@@ -0,0 +1,376 @@
+// Native plugins providing models and tools from out-of-tree crates, loaded
+// from the shared libraries in a directory at startup. A plugin is a `cdylib`
+// exporting `cognition_plugin`, a `PluginEntry` returning its
+// `PluginDescriptor`. The descriptor, its strings and function tables must
+// live as long as the library. Calls block the session's thread, must not
+// unwind and are made from one thread at a time per model. Strings passed in
+// are UTF-8 and borrowed; strings returned, including errors, are released
+// with the descriptor's `free_string`.
+//
+// The ABI only changes with `PLUGIN_ABI_VERSION`, and libraries built for
+// another version are refused. Plugins run with the process's rights, so only
+// load libraries you trust.
+use crate::{models::ModelRegistry, CognitionError, Tool};
+use std::ffi::{c_char, c_void};
+
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+// Symbol every plugin exports
+pub const PLUGIN_ENTRY: &str = "cognition_plugin";
+
+pub type PluginEntry = unsafe extern "C" fn() -> *const PluginDescriptor;
+
+#[repr(C)]
+pub struct PluginDescriptor {
+    // `PLUGIN_ABI_VERSION` the plugin was built for
+    pub abi_version: u32,
+    pub name: *const c_char,
+    pub models: *const PluginModel,
+    pub model_count: usize,
+    pub tools: *const PluginTool,
+    pub tool_count: usize,
+    pub free_string: unsafe extern "C" fn(value: *mut c_char),
+}
+
+// Backend selected with `model: <name>`
+#[repr(C)]
+pub struct PluginModel {
+    pub name: *const c_char,
+    // Model for the YAML config, or null with `error` set
+    pub create: unsafe extern "C" fn(config: *const c_char, error: *mut *mut c_char) -> *mut c_void,
+    // Generated text, or null with `error` set
+    pub generate: unsafe extern "C" fn(
+        model: *mut c_void,
+        prompt: *const c_char,
+        max_length: usize,
+        temperature: f32,
+        error: *mut *mut c_char,
+    ) -> *mut c_char,
+    pub destroy: unsafe extern "C" fn(model: *mut c_void),
+}
+
+#[repr(C)]
+pub struct PluginTool {
+    pub id: *const c_char,
+    pub name: *const c_char,
+    pub description: *const c_char,
+    // Response, or null without one; null with `error` set on failure
+    pub run: unsafe extern "C" fn(input: *const c_char, error: *mut *mut c_char) -> *mut c_char,
+}
+
+#[cfg(feature = "plugins")]
+pub use loader::{load_dir, Plugin};
+
+// Plugin models by name and plugin tools, from `plugins.dir`
+pub(crate) fn from_config(
+    config: &str,
+) -> Result<(ModelRegistry, Vec<Box<dyn Tool>>), CognitionError> {
+    let Some(dir) = crate::config::string_by_path(config, "plugins.dir") else {
+        return Ok((ModelRegistry::empty(), vec![]));
+    };
+    #[cfg(feature = "plugins")]
+    {
+        let mut registry = ModelRegistry::empty();
+        let mut tools = vec![];
+        for plugin in load_dir(std::path::Path::new(&dir))? {
+            plugin.register_models(&mut registry);
+            tools.extend(plugin.tools());
+        }
+        Ok((registry, tools))
+    }
+    #[cfg(not(feature = "plugins"))]
+    Err(CognitionError(format!(
+        "plugins.dir {} needs the `plugins` feature",
+        dir
+    )))
+}
+
+#[cfg(feature = "plugins")]
+mod loader {
+    use super::*;
+    use crate::{
+        models::{InferenceResult, LargeLanguageModel, ModelError},
+        ToolResponse,
+    };
+    use async_trait::async_trait;
+    use libloading::Library;
+    use log::info;
+    use std::ffi::{CStr, CString};
+    use std::path::Path;
+    use std::ptr;
+    use std::sync::Arc;
+
+    // Loaded library with its descriptor, which stays valid while any of its
+    // models or tools holds the library
+    pub struct Plugin {
+        pub name: String,
+        library: Arc<Library>,
+        descriptor: *const PluginDescriptor,
+    }
+
+    impl Plugin {
+        // Load the library at `path`, running its initialization code
+        pub fn load(path: &Path) -> Result<Self, CognitionError> {
+            let failed =
+                |err: String| CognitionError(format!("Failed to load plugin {:?}: {}", path, err));
+            // Safety: trusting the library is the caller's choice, see above
+            let library = unsafe { Library::new(path) }.map_err(|err| failed(err.to_string()))?;
+            let descriptor = unsafe {
+                let entry = library
+                    .get::<PluginEntry>(PLUGIN_ENTRY.as_bytes())
+                    .map_err(|err| failed(err.to_string()))?;
+                entry()
+            };
+            if descriptor.is_null() {
+                return Err(failed("no descriptor".to_string()));
+            }
+            let abi_version = unsafe { (*descriptor).abi_version };
+            if abi_version != PLUGIN_ABI_VERSION {
+                return Err(failed(format!(
+                    "built for ABI version {}, expected {}",
+                    abi_version, PLUGIN_ABI_VERSION
+                )));
+            }
+            let name = unsafe { borrowed((*descriptor).name) }.map_err(failed)?;
+            Ok(Self {
+                name,
+                library: Arc::new(library),
+                descriptor,
+            })
+        }
+
+        fn descriptor(&self) -> &PluginDescriptor {
+            unsafe { &*self.descriptor }
+        }
+
+        fn models(&self) -> &[PluginModel] {
+            let descriptor = self.descriptor();
+            if descriptor.models.is_null() {
+                return &[];
+            }
+            unsafe { std::slice::from_raw_parts(descriptor.models, descriptor.model_count) }
+        }
+
+        fn plugin_tools(&self) -> &[PluginTool] {
+            let descriptor = self.descriptor();
+            if descriptor.tools.is_null() {
+                return &[];
+            }
+            unsafe { std::slice::from_raw_parts(descriptor.tools, descriptor.tool_count) }
+        }
+
+        // Register every model of the plugin under its name
+        pub fn register_models(&self, registry: &mut ModelRegistry) {
+            for model in self.models() {
+                let Ok(name) = (unsafe { borrowed(model.name) }) else {
+                    continue;
+                };
+                let library = self.library.clone();
+                let functions: *const PluginModel = model;
+                let free_string = self.descriptor().free_string;
+                registry.register(
+                    &name,
+                    Box::new(move |config| {
+                        let model =
+                            NativeModel::create(library.clone(), functions, free_string, config)?;
+                        Ok(Box::new(model) as Box<dyn LargeLanguageModel>)
+                    }),
+                );
+            }
+        }
+
+        pub fn tools(&self) -> Vec<Box<dyn Tool>> {
+            let free_string = self.descriptor().free_string;
+            self.plugin_tools()
+                .iter()
+                .filter_map(|tool| {
+                    let strings = unsafe {
+                        (
+                            borrowed(tool.id),
+                            borrowed(tool.name),
+                            borrowed(tool.description),
+                        )
+                    };
+                    let (Ok(id), Ok(name), Ok(description)) = strings else {
+                        return None;
+                    };
+                    Some(Box::new(NativeTool {
+                        id,
+                        name,
+                        description,
+                        run: tool.run,
+                        free_string,
+                        _library: self.library.clone(),
+                    }) as Box<dyn Tool>)
+                })
+                .collect()
+        }
+    }
+
+    // Plugins of the shared libraries in `dir`, in file name order
+    pub fn load_dir(dir: &Path) -> Result<Vec<Plugin>, CognitionError> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|err| CognitionError(format!("Failed to read plugins {:?}: {}", dir, err)))?;
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == std::env::consts::DLL_EXTENSION)
+            })
+            .collect();
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| {
+                let plugin = Plugin::load(path)?;
+                info!("Loaded plugin {} from {:?}", plugin.name, path);
+                Ok(plugin)
+            })
+            .collect()
+    }
+
+    // Borrowed UTF-8 string of the plugin
+    unsafe fn borrowed(value: *const c_char) -> Result<String, String> {
+        if value.is_null() {
+            return Err("null string".to_string());
+        }
+        CStr::from_ptr(value)
+            .to_str()
+            .map(str::to_string)
+            .map_err(|_| "string is not UTF-8".to_string())
+    }
+
+    // String returned by the plugin, released after copying
+    unsafe fn owned(value: *mut c_char, free_string: unsafe extern "C" fn(*mut c_char)) -> String {
+        let text = CStr::from_ptr(value).to_string_lossy().into_owned();
+        free_string(value);
+        text
+    }
+
+    fn c_string(value: &str) -> CString {
+        CString::new(value.replace('\0', " ")).unwrap_or_default()
+    }
+
+    // Result of a call returning a string, with its error when it failed
+    unsafe fn call(
+        free_string: unsafe extern "C" fn(*mut c_char),
+        call: impl FnOnce(*mut *mut c_char) -> *mut c_char,
+    ) -> Result<Option<String>, String> {
+        let mut error: *mut c_char = ptr::null_mut();
+        let value = call(&mut error);
+        if !error.is_null() {
+            if !value.is_null() {
+                free_string(value);
+            }
+            return Err(owned(error, free_string));
+        }
+        Ok((!value.is_null()).then(|| owned(value, free_string)))
+    }
+
+    struct NativeModel {
+        functions: *const PluginModel,
+        free_string: unsafe extern "C" fn(*mut c_char),
+        model: *mut c_void,
+        // Dropped after the model is destroyed
+        _library: Arc<Library>,
+    }
+
+    impl NativeModel {
+        fn create(
+            library: Arc<Library>,
+            functions: *const PluginModel,
+            free_string: unsafe extern "C" fn(*mut c_char),
+            config: &str,
+        ) -> Result<Self, ModelError> {
+            let config = c_string(config);
+            let mut error: *mut c_char = ptr::null_mut();
+            let model = unsafe { ((*functions).create)(config.as_ptr(), &mut error) };
+            if model.is_null() {
+                let message = if error.is_null() {
+                    "Plugin failed to create the model".to_string()
+                } else {
+                    unsafe { owned(error, free_string) }
+                };
+                return Err(ModelError::new(&message));
+            }
+            Ok(Self {
+                functions,
+                free_string,
+                model,
+                _library: library,
+            })
+        }
+    }
+
+    impl Drop for NativeModel {
+        fn drop(&mut self) {
+            unsafe { ((*self.functions).destroy)(self.model) }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl LargeLanguageModel for NativeModel {
+        fn new(_config: &String) -> Result<Self, ModelError> {
+            Err(ModelError::new("Plugin models are created by their plugin"))
+        }
+
+        async fn generate(
+            &self,
+            prompt: &str,
+            max_length: usize,
+            temperature: f32,
+        ) -> Result<InferenceResult, ModelError> {
+            let prompt = c_string(prompt);
+            let text = unsafe {
+                call(self.free_string, |error| {
+                    ((*self.functions).generate)(
+                        self.model,
+                        prompt.as_ptr(),
+                        max_length,
+                        temperature,
+                        error,
+                    )
+                })
+            }
+            .map_err(|err| ModelError::new(&err))?;
+            Ok(InferenceResult {
+                text: text.unwrap_or_default(),
+                probabilities: vec![],
+            })
+        }
+    }
+
+    struct NativeTool {
+        id: String,
+        name: String,
+        description: String,
+        run: unsafe extern "C" fn(*const c_char, *mut *mut c_char) -> *mut c_char,
+        free_string: unsafe extern "C" fn(*mut c_char),
+        _library: Arc<Library>,
+    }
+
+    #[async_trait(?Send)]
+    impl Tool for NativeTool {
+        fn id(&self) -> &String {
+            &self.id
+        }
+
+        fn name(&self) -> &String {
+            &self.name
+        }
+
+        fn description(&self) -> &String {
+            &self.description
+        }
+
+        async fn run(&self, input: &String) -> Result<Option<ToolResponse>, CognitionError> {
+            let input = c_string(input);
+            let response =
+                unsafe { call(self.free_string, |error| (self.run)(input.as_ptr(), error)) }
+                    .map_err(|err| CognitionError(format!("{} failed: {}", self.id, err)))?;
+            Ok(response.map(|response| ToolResponse {
+                id: self.id.clone(),
+                response,
+            }))
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::DecisionResult;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// How much of prompts, user input and model output is kept in logs, the turn
+// log and exported transcripts, `privacy.prompts` in the config. Node ids,
+// choices and metrics are always kept.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PromptPrivacy {
+    #[default]
+    Full,
+    // Replaced by `[redacted]`
+    Redact,
+    // Replaced by a SHA-256 prefix, so equal texts can still be correlated
+    Hash,
+}
+
+impl PromptPrivacy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "full" => Some(Self::Full),
+            "redact" => Some(Self::Redact),
+            "hash" => Some(Self::Hash),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Self::Full => text.to_string(),
+            Self::Redact => "[redacted]".to_string(),
+            Self::Hash => {
+                let digest = Sha256::digest(text.as_bytes());
+                let hex: String = digest[..8]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                format!("sha256:{}", hex)
+            }
+        }
+    }
+
+    // Copy of `result` safe to export, e.g. to a transcript. Redacted or hashed
+    // transcripts can no longer be replayed.
+    pub fn result(&self, result: &DecisionResult) -> DecisionResult {
+        let mut result = result.clone();
+        if *self != Self::Full {
+            result.user_input = result.user_input.map(|text| self.apply(&text));
+            result.decision_prompt = result.decision_prompt.map(|text| self.apply(&text));
+        }
+        result
+    }
+}
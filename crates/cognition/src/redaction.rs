@@ -0,0 +1,101 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+
+// Replaces emails, phone numbers and credit card numbers in user input with
+// placeholders like `[EMAIL_1]`, keeping the real values locally so tools that
+// need them can get them back with `restore`
+pub struct Redactor {
+    patterns: Vec<(&'static str, Regex)>,
+    // Placeholder to real value
+    values: BTreeMap<String, String>,
+}
+
+impl Redactor {
+    pub fn new() -> Self {
+        // Cards before phones, a card number also looks like a long phone number
+        let patterns = vec![
+            (
+                "EMAIL",
+                Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap(),
+            ),
+            ("CARD", Regex::new(r"\b(?:\d[ -]?){12,18}\d\b").unwrap()),
+            ("PHONE", Regex::new(r"\+?\(?\d[\d\s().-]{6,}\d").unwrap()),
+        ];
+        Self {
+            patterns,
+            values: BTreeMap::new(),
+        }
+    }
+
+    // Replace personal data in `text` with placeholders, the same value always
+    // getting the same placeholder within the session
+    pub fn redact(&mut self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (kind, pattern) in &self.patterns {
+            let matches: Vec<String> = pattern
+                .find_iter(&text)
+                .map(|found| found.as_str().to_string())
+                .filter(|value| match *kind {
+                    "CARD" => luhn_valid(value),
+                    "PHONE" => value.chars().filter(char::is_ascii_digit).count() >= 8,
+                    _ => true,
+                })
+                .collect();
+            for value in matches {
+                let placeholder = match self.values.iter().find(|(_, known)| **known == value) {
+                    Some((placeholder, _)) => placeholder.clone(),
+                    None => {
+                        let count = self
+                            .values
+                            .keys()
+                            .filter(|placeholder| placeholder.starts_with(&format!("[{}_", kind)))
+                            .count();
+                        let placeholder = format!("[{}_{}]", kind, count + 1);
+                        self.values.insert(placeholder.clone(), value.clone());
+                        placeholder
+                    }
+                };
+                text = text.replace(&value, &placeholder);
+            }
+        }
+        text
+    }
+
+    // Put the real values back into redacted text
+    pub fn restore(&self, text: &str) -> String {
+        self.values
+            .iter()
+            .fold(text.to_string(), |text, (placeholder, value)| {
+                text.replace(placeholder, value)
+            })
+    }
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Luhn checksum, to tell card numbers from other long digit runs
+fn luhn_valid(value: &str) -> bool {
+    let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| {
+            if index % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                *digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
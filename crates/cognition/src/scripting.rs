@@ -0,0 +1,117 @@
+// Node scripts, run in a sandboxed Rhai engine with the `rhai` feature. A
+// script reads the user's `input`, reads and writes the session's `vars`, can
+// set `tool_input` for the node's tools, and returns the node to go to next,
+// if any. Without the feature every script fails.
+use crate::CognitionError;
+use cognition_core::Transition;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default)]
+pub(crate) struct ScriptOutcome {
+    pub next: Option<Transition>,
+    pub tool_input: Option<String>,
+}
+
+#[cfg(feature = "rhai")]
+pub(crate) fn run(
+    script: &str,
+    vars: &mut BTreeMap<String, String>,
+    input: Option<&str>,
+    max_operations: u64,
+) -> Result<ScriptOutcome, CognitionError> {
+    use log::info;
+    use rhai::{Dynamic, Engine, Map, Scope};
+
+    // No file, network or module access, and bounded work and memory
+    let mut engine = Engine::new();
+    engine.set_max_operations(max_operations);
+    engine.set_max_call_levels(16);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(10_000);
+    engine.set_max_array_size(1_000);
+    engine.set_max_map_size(1_000);
+    engine.disable_symbol("eval");
+    engine.on_print(|text| info!("Script: {}", text));
+    engine.on_debug(|text, _, _| info!("Script: {}", text));
+
+    let map: Map = vars
+        .iter()
+        .map(|(name, value)| (name.into(), from_variable(value)))
+        .collect();
+    let mut scope = Scope::new();
+    scope.push("vars", map);
+    scope.push_constant(
+        "input",
+        input
+            .map(|input| input.to_string().into())
+            .unwrap_or(Dynamic::UNIT),
+    );
+    scope.push("tool_input", Dynamic::UNIT);
+
+    let result = engine
+        .eval_with_scope::<Dynamic>(&mut scope, script)
+        .map_err(|err| CognitionError(format!("Script failed: {}", err)))?;
+
+    // Variables set to `()` are removed
+    if let Some(map) = scope.get_value::<Map>("vars") {
+        *vars = map
+            .into_iter()
+            .filter(|(_, value)| !value.is_unit())
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+    }
+
+    // Maps and arrays are passed to tools as JSON
+    let tool_input = scope
+        .get_value::<Dynamic>("tool_input")
+        .filter(|value| !value.is_unit())
+        .map(|value| {
+            if value.is_map() || value.is_array() {
+                serde_json::to_string(&value).map_err(|err| {
+                    CognitionError(format!("Failed to serialize tool input: {}", err))
+                })
+            } else {
+                Ok(value.to_string())
+            }
+        })
+        .transpose()?;
+
+    let next = if result.is_unit() {
+        None
+    } else if result.is_string() {
+        Some(Transition::from(result.to_string()))
+    } else {
+        return Err(CognitionError(format!(
+            "Script returned {} instead of a node ID",
+            result.type_name()
+        )));
+    };
+
+    Ok(ScriptOutcome { next, tool_input })
+}
+
+#[cfg(not(feature = "rhai"))]
+pub(crate) fn run(
+    _script: &str,
+    _vars: &mut BTreeMap<String, String>,
+    _input: Option<&str>,
+    _max_operations: u64,
+) -> Result<ScriptOutcome, CognitionError> {
+    Err(CognitionError(
+        "Node scripts need the `rhai` feature".to_string(),
+    ))
+}
+
+// Variables are text, scripts see numbers and booleans as such
+#[cfg(feature = "rhai")]
+fn from_variable(value: &str) -> rhai::Dynamic {
+    if let Ok(number) = value.parse::<rhai::INT>() {
+        number.into()
+    } else if let Ok(number) = value.parse::<rhai::FLOAT>() {
+        number.into()
+    } else if let Ok(flag) = value.parse::<bool>() {
+        flag.into()
+    } else {
+        value.into()
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{
+    run_decision_with_cancel, run_idle, CancellationToken, CognitionError, DecisionResult,
+    DecisionState,
+};
+use tokio::sync::{Mutex, MutexGuard};
+
+// What a turn does while another turn of the same session is running
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ConcurrencyPolicy {
+    // Wait for the running turn, turns run in the order they were submitted
+    #[default]
+    Queue,
+    // Fail with `SessionError::Busy`
+    Reject,
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    // Another turn of the session is running, with the session ID
+    Busy(String),
+    Cognition(CognitionError),
+}
+
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::Busy(session_id) => write!(f, "Session {} is busy", session_id),
+            SessionError::Cognition(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<CognitionError> for SessionError {
+    fn from(err: CognitionError) -> Self {
+        SessionError::Cognition(err)
+    }
+}
+
+// Decision state shared by request handlers, running one turn at a time so
+// double submits from a frontend cannot interleave and corrupt the history
+pub struct Session {
+    session_id: String,
+    state: Mutex<DecisionState>,
+    policy: ConcurrencyPolicy,
+}
+
+impl Session {
+    pub fn new(state: DecisionState, policy: ConcurrencyPolicy) -> Self {
+        Self {
+            session_id: state.session_id.clone(),
+            state: Mutex::new(state),
+            policy,
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    // Exclusive access to the state between turns, subject to the policy
+    pub async fn lock(&self) -> Result<MutexGuard<'_, DecisionState>, SessionError> {
+        match self.policy {
+            ConcurrencyPolicy::Queue => Ok(self.state.lock().await),
+            ConcurrencyPolicy::Reject => self
+                .state
+                .try_lock()
+                .map_err(|_| SessionError::Busy(self.session_id.clone())),
+        }
+    }
+
+    pub async fn run(
+        &self,
+        user_input: Option<String>,
+    ) -> Result<Option<DecisionResult>, SessionError> {
+        self.run_with_cancel(user_input, &CancellationToken::new())
+            .await
+    }
+
+    pub async fn run_with_cancel(
+        &self,
+        user_input: Option<String>,
+        cancel: &CancellationToken,
+    ) -> Result<Option<DecisionResult>, SessionError> {
+        let mut state = self.lock().await?;
+        Ok(run_decision_with_cancel(user_input, &mut state, cancel).await?)
+    }
+
+    pub async fn run_idle(&self) -> Result<Option<DecisionResult>, SessionError> {
+        let mut state = self.lock().await?;
+        Ok(run_idle(&mut state).await?)
+    }
+}
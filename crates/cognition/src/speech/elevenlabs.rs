@@ -0,0 +1,57 @@
+use super::*;
+use reqwest::Client;
+use serde_json::json;
+
+const DEFAULT_BASE_URL: &str = "https://api.elevenlabs.io/v1";
+
+// ElevenLabs voices, from `speech.elevenlabs.api_key` and `voice_id`, and
+// optionally `model_id`, as MP3
+pub struct ElevenLabs {
+    client: Client,
+    api_key: String,
+    base_url: String,
+    voice_id: String,
+    model_id: String,
+}
+
+impl ElevenLabs {
+    pub fn new(config: &str) -> Result<Self, CognitionError> {
+        Ok(Self {
+            client: Client::new(),
+            api_key: required(config, "speech.elevenlabs.api_key")?,
+            base_url: string_by_path(config, "speech.elevenlabs.base_url")
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            voice_id: required(config, "speech.elevenlabs.voice_id")?,
+            model_id: string_by_path(config, "speech.elevenlabs.model_id")
+                .unwrap_or_else(|| "eleven_multilingual_v2".to_string()),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl TextToSpeech for ElevenLabs {
+    async fn synthesize(&self, text: &str) -> Result<Vec<u8>, CognitionError> {
+        let audio = self
+            .client
+            .post(format!(
+                "{}/text-to-speech/{}",
+                self.base_url, self.voice_id
+            ))
+            .header("xi-api-key", &self.api_key)
+            .header("accept", "audio/mpeg")
+            .json(&json!({ "text": text, "model_id": self.model_id }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| CognitionError(format!("ElevenLabs request failed: {}", err)))?
+            .bytes()
+            .await
+            .map_err(|err| CognitionError(format!("Invalid ElevenLabs response: {}", err)))?;
+        Ok(audio.to_vec())
+    }
+
+    fn format(&self) -> &str {
+        "mp3"
+    }
+}
@@ -0,0 +1,74 @@
+// Voice input and output for voice-driven trees, e.g. robots and simulations.
+// Backends are picked with `speech.stt` and `speech.tts` in the config:
+//
+// speech:
+//   stt: whisper_api     # or whisper_cpp
+//   tts: openai          # or elevenlabs
+//   whisper_api: { api_key: ..., model: whisper-1 }
+//   whisper_cpp: { binary: whisper-cli, model_path: models/ggml-base.en.bin }
+//   openai: { api_key: ..., voice: alloy }
+//   elevenlabs: { api_key: ..., voice_id: ... }
+use crate::{config::string_by_path, CognitionError};
+use async_trait::async_trait;
+use std::path::PathBuf;
+
+pub use elevenlabs::ElevenLabs;
+pub use openai_tts::OpenAITextToSpeech;
+pub use whisper_api::WhisperApi;
+pub use whisper_cpp::WhisperCpp;
+
+mod elevenlabs;
+mod openai_tts;
+mod whisper_api;
+mod whisper_cpp;
+
+#[async_trait(?Send)]
+pub trait SpeechToText {
+    // Text spoken in a WAV recording
+    async fn transcribe(&self, audio: &[u8]) -> Result<String, CognitionError>;
+}
+
+#[async_trait(?Send)]
+pub trait TextToSpeech {
+    async fn synthesize(&self, text: &str) -> Result<Vec<u8>, CognitionError>;
+
+    // File extension of the synthesized audio, like `wav` or `mp3`
+    fn format(&self) -> &str;
+}
+
+// Speech recognition backend of `speech.stt`, the Whisper API by default
+pub fn speech_to_text(config: &str) -> Result<Box<dyn SpeechToText>, CognitionError> {
+    match string_by_path(config, "speech.stt").as_deref() {
+        None | Some("whisper_api") => Ok(Box::new(WhisperApi::new(config)?)),
+        Some("whisper_cpp") => Ok(Box::new(WhisperCpp::new(config)?)),
+        Some(other) => Err(CognitionError(format!(
+            "Unknown speech-to-text backend: {}",
+            other
+        ))),
+    }
+}
+
+// Speech synthesis backend of `speech.tts`, OpenAI by default
+pub fn text_to_speech(config: &str) -> Result<Box<dyn TextToSpeech>, CognitionError> {
+    match string_by_path(config, "speech.tts").as_deref() {
+        None | Some("openai") => Ok(Box::new(OpenAITextToSpeech::new(config)?)),
+        Some("elevenlabs") => Ok(Box::new(ElevenLabs::new(config)?)),
+        Some(other) => Err(CognitionError(format!(
+            "Unknown text-to-speech backend: {}",
+            other
+        ))),
+    }
+}
+
+fn required(config: &str, path: &str) -> Result<String, CognitionError> {
+    string_by_path(config, path).ok_or_else(|| CognitionError(format!("Missing {}", path)))
+}
+
+// Path of a new file in the temporary directory
+pub(crate) fn temp_file(extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "cognition-{:016x}.{}",
+        rand::random::<u64>(),
+        extension
+    ))
+}
@@ -0,0 +1,59 @@
+use super::*;
+use reqwest::Client;
+use serde_json::json;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+// OpenAI speech, from `speech.openai.api_key` and optionally `base_url`,
+// `model` (`tts-1`) and `voice` (`alloy`), as WAV
+pub struct OpenAITextToSpeech {
+    client: Client,
+    api_key: String,
+    base_url: String,
+    model: String,
+    voice: String,
+}
+
+impl OpenAITextToSpeech {
+    pub fn new(config: &str) -> Result<Self, CognitionError> {
+        Ok(Self {
+            client: Client::new(),
+            api_key: required(config, "speech.openai.api_key")?,
+            base_url: string_by_path(config, "speech.openai.base_url")
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            model: string_by_path(config, "speech.openai.model")
+                .unwrap_or_else(|| "tts-1".to_string()),
+            voice: string_by_path(config, "speech.openai.voice")
+                .unwrap_or_else(|| "alloy".to_string()),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl TextToSpeech for OpenAITextToSpeech {
+    async fn synthesize(&self, text: &str) -> Result<Vec<u8>, CognitionError> {
+        let audio = self
+            .client
+            .post(format!("{}/audio/speech", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&json!({
+                "model": self.model,
+                "voice": self.voice,
+                "input": text,
+                "response_format": "wav",
+            }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| CognitionError(format!("Speech request failed: {}", err)))?
+            .bytes()
+            .await
+            .map_err(|err| CognitionError(format!("Invalid speech response: {}", err)))?;
+        Ok(audio.to_vec())
+    }
+
+    fn format(&self) -> &str {
+        "wav"
+    }
+}
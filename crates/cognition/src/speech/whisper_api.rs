@@ -0,0 +1,62 @@
+use super::*;
+use reqwest::{
+    multipart::{Form, Part},
+    Client,
+};
+use serde::Deserialize;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+// OpenAI's hosted Whisper, or any server with its transcriptions API, from
+// `speech.whisper_api.api_key` and optionally `base_url` and `model`
+pub struct WhisperApi {
+    client: Client,
+    api_key: String,
+    base_url: String,
+    model: String,
+}
+
+#[derive(Deserialize)]
+struct Transcription {
+    text: String,
+}
+
+impl WhisperApi {
+    pub fn new(config: &str) -> Result<Self, CognitionError> {
+        Ok(Self {
+            client: Client::new(),
+            api_key: required(config, "speech.whisper_api.api_key")?,
+            base_url: string_by_path(config, "speech.whisper_api.base_url")
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            model: string_by_path(config, "speech.whisper_api.model")
+                .unwrap_or_else(|| "whisper-1".to_string()),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl SpeechToText for WhisperApi {
+    async fn transcribe(&self, audio: &[u8]) -> Result<String, CognitionError> {
+        let file = Part::bytes(audio.to_vec())
+            .file_name("speech.wav")
+            .mime_str("audio/wav")
+            .map_err(|err| CognitionError(format!("Whisper request error: {}", err)))?;
+        let form = Form::new()
+            .text("model", self.model.clone())
+            .part("file", file);
+        let transcription: Transcription = self
+            .client
+            .post(format!("{}/audio/transcriptions", self.base_url))
+            .bearer_auth(&self.api_key)
+            .multipart(form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| CognitionError(format!("Whisper request failed: {}", err)))?
+            .json()
+            .await
+            .map_err(|err| CognitionError(format!("Invalid Whisper response: {}", err)))?;
+        Ok(transcription.text.trim().to_string())
+    }
+}
@@ -0,0 +1,47 @@
+use super::*;
+use tokio::process::Command;
+
+// Local whisper.cpp, run as `speech.whisper_cpp.binary` (`whisper-cli` by
+// default) with the GGML model at `speech.whisper_cpp.model_path`. Audio never
+// leaves the machine.
+pub struct WhisperCpp {
+    binary: String,
+    model_path: String,
+}
+
+impl WhisperCpp {
+    pub fn new(config: &str) -> Result<Self, CognitionError> {
+        Ok(Self {
+            binary: string_by_path(config, "speech.whisper_cpp.binary")
+                .unwrap_or_else(|| "whisper-cli".to_string()),
+            model_path: required(config, "speech.whisper_cpp.model_path")?,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl SpeechToText for WhisperCpp {
+    async fn transcribe(&self, audio: &[u8]) -> Result<String, CognitionError> {
+        let file = temp_file("wav");
+        std::fs::write(&file, audio)
+            .map_err(|err| CognitionError(format!("Failed to write audio: {}", err)))?;
+        // Text only, without timestamps or progress
+        let output = Command::new(&self.binary)
+            .args(["-m", &self.model_path, "-nt", "-np", "-f"])
+            .arg(&file)
+            .output()
+            .await;
+        let _ = std::fs::remove_file(&file);
+
+        let output =
+            output.map_err(|err| CognitionError(format!("Failed to run whisper.cpp: {}", err)))?;
+        if !output.status.success() {
+            return Err(CognitionError(format!(
+                "whisper.cpp failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        Ok(text.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+}
@@ -0,0 +1,464 @@
+use super::*;
+use crate::{
+    analytics::{Funnel, SessionAnalytics},
+    engine::unix_now,
+    store::{InvocationStatus, SessionFilter, SessionRecord, ToolInvocation},
+    turn_log::TurnRecord,
+    SessionOutcome, SessionSnapshot, Usage,
+};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{
+    any::{AnyArguments, AnyPoolOptions, AnyRow},
+    query::Query,
+    Any, AnyPool, Row,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+const MAX_CONNECTIONS: u32 = 5;
+
+// Portable between SQLite and Postgres, JSON kept as text
+const MIGRATIONS: [&str; 4] = [
+    "CREATE TABLE IF NOT EXISTS cognition_sessions (
+        session_id TEXT PRIMARY KEY,
+        tags TEXT NOT NULL,
+        outcome TEXT,
+        started_at BIGINT NOT NULL,
+        updated_at BIGINT NOT NULL,
+        model_calls BIGINT NOT NULL DEFAULT 0,
+        prompt_tokens BIGINT NOT NULL DEFAULT 0,
+        completion_tokens BIGINT NOT NULL DEFAULT 0,
+        cost DOUBLE PRECISION,
+        state TEXT
+    )",
+    "CREATE TABLE IF NOT EXISTS cognition_turns (
+        session_id TEXT NOT NULL,
+        turn BIGINT NOT NULL,
+        created_at BIGINT NOT NULL,
+        node_id TEXT NOT NULL,
+        next_id TEXT NOT NULL,
+        latency_ms BIGINT NOT NULL,
+        tokens BIGINT NOT NULL,
+        record TEXT NOT NULL,
+        PRIMARY KEY (session_id, turn)
+    )",
+    "CREATE TABLE IF NOT EXISTS cognition_tool_calls (
+        id TEXT PRIMARY KEY,
+        session_id TEXT NOT NULL,
+        node_id TEXT NOT NULL,
+        tool_id TEXT NOT NULL,
+        status TEXT NOT NULL,
+        record TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS cognition_sessions_started_at
+        ON cognition_sessions (started_at)",
+];
+
+// `storage.url` of the config as the session store and a log sink
+// Session usage with the session's record
+#[derive(Clone, Debug)]
+pub struct SessionUsage {
+    pub session: SessionRecord,
+    pub usage: Usage,
+}
+
+// Database of sessions, clones share the connection pool
+#[derive(Clone)]
+pub struct SqlStorage {
+    pub id: String,
+    pool: AnyPool,
+    migrated: Arc<OnceCell<()>>,
+}
+
+impl SqlStorage {
+    // Connects on first use
+    pub fn new(url: &str) -> Result<Self, CognitionError> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect_lazy(url)
+            .map_err(|err| CognitionError(format!("Invalid storage URL: {}", err)))?;
+        Ok(Self {
+            id: "storage".to_string(),
+            pool,
+            migrated: Arc::default(),
+        })
+    }
+
+    async fn pool(&self) -> Result<&AnyPool, CognitionError> {
+        self.migrated
+            .get_or_try_init(|| async {
+                for migration in MIGRATIONS {
+                    sqlx::query(migration)
+                        .execute(&self.pool)
+                        .await
+                        .map_err(|err| db_error("migrate", err))?;
+                }
+                Ok::<_, CognitionError>(())
+            })
+            .await?;
+        Ok(&self.pool)
+    }
+
+    // Sessions matching `filter` with their token usage, by start time
+    pub async fn session_usage(
+        &self,
+        filter: &SessionFilter,
+    ) -> Result<Vec<SessionUsage>, CognitionError> {
+        let (conditions, values) = time_conditions(filter, "started_at");
+        let sql = format!(
+            "SELECT session_id, tags, outcome, started_at, updated_at, model_calls,
+                prompt_tokens, completion_tokens, cost
+            FROM cognition_sessions{} ORDER BY started_at",
+            conditions
+        );
+        let mut query = sqlx::query(&sql);
+        for value in values {
+            query = query.bind(value);
+        }
+        let rows = query
+            .fetch_all(self.pool().await?)
+            .await
+            .map_err(|err| db_error("query sessions", err))?;
+
+        let mut sessions = vec![];
+        for row in rows {
+            let session = session_usage(&row).map_err(|err| db_error("read session", err))?;
+            if filter.matches(&session.session) {
+                sessions.push(session);
+            }
+        }
+        Ok(sessions)
+    }
+
+    // Token usage and cost of the sessions matching `filter`
+    pub async fn usage(&self, filter: &SessionFilter) -> Result<Usage, CognitionError> {
+        let mut total = Usage::default();
+        for session in self.session_usage(filter).await? {
+            total.add(&session.usage);
+        }
+        Ok(total)
+    }
+
+    // Turns of the sessions matching `filter`, in order within each session
+    pub async fn turns(&self, filter: &SessionFilter) -> Result<Vec<TurnRecord>, CognitionError> {
+        let sessions: HashSet<String> = self
+            .session_usage(filter)
+            .await?
+            .into_iter()
+            .map(|session| session.session.session_id)
+            .collect();
+        let (conditions, values) = time_conditions(filter, "s.started_at");
+        let sql = format!(
+            "SELECT t.session_id, t.record FROM cognition_turns t
+            JOIN cognition_sessions s ON s.session_id = t.session_id{}
+            ORDER BY t.session_id, t.turn",
+            conditions
+        );
+        self.records(&sql, values, |row| sessions.contains(row), "turn")
+            .await
+    }
+
+    // Tool calls of the sessions matching `filter`
+    pub async fn tool_calls(
+        &self,
+        filter: &SessionFilter,
+    ) -> Result<Vec<ToolInvocation>, CognitionError> {
+        let sessions: HashSet<String> = self
+            .session_usage(filter)
+            .await?
+            .into_iter()
+            .map(|session| session.session.session_id)
+            .collect();
+        let (conditions, values) = time_conditions(filter, "s.started_at");
+        let sql = format!(
+            "SELECT c.session_id, c.record FROM cognition_tool_calls c
+            JOIN cognition_sessions s ON s.session_id = c.session_id{}
+            ORDER BY c.session_id, c.id",
+            conditions
+        );
+        self.records(&sql, values, |row| sessions.contains(row), "tool call")
+            .await
+    }
+
+    // Calls per tool in the sessions matching `filter`
+    pub async fn tool_call_counts(
+        &self,
+        filter: &SessionFilter,
+    ) -> Result<BTreeMap<String, usize>, CognitionError> {
+        let mut counts = BTreeMap::new();
+        for call in self.tool_calls(filter).await? {
+            *counts.entry(call.tool_id).or_default() += 1;
+        }
+        Ok(counts)
+    }
+
+    // Node, path and funnel analytics of the sessions matching `filter`, like
+    // `SessionAnalytics::load_dir` over turn logs
+    pub async fn analytics(
+        &self,
+        filter: &SessionFilter,
+        funnels: &[Funnel],
+    ) -> Result<SessionAnalytics, CognitionError> {
+        Ok(SessionAnalytics::from_turns(
+            &self.turns(filter).await?,
+            funnels,
+        ))
+    }
+
+    // JSON `record` column of the rows whose `session_id` is kept
+    async fn records<T: DeserializeOwned>(
+        &self,
+        sql: &str,
+        values: Vec<i64>,
+        keep: impl Fn(&str) -> bool,
+        what: &str,
+    ) -> Result<Vec<T>, CognitionError> {
+        let mut query = sqlx::query(sql);
+        for value in values {
+            query = query.bind(value);
+        }
+        let rows = query
+            .fetch_all(self.pool().await?)
+            .await
+            .map_err(|err| db_error(&format!("query {}s", what), err))?;
+
+        let mut records = vec![];
+        for row in rows {
+            let session_id: String = row
+                .try_get("session_id")
+                .map_err(|err| db_error(&format!("read {}", what), err))?;
+            if !keep(&session_id) {
+                continue;
+            }
+            let record: String = row
+                .try_get("record")
+                .map_err(|err| db_error(&format!("read {}", what), err))?;
+            records.push(from_json(&record, what)?);
+        }
+        Ok(records)
+    }
+
+    async fn execute<'q>(
+        &self,
+        query: Query<'q, Any, AnyArguments<'q>>,
+        action: &str,
+    ) -> Result<(), CognitionError> {
+        query
+            .execute(self.pool().await?)
+            .await
+            .map(|_| ())
+            .map_err(|err| db_error(action, err))
+    }
+}
+
+#[async_trait(?Send)]
+impl SessionStore for SqlStorage {
+    async fn put_invocation(&self, invocation: &ToolInvocation) -> Result<(), CognitionError> {
+        let query = sqlx::query(
+            "INSERT INTO cognition_tool_calls (id, session_id, node_id, tool_id, status, record)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (id) DO UPDATE SET status = excluded.status, record = excluded.record",
+        )
+        .bind(invocation.id.clone())
+        .bind(invocation.session_id.clone())
+        .bind(invocation.node_id.clone())
+        .bind(invocation.tool_id.clone())
+        .bind(status_name(invocation.status))
+        .bind(to_json(invocation, "invocation")?);
+        self.execute(query, "save tool call").await
+    }
+
+    async fn get_invocation(&self, id: &str) -> Result<Option<ToolInvocation>, CognitionError> {
+        let row = sqlx::query("SELECT record FROM cognition_tool_calls WHERE id = $1")
+            .bind(id.to_string())
+            .fetch_optional(self.pool().await?)
+            .await
+            .map_err(|err| db_error("read tool call", err))?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let record: String = row
+            .try_get("record")
+            .map_err(|err| db_error("read tool call", err))?;
+        from_json(&record, "invocation").map(Some)
+    }
+
+    async fn put_session(&self, session: &SessionRecord) -> Result<(), CognitionError> {
+        let query = sqlx::query(
+            "INSERT INTO cognition_sessions (session_id, tags, outcome, started_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (session_id) DO UPDATE SET tags = excluded.tags,
+                outcome = excluded.outcome, updated_at = excluded.updated_at",
+        )
+        .bind(session.session_id.clone())
+        .bind(to_json(&session.tags, "tags")?)
+        .bind(outcome_name(session.outcome))
+        .bind(session.started_at as i64)
+        .bind(session.updated_at as i64);
+        self.execute(query, "save session").await
+    }
+
+    async fn find_sessions(
+        &self,
+        filter: &SessionFilter,
+    ) -> Result<Vec<SessionRecord>, CognitionError> {
+        Ok(self
+            .session_usage(filter)
+            .await?
+            .into_iter()
+            .map(|session| session.session)
+            .collect())
+    }
+
+    // Also records the session's token usage
+    async fn put_state(&self, snapshot: &SessionSnapshot) -> Result<(), CognitionError> {
+        let usage = snapshot.usage;
+        let query = sqlx::query(
+            "INSERT INTO cognition_sessions (session_id, tags, outcome, started_at, updated_at,
+                model_calls, prompt_tokens, completion_tokens, cost, state)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (session_id) DO UPDATE SET tags = excluded.tags,
+                outcome = excluded.outcome, updated_at = excluded.updated_at,
+                model_calls = excluded.model_calls, prompt_tokens = excluded.prompt_tokens,
+                completion_tokens = excluded.completion_tokens, cost = excluded.cost,
+                state = excluded.state",
+        )
+        .bind(snapshot.session_id.clone())
+        .bind(to_json(&snapshot.tags, "tags")?)
+        .bind(outcome_name(snapshot.outcome))
+        .bind(snapshot.started_at as i64)
+        .bind(unix_now() as i64)
+        .bind(usage.model_calls as i64)
+        .bind(usage.prompt_tokens as i64)
+        .bind(usage.completion_tokens as i64)
+        .bind(usage.cost)
+        .bind(to_json(snapshot, "session state")?);
+        self.execute(query, "save session state").await
+    }
+
+    async fn get_state(&self, session_id: &str) -> Result<Option<SessionSnapshot>, CognitionError> {
+        let row = sqlx::query("SELECT state FROM cognition_sessions WHERE session_id = $1")
+            .bind(session_id.to_string())
+            .fetch_optional(self.pool().await?)
+            .await
+            .map_err(|err| db_error("read session state", err))?;
+        let state: Option<String> = match row {
+            Some(row) => row
+                .try_get("state")
+                .map_err(|err| db_error("read session state", err))?,
+            None => None,
+        };
+        state
+            .map(|state| from_json(&state, "session state"))
+            .transpose()
+    }
+
+    // Keeps the session's record, usage, turns and tool calls
+    async fn delete_state(&self, session_id: &str) -> Result<(), CognitionError> {
+        let query = sqlx::query("UPDATE cognition_sessions SET state = NULL WHERE session_id = $1")
+            .bind(session_id.to_string());
+        self.execute(query, "delete session state").await
+    }
+}
+
+#[async_trait(?Send)]
+impl LogSink for SqlStorage {
+    fn id(&self) -> &String {
+        &self.id
+    }
+
+    async fn send(&self, record: &TurnRecord) -> Result<(), CognitionError> {
+        let query = sqlx::query(
+            "INSERT INTO cognition_turns (session_id, turn, created_at, node_id, next_id,
+                latency_ms, tokens, record)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (session_id, turn) DO UPDATE SET created_at = excluded.created_at,
+                node_id = excluded.node_id, next_id = excluded.next_id,
+                latency_ms = excluded.latency_ms, tokens = excluded.tokens,
+                record = excluded.record",
+        )
+        .bind(record.session_id.clone())
+        .bind(record.turn as i64)
+        .bind(record.timestamp as i64)
+        .bind(record.node_id.clone())
+        .bind(record.next_id.clone())
+        .bind(record.latency_ms as i64)
+        .bind(record.tokens as i64)
+        .bind(to_json(record, "turn")?);
+        self.execute(query, "save turn").await
+    }
+}
+
+// ` WHERE` clause on the filter's start time bounds, and their values
+fn time_conditions(filter: &SessionFilter, column: &str) -> (String, Vec<i64>) {
+    let mut conditions = vec![];
+    let mut values = vec![];
+    if let Some(after) = filter.started_after {
+        values.push(after as i64);
+        conditions.push(format!("{} >= ${}", column, values.len()));
+    }
+    if let Some(before) = filter.started_before {
+        values.push(before as i64);
+        conditions.push(format!("{} < ${}", column, values.len()));
+    }
+    if conditions.is_empty() {
+        return (String::new(), values);
+    }
+    (format!(" WHERE {}", conditions.join(" AND ")), values)
+}
+
+fn session_usage(row: &AnyRow) -> Result<SessionUsage, sqlx::Error> {
+    let tags: String = row.try_get("tags")?;
+    let outcome: Option<String> = row.try_get("outcome")?;
+    let started_at: i64 = row.try_get("started_at")?;
+    let updated_at: i64 = row.try_get("updated_at")?;
+    let model_calls: i64 = row.try_get("model_calls")?;
+    let prompt_tokens: i64 = row.try_get("prompt_tokens")?;
+    let completion_tokens: i64 = row.try_get("completion_tokens")?;
+    Ok(SessionUsage {
+        session: SessionRecord {
+            session_id: row.try_get("session_id")?,
+            tags: serde_json::from_str(&tags).unwrap_or_default(),
+            outcome: outcome.and_then(|outcome| serde_json::from_value(outcome.into()).ok()),
+            started_at: started_at as u64,
+            updated_at: updated_at as u64,
+        },
+        usage: Usage {
+            model_calls: model_calls as usize,
+            prompt_tokens: prompt_tokens as usize,
+            completion_tokens: completion_tokens as usize,
+            cost: row.try_get("cost")?,
+        },
+    })
+}
+
+fn status_name(status: InvocationStatus) -> &'static str {
+    match status {
+        InvocationStatus::Pending => "pending",
+        InvocationStatus::Completed => "completed",
+    }
+}
+
+// `completed`, `abandoned`... as serialized
+fn outcome_name(outcome: Option<SessionOutcome>) -> Option<String> {
+    outcome
+        .and_then(|outcome| serde_json::to_value(outcome).ok())
+        .and_then(|outcome| outcome.as_str().map(str::to_string))
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T, what: &str) -> Result<String, CognitionError> {
+    serde_json::to_string(value)
+        .map_err(|err| CognitionError(format!("Failed to serialize {}: {}", what, err)))
+}
+
+fn from_json<T: DeserializeOwned>(json: &str, what: &str) -> Result<T, CognitionError> {
+    serde_json::from_str(json)
+        .map_err(|err| CognitionError(format!("Failed to parse {}: {}", what, err)))
+}
+
+fn db_error(action: &str, err: sqlx::Error) -> CognitionError {
+    CognitionError(format!("Failed to {}: {}", action, err))
+}
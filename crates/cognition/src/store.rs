@@ -0,0 +1,494 @@
+use crate::{clock::Instant, CognitionError, SessionOutcome, SessionSnapshot, ToolResponse};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvocationStatus {
+    // Persisted before the tool ran, the side effect may or may not have happened
+    Pending,
+    Completed,
+}
+
+// Invocation of a side-effecting tool, keyed by session, turn, node and tool so a
+// replayed session finds the invocations of the original run
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ToolInvocation {
+    pub id: String,
+    pub session_id: String,
+    pub node_id: String,
+    pub tool_id: String,
+    pub input: String,
+    // Passed to tools that support idempotency, reused by retries and replays
+    #[serde(default)]
+    pub idempotency_key: String,
+    pub status: InvocationStatus,
+    pub response: Option<ToolResponse>,
+}
+
+// Summary of a session, searchable by its tags (tenant, channel, campaign...)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SessionRecord {
+    pub session_id: String,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    pub outcome: Option<SessionOutcome>,
+    // Seconds since the Unix epoch
+    pub started_at: u64,
+    pub updated_at: u64,
+}
+
+// Sessions to find: every set field must match
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SessionFilter {
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    pub outcome: Option<SessionOutcome>,
+    // Sessions started at or after, in seconds since the Unix epoch
+    pub started_after: Option<u64>,
+    pub started_before: Option<u64>,
+}
+
+impl SessionFilter {
+    pub fn matches(&self, session: &SessionRecord) -> bool {
+        self.tags
+            .iter()
+            .all(|(name, value)| session.tags.get(name) == Some(value))
+            && self
+                .outcome
+                .is_none_or(|outcome| session.outcome == Some(outcome))
+            && self
+                .started_after
+                .is_none_or(|after| session.started_at >= after)
+            && self
+                .started_before
+                .is_none_or(|before| session.started_at < before)
+    }
+}
+
+// Durable session state used for crash recovery and analytics
+#[async_trait(?Send)]
+pub trait SessionStore {
+    async fn put_invocation(&self, invocation: &ToolInvocation) -> Result<(), CognitionError>;
+    async fn get_invocation(&self, id: &str) -> Result<Option<ToolInvocation>, CognitionError>;
+    async fn put_session(&self, session: &SessionRecord) -> Result<(), CognitionError>;
+    async fn find_sessions(
+        &self,
+        filter: &SessionFilter,
+    ) -> Result<Vec<SessionRecord>, CognitionError>;
+    // Conversation state between turns, for sessions continued by another
+    // process or after a restart, see `DecisionState::resume`
+    async fn put_state(&self, snapshot: &SessionSnapshot) -> Result<(), CognitionError>;
+    async fn get_state(&self, session_id: &str) -> Result<Option<SessionSnapshot>, CognitionError>;
+    async fn delete_state(&self, session_id: &str) -> Result<(), CognitionError>;
+}
+
+fn to_json<T: Serialize>(value: &T, what: &str) -> Result<String, CognitionError> {
+    serde_json::to_string(value)
+        .map_err(|err| CognitionError(format!("Failed to serialize {}: {}", what, err)))
+}
+
+fn from_json<T: DeserializeOwned>(json: &str, what: &str) -> Result<T, CognitionError> {
+    serde_json::from_str(json)
+        .map_err(|err| CognitionError(format!("Failed to parse {}: {}", what, err)))
+}
+
+// Session store keeping one JSON file per invocation in a directory, one per
+// session in its `sessions` subdirectory and one per session state in `states`
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: &str) -> Result<Self, CognitionError> {
+        let dir = PathBuf::from(dir);
+        fs::create_dir_all(dir.join("sessions"))
+            .and_then(|_| fs::create_dir_all(dir.join("states")))
+            .map_err(|err| CognitionError(format!("Failed to create store: {}", err)))?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        let name: String = id
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(format!("{}.json", name))
+    }
+
+    fn session_path(&self, session_id: &str) -> PathBuf {
+        self.dir
+            .join("sessions")
+            .join(self.path(session_id).file_name().unwrap())
+    }
+
+    fn state_path(&self, session_id: &str) -> PathBuf {
+        self.dir
+            .join("states")
+            .join(self.path(session_id).file_name().unwrap())
+    }
+}
+
+// Write then rename, so a crash never leaves a half-written file
+fn write_atomic(path: &Path, content: &str) -> Result<(), CognitionError> {
+    let temp = path.with_extension("json.tmp");
+    let mut file = File::create(&temp)
+        .map_err(|err| CognitionError(format!("Failed to create {:?}: {}", temp, err)))?;
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(|err| CognitionError(format!("Failed to write {:?}: {}", temp, err)))?;
+    fs::rename(&temp, path)
+        .map_err(|err| CognitionError(format!("Failed to persist {:?}: {}", path, err)))
+}
+
+#[async_trait(?Send)]
+impl SessionStore for FileStore {
+    async fn put_invocation(&self, invocation: &ToolInvocation) -> Result<(), CognitionError> {
+        let content = serde_json::to_string_pretty(invocation)
+            .map_err(|err| CognitionError(format!("Failed to serialize invocation: {}", err)))?;
+
+        write_atomic(&self.path(&invocation.id), &content)
+    }
+
+    async fn get_invocation(&self, id: &str) -> Result<Option<ToolInvocation>, CognitionError> {
+        let content = match fs::read_to_string(self.path(id)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(CognitionError(format!(
+                    "Failed to read invocation: {}",
+                    err
+                )))
+            }
+        };
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|err| CognitionError(format!("Failed to parse invocation: {}", err)))
+    }
+
+    async fn put_session(&self, session: &SessionRecord) -> Result<(), CognitionError> {
+        let content = serde_json::to_string_pretty(session)
+            .map_err(|err| CognitionError(format!("Failed to serialize session: {}", err)))?;
+        write_atomic(&self.session_path(&session.session_id), &content)
+    }
+
+    async fn find_sessions(
+        &self,
+        filter: &SessionFilter,
+    ) -> Result<Vec<SessionRecord>, CognitionError> {
+        let entries = fs::read_dir(self.dir.join("sessions"))
+            .map_err(|err| CognitionError(format!("Failed to list sessions: {}", err)))?;
+
+        let mut sessions = vec![];
+        for entry in entries {
+            let path = entry
+                .map_err(|err| CognitionError(format!("Failed to list sessions: {}", err)))?
+                .path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let content = fs::read_to_string(&path)
+                .map_err(|err| CognitionError(format!("Failed to read session: {}", err)))?;
+            let session: SessionRecord = serde_json::from_str(&content)
+                .map_err(|err| CognitionError(format!("Failed to parse session: {}", err)))?;
+            if filter.matches(&session) {
+                sessions.push(session);
+            }
+        }
+        sessions.sort_by_key(|session| session.started_at);
+        Ok(sessions)
+    }
+
+    async fn put_state(&self, snapshot: &SessionSnapshot) -> Result<(), CognitionError> {
+        let content = to_json(snapshot, "session state")?;
+        write_atomic(&self.state_path(&snapshot.session_id), &content)
+    }
+
+    async fn get_state(&self, session_id: &str) -> Result<Option<SessionSnapshot>, CognitionError> {
+        match fs::read_to_string(self.state_path(session_id)) {
+            Ok(content) => from_json(&content, "session state").map(Some),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(CognitionError(format!(
+                "Failed to read session state: {}",
+                err
+            ))),
+        }
+    }
+
+    async fn delete_state(&self, session_id: &str) -> Result<(), CognitionError> {
+        match fs::remove_file(self.state_path(session_id)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(CognitionError(format!(
+                "Failed to delete session state: {}",
+                err
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Key of a value in key-value stores, by kind (`invocation`, `session` or
+// `state`) and ID
+fn store_key(prefix: &str, kind: &str, id: &str) -> String {
+    format!("{}{}:{}", prefix, kind, id)
+}
+
+// JSON values of a `MemoryStore` by key, with their expiry
+type MemoryValues = HashMap<String, (String, Option<Instant>)>;
+
+// Session store in process memory, for tests and single-process hosts. Values
+// are kept as JSON, like in Redis, and expire `ttl` after they were written.
+// Clones share the same values.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    values: Arc<Mutex<MemoryValues>>,
+    ttl: Option<Duration>,
+}
+
+impl MemoryStore {
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            values: Arc::default(),
+            ttl,
+        }
+    }
+
+    fn set(&self, key: String, json: String) {
+        let expires = self.ttl.map(|ttl| Instant::now() + ttl);
+        if let Ok(mut values) = self.values.lock() {
+            values.insert(key, (json, expires));
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        let mut values = self.values.lock().ok()?;
+        match values.get(key) {
+            Some((_, Some(expires))) if *expires <= Instant::now() => {
+                values.remove(key);
+                None
+            }
+            value => value.map(|(json, _)| json.clone()),
+        }
+    }
+
+    // Unexpired values of keys starting with `prefix`
+    fn scan(&self, prefix: &str) -> Vec<String> {
+        let now = Instant::now();
+        let Ok(mut values) = self.values.lock() else {
+            return vec![];
+        };
+        values.retain(|_, (_, expires)| expires.is_none_or(|expires| expires > now));
+        values
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(_, (json, _))| json.clone())
+            .collect()
+    }
+}
+
+#[async_trait(?Send)]
+impl SessionStore for MemoryStore {
+    async fn put_invocation(&self, invocation: &ToolInvocation) -> Result<(), CognitionError> {
+        let json = to_json(invocation, "invocation")?;
+        self.set(store_key("", "invocation", &invocation.id), json);
+        Ok(())
+    }
+
+    async fn get_invocation(&self, id: &str) -> Result<Option<ToolInvocation>, CognitionError> {
+        self.get(&store_key("", "invocation", id))
+            .map(|json| from_json(&json, "invocation"))
+            .transpose()
+    }
+
+    async fn put_session(&self, session: &SessionRecord) -> Result<(), CognitionError> {
+        let json = to_json(session, "session")?;
+        self.set(store_key("", "session", &session.session_id), json);
+        Ok(())
+    }
+
+    async fn find_sessions(
+        &self,
+        filter: &SessionFilter,
+    ) -> Result<Vec<SessionRecord>, CognitionError> {
+        let mut sessions = vec![];
+        for json in self.scan(&store_key("", "session", "")) {
+            let session: SessionRecord = from_json(&json, "session")?;
+            if filter.matches(&session) {
+                sessions.push(session);
+            }
+        }
+        sessions.sort_by_key(|session| session.started_at);
+        Ok(sessions)
+    }
+
+    async fn put_state(&self, snapshot: &SessionSnapshot) -> Result<(), CognitionError> {
+        let json = to_json(snapshot, "session state")?;
+        self.set(store_key("", "state", &snapshot.session_id), json);
+        Ok(())
+    }
+
+    async fn get_state(&self, session_id: &str) -> Result<Option<SessionSnapshot>, CognitionError> {
+        self.get(&store_key("", "state", session_id))
+            .map(|json| from_json(&json, "session state"))
+            .transpose()
+    }
+
+    async fn delete_state(&self, session_id: &str) -> Result<(), CognitionError> {
+        if let Ok(mut values) = self.values.lock() {
+            values.remove(&store_key("", "state", session_id));
+        }
+        Ok(())
+    }
+}
+
+// Session store in Redis, so sessions are shared by every server and survive
+// restarts. Values are JSON under `<prefix><kind>:<id>` keys and expire `ttl`
+// after they were last written. Clones share the connection.
+#[cfg(feature = "redis")]
+#[derive(Clone)]
+pub struct RedisStore {
+    client: redis::Client,
+    connection: Arc<tokio::sync::OnceCell<redis::aio::MultiplexedConnection>>,
+    prefix: String,
+    ttl: Option<Duration>,
+}
+
+#[cfg(feature = "redis")]
+impl RedisStore {
+    // Connects on first use, `url` is like `redis://127.0.0.1:6379/0`
+    pub fn new(url: &str, prefix: &str, ttl: Option<Duration>) -> Result<Self, CognitionError> {
+        let client = redis::Client::open(url)
+            .map_err(|err| CognitionError(format!("Invalid Redis URL: {}", err)))?;
+        Ok(Self {
+            client,
+            connection: Arc::default(),
+            prefix: prefix.to_string(),
+            ttl,
+        })
+    }
+
+    async fn connection(&self) -> Result<redis::aio::MultiplexedConnection, CognitionError> {
+        self.connection
+            .get_or_try_init(|| self.client.get_multiplexed_tokio_connection())
+            .await
+            .cloned()
+            .map_err(|err| CognitionError(format!("Failed to connect to Redis: {}", err)))
+    }
+
+    async fn set(&self, kind: &str, id: &str, json: String) -> Result<(), CognitionError> {
+        let mut command = redis::cmd("SET");
+        command.arg(store_key(&self.prefix, kind, id)).arg(json);
+        if let Some(ttl) = self.ttl {
+            command.arg("EX").arg(ttl.as_secs().max(1));
+        }
+        self.query(command).await
+    }
+
+    async fn get(&self, kind: &str, id: &str) -> Result<Option<String>, CognitionError> {
+        let mut command = redis::cmd("GET");
+        command.arg(store_key(&self.prefix, kind, id));
+        self.query(command).await
+    }
+
+    async fn query<T: redis::FromRedisValue>(
+        &self,
+        command: redis::Cmd,
+    ) -> Result<T, CognitionError> {
+        let mut connection = self.connection().await?;
+        command
+            .query_async(&mut connection)
+            .await
+            .map_err(|err| CognitionError(format!("Redis command failed: {}", err)))
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait(?Send)]
+impl SessionStore for RedisStore {
+    async fn put_invocation(&self, invocation: &ToolInvocation) -> Result<(), CognitionError> {
+        let json = to_json(invocation, "invocation")?;
+        self.set("invocation", &invocation.id, json).await
+    }
+
+    async fn get_invocation(&self, id: &str) -> Result<Option<ToolInvocation>, CognitionError> {
+        self.get("invocation", id)
+            .await?
+            .map(|json| from_json(&json, "invocation"))
+            .transpose()
+    }
+
+    async fn put_session(&self, session: &SessionRecord) -> Result<(), CognitionError> {
+        let json = to_json(session, "session")?;
+        self.set("session", &session.session_id, json).await
+    }
+
+    // Scans the keyspace, meant for analytics rather than every turn
+    async fn find_sessions(
+        &self,
+        filter: &SessionFilter,
+    ) -> Result<Vec<SessionRecord>, CognitionError> {
+        let pattern = format!("{}*", store_key(&self.prefix, "session", ""));
+        let mut keys: Vec<String> = vec![];
+        let mut cursor = 0u64;
+        loop {
+            let mut command = redis::cmd("SCAN");
+            command
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(500);
+            let (next, batch): (u64, Vec<String>) = self.query(command).await?;
+            keys.extend(batch);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        let mut sessions = vec![];
+        for keys in keys.chunks(500) {
+            let mut command = redis::cmd("MGET");
+            command.arg(keys);
+            let values: Vec<Option<String>> = self.query(command).await?;
+            // Expired between the scan and the read when `None`
+            for json in values.into_iter().flatten() {
+                let session: SessionRecord = from_json(&json, "session")?;
+                if filter.matches(&session) {
+                    sessions.push(session);
+                }
+            }
+        }
+        sessions.sort_by_key(|session| session.started_at);
+        Ok(sessions)
+    }
+
+    async fn put_state(&self, snapshot: &SessionSnapshot) -> Result<(), CognitionError> {
+        let json = to_json(snapshot, "session state")?;
+        self.set("state", &snapshot.session_id, json).await
+    }
+
+    async fn get_state(&self, session_id: &str) -> Result<Option<SessionSnapshot>, CognitionError> {
+        self.get("state", session_id)
+            .await?
+            .map(|json| from_json(&json, "session state"))
+            .transpose()
+    }
+
+    async fn delete_state(&self, session_id: &str) -> Result<(), CognitionError> {
+        let mut command = redis::cmd("DEL");
+        command.arg(store_key(&self.prefix, "state", session_id));
+        self.query::<()>(command).await
+    }
+}
@@ -0,0 +1,48 @@
+// A turn as a stream of events, for async UIs rendering progress as it happens
+// instead of waiting for the `DecisionResult`
+use crate::{run_decision, DecisionEvent, DecisionState, Observer};
+use async_trait::async_trait;
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::stream::{self, Stream, StreamExt};
+
+struct ChannelObserver(UnboundedSender<DecisionEvent>);
+
+#[async_trait(?Send)]
+impl Observer for ChannelObserver {
+    async fn notify(&self, event: &DecisionEvent) {
+        let _ = self.0.unbounded_send(event.clone());
+    }
+}
+
+// Run a turn like `run_decision`, yielding the events observers receive while
+// it runs. The stream ends with `AwaitingInput` and the turn's result, with
+// `TurnFailed`, or right after `SessionEnded` when the session is over.
+pub fn decision_stream(
+    user_input: Option<String>,
+    state: &mut DecisionState,
+) -> impl Stream<Item = DecisionEvent> + '_ {
+    let (sender, receiver) = unbounded();
+    state.add_observer(Box::new(ChannelObserver(sender.clone())));
+    let turn = async move {
+        let outcome = run_decision(user_input, state).await;
+        state.remove_last_observer();
+        let session_id = state.session_id.clone();
+        let last = match outcome {
+            Ok(Some(result)) => Some(DecisionEvent::AwaitingInput {
+                session_id,
+                node_id: result.current_id.clone(),
+                result: Box::new(result),
+            }),
+            Ok(None) => None,
+            Err(err) => Some(DecisionEvent::TurnFailed {
+                session_id,
+                message: err.0,
+            }),
+        };
+        if let Some(last) = last {
+            let _ = sender.unbounded_send(last);
+        }
+    };
+    // The receiver ends once the turn is over and both senders are dropped
+    stream::select(receiver, stream::once(turn).filter_map(|()| async { None }))
+}
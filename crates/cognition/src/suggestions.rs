@@ -0,0 +1,611 @@
+// Tree edits proposed from recorded turns (`logging.turns`): aliases for
+// answers that often matched nothing, clearer text for the choices they were
+// meant for, and splits of nodes with too many choices or retries. The model
+// writes the edits, which are applied to a copy of the tree and shown as a diff
+// for an author to review.
+use crate::{CognitionError, Decision, DecisionState, NodeStats, SessionAnalytics, TurnRecord};
+use cognition_core::{validate_tree, Choice, Transition};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TreeSuggestion {
+    // Answers given at the node that matched nothing but mean `choice`
+    AddAliases {
+        node_id: String,
+        choice: String,
+        aliases: Vec<String>,
+    },
+    // Clearer text for a choice answers were not matched to
+    RewordChoice {
+        node_id: String,
+        choice: String,
+        text: String,
+        reason: String,
+    },
+    // Choices of an overloaded node grouped under new nodes, one per group
+    SplitNode {
+        node_id: String,
+        groups: Vec<ChoiceGroup>,
+        reason: String,
+    },
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ChoiceGroup {
+    // Choice of the split node leading to the group's node
+    pub label: String,
+    // Text of the group's node
+    pub question: String,
+    // Texts of the choices moved to the group's node
+    pub choices: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SuggestionOptions {
+    // Times an unmatched answer must be given to be reviewed
+    pub min_count: usize,
+    // Nodes with more choices are split
+    pub max_choices: usize,
+    // Nodes with more unmatched answers per matched one are split
+    pub max_retries: f64,
+}
+
+impl Default for SuggestionOptions {
+    fn default() -> Self {
+        Self {
+            min_count: 2,
+            max_choices: 6,
+            max_retries: 1.0,
+        }
+    }
+}
+
+// Most unmatched answers reviewed per node
+const MAX_ANSWERS: usize = 20;
+
+#[derive(Deserialize)]
+struct AnswerReview {
+    #[serde(default)]
+    aliases: Vec<AliasProposal>,
+    #[serde(default)]
+    rewordings: Vec<Rewording>,
+}
+
+#[derive(Deserialize)]
+struct AliasProposal {
+    answer: String,
+    // Number of the choice, from 1
+    choice: usize,
+}
+
+#[derive(Deserialize)]
+struct Rewording {
+    choice: usize,
+    text: String,
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Deserialize)]
+struct SplitProposal {
+    groups: Vec<ProposedGroup>,
+}
+
+#[derive(Deserialize)]
+struct ProposedGroup {
+    label: String,
+    question: String,
+    choices: Vec<usize>,
+}
+
+// Edits of `nodes` suggested by the model configured in `state` from the
+// sessions of `turns`. Nodes the model fails on are skipped with a warning.
+pub async fn suggest_improvements(
+    state: &DecisionState,
+    nodes: &[Decision],
+    turns: &[TurnRecord],
+    options: &SuggestionOptions,
+) -> Result<Vec<TreeSuggestion>, CognitionError> {
+    let analytics = SessionAnalytics::from_turns(turns, &[]);
+    let unmatched = unmatched_answers(turns);
+
+    let mut suggestions = vec![];
+    for node in nodes {
+        let choices = node.choices();
+        if choices.is_empty() || node.picks_randomly() || node.expects_secret() {
+            continue;
+        }
+
+        let answers = frequent_answers(unmatched.get(node.id.as_str()), options.min_count);
+        if !answers.is_empty() {
+            match review_answers(state, node, &answers).await {
+                Ok(review) => suggestions.extend(answer_suggestions(node, &answers, review)),
+                Err(err) => log::warn!("Failed to review answers at {}: {}", node.id, err),
+            }
+        }
+
+        if let Some(reason) = overloaded(node, analytics.nodes.get(&node.id), options) {
+            match split_node(state, node).await {
+                Ok(Some(groups)) => suggestions.push(TreeSuggestion::SplitNode {
+                    node_id: node.id.clone(),
+                    groups,
+                    reason,
+                }),
+                Ok(None) => log::warn!("Invalid split of {} suggested", node.id),
+                Err(err) => log::warn!("Failed to split {}: {}", node.id, err),
+            }
+        }
+    }
+    Ok(suggestions)
+}
+
+// Answers that matched no choice, by node and then lowercased answer, with the
+// first spelling seen and the times given
+fn unmatched_answers(turns: &[TurnRecord]) -> BTreeMap<&str, BTreeMap<String, (&str, usize)>> {
+    let mut unmatched: BTreeMap<&str, BTreeMap<String, (&str, usize)>> = BTreeMap::new();
+    for turn in turns.iter().filter(|turn| turn.choices.is_empty()) {
+        let Some(answer) = turn.user_input.as_deref().map(str::trim) else {
+            continue;
+        };
+        if answer.is_empty() {
+            continue;
+        }
+        unmatched
+            .entry(&turn.node_id)
+            .or_default()
+            .entry(answer.to_lowercase())
+            .or_insert((answer, 0))
+            .1 += 1;
+    }
+    unmatched
+}
+
+// Answers given at least `min_count` times, most frequent first
+fn frequent_answers(
+    answers: Option<&BTreeMap<String, (&str, usize)>>,
+    min_count: usize,
+) -> Vec<(String, usize)> {
+    let mut answers: Vec<(String, usize)> = answers
+        .into_iter()
+        .flat_map(|answers| answers.values())
+        .filter(|(_, count)| *count >= min_count)
+        .map(|(answer, count)| (answer.to_string(), *count))
+        .collect();
+    answers.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    answers.truncate(MAX_ANSWERS);
+    answers
+}
+
+// Why the node should be split, if it should
+fn overloaded(
+    node: &Decision,
+    stats: Option<&NodeStats>,
+    options: &SuggestionOptions,
+) -> Option<String> {
+    let choices = node.choices().len();
+    if choices > options.max_choices {
+        return Some(format!("{} choices", choices));
+    }
+    // Two choices are split no further
+    let stats = stats.filter(|_| choices > 2)?;
+    let retries = stats.unmatched as f64 / stats.matched.max(1) as f64;
+    (stats.matched + stats.unmatched >= options.min_count && retries > options.max_retries)
+        .then(|| format!("{:.1} unmatched answers per match", retries))
+}
+
+fn numbered_choices(node: &Decision) -> String {
+    node.choices()
+        .iter()
+        .enumerate()
+        .map(|(i, choice)| format!("{}. {}\n", i + 1, choice.text))
+        .collect()
+}
+
+async fn review_answers(
+    state: &DecisionState,
+    node: &Decision,
+    answers: &[(String, usize)],
+) -> Result<AnswerReview, CognitionError> {
+    let answer_list: String = answers
+        .iter()
+        .map(|(answer, count)| format!("- {:?} ({} times)\n", answer, count))
+        .collect();
+    let prompt = format!(
+        "A conversation tree asks: {:?}\n\
+         Its choices are:\n{}\n\
+         Users often gave these answers, which matched none of the choices:\n{}\n\
+         For every answer that means one of the choices, give the answer as written and the \
+         number of that choice. For every choice these answers show to be unclear, suggest \
+         clearer text and why.",
+        node.text,
+        numbered_choices(node),
+        answer_list
+    );
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "aliases": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "answer": { "type": "string" },
+                        "choice": { "type": "integer" },
+                    },
+                    "required": ["answer", "choice"],
+                },
+            },
+            "rewordings": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "choice": { "type": "integer" },
+                        "text": { "type": "string" },
+                        "reason": { "type": "string" },
+                    },
+                    "required": ["choice", "text", "reason"],
+                },
+            },
+        },
+        "required": ["aliases", "rewordings"],
+    });
+    state.extract(&prompt, &schema).await
+}
+
+// Suggestions of a review, keeping only answers that were given and choices
+// that exist
+fn answer_suggestions(
+    node: &Decision,
+    answers: &[(String, usize)],
+    review: AnswerReview,
+) -> Vec<TreeSuggestion> {
+    let choices = node.choices();
+    let choice = |number: usize| number.checked_sub(1).and_then(|i| choices.get(i));
+
+    let mut aliases: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for proposal in review.aliases {
+        let Some(choice) = choice(proposal.choice) else {
+            continue;
+        };
+        let Some((answer, _)) = answers
+            .iter()
+            .find(|(answer, _)| answer.eq_ignore_ascii_case(proposal.answer.trim()))
+        else {
+            continue;
+        };
+        let known = choice
+            .aliases
+            .iter()
+            .chain([&choice.text])
+            .any(|alias| alias.eq_ignore_ascii_case(answer));
+        let added = aliases.entry(proposal.choice).or_default();
+        if !known && !added.contains(answer) {
+            added.push(answer.clone());
+        }
+    }
+
+    let mut suggestions: Vec<TreeSuggestion> = aliases
+        .into_iter()
+        .filter(|(_, aliases)| !aliases.is_empty())
+        .filter_map(|(number, aliases)| {
+            Some(TreeSuggestion::AddAliases {
+                node_id: node.id.clone(),
+                choice: choice(number)?.text.clone(),
+                aliases,
+            })
+        })
+        .collect();
+    for rewording in review.rewordings {
+        let text = rewording.text.trim();
+        match choice(rewording.choice) {
+            Some(choice) if !text.is_empty() && text != choice.text => {
+                suggestions.push(TreeSuggestion::RewordChoice {
+                    node_id: node.id.clone(),
+                    choice: choice.text.clone(),
+                    text: text.to_string(),
+                    reason: rewording.reason,
+                })
+            }
+            _ => {}
+        }
+    }
+    suggestions
+}
+
+// Groups of the node's choices, none if the model's grouping does not put every
+// choice in exactly one of at least two groups
+async fn split_node(
+    state: &DecisionState,
+    node: &Decision,
+) -> Result<Option<Vec<ChoiceGroup>>, CognitionError> {
+    let prompt = format!(
+        "A conversation tree asks: {:?}\n\
+         Its choices are:\n{}\n\
+         It has too many choices, or users struggle to pick one. Group the choices into 2 to 4 \
+         groups of related choices. For each group give a short label the user picks it by, \
+         the question the group's choices answer, and the numbers of its choices. Every choice \
+         goes in exactly one group.",
+        node.text,
+        numbered_choices(node)
+    );
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "groups": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "label": { "type": "string" },
+                        "question": { "type": "string" },
+                        "choices": { "type": "array", "items": { "type": "integer" } },
+                    },
+                    "required": ["label", "question", "choices"],
+                },
+            },
+        },
+        "required": ["groups"],
+    });
+    let proposal: SplitProposal = state.extract(&prompt, &schema).await?;
+
+    let choices = node.choices();
+    let numbers: Vec<usize> = proposal
+        .groups
+        .iter()
+        .flat_map(|group| group.choices.iter().copied())
+        .collect();
+    let unique: BTreeSet<usize> = numbers.iter().copied().collect();
+    let complete = numbers.len() == choices.len()
+        && unique.len() == choices.len()
+        && unique
+            .iter()
+            .all(|number| (1..=choices.len()).contains(number));
+    if proposal.groups.len() < 2 || !complete {
+        return Ok(None);
+    }
+    Ok(Some(
+        proposal
+            .groups
+            .into_iter()
+            .map(|group| ChoiceGroup {
+                label: group.label,
+                question: group.question,
+                choices: group
+                    .choices
+                    .iter()
+                    .map(|number| choices[number - 1].text.clone())
+                    .collect(),
+            })
+            .collect(),
+    ))
+}
+
+// Copy of `nodes` with the suggestions applied, and the suggestions that were.
+// Split nodes lead to new nodes `<node_id>_1`, `<node_id>_2`... inserted after
+// them. Edits that do not apply or would leave the tree invalid, or a node
+// with two choices the same answer matches, are rejected with a warning.
+pub fn apply_suggestions(
+    nodes: &[Decision],
+    suggestions: &[TreeSuggestion],
+) -> (Vec<Decision>, Vec<TreeSuggestion>) {
+    let mut nodes = nodes.to_vec();
+    let valid = validate_tree(&nodes).is_ok();
+    // New texts of reworded choices by node and old text, for the splits
+    let mut renamed: BTreeMap<(String, String), String> = BTreeMap::new();
+    let mut applied = vec![];
+
+    for suggestion in suggestions {
+        let mut edited = nodes.clone();
+        let checked = apply_suggestion(&mut edited, suggestion, &renamed).and_then(|node_id| {
+            if valid {
+                if let Err(errors) = validate_tree(&edited) {
+                    return Err(CognitionError(format!("Invalid tree: {}", errors[0])));
+                }
+            }
+            match ambiguous_answer(&edited, &node_id) {
+                Some(answer) => Err(CognitionError(format!(
+                    "'{}' would match several choices of {}",
+                    answer, node_id
+                ))),
+                None => Ok(()),
+            }
+        });
+        match checked {
+            Ok(()) => {
+                if let TreeSuggestion::RewordChoice {
+                    node_id,
+                    choice,
+                    text,
+                    ..
+                } = suggestion
+                {
+                    renamed.insert((node_id.clone(), choice.clone()), text.clone());
+                }
+                nodes = edited;
+                applied.push(suggestion.clone());
+            }
+            Err(err) => log::warn!("Rejected suggestion {:?}: {}", suggestion, err),
+        }
+    }
+    (nodes, applied)
+}
+
+// Apply one suggestion, returning the ID of the node whose choices changed
+fn apply_suggestion(
+    nodes: &mut Vec<Decision>,
+    suggestion: &TreeSuggestion,
+    renamed: &BTreeMap<(String, String), String>,
+) -> Result<String, CognitionError> {
+    let missing =
+        |node_id: &str, text: &str| CognitionError(format!("No choice '{}' at {}", text, node_id));
+    match suggestion {
+        TreeSuggestion::AddAliases {
+            node_id,
+            choice,
+            aliases,
+        } => {
+            let found =
+                find_choice(nodes, node_id, choice).ok_or_else(|| missing(node_id, choice))?;
+            for alias in aliases {
+                if !found.aliases.contains(alias) {
+                    found.aliases.push(alias.clone());
+                }
+            }
+            Ok(node_id.clone())
+        }
+        TreeSuggestion::RewordChoice {
+            node_id,
+            choice,
+            text,
+            ..
+        } => {
+            let found =
+                find_choice(nodes, node_id, choice).ok_or_else(|| missing(node_id, choice))?;
+            found.text = text.clone();
+            Ok(node_id.clone())
+        }
+        TreeSuggestion::SplitNode {
+            node_id, groups, ..
+        } => {
+            let position = nodes
+                .iter()
+                .position(|node| &node.id == node_id)
+                .ok_or_else(|| CognitionError(format!("No node {}", node_id)))?;
+            let mut remaining = nodes[position].choices.take().unwrap_or_default();
+            let mut parent_choices = vec![];
+            let mut group_nodes = vec![];
+            for group in groups {
+                let id = unused_id(nodes, &group_nodes, node_id);
+                let mut moved = vec![];
+                for text in &group.choices {
+                    let text = renamed
+                        .get(&(node_id.clone(), text.clone()))
+                        .unwrap_or(text);
+                    let i = remaining
+                        .iter()
+                        .position(|choice| &choice.text == text)
+                        .ok_or_else(|| missing(node_id, text))?;
+                    moved.push(remaining.remove(i));
+                }
+                parent_choices.push(Choice::new(&group.label, Transition::Node(id.clone())));
+                group_nodes.push(Decision {
+                    id,
+                    text: group.question.clone(),
+                    choices: Some(moved),
+                    ..Decision::default()
+                });
+            }
+            // Choices the groups missed stay on the split node
+            parent_choices.extend(remaining);
+            nodes[position].choices = Some(parent_choices);
+            nodes.splice(position + 1..position + 1, group_nodes);
+            Ok(node_id.clone())
+        }
+    }
+}
+
+// An answer matching two choices of the node by their text or aliases
+fn ambiguous_answer(nodes: &[Decision], node_id: &str) -> Option<String> {
+    let node = nodes.iter().find(|node| node.id == node_id)?;
+    let mut answers = BTreeSet::new();
+    node.choices()
+        .into_iter()
+        .flat_map(|choice| {
+            let answers: BTreeSet<String> = choice
+                .aliases
+                .iter()
+                .chain([&choice.text])
+                .map(|answer| answer.trim().to_lowercase())
+                .collect();
+            answers
+        })
+        .find(|answer| !answers.insert(answer.clone()))
+}
+
+fn find_choice<'a>(nodes: &'a mut [Decision], node_id: &str, text: &str) -> Option<&'a mut Choice> {
+    nodes
+        .iter_mut()
+        .find(|node| node.id == node_id)?
+        .choices
+        .iter_mut()
+        .flatten()
+        .find(|choice| choice.text == text)
+}
+
+fn unused_id(nodes: &[Decision], added: &[Decision], node_id: &str) -> String {
+    (1..)
+        .map(|n| format!("{}_{}", node_id, n))
+        .find(|id| !nodes.iter().chain(added).any(|node| &node.id == id))
+        .unwrap_or_default()
+}
+
+// Nodes changed, added or removed from `before` to `after`, as a diff of their
+// YAML with one `@@ <node_id> @@` hunk per node
+pub fn tree_diff(before: &[Decision], after: &[Decision]) -> Result<String, CognitionError> {
+    let changed = after
+        .iter()
+        .map(|node| (before.iter().find(|old| old.id == node.id), Some(node)));
+    let removed = before
+        .iter()
+        .filter(|node| !after.iter().any(|new| new.id == node.id))
+        .map(|node| (Some(node), None));
+
+    let mut diff = String::new();
+    for (old, new) in changed.chain(removed) {
+        let id = old.or(new).map(|node| node.id.as_str()).unwrap_or_default();
+        let old = node_yaml(old)?;
+        let new = node_yaml(new)?;
+        if old != new {
+            diff.push_str(&format!("@@ {} @@\n", id));
+            diff.push_str(&line_diff(&old, &new));
+        }
+    }
+    Ok(diff)
+}
+
+fn node_yaml(node: Option<&Decision>) -> Result<String, CognitionError> {
+    let Some(node) = node else {
+        return Ok(String::new());
+    };
+    let yaml = serde_yaml::to_string(&[node])
+        .map_err(|err| CognitionError(format!("Failed to serialize node: {}", err)))?;
+    Ok(yaml.trim_start_matches("---\n").to_string())
+}
+
+// Lines of `old` and `new`, prefixed with `-` when removed, `+` when added and
+// a space when kept, from their longest common subsequence
+fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut diff = String::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push_str(&format!(" {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            diff.push_str(&format!("-{}\n", old[i]));
+            i += 1;
+        } else {
+            diff.push_str(&format!("+{}\n", new[j]));
+            j += 1;
+        }
+    }
+    diff
+}
@@ -0,0 +1,116 @@
+use crate::{CognitionError, DecisionPromptTemplate, History, HistoryStyle, Role};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+// A context to render a prompt template with, and what the rendered prompt must
+// and must not contain. Loaded from a YAML list of fixtures.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct TemplateFixture {
+    pub name: String,
+    // Alternating agent and user turns
+    #[serde(default)]
+    pub history: Vec<String>,
+    // Generated `Question n?` / `Answer n` turns appended to `history`, to test
+    // long histories without writing them out
+    #[serde(default)]
+    pub history_turns: usize,
+    #[serde(default)]
+    pub history_style: HistoryStyle,
+    // Template variables, like `decision_prompt`, `choices`, `user_input` or
+    // `vars.name`. `history` is rendered from the turns above.
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    #[serde(default)]
+    pub contains: Vec<String>,
+    #[serde(default)]
+    pub not_contains: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TemplateFailure {
+    pub fixture: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for TemplateFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.fixture, self.reason)
+    }
+}
+
+impl TemplateFixture {
+    pub fn load(path: &str) -> Result<Vec<Self>, CognitionError> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| CognitionError(format!("Failed to read fixtures: {}", err)))?;
+        serde_yaml::from_str(&content)
+            .map_err(|err| CognitionError(format!("Failed to parse fixtures: {}", err)))
+    }
+
+    pub fn render(&self, template: &DecisionPromptTemplate) -> String {
+        let mut history = History::new(self.history_style);
+        for (index, text) in self.history.iter().enumerate() {
+            let role = if index % 2 == 0 {
+                Role::Agent
+            } else {
+                Role::User
+            };
+            history.push(role, text);
+        }
+        for turn in 1..=self.history_turns {
+            history.push(Role::Agent, &format!("Question {}?", turn));
+            history.push(Role::User, &format!("Answer {}", turn));
+        }
+
+        let mut variables = self.variables.clone();
+        variables.insert("history".to_string(), history.render("Agent", "User"));
+        template.render(&variables)
+    }
+
+    // Render the template and check it, returning every failed expectation
+    pub fn check(&self, template: &DecisionPromptTemplate) -> Vec<TemplateFailure> {
+        let rendered = self.render(template);
+        let failure = |reason: String| TemplateFailure {
+            fixture: self.name.clone(),
+            reason,
+        };
+
+        let mut failures = vec![];
+        for expected in &self.contains {
+            if !rendered.contains(expected.as_str()) {
+                failures.push(failure(format!("missing {:?}", expected)));
+            }
+        }
+        for unexpected in &self.not_contains {
+            if rendered.contains(unexpected.as_str()) {
+                failures.push(failure(format!("unexpected {:?}", unexpected)));
+            }
+        }
+
+        // Placeholders left in the template itself, values are never expanded
+        let placeholder = Regex::new(r"\{\{[^{}]*\}\}").unwrap();
+        let mut values = self.variables.values().cloned().collect::<Vec<_>>();
+        values.extend(self.history.iter().cloned());
+        for found in placeholder.find_iter(&rendered) {
+            if !values.iter().any(|value| value.contains(found.as_str())) {
+                failures.push(failure(format!(
+                    "unrendered placeholder {}",
+                    found.as_str()
+                )));
+            }
+        }
+        failures
+    }
+}
+
+// Check `template` against every fixture
+pub fn test_template(
+    template: &DecisionPromptTemplate,
+    fixtures: &[TemplateFixture],
+) -> Vec<TemplateFailure> {
+    fixtures
+        .iter()
+        .flat_map(|fixture| fixture.check(template))
+        .collect()
+}
@@ -0,0 +1,68 @@
+use super::*;
+use reqwest::Client;
+use serde_json::Value;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// Template served by a CMS or any HTTP endpoint at `prompt_template.url`, with
+// `prompt_template.headers` such as an API key. The body is the template, or
+// with `prompt_template.field` a JSON body holding it at that JSON pointer,
+// e.g. `/fields/body`.
+pub struct HttpTemplate {
+    client: Client,
+    url: String,
+    field: Option<String>,
+}
+
+impl HttpTemplate {
+    pub fn new(config: &str) -> Result<Self, CognitionError> {
+        let url = string_by_path(config, "prompt_template.url")
+            .ok_or_else(|| CognitionError("Missing prompt_template.url".to_string()))?;
+
+        let mut builder = Client::builder().timeout(TIMEOUT);
+        if let Some(headers) = object_by_path(config, "prompt_template.headers") {
+            let headers: std::collections::HashMap<String, String> =
+                serde_json::from_value(headers).map_err(|err| {
+                    CognitionError(format!("Invalid prompt_template.headers: {}", err))
+                })?;
+            builder = builder.default_headers((&headers).try_into().map_err(|err| {
+                CognitionError(format!("Invalid prompt_template.headers: {}", err))
+            })?);
+        }
+        let client = builder
+            .build()
+            .map_err(|err| CognitionError(format!("HTTP client error: {}", err)))?;
+
+        Ok(Self {
+            client,
+            url,
+            field: string_by_path(config, "prompt_template.field"),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl TemplateProvider for HttpTemplate {
+    async fn fetch(&self) -> Result<String, CognitionError> {
+        let body = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| CognitionError(format!("Template request failed: {}", err)))?
+            .text()
+            .await
+            .map_err(|err| CognitionError(format!("Invalid template response: {}", err)))?;
+
+        let Some(field) = &self.field else {
+            return Ok(body);
+        };
+        let json: Value = serde_json::from_str(&body)
+            .map_err(|err| CognitionError(format!("Invalid template response: {}", err)))?;
+        json.pointer(field)
+            .and_then(|value| value.as_str())
+            .map(|template| template.to_string())
+            .ok_or_else(|| CognitionError(format!("No template at {} in the response", field)))
+    }
+}
@@ -1 +1,126 @@
-pub mod decision;
\ No newline at end of file
+use crate::{
+    clock::Instant,
+    config::{duration_by_path, object_by_path, string_by_path},
+    CognitionError,
+};
+use async_trait::async_trait;
+use log::warn;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[cfg(feature = "remote-templates")]
+pub use http::HttpTemplate;
+
+#[cfg(feature = "remote-templates")]
+mod http;
+
+const DEFAULT_REFRESH: Duration = Duration::from_secs(60);
+
+// Source of the decision prompt template, fetched again at the start of every
+// turn so prompt copy kept in a file, database or CMS changes without a
+// redeploy. Slow sources are wrapped in `CachedTemplate`. A failing fetch is
+// logged and the session keeps its current template.
+#[async_trait(?Send)]
+pub trait TemplateProvider {
+    async fn fetch(&self) -> Result<String, CognitionError>;
+}
+
+// Template compiled into the binary, e.g. with `include_str!`
+pub struct EmbeddedTemplate(pub &'static str);
+
+#[async_trait(?Send)]
+impl TemplateProvider for EmbeddedTemplate {
+    async fn fetch(&self) -> Result<String, CognitionError> {
+        Ok(self.0.to_string())
+    }
+}
+
+// Template file read again on every fetch, picking up edits on disk
+pub struct FileTemplate {
+    path: String,
+}
+
+impl FileTemplate {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl TemplateProvider for FileTemplate {
+    async fn fetch(&self) -> Result<String, CognitionError> {
+        std::fs::read_to_string(&self.path)
+            .map_err(|err| CognitionError(format!("Failed to read {}: {}", self.path, err)))
+    }
+}
+
+// Template of another provider, fetched at most once per `refresh` interval.
+// Until a fetch succeeds again, failures serve the last template fetched.
+pub struct CachedTemplate<P> {
+    provider: P,
+    refresh: Duration,
+    cached: Mutex<Option<(Instant, String)>>,
+}
+
+impl<P: TemplateProvider> CachedTemplate<P> {
+    pub fn new(provider: P, refresh: Duration) -> Self {
+        Self {
+            provider,
+            refresh,
+            cached: Mutex::new(None),
+        }
+    }
+
+    fn cached(&self) -> Option<(Instant, String)> {
+        self.cached.lock().unwrap().clone()
+    }
+}
+
+#[async_trait(?Send)]
+impl<P: TemplateProvider> TemplateProvider for CachedTemplate<P> {
+    async fn fetch(&self) -> Result<String, CognitionError> {
+        let cached = self.cached();
+        if let Some((fetched, template)) = &cached {
+            if fetched.elapsed() < self.refresh {
+                return Ok(template.clone());
+            }
+        }
+
+        let template = match (self.provider.fetch().await, cached) {
+            (Ok(template), _) => template,
+            (Err(err), Some((_, template))) => {
+                warn!("Serving the cached prompt template: {}", err);
+                template
+            }
+            (Err(err), None) => return Err(err),
+        };
+        *self.cached.lock().unwrap() = Some((Instant::now(), template.clone()));
+        Ok(template)
+    }
+}
+
+// Provider of `prompt_template.path` or `prompt_template.url`, cached for
+// `prompt_template.refresh` (1 minute by default)
+pub(crate) fn from_config(
+    config: &str,
+) -> Result<Option<Box<dyn TemplateProvider>>, CognitionError> {
+    let refresh = duration_by_path(config, "prompt_template.refresh").unwrap_or(DEFAULT_REFRESH);
+    if let Some(path) = string_by_path(config, "prompt_template.path") {
+        let provider = CachedTemplate::new(FileTemplate::new(&path), refresh);
+        return Ok(Some(Box::new(provider)));
+    }
+    if object_by_path(config, "prompt_template.url").is_some() {
+        #[cfg(feature = "remote-templates")]
+        return Ok(Some(Box::new(CachedTemplate::new(
+            HttpTemplate::new(config)?,
+            refresh,
+        ))));
+        #[cfg(not(feature = "remote-templates"))]
+        return Err(CognitionError(
+            "prompt_template.url needs the `remote-templates` feature".to_string(),
+        ));
+    }
+    Ok(None)
+}
@@ -0,0 +1,156 @@
+use super::*;
+use crate::clock::Instant;
+use reqwest::RequestBuilder;
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Tokens are fetched again this long before they expire
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+// Lifetime of tokens whose response has no `expires_in`
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(3600);
+
+// How a tool authenticates to its API. Secrets written as `${NAME}` are read
+// from the environment variable `NAME` when a request is made.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthScheme {
+    // `Authorization: Bearer <token>`
+    Bearer {
+        token: String,
+    },
+    Basic {
+        username: String,
+        password: String,
+    },
+    // API key in a header, e.g. `X-Api-Key`
+    Header {
+        name: String,
+        value: String,
+    },
+    // API key in the query string, e.g. `appid`
+    Query {
+        name: String,
+        value: String,
+    },
+    // Bearer token from the client credentials grant, fetched again before it
+    // expires or once the API rejects it
+    #[serde(rename = "oauth2")]
+    OAuth2 {
+        token_url: String,
+        client_id: String,
+        client_secret: String,
+        scope: Option<String>,
+    },
+}
+
+// Authentication of a tool's requests, keeping its OAuth2 token
+pub struct ToolAuth {
+    pub scheme: AuthScheme,
+    client: reqwest::Client,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl ToolAuth {
+    pub fn new(scheme: AuthScheme) -> Self {
+        Self {
+            scheme,
+            client: reqwest::Client::new(),
+            token: Mutex::new(None),
+        }
+    }
+
+    // The request with its credentials
+    pub async fn apply(&self, request: RequestBuilder) -> Result<RequestBuilder, CognitionError> {
+        Ok(match &self.scheme {
+            AuthScheme::Bearer { token } => request.bearer_auth(secret(token)?),
+            AuthScheme::Basic { username, password } => {
+                request.basic_auth(secret(username)?, Some(secret(password)?))
+            }
+            AuthScheme::Header { name, value } => request.header(name, secret(value)?),
+            AuthScheme::Query { name, value } => request.query(&[(name, secret(value)?)]),
+            AuthScheme::OAuth2 { .. } => request.bearer_auth(self.access_token().await?),
+        })
+    }
+
+    // Forget the OAuth2 token after the API rejected it
+    pub fn invalidate(&self) {
+        if let Ok(mut token) = self.token.lock() {
+            *token = None;
+        }
+    }
+
+    // Whether a rejected request may succeed again with new credentials
+    pub fn refreshes(&self) -> bool {
+        matches!(self.scheme, AuthScheme::OAuth2 { .. })
+    }
+
+    async fn access_token(&self) -> Result<String, CognitionError> {
+        let cached = self.token.lock().ok().and_then(|token| token.clone());
+        if let Some((token, expires)) = cached {
+            if Instant::now() + REFRESH_MARGIN < expires {
+                return Ok(token);
+            }
+        }
+        let AuthScheme::OAuth2 {
+            token_url,
+            client_id,
+            client_secret,
+            scope,
+        } = &self.scheme
+        else {
+            return Err(CognitionError("Not an OAuth2 scheme".to_string()));
+        };
+
+        let mut form = vec![
+            ("grant_type", "client_credentials".to_string()),
+            ("client_id", secret(client_id)?),
+            ("client_secret", secret(client_secret)?),
+        ];
+        if let Some(scope) = scope {
+            form.push(("scope", scope.clone()));
+        }
+        let response = self
+            .client
+            .post(token_url)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|err| CognitionError(format!("Failed to request a token: {}", err)))?;
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|err| CognitionError(format!("Invalid token response: {}", err)))?;
+        let Some(token) = body["access_token"]
+            .as_str()
+            .filter(|_| status.is_success())
+        else {
+            return Err(CognitionError(format!(
+                "Token request failed with {}: {}",
+                status, body["error"]
+            )));
+        };
+        let lifetime = body["expires_in"]
+            .as_u64()
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TOKEN_LIFETIME);
+
+        if let Ok(mut cached) = self.token.lock() {
+            *cached = Some((token.to_string(), Instant::now() + lifetime));
+        }
+        Ok(token.to_string())
+    }
+}
+
+// Value of a secret, read from the environment when written as `${NAME}`
+fn secret(value: &str) -> Result<String, CognitionError> {
+    match value
+        .strip_prefix("${")
+        .and_then(|name| name.strip_suffix('}'))
+    {
+        Some(name) => std::env::var(name)
+            .map_err(|_| CognitionError(format!("Missing environment variable {}", name))),
+        None => Ok(value.to_string()),
+    }
+}
@@ -0,0 +1,191 @@
+use super::*;
+use crate::config::object_by_path;
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// Tool calling an HTTP API, declared in the config. GET requests send the
+// parameters in the query string, other methods as a JSON body. Parameters
+// are written by the model from `args`, or are the user's input as the
+// `input` parameter. The response body is the tool's response, error statuses
+// fail the call.
+pub struct HttpTool {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub url: String,
+    pub method: Method,
+    pub headers: BTreeMap<String, String>,
+    // Parameter getting the user's input
+    pub input: Option<String>,
+    pub args_prompt: Option<String>,
+    pub auth: Option<ToolAuth>,
+    pub side_effecting: bool,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct HttpToolConfig {
+    id: String,
+    name: Option<String>,
+    #[serde(default)]
+    description: String,
+    url: String,
+    method: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    input: Option<String>,
+    args: Option<String>,
+    auth: Option<AuthScheme>,
+    #[serde(default)]
+    side_effecting: bool,
+}
+
+impl HttpTool {
+    // GET `url` with the input as the `input` parameter
+    pub fn new(id: &str, url: &str, input: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            url: url.to_string(),
+            method: Method::GET,
+            headers: BTreeMap::new(),
+            input: Some(input.to_string()),
+            args_prompt: None,
+            auth: None,
+            side_effecting: false,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    // The tools of `tools.http`
+    pub fn from_config(config: &str) -> Result<Vec<Self>, CognitionError> {
+        let Some(tools) = object_by_path(config, "tools.http") else {
+            return Ok(vec![]);
+        };
+        let tools: Vec<HttpToolConfig> = serde_json::from_value(tools)
+            .map_err(|err| CognitionError(format!("Invalid tools.http: {}", err)))?;
+        tools
+            .into_iter()
+            .map(|tool| {
+                if tool.args.is_none() && tool.input.is_none() {
+                    return Err(CognitionError(format!(
+                        "HTTP tool {} needs `args` or `input`",
+                        tool.id
+                    )));
+                }
+                let method = tool.method.as_deref().unwrap_or("GET");
+                let method = Method::from_bytes(method.to_uppercase().as_bytes())
+                    .map_err(|_| CognitionError(format!("Invalid method of {}", tool.id)))?;
+                Ok(Self {
+                    name: tool.name.unwrap_or_else(|| tool.id.clone()),
+                    id: tool.id,
+                    description: tool.description,
+                    url: tool.url,
+                    method,
+                    headers: tool.headers,
+                    input: tool.input,
+                    args_prompt: tool.args,
+                    auth: tool.auth.map(ToolAuth::new),
+                    side_effecting: tool.side_effecting,
+                    client: reqwest::Client::new(),
+                })
+            })
+            .collect()
+    }
+
+    fn params(&self, input: &str) -> Result<serde_json::Map<String, Value>, CognitionError> {
+        match &self.input {
+            Some(name) if self.args_prompt.is_none() => {
+                let mut params = serde_json::Map::new();
+                params.insert(name.clone(), Value::String(input.to_string()));
+                Ok(params)
+            }
+            _ => serde_json::from_str(input)
+                .map_err(|err| CognitionError(format!("Invalid parameters: {}", err))),
+        }
+    }
+
+    async fn send(
+        &self,
+        params: &serde_json::Map<String, Value>,
+    ) -> Result<(StatusCode, String), CognitionError> {
+        let mut request = self.client.request(self.method.clone(), &self.url);
+        request = if self.method == Method::GET {
+            let query: Vec<(&String, String)> = params
+                .iter()
+                .map(|(name, value)| match value {
+                    Value::String(value) => (name, value.clone()),
+                    value => (name, value.to_string()),
+                })
+                .collect();
+            request.query(&query)
+        } else {
+            request.json(params)
+        };
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(auth) = &self.auth {
+            request = auth.apply(request).await?;
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|err| CognitionError(format!("Failed to send request to tool: {}", err)))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|err| CognitionError(format!("Failed to get response text: {}", err)))?;
+        Ok((status, body))
+    }
+}
+
+#[async_trait(?Send)]
+impl Tool for HttpTool {
+    fn id(&self) -> &String {
+        &self.id
+    }
+
+    fn name(&self) -> &String {
+        &self.name
+    }
+
+    fn description(&self) -> &String {
+        &self.description
+    }
+
+    fn side_effecting(&self) -> bool {
+        self.side_effecting
+    }
+
+    fn args_prompt(&self) -> Option<&str> {
+        self.args_prompt.as_deref()
+    }
+
+    async fn run(&self, input: &String) -> Result<Option<ToolResponse>, CognitionError> {
+        let params = self.params(input)?;
+        let mut response = self.send(&params).await?;
+        // Once more with a new token
+        if let Some(auth) = self.auth.as_ref().filter(|auth| auth.refreshes()) {
+            if response.0 == StatusCode::UNAUTHORIZED {
+                auth.invalidate();
+                response = self.send(&params).await?;
+            }
+        }
+        let (status, body) = response;
+        debug!("{}: {} {}", self.id, status, body);
+        if !status.is_success() {
+            return Err(CognitionError(format!(
+                "{} failed with {}: {}",
+                self.id, status, body
+            )));
+        }
+        Ok(Some(ToolResponse {
+            id: self.id.clone(),
+            response: body,
+        }))
+    }
+}
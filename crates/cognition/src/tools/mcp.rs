@@ -0,0 +1,582 @@
+use super::*;
+use crate::config::{duration_by_path, object_by_path};
+use serde_json::{json, Value};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::process::Stdio;
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+
+const PROTOCOL_VERSION: &str = "2025-06-18";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+thread_local! {
+    // Clients by their config, so sessions share a server's process
+    static CLIENTS: RefCell<HashMap<String, Weak<McpClient>>> = RefCell::new(HashMap::new());
+}
+
+// Client of a Model Context Protocol server, run as a process talking over
+// stdio or reached over streamable HTTP. Connects on the first request and
+// again after the connection failed. Requests are sent one at a time.
+pub struct McpClient {
+    pub id: String,
+    transport: Transport,
+    timeout: Duration,
+    connection: Mutex<Option<Connection>>,
+    next_id: Cell<u64>,
+}
+
+enum Transport {
+    Stdio {
+        command: Vec<String>,
+        env: BTreeMap<String, String>,
+    },
+    Http {
+        url: String,
+        headers: BTreeMap<String, String>,
+        auth: Option<ToolAuth>,
+        client: reqwest::Client,
+    },
+}
+
+enum Connection {
+    Stdio(Box<Process>),
+    Http { session_id: Option<String> },
+}
+
+struct Process {
+    // Killed when dropped
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+// Tool listed by a server
+#[derive(Debug, Clone)]
+pub struct McpToolInfo {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+    // Annotated as not modifying its environment
+    pub read_only: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ServerConfig {
+    id: String,
+    // Program and arguments of a stdio server
+    command: Option<Vec<String>>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    // Endpoint of a streamable HTTP server
+    url: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    auth: Option<AuthScheme>,
+    #[serde(default)]
+    tools: Vec<ToolConfig>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ToolConfig {
+    // Tool name on the server
+    name: String,
+    id: Option<String>,
+    #[serde(default)]
+    description: String,
+    // Parameters for the model to write, see `Tool::args_prompt`
+    args: Option<String>,
+    // Argument getting the user's input as is
+    input: Option<String>,
+    #[serde(default)]
+    side_effecting: bool,
+}
+
+impl McpClient {
+    pub fn stdio(id: &str, command: Vec<String>, env: BTreeMap<String, String>) -> Self {
+        Self::new(id, Transport::Stdio { command, env })
+    }
+
+    pub fn http(
+        id: &str,
+        url: &str,
+        headers: BTreeMap<String, String>,
+        auth: Option<ToolAuth>,
+    ) -> Self {
+        Self::new(
+            id,
+            Transport::Http {
+                url: url.to_string(),
+                headers,
+                auth,
+                client: reqwest::Client::new(),
+            },
+        )
+    }
+
+    fn new(id: &str, transport: Transport) -> Self {
+        Self {
+            id: id.to_string(),
+            transport,
+            timeout: DEFAULT_TIMEOUT,
+            connection: Mutex::new(None),
+            next_id: Cell::new(1),
+        }
+    }
+
+    // Time a request, including connecting, may take
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>, CognitionError> {
+        let mut tools = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            for tool in result["tools"].as_array().into_iter().flatten() {
+                tools.push(McpToolInfo {
+                    name: tool["name"].as_str().unwrap_or_default().to_string(),
+                    description: tool["description"].as_str().unwrap_or_default().to_string(),
+                    input_schema: tool["inputSchema"].clone(),
+                    read_only: tool["annotations"]["readOnlyHint"].as_bool() == Some(true),
+                });
+            }
+            cursor = result["nextCursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    // Text content of the tool's result, or its structured content
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<String, CognitionError> {
+        let result = self
+            .request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        let text: Vec<&str> = result["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|content| content["type"] == "text")
+            .filter_map(|content| content["text"].as_str())
+            .collect();
+        let text = match (text.is_empty(), result.get("structuredContent")) {
+            (true, Some(structured)) => structured.to_string(),
+            _ => text.join("\n"),
+        };
+        if result["isError"].as_bool() == Some(true) {
+            return Err(CognitionError(format!("{} failed: {}", name, text)));
+        }
+        Ok(text)
+    }
+
+    // Result of a request, connecting first if needed. Failed connections are
+    // dropped, errors the server answers with are not.
+    async fn request(&self, method: &str, params: Value) -> Result<Value, CognitionError> {
+        let mut connection = self.connection.lock().await;
+        let sent = crate::clock::timeout(self.timeout, async {
+            if connection.is_none() {
+                *connection = Some(self.connect().await?);
+            }
+            let Some(open) = connection.as_mut() else {
+                return Err(CognitionError("Not connected".to_string()));
+            };
+            self.send(open, method, params).await
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(CognitionError(format!(
+                "{} timed out after {:?}",
+                method, self.timeout
+            )))
+        });
+        let response = match sent {
+            Ok(response) => response,
+            Err(err) => {
+                *connection = None;
+                return Err(CognitionError(format!("MCP server {}: {}", self.id, err.0)));
+            }
+        };
+        match response.get("error") {
+            Some(error) => Err(CognitionError(format!(
+                "MCP server {}: {}",
+                self.id,
+                error["message"].as_str().unwrap_or("request failed")
+            ))),
+            None => Ok(response["result"].clone()),
+        }
+    }
+
+    async fn connect(&self) -> Result<Connection, CognitionError> {
+        let mut connection = match &self.transport {
+            Transport::Stdio { command, env } => {
+                let Some((program, args)) = command.split_first() else {
+                    return Err(CognitionError("Missing command".to_string()));
+                };
+                let mut child = Command::new(program)
+                    .args(args)
+                    .envs(env)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|err| CognitionError(format!("Failed to run {}: {}", program, err)))?;
+                let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+                    return Err(CognitionError("Missing stdio".to_string()));
+                };
+                Connection::Stdio(Box::new(Process {
+                    _child: child,
+                    stdin,
+                    stdout: BufReader::new(stdout).lines(),
+                }))
+            }
+            Transport::Http { .. } => Connection::Http { session_id: None },
+        };
+        let params = json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "cognition", "version": env!("CARGO_PKG_VERSION") },
+        });
+        let response = self.send(&mut connection, "initialize", params).await?;
+        if let Some(error) = response.get("error") {
+            return Err(CognitionError(format!("Initialization failed: {}", error)));
+        }
+        self.notify(&mut connection, "notifications/initialized")
+            .await?;
+        Ok(connection)
+    }
+
+    // Response to a request
+    async fn send(
+        &self,
+        connection: &mut Connection,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, CognitionError> {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        match connection {
+            Connection::Stdio(process) => {
+                let Process { stdin, stdout, .. } = process.as_mut();
+                write_line(stdin, &message).await?;
+                loop {
+                    let line = stdout
+                        .next_line()
+                        .await
+                        .map_err(|err| CognitionError(err.to_string()))?
+                        .ok_or_else(|| CognitionError("Server exited".to_string()))?;
+                    let Ok(received) = serde_json::from_str::<Value>(&line) else {
+                        continue;
+                    };
+                    if received.get("method").is_none() {
+                        if received["id"] == id {
+                            return Ok(received);
+                        }
+                        continue;
+                    }
+                    // Requests of the server, answered so it does not wait
+                    if let Some(request_id) = received.get("id") {
+                        let reply = match received["method"].as_str() {
+                            Some("ping") => {
+                                json!({ "jsonrpc": "2.0", "id": request_id, "result": {} })
+                            }
+                            _ => json!({
+                                "jsonrpc": "2.0",
+                                "id": request_id,
+                                "error": { "code": -32601, "message": "Method not found" },
+                            }),
+                        };
+                        write_line(stdin, &reply).await?;
+                    }
+                }
+            }
+            Connection::Http { session_id } => {
+                let response = self.post(session_id, &message).await?;
+                let messages = match response {
+                    Some(body) => body,
+                    None => return Err(CognitionError("Empty response".to_string())),
+                };
+                messages
+                    .into_iter()
+                    .find(|received| received.get("method").is_none() && received["id"] == id)
+                    .ok_or_else(|| CognitionError(format!("No response to {}", method)))
+            }
+        }
+    }
+
+    async fn notify(
+        &self,
+        connection: &mut Connection,
+        method: &str,
+    ) -> Result<(), CognitionError> {
+        let message = json!({ "jsonrpc": "2.0", "method": method });
+        match connection {
+            Connection::Stdio(process) => write_line(&mut process.stdin, &message).await,
+            Connection::Http { session_id } => self.post(session_id, &message).await.map(|_| ()),
+        }
+    }
+
+    // Messages of the response, as JSON or an event stream, keeping the
+    // session the server assigned
+    async fn post(
+        &self,
+        session_id: &mut Option<String>,
+        message: &Value,
+    ) -> Result<Option<Vec<Value>>, CognitionError> {
+        let Transport::Http {
+            url,
+            headers,
+            auth,
+            client,
+        } = &self.transport
+        else {
+            return Err(CognitionError("Not an HTTP server".to_string()));
+        };
+        let mut request = client
+            .post(url)
+            .header("Accept", "application/json, text/event-stream")
+            .header("MCP-Protocol-Version", PROTOCOL_VERSION)
+            .json(message);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        if let Some(session_id) = session_id.as_deref() {
+            request = request.header("Mcp-Session-Id", session_id);
+        }
+        if let Some(auth) = auth {
+            request = auth.apply(request).await?;
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|err| CognitionError(err.to_string()))?;
+        // Reconnecting fetches a new token
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            if let Some(auth) = auth {
+                auth.invalidate();
+            }
+        }
+        if !response.status().is_success() {
+            return Err(CognitionError(format!("HTTP {}", response.status())));
+        }
+        if let Some(assigned) = response
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|value| value.to_str().ok())
+        {
+            *session_id = Some(assigned.to_string());
+        }
+        let event_stream = response
+            .headers()
+            .get("content-type")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        let body = response
+            .text()
+            .await
+            .map_err(|err| CognitionError(err.to_string()))?;
+        if body.trim().is_empty() {
+            return Ok(None);
+        }
+        if event_stream {
+            return Ok(Some(events(&body)));
+        }
+        let received: Value = serde_json::from_str(&body)
+            .map_err(|err| CognitionError(format!("Invalid response: {}", err)))?;
+        Ok(Some(match received {
+            Value::Array(messages) => messages,
+            message => vec![message],
+        }))
+    }
+}
+
+async fn write_line(stdin: &mut ChildStdin, message: &Value) -> Result<(), CognitionError> {
+    let mut line = message.to_string();
+    line.push('\n');
+    stdin
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|err| CognitionError(err.to_string()))?;
+    stdin
+        .flush()
+        .await
+        .map_err(|err| CognitionError(err.to_string()))
+}
+
+// JSON messages in the data of server-sent events
+fn events(body: &str) -> Vec<Value> {
+    body.split("\n\n")
+        .filter_map(|event| {
+            let data: Vec<&str> = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(str::trim_start)
+                .collect();
+            serde_json::from_str(&data.join("\n")).ok()
+        })
+        .collect()
+}
+
+// Tool of an MCP server. Its arguments are written by the model when it has
+// an `args_prompt`, or are the user's input as the `input` argument.
+pub struct McpTool {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub client: Rc<McpClient>,
+    // Tool name on the server
+    pub tool: String,
+    pub args_prompt: Option<String>,
+    pub input: Option<String>,
+    pub side_effecting: bool,
+}
+
+impl McpTool {
+    // Tool with the server's description, whose arguments the model writes
+    // from the tool's input schema
+    pub fn from_info(client: Rc<McpClient>, info: &McpToolInfo) -> Self {
+        Self {
+            id: format!("{}_{}", client.id, info.name),
+            name: info.name.clone(),
+            description: info.description.clone(),
+            tool: info.name.clone(),
+            args_prompt: Some(format!(
+                "Arguments matching this JSON Schema: {}",
+                info.input_schema
+            )),
+            input: None,
+            side_effecting: !info.read_only,
+            client,
+        }
+    }
+
+    // Every tool of the server
+    pub async fn discover(client: Rc<McpClient>) -> Result<Vec<Self>, CognitionError> {
+        let tools = client.list_tools().await?;
+        Ok(tools
+            .iter()
+            .map(|info| Self::from_info(client.clone(), info))
+            .collect())
+    }
+
+    // The tools listed in `mcp.servers`
+    pub fn from_config(config: &str) -> Result<Vec<Self>, CognitionError> {
+        let Some(servers) = object_by_path(config, "mcp.servers") else {
+            return Ok(vec![]);
+        };
+        let servers: Vec<ServerConfig> = serde_json::from_value(servers)
+            .map_err(|err| CognitionError(format!("Invalid mcp.servers: {}", err)))?;
+        let timeout = duration_by_path(config, "mcp.timeout").unwrap_or(DEFAULT_TIMEOUT);
+
+        let mut tools = vec![];
+        for server in servers {
+            let client = shared_client(&server, timeout)?;
+            for tool in server.tools {
+                if tool.args.is_none() && tool.input.is_none() {
+                    return Err(CognitionError(format!(
+                        "MCP tool {} of {} needs `args` or `input`",
+                        tool.name, server.id
+                    )));
+                }
+                tools.push(Self {
+                    id: tool
+                        .id
+                        .unwrap_or_else(|| format!("{}_{}", server.id, tool.name)),
+                    name: tool.name.clone(),
+                    description: tool.description,
+                    client: client.clone(),
+                    tool: tool.name,
+                    args_prompt: tool.args,
+                    input: tool.input,
+                    side_effecting: tool.side_effecting,
+                });
+            }
+        }
+        Ok(tools)
+    }
+}
+
+// Client of the server, shared with live tools configured the same way
+fn shared_client(
+    server: &ServerConfig,
+    timeout: Duration,
+) -> Result<Rc<McpClient>, CognitionError> {
+    let key = json!([server, timeout.as_millis() as u64]).to_string();
+    if let Some(client) = CLIENTS.with(|clients| clients.borrow().get(&key).and_then(Weak::upgrade))
+    {
+        return Ok(client);
+    }
+    let client = match (&server.command, &server.url) {
+        (Some(command), None) => McpClient::stdio(&server.id, command.clone(), server.env.clone()),
+        (None, Some(url)) => McpClient::http(
+            &server.id,
+            url,
+            server.headers.clone(),
+            server.auth.clone().map(ToolAuth::new),
+        ),
+        _ => {
+            return Err(CognitionError(format!(
+                "MCP server {} needs either `command` or `url`",
+                server.id
+            )))
+        }
+    };
+    let client = Rc::new(client.with_timeout(timeout));
+    CLIENTS.with(|clients| {
+        let mut clients = clients.borrow_mut();
+        clients.retain(|_, client| client.strong_count() > 0);
+        clients.insert(key, Rc::downgrade(&client));
+    });
+    Ok(client)
+}
+
+#[async_trait(?Send)]
+impl Tool for McpTool {
+    fn id(&self) -> &String {
+        &self.id
+    }
+
+    fn name(&self) -> &String {
+        &self.name
+    }
+
+    fn description(&self) -> &String {
+        &self.description
+    }
+
+    fn side_effecting(&self) -> bool {
+        self.side_effecting
+    }
+
+    fn args_prompt(&self) -> Option<&str> {
+        self.args_prompt.as_deref()
+    }
+
+    async fn run(&self, input: &String) -> Result<Option<ToolResponse>, CognitionError> {
+        debug!("{}: {}", self.id, input);
+        let arguments = match &self.input {
+            Some(argument) => json!({ argument: input }),
+            None => serde_json::from_str(input)
+                .map_err(|err| CognitionError(format!("Invalid arguments: {}", err)))?,
+        };
+        let response = self.client.call_tool(&self.tool, arguments).await?;
+        Ok(Some(ToolResponse {
+            id: self.id.clone(),
+            response,
+        }))
+    }
+}
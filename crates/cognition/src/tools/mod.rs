@@ -1,26 +1,165 @@
-use crate::CognitionError;
+use crate::{config::object_by_path, history::unindent, CognitionError};
 use async_trait::async_trait;
 use log::debug;
-use reqwest::{header::HeaderMap, Url};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 
 // Easy access to tools
+#[cfg(feature = "reqwest")]
+pub use auth::{AuthScheme, ToolAuth};
+#[cfg(feature = "tools-http")]
+pub use http::HttpTool;
+#[cfg(feature = "mcp")]
+pub use mcp::{McpClient, McpTool, McpToolInfo};
+#[cfg(feature = "tools-shell")]
+pub use shell::Shell;
 pub use signal::Signal;
+#[cfg(feature = "tools-http")]
 pub use wolfram_alpha::WolframAlpha;
 
+#[cfg(feature = "reqwest")]
+mod auth;
+#[cfg(feature = "tools-http")]
+mod http;
+#[cfg(feature = "mcp")]
+mod mcp;
+#[cfg(feature = "tools-shell")]
+mod shell;
 mod signal;
+#[cfg(feature = "tools-http")]
 mod wolfram_alpha;
 
+// Tools set up by the config, e.g. `tools.http`, `tools.shell.commands` or
+// `mcp.servers`
+pub(crate) fn from_config(config: &str) -> Result<Vec<Box<dyn Tool>>, CognitionError> {
+    // Every kind can be disabled by features
+    #[allow(unused_mut)]
+    let mut tools: Vec<Box<dyn Tool>> = vec![];
+    if object_by_path(config, "tools.http").is_some() {
+        #[cfg(feature = "tools-http")]
+        tools.extend(
+            HttpTool::from_config(config)?
+                .into_iter()
+                .map(|tool| Box::new(tool) as Box<dyn Tool>),
+        );
+        #[cfg(not(feature = "tools-http"))]
+        return Err(CognitionError(
+            "tools.http needs the `tools-http` feature".to_string(),
+        ));
+    }
+    if object_by_path(config, "tools.shell").is_some() {
+        #[cfg(feature = "tools-shell")]
+        tools.extend(
+            Shell::from_config(config)?
+                .into_iter()
+                .map(|tool| Box::new(tool) as Box<dyn Tool>),
+        );
+        #[cfg(not(feature = "tools-shell"))]
+        return Err(CognitionError(
+            "tools.shell needs the `tools-shell` feature".to_string(),
+        ));
+    }
+    if object_by_path(config, "mcp.servers").is_some() {
+        #[cfg(feature = "mcp")]
+        tools.extend(
+            McpTool::from_config(config)?
+                .into_iter()
+                .map(|tool| Box::new(tool) as Box<dyn Tool>),
+        );
+        #[cfg(not(feature = "mcp"))]
+        return Err(CognitionError(
+            "mcp.servers needs the `mcp` feature".to_string(),
+        ));
+    }
+    Ok(tools)
+}
+
+// Receives intermediate status from long-running tools
+#[async_trait(?Send)]
+pub trait ToolProgress {
+    async fn on_tool_progress(&self, message: &str, percent: Option<f32>);
+}
+
 #[async_trait(?Send)]
 pub trait Tool {
     fn id(&self) -> &String;
     fn name(&self) -> &String;
     fn description(&self) -> &String;
+    #[allow(clippy::ptr_arg)] // `&String` is part of the public trait
     async fn run(&self, input: &String) -> Result<Option<ToolResponse>, CognitionError>;
+
+    // Tools whose downstream API deduplicates requests (payments, ticketing)
+    // declare it here and receive an idempotency key in `run_idempotent`
+    fn supports_idempotency(&self) -> bool {
+        false
+    }
+
+    // Run with a key that stays the same across every retry of this invocation
+    async fn run_idempotent(
+        &self,
+        input: &str,
+        _idempotency_key: &str,
+        progress: &dyn ToolProgress,
+    ) -> Result<Option<ToolResponse>, CognitionError> {
+        self.run_with_progress(input, progress).await
+    }
+
+    // With `redaction.enabled`, tools get user input with personal data
+    // replaced by placeholders unless they need the real values
+    fn needs_personal_data(&self) -> bool {
+        false
+    }
+
+    // Tools with side effects (bookings, emails) are persisted to the session
+    // store before and after they run, so a replay after a crash skips them
+    fn side_effecting(&self) -> bool {
+        false
+    }
+
+    // Tools taking structured parameters describe them here, e.g. `The city
+    // to get the weather for, as {"city": "..."}`. The model then writes them
+    // as a JSON object from the conversation, which the tool gets as input.
+    fn args_prompt(&self) -> Option<&str> {
+        None
+    }
+
+    // Long-running tools override this to report progress while they work
+    async fn run_with_progress(
+        &self,
+        input: &str,
+        _progress: &dyn ToolProgress,
+    ) -> Result<Option<ToolResponse>, CognitionError> {
+        self.run(&input.to_string()).await
+    }
 }
 
-#[derive(Debug, Clone)]
+// Prompt for the model to write a tool's parameters from the history
+pub(crate) fn args_prompt(history: &str, tool: &dyn Tool, args_prompt: &str) -> String {
+    format!(
+        "Conversation so far:\n{}\n\nTool: {} ({})\n{}\nRespond with the parameters as a JSON object only.\n\nParameters:",
+        unindent(history),
+        tool.name(),
+        tool.description(),
+        args_prompt
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ToolResponse {
     pub id: String,
     pub response: String,
 }
+
+// Result of one tool call of a turn. Failed calls are reported rather than
+// failing the turn.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ToolOutcome {
+    Ok(ToolResponse),
+    Error {
+        tool_id: String,
+        message: String,
+        // Whether running the tool again is safe, it has no side effects or
+        // supports idempotency
+        retryable: bool,
+    },
+}
@@ -0,0 +1,239 @@
+use super::*;
+use crate::config::{duration_by_path, f64_by_path, object_by_path};
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_MAX_OUTPUT: usize = 64 * 1024;
+const INPUT_PLACEHOLDER: &str = "{{input}}";
+
+// Local command run with the user input, for automation trees. Safe by
+// default: the program must be in `allow`, it runs without a shell, in
+// `working_dir`, with a cleared environment but `PATH`, and is killed after
+// `timeout` or once its output passes `max_output` bytes. The input replaces
+// `{{input}}` within a single argument, and is refused when it looks like an
+// option or a path leaving the working directory.
+pub struct Shell {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    // Program and arguments
+    pub command: Vec<String>,
+    // Programs that may run, as written in commands
+    pub allow: Vec<String>,
+    pub working_dir: PathBuf,
+    pub timeout: Duration,
+    pub max_output: usize,
+}
+
+#[derive(Deserialize)]
+struct ShellCommand {
+    id: String,
+    name: Option<String>,
+    #[serde(default)]
+    description: String,
+    command: Vec<String>,
+}
+
+impl Shell {
+    // Runs nothing until its program is allowed
+    pub fn new(id: &str, command: Vec<String>, working_dir: PathBuf) -> Self {
+        Self {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            command,
+            allow: vec![],
+            working_dir,
+            timeout: DEFAULT_TIMEOUT,
+            max_output: DEFAULT_MAX_OUTPUT,
+        }
+    }
+
+    // The commands of `tools.shell`
+    pub fn from_config(config: &str) -> Result<Vec<Self>, CognitionError> {
+        let Some(shell) = object_by_path(config, "tools.shell") else {
+            return Ok(vec![]);
+        };
+        let allow: Vec<String> = serde_json::from_value(shell["allow"].clone())
+            .map_err(|_| CognitionError("Missing tools.shell.allow".to_string()))?;
+        let working_dir = shell["working_dir"]
+            .as_str()
+            .ok_or_else(|| CognitionError("Missing tools.shell.working_dir".to_string()))?;
+        let commands: Vec<ShellCommand> = serde_json::from_value(shell["commands"].clone())
+            .map_err(|err| CognitionError(format!("Invalid tools.shell.commands: {}", err)))?;
+        let timeout = duration_by_path(config, "tools.shell.timeout").unwrap_or(DEFAULT_TIMEOUT);
+        let max_output = f64_by_path(config, "tools.shell.max_output")
+            .map(|max_output| max_output as usize)
+            .unwrap_or(DEFAULT_MAX_OUTPUT);
+
+        commands
+            .into_iter()
+            .map(|command| {
+                let mut tool = Shell::new(&command.id, command.command, working_dir.into());
+                tool.name = command.name.unwrap_or(command.id);
+                tool.description = command.description;
+                tool.allow = allow.clone();
+                tool.timeout = timeout;
+                tool.max_output = max_output;
+                tool.check()?;
+                Ok(tool)
+            })
+            .collect()
+    }
+
+    // The program is allowed and the working directory exists
+    pub fn check(&self) -> Result<PathBuf, CognitionError> {
+        let Some(program) = self.command.first() else {
+            return Err(CognitionError(format!(
+                "Shell tool {} has no command",
+                self.id
+            )));
+        };
+        if !self.allow.contains(program) {
+            return Err(CognitionError(format!(
+                "Shell tool {} runs {}, which is not allowed",
+                self.id, program
+            )));
+        }
+        self.working_dir.canonicalize().map_err(|err| {
+            CognitionError(format!(
+                "Invalid working directory {:?}: {}",
+                self.working_dir, err
+            ))
+        })
+    }
+
+    fn args(&self, input: &str) -> Result<Vec<String>, CognitionError> {
+        let input = input.trim();
+        if input.starts_with('-') {
+            return Err(CognitionError(
+                "Shell input must not be an option".to_string(),
+            ));
+        }
+        if escapes(Path::new(input)) {
+            return Err(CognitionError(
+                "Shell input must stay within the working directory".to_string(),
+            ));
+        }
+        Ok(self.command[1..]
+            .iter()
+            .map(|arg| arg.replace(INPUT_PLACEHOLDER, input))
+            .collect())
+    }
+
+    async fn execute(&self, input: &str) -> Result<String, CognitionError> {
+        let working_dir = self.check()?;
+        let mut command = Command::new(&self.command[0]);
+        command
+            .args(self.args(input)?)
+            .current_dir(working_dir)
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Ok(path) = std::env::var("PATH") {
+            command.env("PATH", path);
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|err| CognitionError(format!("Failed to run {}: {}", self.command[0], err)))?;
+        let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+            return Err(CognitionError("Missing command output".to_string()));
+        };
+
+        // Dropping the child on timeout kills it
+        let run = async {
+            let (stdout, stderr) = tokio::join!(
+                read_capped(stdout, self.max_output),
+                read_capped(stderr, self.max_output)
+            );
+            let (Some(stdout), Some(stderr)) = (stdout?, stderr?) else {
+                // Blocked writing the rest
+                let _ = child.kill().await;
+                return Err(CognitionError(format!(
+                    "{} wrote more than {} bytes",
+                    self.id, self.max_output
+                )));
+            };
+            let status = child
+                .wait()
+                .await
+                .map_err(|err| CognitionError(format!("Failed to run command: {}", err)))?;
+            Ok((status, stdout, stderr))
+        };
+        let (status, stdout, stderr) =
+            crate::clock::timeout(self.timeout, run)
+                .await
+                .map_err(|_| {
+                    CognitionError(format!("{} timed out after {:?}", self.id, self.timeout))
+                })??;
+
+        if !status.success() {
+            return Err(CognitionError(format!(
+                "{} failed with {}: {}",
+                self.id,
+                status,
+                String::from_utf8_lossy(&stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+    }
+}
+
+// Output up to `max` bytes, `None` past it
+async fn read_capped(
+    output: impl AsyncRead + Unpin,
+    max: usize,
+) -> Result<Option<Vec<u8>>, CognitionError> {
+    let mut bytes = vec![];
+    output
+        .take(max as u64 + 1)
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(|err| CognitionError(format!("Failed to read command output: {}", err)))?;
+    Ok((bytes.len() <= max).then_some(bytes))
+}
+
+// Absolute, or climbing above where it starts
+fn escapes(path: &Path) -> bool {
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return true,
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+        }
+    }
+    false
+}
+
+#[async_trait(?Send)]
+impl Tool for Shell {
+    fn id(&self) -> &String {
+        &self.id
+    }
+
+    fn name(&self) -> &String {
+        &self.name
+    }
+
+    fn description(&self) -> &String {
+        &self.description
+    }
+
+    async fn run(&self, input: &String) -> Result<Option<ToolResponse>, CognitionError> {
+        debug!("{}: {}", self.id, input);
+        let response = self.execute(input).await?;
+        Ok(Some(ToolResponse {
+            id: self.id.clone(),
+            response,
+        }))
+    }
+}
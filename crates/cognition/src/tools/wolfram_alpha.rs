@@ -1,4 +1,7 @@
 use super::*;
+use reqwest::{header::HeaderMap, Url};
+use serde_json::Value;
+use std::collections::HashMap;
 
 pub struct WolframAlpha {
     pub id: String,
@@ -6,6 +9,8 @@ pub struct WolframAlpha {
     pub description: String,
     pub endpoint: Url,
     pub params: HashMap<String, String>,
+    // See `Tool::args_prompt`, parameters are added to the query
+    pub args_prompt: Option<String>,
 }
 
 impl WolframAlpha {
@@ -16,6 +21,7 @@ impl WolframAlpha {
             description: "Wolfram Alpha is a computational knowledge engine".to_string(),
             endpoint: "https://api.wolframalpha.com/v1/result".try_into().unwrap(),
             params: vec![("appid".to_string(), app_id)].into_iter().collect(),
+            args_prompt: None,
         }
     }
 }
@@ -34,12 +40,30 @@ impl Tool for WolframAlpha {
         &self.description
     }
 
+    fn args_prompt(&self) -> Option<&str> {
+        self.args_prompt.as_deref()
+    }
+
     async fn run(&self, input: &String) -> Result<Option<ToolResponse>, CognitionError> {
         let client = reqwest::Client::new();
         let headers = HeaderMap::new();
 
+        // Parameters written by the model, otherwise the input is the query
         let mut params = self.params.clone();
-        params.insert("i".to_string(), input.clone());
+        match serde_json::from_str::<serde_json::Map<String, Value>>(input) {
+            Ok(args) if self.args_prompt.is_some() => {
+                for (name, value) in args {
+                    let value = match value {
+                        Value::String(value) => value,
+                        value => value.to_string(),
+                    };
+                    params.insert(name, value);
+                }
+            }
+            _ => {
+                params.insert("i".to_string(), input.to_string());
+            }
+        }
 
         // Create query string from params
         let query_string = serde_urlencoded::to_string(params).unwrap();
@@ -60,7 +84,7 @@ impl Tool for WolframAlpha {
         debug!("{}: {}", self.id, response);
         Ok(Some(ToolResponse {
             id: self.id.clone(),
-            response: response,
+            response,
         }))
     }
 }
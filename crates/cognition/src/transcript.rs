@@ -0,0 +1,76 @@
+use crate::{run_decision, CognitionError, DecisionResult, DecisionState};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+
+// Recorded session: every decision result, in order, including the user input
+// that produced it
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Transcript {
+    pub results: Vec<DecisionResult>,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Load a transcript from a JSON file
+    pub fn load(path: &str) -> Result<Self, CognitionError> {
+        let mut file = File::open(path)
+            .map_err(|err| CognitionError(format!("Failed to open transcript: {}", err)))?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)
+            .map_err(|err| CognitionError(format!("Failed to read transcript: {}", err)))?;
+        serde_json::from_str(&content)
+            .map_err(|err| CognitionError(format!("Failed to parse transcript: {}", err)))
+    }
+
+    // Write the transcript to a JSON file
+    pub fn save(&self, path: &str) -> Result<(), CognitionError> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|err| CognitionError(format!("Failed to serialize transcript: {}", err)))?;
+        let mut file = File::create(path)
+            .map_err(|err| CognitionError(format!("Failed to create transcript: {}", err)))?;
+        file.write_all(content.as_bytes())
+            .map_err(|err| CognitionError(format!("Failed to write transcript: {}", err)))
+    }
+
+    pub fn record(&mut self, result: &DecisionResult) {
+        self.results.push(result.clone());
+    }
+
+    // Node path visited by the recorded session
+    pub fn path(&self) -> Vec<&str> {
+        self.results
+            .iter()
+            .map(|result| result.current_id.as_str())
+            .collect()
+    }
+}
+
+// Feed the recorded user inputs back through the engine, failing on the first
+// turn that lands on a different node than the recording
+pub async fn replay(
+    transcript: &Transcript,
+    state: &mut DecisionState,
+) -> Result<Transcript, CognitionError> {
+    let mut replayed = Transcript::new();
+
+    for (turn, recorded) in transcript.results.iter().enumerate() {
+        let result = run_decision(recorded.user_input.clone(), state)
+            .await?
+            .ok_or_else(|| CognitionError(format!("Replay ended early at turn {}", turn)))?;
+
+        if result.current_id != recorded.current_id {
+            return Err(CognitionError(format!(
+                "Replay diverged at turn {}: expected '{}', got '{}'",
+                turn, recorded.current_id, result.current_id
+            )));
+        }
+
+        replayed.record(&result);
+    }
+
+    Ok(replayed)
+}
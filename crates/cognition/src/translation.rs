@@ -0,0 +1,39 @@
+// Translation bridge prompts, so a tree written in one language can serve
+// locales without string bundles. User input is translated to the tree's base
+// language and agent text back to the user's.
+
+// Whether two locales share a language, e.g. `en-US` and `en`
+pub fn same_language(a: &str, b: &str) -> bool {
+    let language = |locale: &str| {
+        locale
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+    };
+    language(a) == language(b)
+}
+
+pub fn prompt(text: &str, from: &str, to: &str) -> String {
+    format!(
+        "Translate the text from {} to {}. Keep placeholders like [EMAIL_1] unchanged. \
+         Respond with the translation only.\n\nText: {}\nTranslation:",
+        from, to, text
+    )
+}
+
+// Model output without quotes or a repeated label around the translation
+pub fn clean(output: &str) -> String {
+    let output = output.trim();
+    let output = output.strip_prefix("Translation:").unwrap_or(output).trim();
+    output
+        .strip_prefix('"')
+        .and_then(|output| output.strip_suffix('"'))
+        .unwrap_or(output)
+        .to_string()
+}
+
+// Room for the translation, a little longer than the text
+pub fn max_length(text: &str) -> usize {
+    crate::compression::estimate_tokens(text) * 2 + 16
+}
@@ -0,0 +1,168 @@
+use crate::Decision;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+
+// Node ID shared with the tree's index, so the engine clones and compares IDs
+// without copying strings. IDs taken from the same tree compare by pointer.
+#[derive(Clone, Debug, Eq, PartialOrd, Ord)]
+pub struct NodeId(Arc<str>);
+
+impl NodeId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for NodeId {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+// Hashes like `str` so the index can be searched with a `&str`
+impl Hash for NodeId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl Borrow<str> for NodeId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for NodeId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for NodeId {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for NodeId {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl From<&str> for NodeId {
+    fn from(id: &str) -> Self {
+        Self(Arc::from(id))
+    }
+}
+
+impl Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for NodeId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+// Deserialized IDs are not shared with any tree, see `DecisionTree::id`
+impl<'de> Deserialize<'de> for NodeId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|id| NodeId::from(id.as_str()))
+    }
+}
+
+// Decision nodes with an index from ID to position, built once so lookups on
+// every turn do not scan the nodes. With duplicate IDs the first node wins,
+// like a linear search.
+#[derive(Clone, Debug, Default)]
+pub struct DecisionTree {
+    nodes: Vec<Decision>,
+    index: HashMap<NodeId, usize>,
+    version: u64,
+}
+
+impl DecisionTree {
+    pub fn new(nodes: Vec<Decision>) -> Self {
+        let mut tree = Self::default();
+        tree.extend(nodes);
+        tree
+    }
+
+    pub fn extend(&mut self, nodes: Vec<Decision>) {
+        for decision_node in nodes {
+            let position = self.nodes.len();
+            if !self.index.contains_key(decision_node.id.as_str()) {
+                self.index
+                    .insert(NodeId::from(decision_node.id.as_str()), position);
+            }
+            self.nodes.push(decision_node);
+        }
+
+        let mut hasher = DefaultHasher::new();
+        for decision_node in &self.nodes {
+            decision_node.id.hash(&mut hasher);
+            for choice in decision_node.choices() {
+                choice.text.hash(&mut hasher);
+                choice.aliases.hash(&mut hasher);
+            }
+        }
+        self.version = hasher.finish();
+    }
+
+    // Hash of the node IDs and choice wording, changing when the tree is
+    // edited or localized, for caches derived from the choices
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Decision> {
+        self.index.get(id).map(|&position| &self.nodes[position])
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.index.contains_key(id)
+    }
+
+    // The tree's own copy of an ID, or a new one for IDs not in the tree
+    pub fn id(&self, id: &str) -> NodeId {
+        match self.index.get_key_value(id) {
+            Some((node_id, _)) => node_id.clone(),
+            None => NodeId::from(id),
+        }
+    }
+
+    pub fn nodes(&self) -> &[Decision] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+// Embed a YAML tree in the binary, parsed and validated at compile time so an
+// invalid tree fails the build. The path is relative to the crate's manifest:
+//
+//     let decision_nodes: Vec<Decision> = cognition::include_tree!("decision_tree.yaml");
+#[macro_export]
+macro_rules! include_tree {
+    ($path:literal) => {
+        $crate::embedded_tree($crate::__tree_json!($path))
+    };
+}
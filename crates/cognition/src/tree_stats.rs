@@ -0,0 +1,127 @@
+use crate::{
+    compression::estimate_tokens, validate_tree, Decision, Transition, TreeError, START_ID,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+// Derived information about a node, for editors and visualizers
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NodeInfo {
+    // Fewest transitions from the start node, none when unreachable
+    pub depth: Option<usize>,
+    pub reachable: bool,
+    // Validation problems of the node
+    pub errors: Vec<String>,
+    // Estimated tokens of the node text, and of its choices in prompts
+    pub text_tokens: usize,
+    pub choice_tokens: usize,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TreeStats {
+    pub nodes: BTreeMap<String, NodeInfo>,
+    // Validation problems not tied to a node, like a missing start node
+    pub errors: Vec<String>,
+    pub max_depth: usize,
+}
+
+impl TreeStats {
+    // Walk the tree from the start node, following choices, default and tool
+    // error transitions and subtree calls. Nodes only entered from the config,
+    // like `on_idle.node`, count as unreachable.
+    pub fn new(decision_nodes: &[Decision]) -> Self {
+        let mut nodes: BTreeMap<String, NodeInfo> = decision_nodes
+            .iter()
+            .map(|decision_node| {
+                let info = NodeInfo {
+                    text_tokens: estimate_tokens(&decision_node.text),
+                    choice_tokens: decision_node
+                        .choices()
+                        .iter()
+                        .map(|choice| estimate_tokens(&choice.text))
+                        .sum(),
+                    ..NodeInfo::default()
+                };
+                (decision_node.id.clone(), info)
+            })
+            .collect();
+
+        let mut errors = vec![];
+        for error in validate_tree(decision_nodes).err().unwrap_or_default() {
+            let node_id = match &error {
+                TreeError::DuplicateId(id) => id,
+                TreeError::UnknownTarget { node_id, .. } => node_id,
+                TreeError::MissingStart => {
+                    errors.push(error.to_string());
+                    continue;
+                }
+            };
+            if let Some(info) = nodes.get_mut(node_id) {
+                info.errors.push(error.to_string());
+            }
+        }
+
+        // Breadth first, so the first visit is the shortest path
+        let mut by_id = HashMap::new();
+        for decision_node in decision_nodes {
+            by_id
+                .entry(decision_node.id.as_str())
+                .or_insert(decision_node);
+        }
+        let mut queue = VecDeque::new();
+        if let Some(info) = nodes.get_mut(START_ID) {
+            info.depth = Some(0);
+            info.reachable = true;
+            queue.push_back((START_ID.to_string(), 0));
+        }
+        let mut max_depth = 0;
+        while let Some((id, depth)) = queue.pop_front() {
+            max_depth = max_depth.max(depth);
+            let Some(decision_node) = by_id.get(id.as_str()) else {
+                continue;
+            };
+            let transitions = decision_node
+                .choices()
+                .into_iter()
+                .map(|choice| &choice.next_id)
+                .chain(decision_node.next.iter())
+                .chain(decision_node.default_next_id.iter())
+                .chain(decision_node.on_tool_error.iter())
+                .chain(decision_node.on_fail.iter());
+            let targets = transitions
+                .filter_map(|transition| match transition {
+                    Transition::Node(target) | Transition::Call(target) => Some(target),
+                    _ => None,
+                })
+                .chain(
+                    decision_node
+                        .choices()
+                        .into_iter()
+                        .filter_map(|choice| choice.return_id.as_ref()),
+                );
+            for target in targets {
+                if let Some(info) = nodes.get_mut(target) {
+                    if !info.reachable {
+                        info.depth = Some(depth + 1);
+                        info.reachable = true;
+                        queue.push_back((target.clone(), depth + 1));
+                    }
+                }
+            }
+        }
+
+        Self {
+            nodes,
+            errors,
+            max_depth,
+        }
+    }
+
+    pub fn unreachable(&self) -> Vec<&str> {
+        self.nodes
+            .iter()
+            .filter(|(_, info)| !info.reachable)
+            .map(|(id, _)| id.as_str())
+            .collect()
+    }
+}
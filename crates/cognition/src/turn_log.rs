@@ -0,0 +1,72 @@
+use crate::{CognitionError, ToolResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+// One line of the turn log, see `logging.turns` in the config
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TurnRecord {
+    pub session_id: String,
+    pub turn: usize,
+    // Seconds since the Unix epoch
+    pub timestamp: u64,
+    // Node the user answered
+    pub node_id: String,
+    // Node the turn ended on
+    pub next_id: String,
+    pub user_input: Option<String>,
+    pub prompt: Option<String>,
+    pub model_outputs: Vec<String>,
+    // Choices taken, the user's first, then any predicted ones
+    pub choices: Vec<String>,
+    pub tool_responses: Vec<ToolResponse>,
+    pub latency_ms: u64,
+    // Estimated prompt and completion tokens of every model call in the turn
+    pub tokens: usize,
+    // A/B variants of the session by node ID
+    #[serde(default)]
+    pub variants: BTreeMap<String, String>,
+}
+
+impl TurnRecord {
+    // Turns of every `*.jsonl` turn log in `dir`
+    pub fn load_dir(dir: &str) -> Result<Vec<Self>, CognitionError> {
+        let entries = fs::read_dir(dir)
+            .map_err(|err| CognitionError(format!("Failed to list logs: {}", err)))?;
+
+        let mut turns = vec![];
+        for entry in entries {
+            let path = entry
+                .map_err(|err| CognitionError(format!("Failed to list logs: {}", err)))?
+                .path();
+            if path
+                .extension()
+                .is_none_or(|extension| extension != "jsonl")
+            {
+                continue;
+            }
+            let content = fs::read_to_string(&path)
+                .map_err(|err| CognitionError(format!("Failed to read {:?}: {}", path, err)))?;
+            for line in content.lines().filter(|line| !line.trim().is_empty()) {
+                turns.push(serde_json::from_str(line).map_err(|err| {
+                    CognitionError(format!("Failed to parse turn in {:?}: {}", path, err))
+                })?);
+            }
+        }
+        Ok(turns)
+    }
+}
+
+// Append `record` as one JSON line to the file at `path`
+pub fn append(path: &str, record: &TurnRecord) -> Result<(), CognitionError> {
+    let mut line = serde_json::to_string(record)
+        .map_err(|err| CognitionError(format!("Failed to serialize turn: {}", err)))?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|err| CognitionError(format!("Failed to write turn log: {}", err)))
+}
@@ -0,0 +1,90 @@
+use crate::{compression::estimate_tokens, config::f64_by_path};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+// Price per 1000 tokens, e.g. `pricing: { prompt_per_1k: 0.02, completion_per_1k: 0.02 }`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pricing {
+    pub prompt_per_1k: f64,
+    pub completion_per_1k: f64,
+}
+
+impl Pricing {
+    pub fn from_config(config: &str) -> Option<Self> {
+        let prompt_per_1k = f64_by_path(config, "pricing.prompt_per_1k");
+        let completion_per_1k = f64_by_path(config, "pricing.completion_per_1k");
+        if prompt_per_1k.is_none() && completion_per_1k.is_none() {
+            return None;
+        }
+        Some(Self {
+            prompt_per_1k: prompt_per_1k.unwrap_or_default(),
+            completion_per_1k: completion_per_1k.unwrap_or_default(),
+        })
+    }
+}
+
+// Model calls and estimated tokens, of a turn or a whole session. Cost is in
+// the currency of the config's `pricing`, unknown without it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Usage {
+    pub model_calls: usize,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub cost: Option<f64>,
+}
+
+impl Usage {
+    pub fn total_tokens(&self) -> usize {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    pub(crate) fn record(&mut self, prompt: &str, completion: &str, pricing: Option<Pricing>) {
+        let prompt_tokens = estimate_tokens(prompt);
+        let completion_tokens = estimate_tokens(completion);
+        self.model_calls += 1;
+        self.prompt_tokens += prompt_tokens;
+        self.completion_tokens += completion_tokens;
+        if let Some(pricing) = pricing {
+            let cost = (prompt_tokens as f64 * pricing.prompt_per_1k
+                + completion_tokens as f64 * pricing.completion_per_1k)
+                / 1000.0;
+            self.cost = Some(self.cost.unwrap_or_default() + cost);
+        }
+    }
+
+    // Add the usage of another session or turn
+    pub fn add(&mut self, other: &Usage) {
+        self.model_calls += other.model_calls;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        if let Some(cost) = other.cost {
+            self.cost = Some(self.cost.unwrap_or_default() + cost);
+        }
+    }
+
+    // What was used after `earlier`, a snapshot of the same counter
+    pub fn since(&self, earlier: &Usage) -> Usage {
+        Usage {
+            model_calls: self.model_calls - earlier.model_calls,
+            prompt_tokens: self.prompt_tokens - earlier.prompt_tokens,
+            completion_tokens: self.completion_tokens - earlier.completion_tokens,
+            cost: self
+                .cost
+                .map(|cost| cost - earlier.cost.unwrap_or_default()),
+        }
+    }
+}
+
+impl Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} model calls, {} prompt + {} completion tokens",
+            self.model_calls, self.prompt_tokens, self.completion_tokens
+        )?;
+        if let Some(cost) = self.cost {
+            write!(f, ", cost {:.4}", cost)?;
+        }
+        Ok(())
+    }
+}
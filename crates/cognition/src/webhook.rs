@@ -0,0 +1,248 @@
+// Signed inbound messages, for hosts relaying user messages from channels like
+// Slack or Telegram into sessions. The sender signs `v0:<timestamp>:<body>`
+// with HMAC-SHA256 and a shared secret, and sends `v0=<hex>` with the Unix
+// timestamp. Stale timestamps and signatures seen before are refused, so a
+// captured request cannot be replayed. From the config's `webhook` section:
+//
+// webhook:
+//   secret: ...
+//   tolerance_secs: 300
+//
+// Outgoing webhooks are signed the same way, see `Webhook`.
+use crate::{
+    config::{f64_by_path, object_by_path, string_by_path},
+    CognitionError, Observer,
+};
+#[cfg(feature = "reqwest")]
+use crate::{engine::unix_now, DecisionEvent};
+#[cfg(feature = "reqwest")]
+use async_trait::async_trait;
+#[cfg(feature = "reqwest")]
+use log::warn;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+#[cfg(feature = "reqwest")]
+use std::time::Duration;
+
+const DEFAULT_TOLERANCE_SECS: u64 = 300;
+const BLOCK_SIZE: usize = 64;
+// Events posted by webhooks without `events`
+const DEFAULT_EVENTS: [&str; 5] = [
+    "node_entered",
+    "choice_resolved",
+    "tool_started",
+    "tool_finished",
+    "session_ended",
+];
+#[cfg(feature = "reqwest")]
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct WebhookVerifier {
+    secret: Vec<u8>,
+    // Largest difference between a request's timestamp and now
+    pub tolerance_secs: u64,
+    // Signatures accepted within the tolerance, by their timestamp
+    seen: Mutex<HashMap<String, u64>>,
+}
+
+impl WebhookVerifier {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            secret: secret.as_bytes().to_vec(),
+            tolerance_secs: DEFAULT_TOLERANCE_SECS,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_config(config: &str) -> Result<Self, CognitionError> {
+        let secret = string_by_path(config, "webhook.secret")
+            .ok_or_else(|| CognitionError("Missing webhook.secret".to_string()))?;
+        let mut verifier = Self::new(&secret);
+        if let Some(tolerance_secs) = f64_by_path(config, "webhook.tolerance_secs") {
+            verifier.tolerance_secs = tolerance_secs as u64;
+        }
+        Ok(verifier)
+    }
+
+    // The `v0=<hex>` signature of a body sent at `timestamp`
+    pub fn sign(&self, timestamp: u64, body: &[u8]) -> String {
+        let mut message = format!("v0:{}:", timestamp).into_bytes();
+        message.extend_from_slice(body);
+        let hex: String = hmac_sha256(&self.secret, &message)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("v0={}", hex)
+    }
+
+    // Accept a request once: its signature must match, and its timestamp, as
+    // sent in a header, be within the tolerance of `now`
+    pub fn verify(
+        &self,
+        timestamp: &str,
+        body: &[u8],
+        signature: &str,
+        now: u64,
+    ) -> Result<(), CognitionError> {
+        let timestamp: u64 = timestamp
+            .trim()
+            .parse()
+            .map_err(|_| CognitionError("Invalid webhook timestamp".to_string()))?;
+        if timestamp.abs_diff(now) > self.tolerance_secs {
+            return Err(CognitionError("Stale webhook timestamp".to_string()));
+        }
+
+        let expected = self.sign(timestamp, body);
+        if !constant_time_eq(expected.as_bytes(), signature.trim().as_bytes()) {
+            return Err(CognitionError("Invalid webhook signature".to_string()));
+        }
+
+        let mut seen = self.seen.lock().unwrap_or_else(|err| err.into_inner());
+        let tolerance_secs = self.tolerance_secs;
+        seen.retain(|_, seen_at| seen_at.abs_diff(now) <= tolerance_secs);
+        if seen.insert(expected, timestamp).is_some() {
+            return Err(CognitionError("Replayed webhook request".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// Integration receiving engine events, like a CRM or an analytics pipeline.
+// Each event is POSTed as its JSON with `X-Cognition-Event`, and when the
+// webhook has a secret with `X-Cognition-Timestamp` and `X-Cognition-Signature`
+// for `WebhookVerifier`. From the config's `webhooks` list:
+//
+// webhooks:
+//   - url: https://crm.example.com/hooks/cognition
+//     secret: ...
+//     events: [node_entered, tool_finished, session_ended]
+//
+// Failed deliveries are logged and never fail the turn.
+#[derive(Deserialize, Clone, Debug)]
+pub struct WebhookTarget {
+    pub url: String,
+    pub secret: Option<String>,
+    // Event names, like `session_ended`, node transitions, tool calls and
+    // session ends when empty
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl WebhookTarget {
+    pub fn wants(&self, event: &str) -> bool {
+        if self.events.is_empty() {
+            return DEFAULT_EVENTS.contains(&event);
+        }
+        self.events.iter().any(|wanted| wanted == event)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+pub struct Webhook {
+    target: WebhookTarget,
+    signer: Option<WebhookVerifier>,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl Webhook {
+    pub fn new(target: WebhookTarget) -> Result<Self, CognitionError> {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .map_err(|err| CognitionError(format!("Failed to create webhook client: {}", err)))?;
+        Ok(Self {
+            signer: target.secret.as_deref().map(WebhookVerifier::new),
+            target,
+            client,
+        })
+    }
+
+    async fn post(&self, event: &str, body: Vec<u8>) -> Result<(), CognitionError> {
+        let mut request = self
+            .client
+            .post(&self.target.url)
+            .header("Content-Type", "application/json")
+            .header("X-Cognition-Event", event);
+        if let Some(signer) = &self.signer {
+            let timestamp = unix_now();
+            request = request
+                .header("X-Cognition-Timestamp", timestamp.to_string())
+                .header("X-Cognition-Signature", signer.sign(timestamp, &body));
+        }
+        request
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|err| CognitionError(format!("Webhook {} failed: {}", self.target.url, err)))
+    }
+}
+
+#[cfg(feature = "reqwest")]
+#[async_trait(?Send)]
+impl Observer for Webhook {
+    async fn notify(&self, event: &DecisionEvent) {
+        let Ok(json) = serde_json::to_value(event) else {
+            return;
+        };
+        let name = json["event"].as_str().unwrap_or_default();
+        if !self.target.wants(name) {
+            return;
+        }
+        if let Err(err) = self.post(name, json.to_string().into_bytes()).await {
+            warn!("{}", err.0);
+        }
+    }
+}
+
+// Webhooks of the config's `webhooks` list
+pub(crate) fn from_config(config: &str) -> Result<Vec<Box<dyn Observer>>, CognitionError> {
+    let Some(targets) = object_by_path(config, "webhooks") else {
+        return Ok(vec![]);
+    };
+    let targets: Vec<WebhookTarget> = serde_json::from_value(targets)
+        .map_err(|err| CognitionError(format!("Invalid webhooks: {}", err)))?;
+    if targets.is_empty() {
+        return Ok(vec![]);
+    }
+    #[cfg(feature = "reqwest")]
+    {
+        targets
+            .into_iter()
+            .map(|target| Ok(Box::new(Webhook::new(target)?) as Box<dyn Observer>))
+            .collect()
+    }
+    #[cfg(not(feature = "reqwest"))]
+    Err(CognitionError(
+        "webhooks need the `reqwest` feature".to_string(),
+    ))
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner_key: Vec<u8> = block.iter().map(|byte| byte ^ 0x36).collect();
+    let outer_key: Vec<u8> = block.iter().map(|byte| byte ^ 0x5c).collect();
+    let inner = Sha256::new()
+        .chain_update(inner_key)
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(outer_key)
+        .chain_update(inner)
+        .finalize()
+        .to_vec()
+}
+
+// Comparison taking the same time wherever the first difference is
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,48 @@
+// Subtrees called with `call:<id>` hand control back to the calling choice's
+// `return_id` once they reach a `return: true` node.
+mod common;
+
+use cognition::run_decision;
+
+const TREE: &str = r#"
+- id: start
+  text: What would you like to do?
+  choices:
+    - choice: Check out
+      next_id: call:login
+      return_id: checkout
+    - choice: Leave
+      next_id: exit
+- id: login
+  text: Are you a member?
+  choices:
+    - choice: I am a member
+      next_id: logged_in
+- id: logged_in
+  text: Welcome back.
+  return: true
+- id: checkout
+  text: Shall we ship your order?
+  choices:
+    - choice: Ship it
+      next_id: exit
+"#;
+
+#[tokio::test]
+async fn returns_to_the_continuation_of_the_calling_choice() {
+    let mut state = common::state("prediction:\n  policy: off\n", TREE, "");
+    run_decision(None, &mut state).await.unwrap();
+    let result = run_decision(Some("Check out".to_string()), &mut state)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.current_id, "login");
+
+    // The caller's question is not asked again
+    let result = run_decision(Some("I am a member".to_string()), &mut state)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.current_id, "checkout");
+    assert_eq!(state.current_node().unwrap().id, "checkout");
+}
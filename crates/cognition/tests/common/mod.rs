@@ -0,0 +1,118 @@
+// Fixtures shared by the integration tests: scripted models and states built
+// from a tree without any backend.
+#![allow(dead_code)]
+use async_trait::async_trait;
+use cognition::{
+    models::{InferenceResult, LargeLanguageModel, ModelError, ModelRegistry},
+    CognitionError, Decision, DecisionPromptTemplate, DecisionState,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const TREE: &str = include_str!("../../../../decision_tree.yaml");
+pub const TEMPLATE: &str = include_str!("../../../../decision_prompt_template.yaml");
+
+// Answers are choice texts, selected without the model
+pub struct Unused;
+
+#[async_trait(?Send)]
+impl LargeLanguageModel for Unused {
+    fn new(_config: &String) -> Result<Self, ModelError> {
+        Ok(Self)
+    }
+
+    async fn generate(
+        &self,
+        _prompt: &str,
+        _max_length: usize,
+        _temperature: f32,
+    ) -> Result<InferenceResult, ModelError> {
+        Ok(InferenceResult {
+            text: String::new(),
+            probabilities: vec![],
+        })
+    }
+}
+
+// Records the start and end of every call, answering none of the choices
+pub struct Slow(pub Arc<Mutex<Vec<String>>>);
+
+#[async_trait(?Send)]
+impl LargeLanguageModel for Slow {
+    fn new(_config: &String) -> Result<Self, ModelError> {
+        Ok(Self(Arc::default()))
+    }
+
+    async fn generate(
+        &self,
+        prompt: &str,
+        _max_length: usize,
+        _temperature: f32,
+    ) -> Result<InferenceResult, ModelError> {
+        let input = prompt
+            .lines()
+            .find_map(|line| line.strip_prefix("response: "))
+            .unwrap_or_default()
+            .to_string();
+        self.0.lock().unwrap().push(format!("start {}", input));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        self.0.lock().unwrap().push(format!("end {}", input));
+        Ok(InferenceResult {
+            text: "Ambiguous".to_string(),
+            probabilities: vec![],
+        })
+    }
+}
+
+// Never answers
+pub struct Hung;
+
+#[async_trait(?Send)]
+impl LargeLanguageModel for Hung {
+    fn new(_config: &String) -> Result<Self, ModelError> {
+        Ok(Self)
+    }
+
+    async fn generate(
+        &self,
+        _prompt: &str,
+        _max_length: usize,
+        _temperature: f32,
+    ) -> Result<InferenceResult, ModelError> {
+        std::future::pending().await
+    }
+}
+
+// State of `tree` answered by `model`, `config` being the rest of the config
+pub fn state_with_model(
+    config: &str,
+    tree: &str,
+    template: &str,
+    model: impl Fn() -> Box<dyn LargeLanguageModel> + 'static,
+) -> DecisionState {
+    try_state(config, tree, template, model).unwrap()
+}
+
+// As `state_with_model`, failing on an invalid config
+pub fn try_state(
+    config: &str,
+    tree: &str,
+    template: &str,
+    model: impl Fn() -> Box<dyn LargeLanguageModel> + 'static,
+) -> Result<DecisionState, CognitionError> {
+    let mut registry = ModelRegistry::empty();
+    registry.register("test", Box::new(move |_| Ok(model())));
+    let decision_nodes: Vec<Decision> = serde_yaml::from_str(tree).unwrap();
+    let config = format!("model: test\n{}", config);
+    DecisionState::with_registry(
+        &config,
+        DecisionPromptTemplate::new(template.to_string()),
+        decision_nodes,
+        &registry,
+    )
+}
+
+// State of `tree` whose model is never asked
+pub fn state(config: &str, tree: &str, template: &str) -> DecisionState {
+    state_with_model(config, tree, template, || Box::new(Unused))
+}
@@ -0,0 +1,14 @@
+// Trees embedded with `include_tree!` match the YAML they were compiled from
+use cognition::{include_tree, Decision};
+
+const TREE: &str = include_str!("../../../decision_tree.yaml");
+
+#[test]
+fn embedded_tree_matches_yaml() {
+    let embedded: Vec<Decision> = include_tree!("../../decision_tree.yaml");
+    let parsed: Vec<Decision> = serde_yaml::from_str(TREE).unwrap();
+    assert_eq!(
+        serde_json::to_value(&embedded).unwrap(),
+        serde_json::to_value(&parsed).unwrap()
+    );
+}
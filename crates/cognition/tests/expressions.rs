@@ -0,0 +1,112 @@
+// Expressions in template placeholders and `requires` conditions.
+use cognition::{condition, render_variables, ExpressionError};
+use std::collections::BTreeMap;
+
+fn variables() -> BTreeMap<String, String> {
+    [
+        ("vars.count", "2"),
+        ("vars.name", "ada"),
+        ("vars.code", "007"),
+        ("vars.empty", ""),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect()
+}
+
+fn render(expression: &str) -> String {
+    render_variables(&format!("{{{{ {} }}}}", expression), &variables())
+}
+
+#[test]
+fn operators_bind_by_precedence() {
+    assert_eq!(render("1 + 2 * 3"), "7");
+    assert_eq!(render("(1 + 2) * 3"), "9");
+    assert_eq!(render("10 - 4 - 3"), "3");
+    assert_eq!(render("7 % 4 * 2"), "6");
+    assert_eq!(render("-vars.count * 3"), "-6");
+    assert_eq!(render("1 + 1 == 2 && 3 > 4 || !false"), "true");
+    assert_eq!(render("!vars.count > 1"), "false");
+    assert_eq!(render("vars.count + 1 + 'x'"), "3x");
+    assert_eq!(render("'x' + vars.count + 1"), "x21");
+}
+
+#[test]
+fn strings_and_numbers_compare() {
+    let variables = variables();
+    let holds = |expression| condition(expression, &variables);
+    // Text that reads as a number compares as one
+    assert_eq!(holds("vars.code == 7"), Ok(true));
+    assert_eq!(holds("vars.code == '7'"), Ok(true));
+    assert_eq!(holds("vars.count < 10"), Ok(true));
+    assert_eq!(holds("vars.name == 'ada'"), Ok(true));
+    assert_eq!(holds("vars.name != 2"), Ok(true));
+    assert_eq!(holds("len(vars.empty) == 0 && !vars.empty"), Ok(true));
+    // Only numbers are ordered
+    assert_eq!(
+        holds("vars.name < 'bob'"),
+        Err(ExpressionError::Invalid { position: 17 })
+    );
+}
+
+#[test]
+fn unknown_variables_fail() {
+    let variables = variables();
+    assert_eq!(
+        condition("vars.count > 1 && vars.missing", &variables),
+        Err(ExpressionError::UnknownVariable("vars.missing".to_string()))
+    );
+    assert_eq!(
+        condition("upper(vars.missing)", &variables),
+        Err(ExpressionError::UnknownVariable("vars.missing".to_string()))
+    );
+    assert_eq!(render("vars.missing + 1"), "{{ vars.missing + 1 }}");
+}
+
+#[test]
+fn malformed_expressions_fail_without_panicking() {
+    let variables = variables();
+    let malformed = [
+        "",
+        "vars.count <",
+        "(1 + 2",
+        "1 + 2)",
+        "'unterminated",
+        "1.2.3",
+        "1 / 0",
+        "upper(",
+        "upper(1,)",
+        "unknown(1)",
+        "min(1)",
+        "&& 1",
+        "1 ** 2",
+        "vars.count vars.count",
+        "1 + #",
+    ];
+    for expression in malformed {
+        let result = condition(expression, &variables);
+        assert!(
+            matches!(result, Err(ExpressionError::Invalid { .. })),
+            "{}: {:?}",
+            expression,
+            result
+        );
+        assert_eq!(
+            render_variables(&format!("{{{{{}}}}}", expression), &variables),
+            format!("{{{{{}}}}}", expression)
+        );
+    }
+
+    // Nested too deeply to evaluate
+    let nested = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+    assert!(condition(&nested, &variables).is_err());
+    let negated = format!("{}true", "!".repeat(100_000));
+    assert!(condition(&negated, &variables).is_err());
+    assert_eq!(
+        condition(
+            &format!("{}1{}", "(".repeat(10), ")".repeat(10)),
+            &variables
+        ),
+        Ok(true)
+    );
+}
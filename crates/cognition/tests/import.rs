@@ -0,0 +1,158 @@
+// Stories and sketches in other formats imported as valid decision trees.
+mod common;
+
+use cognition::{import, validate_tree, Decision};
+
+// Choices of a node as `text -> target`
+fn choices(decision_nodes: &[Decision], id: &str) -> Vec<String> {
+    let decision_node = decision_nodes
+        .iter()
+        .find(|decision_node| decision_node.id == id)
+        .unwrap();
+    decision_node
+        .choices()
+        .into_iter()
+        .map(|choice| format!("{} -> {}", choice.text, choice.next_id))
+        .collect()
+}
+
+const TWEE: &str = r#"
+:: StoryTitle
+The Shop
+
+:: StoryData
+{
+  "ifid": "D674C58C-DEFA-4F70-B7A2-27742230C0FC",
+  "format": "Harlowe",
+  "start": "Front door"
+}
+
+:: Front door {"position":"100,100"}
+Welcome in. Are you buying or browsing?
+[[Buying->Counter]]
+[[Browsing|Shelves]]
+
+:: Counter [busy]
+What would you like?
+(link-goto: "Nothing after all", "Front door")
+
+:: Shelves
+Take your time.
+[[Front door<-Done looking]]
+"#;
+
+#[test]
+fn twee_stories_import_as_valid_trees() {
+    let decision_nodes = import::twine::from_twee(TWEE).unwrap();
+    assert!(validate_tree(&decision_nodes).is_ok());
+
+    let ids: Vec<&str> = decision_nodes.iter().map(|node| node.id.as_str()).collect();
+    assert_eq!(ids, ["start", "counter", "shelves"]);
+    assert_eq!(
+        decision_nodes[0].text,
+        "Welcome in. Are you buying or browsing?"
+    );
+    assert_eq!(
+        choices(&decision_nodes, "start"),
+        ["Buying -> counter", "Browsing -> shelves"]
+    );
+    assert_eq!(
+        choices(&decision_nodes, "counter"),
+        ["Nothing after all -> start"]
+    );
+    assert_eq!(
+        choices(&decision_nodes, "shelves"),
+        ["Done looking -> start"]
+    );
+}
+
+const INK: &str = r#"
+VAR gold = 10
+Welcome to the harbour.
+-> docks
+
+=== docks ===
+A sailor waves at you.
+* [Hail the sailor] -> sailor
++ Walk [away] on -> END
+
+=== sailor ===
+"Need a ship?" she asks.
+* Yes, please
+  She grins. "Then we sail at dawn."
+  -> DONE
+* [No thanks] -> docks
+"#;
+
+#[test]
+fn ink_scripts_import_as_valid_trees() {
+    let decision_nodes = import::ink::from_ink(INK).unwrap();
+    assert!(validate_tree(&decision_nodes).is_ok());
+
+    let ids: Vec<&str> = decision_nodes.iter().map(|node| node.id.as_str()).collect();
+    assert_eq!(ids, ["start", "docks", "sailor", "sailor_1"]);
+    assert_eq!(choices(&decision_nodes, "start"), ["Continue -> docks"]);
+    assert_eq!(
+        choices(&decision_nodes, "docks"),
+        ["Hail the sailor -> sailor", "Walk away -> exit"]
+    );
+    // Text after a choice is a node of its own
+    assert_eq!(
+        choices(&decision_nodes, "sailor"),
+        ["Yes, please -> sailor_1", "No thanks -> docks"]
+    );
+    assert_eq!(
+        decision_nodes[3].text,
+        "She grins. \"Then we sail at dawn.\""
+    );
+    assert_eq!(choices(&decision_nodes, "sailor_1"), ["Continue -> exit"]);
+}
+
+const MERMAID: &str = r#"
+flowchart TD
+    start{How can I help?} -->|Track an order| track[Your order number?]
+    start -- Talk to someone --> human([Connecting you now.])
+    start --> exit
+    track -->|Not sure| back
+    track -.-> lookup[Looking it up.]
+    lookup -->|next| start
+    classDef done fill:#eee
+"#;
+
+#[test]
+fn mermaid_flowcharts_import_as_valid_trees() {
+    let decision_nodes = import::mermaid::from_mermaid(MERMAID).unwrap();
+    assert!(validate_tree(&decision_nodes).is_ok());
+
+    let ids: Vec<&str> = decision_nodes.iter().map(|node| node.id.as_str()).collect();
+    assert_eq!(ids, ["start", "track", "human", "lookup"]);
+    assert_eq!(decision_nodes[0].text, "How can I help?");
+    assert_eq!(
+        choices(&decision_nodes, "start"),
+        [
+            "Track an order -> track",
+            "Talk to someone -> human",
+            "exit -> exit"
+        ]
+    );
+    assert_eq!(
+        choices(&decision_nodes, "track"),
+        ["Not sure -> back", "Looking it up. -> call:lookup"]
+    );
+    assert_eq!(
+        decision_nodes[3].next.as_ref().unwrap().to_string(),
+        "start"
+    );
+
+    // Exported and imported again
+    let tree: Vec<Decision> = serde_yaml::from_str(common::TREE).unwrap();
+    let exported = cognition::export::mermaid::to_mermaid(&tree);
+    let reimported = import::mermaid::from_mermaid(&exported).unwrap();
+    assert!(validate_tree(&reimported).is_ok());
+    for decision_node in &tree {
+        assert_eq!(
+            choices(&reimported, &decision_node.id),
+            choices(&tree, &decision_node.id)
+        );
+    }
+}
@@ -0,0 +1,222 @@
+// Snapshot tests for prompt rendering. The rendered prompt is a public contract,
+// any change here changes what every model sees.
+use cognition::{
+    condition, render_variables, test_template, DecisionPromptTemplate, History, HistoryStyle,
+    Role, TemplateFixture,
+};
+
+const DEFAULT_TEMPLATE: &str = include_str!("../../../decision_prompt_template.yaml");
+
+const CHOICES: &str = "I want to buy something.\n  - I have a question.";
+
+fn history(style: HistoryStyle, turns: &[(&str, &str)]) -> History {
+    let mut history = History::new(style);
+    for (agent, user) in turns {
+        history.push(Role::Agent, agent);
+        history.push(Role::User, user);
+    }
+    history
+}
+
+fn render(template: &str, history: &History, decision_prompt: &str, user_input: &str) -> String {
+    DecisionPromptTemplate::new(template.to_string()).format(
+        &history.render("Agent", "User"),
+        decision_prompt,
+        CHOICES,
+        user_input,
+    )
+}
+
+#[test]
+fn empty_history() {
+    let history = history(HistoryStyle::Yaml, &[]);
+    insta::assert_snapshot!(render(
+        DEFAULT_TEMPLATE,
+        &history,
+        "Hello! How can I help you today?",
+        "I'd like a book"
+    ));
+}
+
+#[test]
+fn single_turn_history() {
+    let history = history(
+        HistoryStyle::Yaml,
+        &[(
+            "Hello! How can I help you today?",
+            "I want to buy something",
+        )],
+    );
+    insta::assert_snapshot!(render(
+        DEFAULT_TEMPLATE,
+        &history,
+        "What would you like to buy?",
+        "A book."
+    ));
+}
+
+#[test]
+fn long_history() {
+    let turns: Vec<(String, String)> = (1..=12)
+        .map(|turn| (format!("Question {}?", turn), format!("Answer {}", turn)))
+        .collect();
+    let turns: Vec<(&str, &str)> = turns
+        .iter()
+        .map(|(agent, user)| (agent.as_str(), user.as_str()))
+        .collect();
+    let history = history(HistoryStyle::Yaml, &turns);
+    insta::assert_snapshot!(render(
+        DEFAULT_TEMPLATE,
+        &history,
+        "All Done?",
+        "start over"
+    ));
+}
+
+#[test]
+fn history_styles() {
+    let turns = [
+        ("Hello! How can I help you today?", "I have a question"),
+        ("What is your question?", "What time is it?"),
+    ];
+    let rendered: Vec<String> = [
+        HistoryStyle::Yaml,
+        HistoryStyle::Prefix,
+        HistoryStyle::ChatMl,
+        HistoryStyle::Llama,
+    ]
+    .iter()
+    .map(|style| {
+        format!(
+            "### {:?}\n{}",
+            style,
+            history(*style, &turns).render("Agent", "User")
+        )
+    })
+    .collect();
+    insta::assert_snapshot!(rendered.join("\n\n"));
+}
+
+#[test]
+fn custom_template() {
+    let template = "[INST] {{history}}\n{{decision_prompt}}\nOptions:\n  - {{choices}}\nUser said: {{user_input}} [/INST]\n";
+    let history = history(
+        HistoryStyle::Llama,
+        &[("Hello! How can I help you today?", "hi")],
+    );
+    insta::assert_snapshot!(render(
+        template,
+        &history,
+        "What is your question?",
+        "Can you help me with my computer?"
+    ));
+}
+
+#[test]
+fn special_characters() {
+    let history = history(
+        HistoryStyle::Yaml,
+        &[(
+            "Price: \"$10\" — ok? {not a placeholder}",
+            "yes: 'definitely' # comment\n- not a list",
+        )],
+    );
+    // Placeholders inside user input must not be expanded
+    insta::assert_snapshot!(render(
+        DEFAULT_TEMPLATE,
+        &history,
+        "Confirm {{user_input}}?",
+        "{{history}} {{choices}} }} {{"
+    ));
+}
+
+#[test]
+fn multi_line_input() {
+    // A pasted log and address must stay inside their values
+    let history = history(
+        HistoryStyle::Yaml,
+        &[(
+            "What went wrong?",
+            "error: build failed\nsrc/main.rs:3: expected `;`",
+        )],
+    );
+    let style = HistoryStyle::Yaml;
+    insta::assert_snapshot!(render(
+        DEFAULT_TEMPLATE,
+        &history,
+        &style.template_value("Where should we ship it?\nWe deliver on weekdays."),
+        &style.template_value("Jane Doe\r\n1 Main St\r\nSpringfield")
+    ));
+}
+
+#[test]
+fn multi_byte_text() {
+    let history = history(
+        HistoryStyle::Yaml,
+        &[("¿Qué desea comprar? 🛒", "本を買いたいです")],
+    );
+    insta::assert_snapshot!(render(
+        DEFAULT_TEMPLATE,
+        &history,
+        "Которую книгу вы хотите купить?",
+        "Ça dépend… 📚 مرحبا"
+    ));
+}
+
+#[test]
+fn expressions() {
+    let variables = [
+        ("vars.count", "2"),
+        ("vars.name", "ada"),
+        ("node.visits", "3"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect();
+    insta::assert_snapshot!(render_variables(
+        "{{ vars.count + 1 }} books for {{ upper(vars.name) }}, visit {{node.visits}} \
+         ({{ round(vars.count / 3 * 100) }}%), {{ vars.missing + 1 }}, {{ 1 / 0 }}",
+        &variables
+    ));
+}
+
+#[test]
+fn conditions() {
+    let variables = [
+        ("vars.authenticated", "true"),
+        ("vars.cart", ""),
+        ("vars.count", "2"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect();
+    let conditions = [
+        "vars.authenticated",
+        "!vars.authenticated",
+        "len(vars.cart) > 0",
+        "vars.count >= 2 && vars.count != 3",
+        "vars.count < 1 || (vars.authenticated == true)",
+        "vars.missing",
+        "vars.count <",
+    ];
+    let results: Vec<String> = conditions
+        .iter()
+        .map(|expression| format!("{}: {:?}", expression, condition(expression, &variables)))
+        .collect();
+    insta::assert_snapshot!(results.join("\n"));
+}
+
+#[test]
+fn template_fixtures() {
+    let fixtures = TemplateFixture::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../template_fixtures.yaml"
+    ))
+    .unwrap();
+    let failures = test_template(
+        &DecisionPromptTemplate::new(DEFAULT_TEMPLATE.to_string()),
+        &fixtures,
+    );
+    let failures: Vec<String> = failures.iter().map(|failure| failure.to_string()).collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,54 @@
+// Simultaneous inputs to one session, as sent by a frontend double-submitting,
+// with a slow scripted model so the second turn arrives mid-turn.
+mod common;
+
+use cognition::{ConcurrencyPolicy, Session, SessionError};
+use common::{Slow, TEMPLATE, TREE};
+use std::sync::{Arc, Mutex};
+
+async fn session(policy: ConcurrencyPolicy) -> (Session, Arc<Mutex<Vec<String>>>) {
+    let calls: Arc<Mutex<Vec<String>>> = Arc::default();
+    let model_calls = calls.clone();
+    let state = common::state_with_model("", TREE, TEMPLATE, move || {
+        Box::new(Slow(model_calls.clone()))
+    });
+
+    let session = Session::new(state, policy);
+    session.run(None).await.unwrap();
+    (session, calls)
+}
+
+#[tokio::test]
+async fn queued_turns_do_not_interleave() {
+    let (session, calls) = session(ConcurrencyPolicy::Queue).await;
+
+    let (first, second) = tokio::join!(
+        session.run(Some("first".to_string())),
+        session.run(Some("second".to_string())),
+    );
+    assert_eq!(first.unwrap().unwrap().user_input.as_deref(), Some("first"));
+    assert_eq!(
+        second.unwrap().unwrap().user_input.as_deref(),
+        Some("second")
+    );
+    assert_eq!(
+        *calls.lock().unwrap(),
+        ["start first", "end first", "start second", "end second"]
+    );
+}
+
+#[tokio::test]
+async fn busy_session_rejects_turns() {
+    let (session, calls) = session(ConcurrencyPolicy::Reject).await;
+
+    let (first, second) = tokio::join!(
+        session.run(Some("first".to_string())),
+        session.run(Some("second".to_string())),
+    );
+    assert!(first.unwrap().is_some());
+    assert!(matches!(second, Err(SessionError::Busy(_))));
+    assert_eq!(*calls.lock().unwrap(), ["start first", "end first"]);
+
+    // Free again once the turn is done
+    assert!(session.run(Some("third".to_string())).await.is_ok());
+}
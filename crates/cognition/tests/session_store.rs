@@ -0,0 +1,88 @@
+// Sessions continued by another state through a shared store, as when a turn
+// lands on a different server or after a restart.
+mod common;
+
+use cognition::{run_decision, DecisionState, MemoryStore, SessionStore};
+use common::{TEMPLATE, TREE};
+use std::time::Duration;
+
+fn state(store: &(impl SessionStore + Clone + 'static)) -> DecisionState {
+    let mut state = common::state("", TREE, TEMPLATE);
+    state.set_store(Box::new(store.clone()));
+    state
+}
+
+#[tokio::test]
+async fn resumed_session_continues_where_it_left_off() {
+    let store = MemoryStore::new(None);
+    let mut first = state(&store);
+    first.vars.insert("plan".to_string(), "pro".to_string());
+    run_decision(None, &mut first).await.unwrap();
+    run_decision(Some("I want to buy something.".to_string()), &mut first)
+        .await
+        .unwrap();
+
+    let mut second = state(&store);
+    assert!(second.resume(&first.session_id).await.unwrap());
+    assert_eq!(second.session_id, first.session_id);
+    assert_eq!(second.current_node().unwrap().id, "buy_something");
+    assert_eq!(second.vars["plan"], "pro");
+
+    let result = run_decision(Some("A book.".to_string()), &mut second)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.current_id, "buy_book");
+    assert_eq!(second.turn_count(), 2);
+    assert!(second.undo());
+    assert_eq!(second.current_node().unwrap().id, "buy_something");
+}
+
+#[tokio::test]
+async fn expired_or_deleted_sessions_are_not_resumed() {
+    let store = MemoryStore::new(Some(Duration::ZERO));
+    let mut first = state(&store);
+    run_decision(None, &mut first).await.unwrap();
+    assert!(!state(&store).resume(&first.session_id).await.unwrap());
+
+    let store = MemoryStore::new(None);
+    let mut first = state(&store);
+    run_decision(None, &mut first).await.unwrap();
+    store.delete_state(&first.session_id).await.unwrap();
+    assert!(!state(&store).resume(&first.session_id).await.unwrap());
+}
+
+#[tokio::test]
+async fn sessions_expire_after_the_ttl() {
+    let store = MemoryStore::new(Some(Duration::from_millis(100)));
+    let mut first = state(&store);
+    run_decision(None, &mut first).await.unwrap();
+    assert!(state(&store).resume(&first.session_id).await.unwrap());
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(!state(&store).resume(&first.session_id).await.unwrap());
+}
+
+// Needs a server, e.g. `REDIS_URL=redis://127.0.0.1:6379/0`
+#[cfg(feature = "redis")]
+#[tokio::test]
+async fn sessions_are_shared_through_redis() {
+    let Ok(url) = std::env::var("REDIS_URL") else {
+        eprintln!("REDIS_URL is not set, skipping");
+        return;
+    };
+    let store =
+        cognition::RedisStore::new(&url, "cognition-test:", Some(Duration::from_secs(60))).unwrap();
+    let mut first = state(&store);
+    run_decision(None, &mut first).await.unwrap();
+    run_decision(Some("I want to buy something.".to_string()), &mut first)
+        .await
+        .unwrap();
+
+    let mut second = state(&store);
+    assert!(second.resume(&first.session_id).await.unwrap());
+    assert_eq!(second.current_node().unwrap().id, "buy_something");
+
+    store.delete_state(&first.session_id).await.unwrap();
+    assert!(!state(&store).resume(&first.session_id).await.unwrap());
+}
@@ -0,0 +1,58 @@
+// Local commands as tools, refused unless allowed and kept to their directory.
+#![cfg(feature = "tools-shell")]
+use cognition::{tools::Shell, Tool};
+use std::time::Duration;
+
+fn shell(command: &[&str]) -> Shell {
+    let mut shell = Shell::new(
+        "shell",
+        command.iter().map(|arg| arg.to_string()).collect(),
+        std::env::temp_dir(),
+    );
+    shell.allow = vec![command[0].to_string()];
+    shell
+}
+
+#[tokio::test]
+async fn runs_allowed_commands_with_the_input_as_one_argument() {
+    let response = shell(&["echo", "hello {{input}}"])
+        .run(&"world; rm -rf .".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(response.response, "hello world; rm -rf .");
+
+    let mut denied = shell(&["echo", "{{input}}"]);
+    denied.allow.clear();
+    assert!(denied.run(&"hello".to_string()).await.is_err());
+}
+
+#[tokio::test]
+async fn refuses_options_and_paths_outside_the_working_directory() {
+    let ls = shell(&["ls", "{{input}}"]);
+    assert!(ls.run(&"-la".to_string()).await.is_err());
+    assert!(ls.run(&"/etc".to_string()).await.is_err());
+    assert!(ls.run(&"a/../../etc".to_string()).await.is_err());
+    assert!(ls.run(&".".to_string()).await.is_ok());
+}
+
+#[tokio::test]
+async fn stops_slow_or_verbose_commands() {
+    let mut sleep = shell(&["sleep", "{{input}}"]);
+    sleep.timeout = Duration::from_millis(100);
+    assert!(sleep
+        .run(&"5".to_string())
+        .await
+        .unwrap_err()
+        .0
+        .contains("timed out"));
+
+    let mut yes = shell(&["yes", "{{input}}"]);
+    yes.max_output = 1024;
+    assert!(yes
+        .run(&"y".to_string())
+        .await
+        .unwrap_err()
+        .0
+        .contains("more than"));
+}
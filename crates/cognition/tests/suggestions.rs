@@ -0,0 +1,140 @@
+// Suggested tree edits applied to a copy of the tree and shown as a diff.
+use cognition::{
+    apply_suggestions, tree_diff, validate_tree, ChoiceGroup, Decision, TreeSuggestion,
+};
+
+const TREE: &str = r#"
+- id: start
+  text: How can I help?
+  choices:
+    - choice: Buy
+      next_id: buy
+    - choice: Sell
+      next_id: sell
+    - choice: Rent
+      next_id: exit
+- id: buy
+  text: What would you like to buy?
+- id: sell
+  text: What would you like to sell?
+"#;
+
+fn tree() -> Vec<Decision> {
+    serde_yaml::from_str(TREE).unwrap()
+}
+
+#[test]
+fn edits_are_applied_to_a_copy() {
+    let nodes = tree();
+    let suggestions = vec![
+        TreeSuggestion::AddAliases {
+            node_id: "start".to_string(),
+            choice: "Buy".to_string(),
+            aliases: vec!["purchase".to_string()],
+        },
+        TreeSuggestion::RewordChoice {
+            node_id: "start".to_string(),
+            choice: "Rent".to_string(),
+            text: "Rent something".to_string(),
+            reason: "Unclear".to_string(),
+        },
+        TreeSuggestion::SplitNode {
+            node_id: "start".to_string(),
+            groups: vec![
+                ChoiceGroup {
+                    label: "Trade".to_string(),
+                    question: "Buy or sell?".to_string(),
+                    choices: vec!["Buy".to_string(), "Sell".to_string()],
+                },
+                ChoiceGroup {
+                    label: "Borrow".to_string(),
+                    question: "For how long?".to_string(),
+                    // Named before its rewording
+                    choices: vec!["Rent".to_string()],
+                },
+            ],
+            reason: "Too many choices".to_string(),
+        },
+    ];
+    let (suggested, applied) = apply_suggestions(&nodes, &suggestions);
+    assert_eq!(applied, suggestions);
+    assert_eq!(nodes[0].choices().len(), 3);
+    assert!(validate_tree(&suggested).is_ok());
+
+    let ids: Vec<&str> = suggested.iter().map(|node| node.id.as_str()).collect();
+    assert_eq!(ids, ["start", "start_1", "start_2", "buy", "sell"]);
+    let texts = |node: &Decision| -> Vec<String> {
+        node.choices()
+            .iter()
+            .map(|choice| choice.text.clone())
+            .collect()
+    };
+    assert_eq!(texts(&suggested[0]), ["Trade", "Borrow"]);
+    assert_eq!(texts(&suggested[1]), ["Buy", "Sell"]);
+    assert_eq!(texts(&suggested[2]), ["Rent something"]);
+    assert_eq!(suggested[1].choices()[0].aliases, ["purchase"]);
+}
+
+#[test]
+fn edits_invalidating_the_tree_are_rejected() {
+    let nodes = tree();
+    let clash = TreeSuggestion::RewordChoice {
+        node_id: "start".to_string(),
+        choice: "Rent".to_string(),
+        text: "buy".to_string(),
+        reason: "Unclear".to_string(),
+    };
+    let missing = TreeSuggestion::AddAliases {
+        node_id: "start".to_string(),
+        choice: "Lease".to_string(),
+        aliases: vec!["hire".to_string()],
+    };
+    let alias = TreeSuggestion::AddAliases {
+        node_id: "start".to_string(),
+        choice: "Sell".to_string(),
+        aliases: vec!["get rid of".to_string()],
+    };
+    let (suggested, applied) = apply_suggestions(&nodes, &[clash, missing, alias.clone()]);
+    assert_eq!(applied, [alias]);
+    assert_eq!(suggested[0].choices()[2].text, "Rent");
+    assert_eq!(suggested[0].choices()[1].aliases, ["get rid of"]);
+}
+
+#[test]
+fn diff_shows_inserted_deleted_and_replaced_lines() {
+    let nodes = tree();
+    let mut edited = nodes.clone();
+    // Replaced
+    edited[1].text = "What are you buying?".to_string();
+    // Deleted
+    edited.remove(2);
+    // Inserted
+    edited[0].choices.as_mut().unwrap()[0]
+        .aliases
+        .push("purchase".to_string());
+    let diff = tree_diff(&nodes, &edited).unwrap();
+    assert_eq!(
+        diff,
+        r#"@@ start @@
+ - id: start
+   text: How can I help?
+   choices:
+     - choice: Buy
+       next_id: buy
++      aliases:
++        - purchase
+     - choice: Sell
+       next_id: sell
+     - choice: Rent
+       next_id: exit
+@@ buy @@
+ - id: buy
+-  text: What would you like to buy?
++  text: What are you buying?
+@@ sell @@
+-- id: sell
+-  text: What would you like to sell?
+"#
+    );
+    assert_eq!(tree_diff(&nodes, &nodes).unwrap(), "");
+}
@@ -0,0 +1,93 @@
+// Tool responses reused for the same input within `tools.cache` TTLs.
+mod common;
+
+use async_trait::async_trait;
+use cognition::{run_tools, CancellationToken, CognitionError, Decision, Tool, ToolResponse};
+use std::cell::Cell;
+use std::rc::Rc;
+
+const TREE: &str = r#"
+- id: start
+  text: Ask away.
+  tool: [lookup, book]
+  choices:
+    - choice: Done
+      next_id: exit
+"#;
+
+// Counts its runs
+struct Counting {
+    id: String,
+    side_effecting: bool,
+    runs: Rc<Cell<usize>>,
+}
+
+#[async_trait(?Send)]
+impl Tool for Counting {
+    fn id(&self) -> &String {
+        &self.id
+    }
+
+    fn name(&self) -> &String {
+        &self.id
+    }
+
+    fn description(&self) -> &String {
+        &self.id
+    }
+
+    fn side_effecting(&self) -> bool {
+        self.side_effecting
+    }
+
+    async fn run(&self, input: &String) -> Result<Option<ToolResponse>, CognitionError> {
+        self.runs.set(self.runs.get() + 1);
+        Ok(Some(ToolResponse {
+            id: self.id.clone(),
+            response: format!("{} #{}", input, self.runs.get()),
+        }))
+    }
+}
+
+#[tokio::test]
+async fn repeated_inputs_are_answered_from_the_cache() {
+    let decision_nodes: Vec<Decision> = serde_yaml::from_str(TREE).unwrap();
+    let node = decision_nodes[0].clone();
+    let mut state = common::state("tools:\n  cache:\n    lookup: 1m\n    book: 1m\n", TREE, "");
+    let lookups = Rc::new(Cell::new(0));
+    let bookings = Rc::new(Cell::new(0));
+    state.add_tool(Box::new(Counting {
+        id: "lookup".to_string(),
+        side_effecting: false,
+        runs: lookups.clone(),
+    }));
+    // Never cached, its side effects must happen every time
+    state.add_tool(Box::new(Counting {
+        id: "book".to_string(),
+        side_effecting: true,
+        runs: bookings.clone(),
+    }));
+
+    let cancel = CancellationToken::new();
+    let first = run_tools(&state, &node, "weather", None, &cancel)
+        .await
+        .unwrap();
+    let second = run_tools(&state, &node, "weather", None, &cancel)
+        .await
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(&first[0]).unwrap(),
+        serde_json::to_value(&second[0]).unwrap()
+    );
+    assert_eq!((lookups.get(), bookings.get()), (1, 2));
+
+    run_tools(&state, &node, "news", None, &cancel)
+        .await
+        .unwrap();
+    assert_eq!(lookups.get(), 2);
+    state.clear_tool_cache();
+    run_tools(&state, &node, "weather", None, &cancel)
+        .await
+        .unwrap();
+    assert_eq!(lookups.get(), 3);
+}
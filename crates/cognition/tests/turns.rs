@@ -0,0 +1,161 @@
+// Turns of a session: undoing them, confirming choices, model calls that never
+// answer and safety limits.
+mod common;
+
+use cognition::{run_decision, run_decision_with_cancel, CancellationToken, DecisionState};
+use std::time::{Duration, Instant};
+
+const TREE: &str = r#"
+- id: start
+  text: What would you like?
+  choices:
+    - choice: Buy
+      next_id: buy
+    - choice: Leave
+      next_id: exit
+- id: buy
+  text: What would you like to buy?
+  script: vars.shopping = true;
+  choices:
+    - choice: A book
+      next_id: book
+      confirm: Order {{choice}}?
+- id: book
+  text: Your book is on its way.
+"#;
+
+fn state() -> DecisionState {
+    common::state("prediction:\n  policy: off\n", TREE, "")
+}
+
+async fn answer(state: &mut DecisionState, input: &str) -> cognition::DecisionResult {
+    run_decision(Some(input.to_string()), state)
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+// Texts of the session's history
+fn history(state: &DecisionState) -> Vec<String> {
+    state
+        .snapshot()
+        .history
+        .into_iter()
+        .map(|entry| entry.text)
+        .collect()
+}
+
+#[tokio::test]
+async fn undo_restores_the_previous_node_history_and_variables() {
+    let mut state = state();
+    assert!(!state.undo());
+    run_decision(None, &mut state).await.unwrap();
+    state.vars.insert("name".to_string(), "Ada".to_string());
+    let before = history(&state);
+
+    assert_eq!(answer(&mut state, "Buy").await.current_id, "buy");
+    // Set by the host after the turn, and by the node's script
+    state.vars.insert("cart".to_string(), "1".to_string());
+    #[cfg(feature = "rhai")]
+    assert_eq!(state.vars["shopping"], "true");
+    assert_eq!(state.visits("buy").unwrap().count, 1);
+    assert_ne!(history(&state), before);
+
+    assert!(state.undo());
+    assert_eq!(state.current_node().unwrap().id, "start");
+    assert_eq!(history(&state), before);
+    assert_eq!(state.vars.keys().collect::<Vec<_>>(), ["name"]);
+    assert!(state.visits("buy").is_none());
+    assert!(!state.undo());
+
+    // The session goes on from the restored node
+    assert_eq!(answer(&mut state, "Buy").await.current_id, "buy");
+}
+
+#[tokio::test]
+async fn confirmed_choices_wait_for_a_yes() {
+    let mut state = state();
+    run_decision(None, &mut state).await.unwrap();
+    answer(&mut state, "Buy").await;
+
+    let result = answer(&mut state, "A book").await;
+    assert_eq!(result.confirm.as_deref(), Some("Order A book?"));
+    assert_eq!(result.current_id, "buy");
+    // Anything but a yes asks the node again
+    let result = answer(&mut state, "No, wait").await;
+    assert_eq!(result.confirm, None);
+    assert_eq!(result.current_id, "buy");
+
+    answer(&mut state, "A book").await;
+    let result = answer(&mut state, "Yes, please!").await;
+    assert_eq!(result.confirm, None);
+    assert_eq!(result.current_id, "book");
+}
+
+#[tokio::test]
+async fn hung_model_calls_time_out_or_are_cancelled() {
+    let config = "prediction:\n  policy: off\ntimeouts:\n  model_ms: 50\n";
+    let mut state =
+        common::state_with_model(config, TREE, common::TEMPLATE, || Box::new(common::Hung));
+    run_decision(None, &mut state).await.unwrap();
+    // Not a choice text, so the model is asked
+    let started = Instant::now();
+    let err = run_decision(Some("I'd like to shop".to_string()), &mut state)
+        .await
+        .unwrap_err();
+    assert!(err.0.contains("timed out"), "{}", err.0);
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let mut state = common::state_with_model("", TREE, common::TEMPLATE, || Box::new(common::Hung));
+    run_decision(None, &mut state).await.unwrap();
+    let cancel = CancellationToken::new();
+    let cancelling = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancelling.cancel();
+    });
+    let err = run_decision_with_cancel(Some("I'd like to shop".to_string()), &mut state, &cancel)
+        .await
+        .unwrap_err();
+    assert!(err.0.contains("cancelled"), "{}", err.0);
+}
+
+#[tokio::test]
+async fn sessions_past_their_limits_fail_as_loops() {
+    let mut state = common::state(
+        "prediction:\n  policy: off\nlimits:\n  max_turns: 1\n",
+        TREE,
+        "",
+    );
+    run_decision(None, &mut state).await.unwrap();
+    answer(&mut state, "Buy").await;
+    let err = run_decision(Some("A book".to_string()), &mut state)
+        .await
+        .unwrap_err();
+    assert!(err.is_loop_detected(), "{}", err);
+    assert!(!cognition::CognitionError("Loop".to_string()).is_loop_detected());
+}
+
+// Error of a state whose config is `config`, if any
+fn config_error(config: &str) -> Option<String> {
+    common::try_state(config, TREE, "", || Box::new(common::Unused))
+        .err()
+        .map(|err| err.0)
+}
+
+#[test]
+fn configured_nodes_must_be_in_the_tree() {
+    assert_eq!(
+        config_error("limits:\n  fallback_node: human\n").as_deref(),
+        Some("limits.fallback_node is unknown node human")
+    );
+    assert_eq!(config_error("limits:\n  fallback_node: book\n"), None);
+    assert_eq!(
+        config_error("guardrails:\n  blocked_node: blocked\n").as_deref(),
+        Some("guardrails.blocked_node is unknown node blocked")
+    );
+    assert_eq!(
+        config_error("on_idle:\n  after: 5m\n  node: nudge\n").as_deref(),
+        Some("on_idle.node is unknown node nudge")
+    );
+}
@@ -0,0 +1,124 @@
+// Signed webhook requests are accepted once, within the timestamp tolerance,
+// and outgoing webhooks sign what they post the same way
+use cognition::webhook::WebhookVerifier;
+#[cfg(feature = "reqwest")]
+use cognition::{
+    webhook::{Webhook, WebhookTarget},
+    DecisionEvent, Observer, SessionOutcome,
+};
+#[cfg(feature = "reqwest")]
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(feature = "reqwest")]
+use std::net::TcpListener;
+#[cfg(feature = "reqwest")]
+use std::sync::mpsc;
+
+const NOW: u64 = 1_700_000_000;
+const BODY: &[u8] = br#"{"input":"hello"}"#;
+
+#[test]
+fn signature_matches_hmac_sha256() {
+    let verifier = WebhookVerifier::new("secret");
+    assert_eq!(
+        verifier.sign(NOW, BODY),
+        "v0=66fe9c3c08612954ac19978deac29a2c7cf313b2bc04fdbcf27733cafa8cf759"
+    );
+    // Keys longer than a block are hashed first
+    assert_eq!(
+        WebhookVerifier::new(&"k".repeat(100)).sign(NOW, b"x"),
+        "v0=a77089e8b803f8996f1ebd1d328fa37c8136ec9d786b3133cac2401b646f1264"
+    );
+}
+
+#[test]
+fn replayed_stale_and_tampered_requests_are_refused() {
+    let verifier = WebhookVerifier::new("secret");
+    let signature = verifier.sign(NOW, BODY);
+    let timestamp = NOW.to_string();
+
+    assert!(verifier
+        .verify(&timestamp, BODY, &signature, NOW + 10)
+        .is_ok());
+    let replayed = verifier.verify(&timestamp, BODY, &signature, NOW + 20);
+    assert_eq!(replayed.unwrap_err().0, "Replayed webhook request");
+
+    let stale = verifier.verify(&timestamp, BODY, &signature, NOW + 301);
+    assert_eq!(stale.unwrap_err().0, "Stale webhook timestamp");
+
+    let tampered = verifier.verify(&timestamp, br#"{"input":"bye"}"#, &signature, NOW);
+    assert_eq!(tampered.unwrap_err().0, "Invalid webhook signature");
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn webhooks_post_signed_events_they_want() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (requests, received) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = vec![];
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                headers.push(line.trim().to_lowercase());
+            }
+            let length: usize = headers
+                .iter()
+                .find_map(|header| header.strip_prefix("content-length: "))
+                .unwrap()
+                .parse()
+                .unwrap();
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .unwrap();
+            requests.send((headers, body)).unwrap();
+        }
+    });
+
+    let webhook = Webhook::new(WebhookTarget {
+        url,
+        secret: Some("secret".to_string()),
+        events: vec!["session_ended".to_string()],
+    })
+    .unwrap();
+    webhook
+        .notify(&DecisionEvent::NodeEntered {
+            session_id: "s1".to_string(),
+            node_id: "start".to_string(),
+        })
+        .await;
+    webhook
+        .notify(&DecisionEvent::SessionEnded {
+            session_id: "s1".to_string(),
+            outcome: SessionOutcome::Completed,
+        })
+        .await;
+
+    // Only the wanted event was posted
+    let (headers, body) = received.recv().unwrap();
+    assert!(received.try_recv().is_err());
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find_map(|header| header.strip_prefix(&format!("{}: ", name)))
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(header("x-cognition-event"), "session_ended");
+    let event: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(event["outcome"], "completed");
+    let timestamp = header("x-cognition-timestamp");
+    let verifier = WebhookVerifier::new("secret");
+    let now = timestamp.parse().unwrap();
+    assert!(verifier
+        .verify(&timestamp, &body, &header("x-cognition-signature"), now)
+        .is_ok());
+}